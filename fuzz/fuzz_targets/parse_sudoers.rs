@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// like `Sudoers::read`, only accept input that is valid UTF-8
+fuzz_target!(|data: &[u8]| {
+    if let Ok(input) = std::str::from_utf8(data) {
+        sudo_rs::fuzz_parse_sudoers(input);
+    }
+});
@@ -0,0 +1,3 @@
+fn main() {
+    sudo_rs::sudo_ts_main()
+}
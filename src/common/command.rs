@@ -1,14 +1,14 @@
 use std::{
     ffi::{OsStr, OsString},
     fmt::Display,
-    os::unix::ffi::OsStringExt,
+    os::{fd::OwnedFd, unix::ffi::OsStringExt},
     path::{Path, PathBuf},
 };
 
 use crate::common::DisplayOsStr;
 use crate::system::escape_os_str_lossy;
 
-use super::resolve::{canonicalize, resolve_path};
+use super::resolve::{canonicalize, canonicalize_in_root, resolve_path, resolve_path_in_root};
 
 #[derive(Debug, Default)]
 #[cfg_attr(test, derive(PartialEq))]
@@ -17,6 +17,29 @@ pub struct CommandAndArguments {
     pub(crate) arguments: Vec<OsString>,
     pub(crate) resolved: bool,
     pub(crate) arg0: Option<PathBuf>,
+    pub(crate) chroot: Option<PathBuf>,
+    /// The file that `command` led to inside `chroot`, if it could be kept open.
+    pub(crate) file: Option<CommandFile>,
+}
+
+/// A command that was found inside a root directory; executing this file instead of looking up
+/// the path again makes sure that the file that was judged is the one that is run.
+#[derive(Debug)]
+pub(crate) struct CommandFile(pub(crate) OwnedFd);
+
+#[cfg(test)]
+impl PartialEq for CommandFile {
+    fn eq(&self, other: &Self) -> bool {
+        use std::os::unix::fs::MetadataExt;
+
+        let id = |CommandFile(fd): &CommandFile| {
+            let meta = std::fs::File::from(fd.try_clone().unwrap())
+                .metadata()
+                .unwrap();
+            (meta.dev(), meta.ino())
+        };
+        id(self) == id(other)
+    }
 }
 
 impl Display for CommandAndArguments {
@@ -56,6 +79,22 @@ fn escaped(arguments: Vec<OsString>) -> OsString {
         .join(OsStr::new(" "))
 }
 
+// shell builtins that don't also exist as a program, e.g. because they only make sense if they
+// change the state of the shell itself; users sometimes try to run these via sudo
+const SHELL_BUILTINS: &[&str] = &[
+    ".", "alias", "bg", "bind", "break", "builtin", "cd", "continue", "declare", "dirs", "disown",
+    "eval", "exec", "exit", "export", "fg", "hash", "history", "jobs", "let", "local", "logout",
+    "popd", "pushd", "readonly", "return", "set", "shift", "shopt", "source", "times", "trap",
+    "typeset", "ulimit", "umask", "unalias", "unset", "wait",
+];
+
+/// Whether a command that could not be found is the name of a shell builtin
+pub(crate) fn is_shell_builtin(command: &Path) -> bool {
+    SHELL_BUILTINS
+        .iter()
+        .any(|builtin| command.as_os_str() == *builtin)
+}
+
 //checks whether the Path is actually describing a qualified path (i.e. contains "/")
 //or just specifying the name of a file (in which case we are going to resolve it via PATH)
 fn is_qualified(path: impl AsRef<Path>) -> bool {
@@ -63,15 +102,20 @@ fn is_qualified(path: impl AsRef<Path>) -> bool {
 }
 
 impl CommandAndArguments {
+    /// If `chroot` is given, the command is looked up as it will be seen from inside that
+    /// root directory, and `command` will hold the path inside of it.
     pub fn build_from_args(
         shell: Option<PathBuf>,
         mut arguments: Vec<OsString>,
         path: &str,
+        chroot: Option<&Path>,
     ) -> Self {
         let mut resolved = true;
         let mut command;
-        let mut arg0 = None;
+        let arg0;
         if let Some(chosen_shell) = shell {
+            // the shell is invoked by the name it was configured with, like ogsudo does
+            arg0 = Some(chosen_shell.clone());
             command = chosen_shell;
             if !arguments.is_empty() {
                 arguments = vec!["-c".into(), escaped(arguments)]
@@ -86,7 +130,11 @@ impl CommandAndArguments {
 
             // resolve the command, remembering errors (but not propagating them)
             if !is_qualified(&command) {
-                match resolve_path(&command, path) {
+                let qualified_path = match chroot {
+                    Some(root) => resolve_path_in_root(&command, path, root),
+                    None => resolve_path(&command, path),
+                };
+                match qualified_path {
                     Some(qualified_path) => command = qualified_path,
                     None => resolved = false,
                 }
@@ -95,8 +143,18 @@ impl CommandAndArguments {
 
         // resolve symlinks, even if the command was obtained through a PATH or SHELL
         // once again, failure to canonicalize should not stop the pipeline
-        match canonicalize(&command) {
-            Ok(canon_path) => command = canon_path,
+        let canon_path = match chroot {
+            Some(root) => {
+                canonicalize_in_root(root, &command).map(|found| (found.path, found.file))
+            }
+            None => canonicalize(&command).map(|path| (path, None)),
+        };
+        let mut file = None;
+        match canon_path {
+            Ok((canon_path, canon_file)) => {
+                command = canon_path;
+                file = canon_file.map(CommandFile);
+            }
             Err(_) => resolved = false,
         }
 
@@ -105,6 +163,8 @@ impl CommandAndArguments {
             arguments,
             resolved,
             arg0,
+            chroot: chroot.map(ToOwned::to_owned),
+            file,
         }
     }
 }
@@ -113,7 +173,7 @@ impl CommandAndArguments {
 mod test {
     use std::ffi::OsString;
 
-    use super::{CommandAndArguments, escaped};
+    use super::{CommandAndArguments, CommandFile, escaped};
 
     #[test]
     fn test_escaped() {
@@ -126,6 +186,50 @@ mod test {
         test(&["a", "b#c"], "a b\\#c");
         test(&["1 2 3"], "1\\ 2\\ 3");
         test(&["! @ $"], "\\!\\ \\@\\ $");
+        test(&["cmd", "--", "-x", "--help"], "cmd -- -x --help");
+    }
+
+    #[test]
+    fn test_is_shell_builtin() {
+        use super::is_shell_builtin;
+        use std::path::Path;
+
+        assert!(is_shell_builtin(Path::new("cd")));
+        assert!(is_shell_builtin(Path::new("ulimit")));
+        assert!(is_shell_builtin(Path::new("export")));
+        assert!(!is_shell_builtin(Path::new("ls")));
+        assert!(!is_shell_builtin(Path::new("/usr/local/bin/cd")));
+        assert!(!is_shell_builtin(Path::new("./cd")));
+    }
+
+    #[test]
+    fn shell_builtins_are_explained_when_not_found() {
+        use crate::common::Error;
+
+        for builtin in ["cd", "ulimit", "export"] {
+            let message = Error::CommandNotFound(builtin.into()).to_string();
+            let mut lines = message.lines();
+            assert_eq!(
+                lines.next().unwrap(),
+                format!("'{builtin}': command not found")
+            );
+            assert_eq!(
+                lines.next().unwrap(),
+                format!("'{builtin}' is a shell builtin, so it cannot be run directly")
+            );
+            assert!(
+                lines
+                    .next()
+                    .unwrap()
+                    .ends_with(&format!("sudo sh -c '{builtin} ...'"))
+            );
+            assert_eq!(lines.next(), None);
+        }
+
+        assert_eq!(
+            Error::CommandNotFound("does-not-exist".into()).to_string(),
+            "'does-not-exist': command not found"
+        );
     }
 
     #[test]
@@ -134,13 +238,16 @@ mod test {
             CommandAndArguments::build_from_args(
                 None,
                 vec!["/usr/bin/fmt".into(), "hello".into()],
-                "/bin"
+                "/bin",
+                None
             ),
             CommandAndArguments {
                 command: "/usr/bin/fmt".into(),
                 arguments: vec!["hello".into()],
                 resolved: true,
                 arg0: Some("/usr/bin/fmt".into()),
+                chroot: None,
+                file: None,
             }
         );
 
@@ -148,13 +255,16 @@ mod test {
             CommandAndArguments::build_from_args(
                 None,
                 vec!["fmt".into(), "hello".into()],
-                "/tmp:/usr/bin:/bin"
+                "/tmp:/usr/bin:/bin",
+                None
             ),
             CommandAndArguments {
                 command: "/usr/bin/fmt".into(),
                 arguments: vec!["hello".into()],
                 resolved: true,
                 arg0: Some("fmt".into()),
+                chroot: None,
+                file: None,
             }
         );
 
@@ -162,13 +272,16 @@ mod test {
             CommandAndArguments::build_from_args(
                 None,
                 vec!["thisdoesnotexist".into(), "hello".into()],
-                ""
+                "",
+                None
             ),
             CommandAndArguments {
                 command: "thisdoesnotexist".into(),
                 arguments: vec!["hello".into()],
                 resolved: false,
                 arg0: Some("thisdoesnotexist".into()),
+                chroot: None,
+                file: None,
             }
         );
 
@@ -176,15 +289,65 @@ mod test {
             CommandAndArguments::build_from_args(
                 Some("shell".into()),
                 vec!["ls".into(), "hello".into()],
-                "/bin"
+                "/bin",
+                None
             ),
             CommandAndArguments {
                 command: "shell".into(),
                 arguments: vec!["-c".into(), "ls hello".into()],
                 resolved: false,
-                arg0: None,
+                arg0: Some("shell".into()),
+                chroot: None,
+                file: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_build_command_in_chroot() {
+        use std::os::unix::fs::{PermissionsExt, symlink};
+
+        let root = crate::system::file::create_temporary_dir().unwrap();
+        std::fs::create_dir_all(root.join("usr/bin")).unwrap();
+        std::fs::write(root.join("usr/bin/ls"), "").unwrap();
+        std::fs::set_permissions(root.join("usr/bin/ls"), PermissionsExt::from_mode(0o755))
+            .unwrap();
+        symlink("/usr/bin", root.join("bin")).unwrap();
+
+        let command = CommandAndArguments::build_from_args(
+            None,
+            vec!["ls".into(), "-l".into()],
+            "/bin",
+            Some(&root),
+        );
+        // on Linux, the kernel finds the file and it is kept open
+        let file = cfg!(target_os = "linux")
+            .then(|| CommandFile(std::fs::File::open(root.join("usr/bin/ls")).unwrap().into()));
+        assert_eq!(
+            command,
+            CommandAndArguments {
+                command: "/usr/bin/ls".into(),
+                arguments: vec!["-l".into()],
+                resolved: true,
+                arg0: Some("ls".into()),
+                chroot: Some(root.clone()),
+                file,
             }
         );
+
+        // commands that only exist outside of the root directory are not found
+        let command = CommandAndArguments::build_from_args(
+            None,
+            vec!["/usr/bin/fmt".into()],
+            "/bin",
+            Some(&root),
+        );
+        assert!(!command.resolved);
+        let command =
+            CommandAndArguments::build_from_args(None, vec!["fmt".into()], "/usr/bin", Some(&root));
+        assert!(!command.resolved);
+
+        std::fs::remove_dir_all(root).unwrap();
     }
 
     #[test]
@@ -1,16 +1,21 @@
 use std::env;
 use std::ffi::OsString;
+use std::path::PathBuf;
+use std::time::Duration;
 
 use crate::common::{Error, HARDENED_ENUM_VALUE_0, HARDENED_ENUM_VALUE_1, HARDENED_ENUM_VALUE_2};
-use crate::exec::RunOptions;
-use crate::sudo::{SudoEditOptions, SudoListOptions, SudoRunOptions, SudoValidateOptions};
+use crate::exec::{FinalCommand, WorkingDir, login_shell_arg0};
+use crate::log::dev_debug;
+use crate::sudo::{
+    Environment, SudoEditOptions, SudoListOptions, SudoRunOptions, SudoValidateOptions,
+};
 use crate::sudoers::Sudoers;
 use crate::sudoers::{DirChange, Restrictions};
 use crate::system::{Group, Hostname, User, audit::sudo_call};
 
 use super::{
-    SudoPath,
-    command::CommandAndArguments,
+    SudoPath, SudoersDuration,
+    command::{CommandAndArguments, CommandFile},
     resolve::{CurrentUser, resolve_shell, resolve_target_user_and_group},
 };
 
@@ -19,7 +24,11 @@ pub struct Context {
     // cli options
     pub launch: LaunchType,
     pub chdir: Option<SudoPath>,
+    pub chroot: Option<SudoPath>,
+    pub command_timeout: Option<SudoersDuration>,
     pub command: CommandAndArguments,
+    // the secure_path that the command was looked up with, if any
+    pub secure_path: Option<String>,
     pub target_user: User,
     pub target_group: Group,
     pub askpass: bool,
@@ -50,15 +59,21 @@ impl Context {
         sudo_options: SudoRunOptions,
         policy: &mut Sudoers,
     ) -> Result<Context, Error> {
-        let hostname = Hostname::resolve();
         let current_user = CurrentUser::resolve()?;
 
         let (target_user, target_group) =
             resolve_target_user_and_group(&sudo_options.user, &sudo_options.group, &current_user)?;
+        let hostname = resolve_hostname(policy, &current_user, Some(&target_user));
+
+        if sudo_options.implied_shell
+            && !policy.shell_noargs(&hostname, &current_user, &target_user)
+        {
+            return Err(Error::NoCommand);
+        }
 
         let launch = if sudo_options.login {
             LaunchType::Login
-        } else if sudo_options.shell {
+        } else if sudo_options.shell || sudo_options.implied_shell {
             LaunchType::Shell
         } else {
             LaunchType::Direct
@@ -66,6 +81,16 @@ impl Context {
 
         let shell = resolve_shell(launch, &current_user, &target_user);
 
+        // the command is looked up in the root directory requested by the user, or the one
+        // set as a default; a CHROOT in the matching rule may still cause another lookup
+        let chroot = match &sudo_options.chroot {
+            Some(chroot) => Some(chroot.clone()),
+            None => policy.default_chroot(&hostname, &current_user, &target_user),
+        }
+        .map(|dir| dir.expand_tilde_in_path(&target_user.name))
+        .transpose()?
+        .map(PathBuf::from);
+
         let override_path = policy.search_path(&hostname, &current_user, &target_user);
 
         let command = {
@@ -79,7 +104,12 @@ impl Context {
             };
 
             sudo_call(&target_user, &target_group, || {
-                CommandAndArguments::build_from_args(shell, sudo_options.positional_args, path)
+                CommandAndArguments::build_from_args(
+                    shell,
+                    sudo_options.positional_args,
+                    path,
+                    chroot.as_deref(),
+                )
             })?
         };
 
@@ -88,12 +118,15 @@ impl Context {
         Ok(Context {
             hostname,
             command,
+            secure_path: override_path.map(str::to_string),
             current_user,
             target_user,
             target_group,
             use_session_records: !sudo_options.reset_timestamp,
             launch,
             chdir: sudo_options.chdir,
+            chroot: sudo_options.chroot,
+            command_timeout: sudo_options.command_timeout,
             askpass: sudo_options.askpass,
             stdin: sudo_options.stdin,
             bell: sudo_options.bell,
@@ -104,13 +137,16 @@ impl Context {
         })
     }
 
-    pub fn from_edit_opts(sudo_options: SudoEditOptions) -> Result<Context, Error> {
+    pub fn from_edit_opts(
+        sudo_options: SudoEditOptions,
+        policy: &mut Sudoers,
+    ) -> Result<Context, Error> {
         use std::path::Path;
-        let hostname = Hostname::resolve();
         let current_user = CurrentUser::resolve()?;
 
         let (target_user, target_group) =
             resolve_target_user_and_group(&sudo_options.user, &sudo_options.group, &current_user)?;
+        let hostname = resolve_hostname(policy, &current_user, Some(&target_user));
 
         // resolve file arguments; if something can't be resolved, don't add it to the "edit" list
         let resolved_args = sudo_call(&target_user, &target_group, || {
@@ -156,12 +192,15 @@ impl Context {
         Ok(Context {
             hostname,
             command,
+            secure_path: None,
             current_user,
             target_user,
             target_group,
             use_session_records: !sudo_options.reset_timestamp,
             launch: Default::default(),
             chdir: sudo_options.chdir,
+            chroot: None,
+            command_timeout: None,
             askpass: sudo_options.askpass,
             stdin: sudo_options.stdin,
             bell: sudo_options.bell,
@@ -171,21 +210,27 @@ impl Context {
             files_to_edit,
         })
     }
-    pub fn from_validate_opts(sudo_options: SudoValidateOptions) -> Result<Context, Error> {
-        let hostname = Hostname::resolve();
+    pub fn from_validate_opts(
+        sudo_options: SudoValidateOptions,
+        policy: &mut Sudoers,
+    ) -> Result<Context, Error> {
         let current_user = CurrentUser::resolve()?;
         let (target_user, target_group) =
             resolve_target_user_and_group(&sudo_options.user, &sudo_options.group, &current_user)?;
+        let hostname = resolve_hostname(policy, &current_user, None);
 
         Ok(Context {
             hostname,
             command: Default::default(),
+            secure_path: None,
             current_user,
             target_user,
             target_group,
             use_session_records: !sudo_options.reset_timestamp,
             launch: Default::default(),
             chdir: None,
+            chroot: None,
+            command_timeout: None,
             askpass: sudo_options.askpass,
             stdin: sudo_options.stdin,
             bell: sudo_options.bell,
@@ -200,10 +245,10 @@ impl Context {
         sudo_options: SudoListOptions,
         policy: &mut Sudoers,
     ) -> Result<Context, Error> {
-        let hostname = Hostname::resolve();
         let current_user = CurrentUser::resolve()?;
         let (target_user, target_group) =
             resolve_target_user_and_group(&sudo_options.user, &sudo_options.group, &current_user)?;
+        let hostname = resolve_hostname(policy, &current_user, Some(&target_user));
 
         let override_path = policy.search_path(&hostname, &current_user, &target_user);
 
@@ -220,19 +265,22 @@ impl Context {
             };
 
             sudo_call(&target_user, &target_group, || {
-                CommandAndArguments::build_from_args(None, sudo_options.positional_args, path)
+                CommandAndArguments::build_from_args(None, sudo_options.positional_args, path, None)
             })?
         };
 
         Ok(Context {
             hostname,
             command,
+            secure_path: override_path.map(str::to_string),
             current_user,
             target_user,
             target_group,
             use_session_records: !sudo_options.reset_timestamp,
             launch: Default::default(),
             chdir: None,
+            chroot: None,
+            command_timeout: None,
             askpass: sudo_options.askpass,
             stdin: sudo_options.stdin,
             bell: sudo_options.bell,
@@ -243,10 +291,72 @@ impl Context {
         })
     }
 
-    pub(crate) fn try_as_run_options(
+    /// The root directory that the command should be run in; this fails if the user requested
+    /// a root directory that the policy does not permit.
+    pub(crate) fn chroot_dir(&self, controls: &Restrictions) -> Result<Option<PathBuf>, Error> {
+        let chroot = match &controls.chroot {
+            DirChange::Any => self.chroot.as_ref(),
+            DirChange::Strict(optdir) => {
+                if let Some(chroot) = &self.chroot {
+                    return Err(Error::ChRootNotAllowed {
+                        chroot: chroot.clone(),
+                        command: self.command.command.clone(),
+                    });
+                } else {
+                    optdir.as_ref()
+                }
+            }
+        };
+
+        Ok(chroot
+            .map(|dir| dir.expand_tilde_in_path(&self.target_user.name))
+            .transpose()?
+            .map(PathBuf::from))
+    }
+
+    /// Look up the command again, as it is seen from inside the given root directory, using
+    /// the given secure_path (or the PATH of the invoking user if there is none).
+    pub(crate) fn resolve_command_in(
+        &mut self,
+        chroot: Option<PathBuf>,
+        path: Option<&str>,
+    ) -> Result<(), Error> {
+        self.secure_path = path.map(str::to_string);
+
+        let system_path;
+        let path = if let Some(path) = path {
+            path
+        } else {
+            system_path = env::var("PATH").unwrap_or_default();
+            system_path.as_ref()
+        };
+
+        // arg0 holds the name the command was invoked with, or the shell
+        let args = self
+            .command
+            .arg0
+            .iter()
+            .map(|arg0| arg0.clone().into_os_string())
+            .chain(self.command.arguments.iter().cloned())
+            .collect();
+
+        self.command = sudo_call(&self.target_user, &self.target_group, || {
+            CommandAndArguments::build_from_args(None, args, path, chroot.as_deref())
+        })?;
+
+        Ok(())
+    }
+
+    /// Determine what will actually be executed, now that the policy has been consulted.
+    ///
+    /// The working directory is, in order of precedence: the one requested using `--chdir` (if
+    /// the policy allows it) or set by the policy, the home directory of the target user for a
+    /// login shell, or otherwise the current directory.
+    pub(crate) fn final_command(
         &self,
         controls: &Restrictions,
-    ) -> Result<RunOptions<'_>, Error> {
+        env: Environment,
+    ) -> Result<FinalCommand, Error> {
         // see if the chdir flag is permitted
         let chdir = match &controls.chdir {
             DirChange::Any => self.chdir.as_ref(),
@@ -267,32 +377,117 @@ impl Context {
             .map(|dir| dir.expand_tilde_in_path(&self.target_user.name))
             .transpose()?;
 
-        Ok(RunOptions {
-            command: if self.command.resolved {
-                &self.command.command
-            } else {
-                return Err(Error::CommandNotFound(self.command.command.clone()));
-            },
-            arguments: &self.command.arguments,
-            arg0: self.command.arg0.as_deref(),
-            chdir: chdir.as_deref().map(ToOwned::to_owned),
-            is_login: self.launch == LaunchType::Login,
-            user: &self.target_user,
-            group: &self.target_group,
+        let is_login = self.launch == LaunchType::Login;
+
+        let cwd = match chdir {
+            Some(dir) => Some(WorkingDir::Required(dir.into())),
+            None if is_login => Some(WorkingDir::Preferred(self.target_user.home.clone().into())),
+            None => None,
+        };
+
+        // see if the chroot flag is permitted, and that the command was looked up in it
+        let chroot = self.chroot_dir(controls)?;
+        if chroot != self.command.chroot {
+            return Err(Error::CommandNotFound(self.command.command.clone()));
+        }
+
+        if !self.command.resolved {
+            return Err(Error::CommandNotFound(self.command.command.clone()));
+        }
+
+        // the user may only ask for a timeout if the policy allows it, and if the policy also
+        // sets one, the shortest of the two applies
+        let requested_timeout = self
+            .command_timeout
+            .and_then(SudoersDuration::to_std)
+            .filter(|timeout| !timeout.is_zero());
+        let policy_timeout = controls.command_timeout.and_then(SudoersDuration::to_std);
+        let timeout = match (requested_timeout, policy_timeout) {
+            (Some(_), _) if !controls.user_command_timeouts => {
+                return Err(Error::TimeoutNotAllowed);
+            }
+            (Some(requested), Some(limit)) => Some(requested.min(limit)),
+            (requested, limit) => requested.or(limit),
+        };
+
+        // arg0 is the name the command (or shell) was invoked with, before any symlinks were
+        // resolved
+        let arg0 = self.command.arg0.as_ref().unwrap_or(&self.command.command);
+        let arg0 = if is_login {
+            login_shell_arg0(arg0)
+        } else {
+            arg0.clone().into_os_string()
+        };
+
+        let command_file = self
+            .command
+            .file
+            .as_ref()
+            .map(|CommandFile(file)| file.try_clone())
+            .transpose()?;
+
+        Ok(FinalCommand {
+            command: self.command.command.clone(),
+            command_file,
+            argv: std::iter::once(arg0)
+                .chain(self.command.arguments.iter().cloned())
+                .collect(),
+            env,
+            cwd,
+            chroot,
+            user: self.target_user.clone(),
+            group: self.target_group.clone(),
             umask: controls.umask,
+            #[cfg(feature = "apparmor")]
+            apparmor_profile: controls.apparmor_profile.clone(),
 
             background: self.background,
             use_pty: controls.use_pty,
             noexec: controls.noexec,
+            timeout,
         })
     }
 }
 
+/// How long we are prepared to wait for the resolver to report the fully qualified hostname
+const FQDN_LOOKUP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The hostname as it is matched against the sudoers file; its fully qualified name is only
+/// looked up if `Defaults fqdn` is set, since that may involve a DNS query.
+fn resolve_hostname(
+    policy: &mut Sudoers,
+    current_user: &User,
+    target_user: Option<&User>,
+) -> Hostname {
+    let hostname = Hostname::resolve();
+
+    if policy.fqdn(&hostname, current_user, target_user) {
+        hostname.qualify(FQDN_LOOKUP_TIMEOUT)
+    } else {
+        dev_debug!("fqdn is not set, skipping the lookup of {hostname}");
+        hostname
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{common::resolve::CurrentUser, sudo::SudoAction, system::Hostname};
-
-    use super::Context;
+    use std::collections::HashSet;
+    use std::path::PathBuf;
+
+    use crate::{
+        common::{
+            Error, SudoPath, SudoersDuration, command::CommandAndArguments, resolve::CurrentUser,
+        },
+        exec::WorkingDir,
+        sudo::SudoAction,
+        sudoers::{DirChange, Restrictions},
+        system::{
+            Group, Hostname, User,
+            interface::{GroupId, UserId},
+        },
+    };
+
+    use super::{Context, LaunchType};
 
     #[test]
     fn test_build_run_context() {
@@ -317,4 +512,227 @@ mod tests {
         assert_eq!(context.hostname, Hostname::resolve());
         assert_eq!(context.target_user.uid, current_user.uid);
     }
+
+    fn fake_context(launch: LaunchType, command: CommandAndArguments) -> Context {
+        let user = User {
+            uid: UserId::new(1000),
+            gid: GroupId::new(1000),
+            name: "test".into(),
+            home: "/home/test".into(),
+            shell: "/bin/sh".into(),
+            groups: vec![],
+        };
+
+        Context {
+            hostname: Hostname::fake("test-ubuntu"),
+            command,
+            secure_path: None,
+            current_user: CurrentUser::fake(user.clone()),
+            target_user: User {
+                uid: UserId::ROOT,
+                gid: GroupId::new(0),
+                name: "root".into(),
+                home: "/root".into(),
+                shell: "/bin/bash".into(),
+                groups: vec![],
+            },
+            target_group: Group {
+                gid: GroupId::new(0),
+                name: Some("root".to_string()),
+            },
+            launch,
+            chdir: None,
+            chroot: None,
+            command_timeout: None,
+            askpass: false,
+            stdin: false,
+            bell: false,
+            background: false,
+            prompt: None,
+            non_interactive: false,
+            use_session_records: false,
+            files_to_edit: vec![],
+        }
+    }
+
+    fn resolved(arg0: &str, command: &str, arguments: &[&str]) -> CommandAndArguments {
+        CommandAndArguments {
+            command: command.into(),
+            arguments: arguments.iter().map(Into::into).collect(),
+            resolved: true,
+            arg0: Some(arg0.into()),
+            chroot: None,
+            file: None,
+        }
+    }
+
+    fn restrictions(env_vars: &HashSet<String>) -> Restrictions<'_> {
+        Restrictions {
+            env_max: 1024,
+            ..Restrictions::with_env_lists(env_vars, env_vars)
+        }
+    }
+
+    #[test]
+    fn final_command_for_a_direct_command() {
+        let env_vars = HashSet::new();
+        let context = fake_context(
+            LaunchType::Direct,
+            resolved("ls", "/usr/bin/ls", &["-l", "/tmp"]),
+        );
+
+        let command = context
+            .final_command(&restrictions(&env_vars), Default::default())
+            .unwrap();
+
+        assert_eq!(command.command, PathBuf::from("/usr/bin/ls"));
+        assert_eq!(command.argv, ["ls", "-l", "/tmp"]);
+        assert_eq!(command.cwd, None);
+        assert_eq!(command.user.name, "root");
+        assert!(command.use_pty);
+        assert!(!command.noexec);
+    }
+
+    #[test]
+    fn final_command_for_a_login_shell() {
+        let env_vars = HashSet::new();
+        let context = fake_context(
+            LaunchType::Login,
+            resolved("/bin/bash", "/usr/bin/bash", &[]),
+        );
+
+        let command = context
+            .final_command(&restrictions(&env_vars), Default::default())
+            .unwrap();
+
+        assert_eq!(command.command, PathBuf::from("/usr/bin/bash"));
+        assert_eq!(command.argv, ["-bash"]);
+        assert_eq!(command.cwd, Some(WorkingDir::Preferred("/root".into())));
+    }
+
+    #[test]
+    fn final_command_for_a_shell() {
+        let env_vars = HashSet::new();
+        let context = fake_context(
+            LaunchType::Shell,
+            CommandAndArguments::build_from_args(
+                Some("/bin/sh".into()),
+                vec!["echo".into(), "a b".into()],
+                "/usr/bin:/bin",
+                None,
+            ),
+        );
+
+        let command = context
+            .final_command(&restrictions(&env_vars), Default::default())
+            .unwrap();
+
+        assert_eq!(command.argv, ["/bin/sh", "-c", "echo a\\ b"]);
+        assert_eq!(command.cwd, None);
+    }
+
+    #[test]
+    fn final_command_with_noexec() {
+        let env_vars = HashSet::new();
+        let context = fake_context(LaunchType::Direct, resolved("ls", "/usr/bin/ls", &[]));
+
+        let command = context
+            .final_command(
+                &Restrictions {
+                    noexec: true,
+                    use_pty: false,
+                    ..restrictions(&env_vars)
+                },
+                Default::default(),
+            )
+            .unwrap();
+
+        assert!(command.noexec);
+        assert!(!command.use_pty);
+        assert_eq!(command.argv, ["ls"]);
+    }
+
+    #[test]
+    fn final_command_timeout() {
+        let env_vars = HashSet::new();
+        let timeout = |requested: Option<u64>, limit: Option<u64>, user_command_timeouts| {
+            let mut context = fake_context(LaunchType::Direct, resolved("ls", "/usr/bin/ls", &[]));
+            context.command_timeout = requested.map(SudoersDuration::from_secs);
+            context
+                .final_command(
+                    &Restrictions {
+                        command_timeout: limit.map(SudoersDuration::from_secs),
+                        user_command_timeouts,
+                        ..restrictions(&env_vars)
+                    },
+                    Default::default(),
+                )
+                .map(|command| command.timeout.map(|timeout| timeout.as_secs()))
+        };
+
+        assert_eq!(timeout(None, None, false).unwrap(), None);
+        assert_eq!(timeout(None, Some(60), false).unwrap(), Some(60));
+        assert_eq!(timeout(Some(0), Some(60), false).unwrap(), Some(60));
+        assert_eq!(timeout(Some(30), None, true).unwrap(), Some(30));
+        // the user can shorten the timeout of the policy, but not extend it
+        assert_eq!(timeout(Some(30), Some(60), true).unwrap(), Some(30));
+        assert_eq!(timeout(Some(90), Some(60), true).unwrap(), Some(60));
+
+        assert!(matches!(
+            timeout(Some(30), Some(60), false),
+            Err(Error::TimeoutNotAllowed)
+        ));
+    }
+
+    #[test]
+    fn final_command_working_directory() {
+        let env_vars = HashSet::new();
+
+        // the policy sets the working directory, even for a login shell
+        let context = fake_context(
+            LaunchType::Login,
+            resolved("/bin/bash", "/usr/bin/bash", &[]),
+        );
+        let command = context
+            .final_command(
+                &Restrictions {
+                    chdir: DirChange::Strict(Some(SudoPath::from("/srv"))),
+                    ..restrictions(&env_vars)
+                },
+                Default::default(),
+            )
+            .unwrap();
+        assert_eq!(command.cwd, Some(WorkingDir::Required("/srv".into())));
+
+        // the user may only pick a working directory if the policy allows it
+        let mut context = fake_context(
+            LaunchType::Login,
+            resolved("/bin/bash", "/usr/bin/bash", &[]),
+        );
+        context.chdir = Some(SudoPath::from("/tmp"));
+        let denied = context.final_command(&restrictions(&env_vars), Default::default());
+        assert!(matches!(denied, Err(Error::ChDirNotAllowed { .. })));
+
+        let command = context
+            .final_command(
+                &Restrictions {
+                    chdir: DirChange::Any,
+                    ..restrictions(&env_vars)
+                },
+                Default::default(),
+            )
+            .unwrap();
+        assert_eq!(command.cwd, Some(WorkingDir::Required("/tmp".into())));
+    }
+
+    #[test]
+    fn final_command_requires_a_resolved_command() {
+        let env_vars = HashSet::new();
+        let mut command = resolved("foo", "foo", &[]);
+        command.resolved = false;
+        let context = fake_context(LaunchType::Direct, command);
+
+        let result = context.final_command(&restrictions(&env_vars), Default::default());
+        assert!(matches!(result, Err(Error::CommandNotFound(_))));
+    }
 }
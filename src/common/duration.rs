@@ -0,0 +1,247 @@
+use std::fmt;
+use std::time::Duration;
+
+/// A period of time as configured in the sudoers file (e.g. `timestamp_timeout`). These are
+/// written as a (possibly fractional) number of minutes, where a negative value means that
+/// the period never ends.
+///
+/// What a period of zero means depends on the setting: for `timestamp_timeout` it means that
+/// a session record is never valid, for `passwd_timeout` that there is no time limit at all;
+/// so this is left for the user of this type to decide.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SudoersDuration {
+    // `None` is an unlimited duration
+    seconds: Option<u64>,
+}
+
+impl SudoersDuration {
+    pub const fn zero() -> Self {
+        Self { seconds: Some(0) }
+    }
+
+    pub const fn infinite() -> Self {
+        Self { seconds: None }
+    }
+
+    pub const fn from_secs(seconds: u64) -> Self {
+        Self {
+            seconds: Some(seconds),
+        }
+    }
+
+    /// Parse the notation used in the sudoers file: an integer or decimal number of minutes,
+    /// or a negative number for an unlimited duration. Fractions of seconds are dropped.
+    pub fn parse_minutes(input: &str) -> Option<Self> {
+        let (negative, magnitude) = match input.strip_prefix('-') {
+            Some(magnitude) => (true, magnitude),
+            None => (false, input),
+        };
+
+        // the parse::<u64> calls below would also accept a leading '+'
+        if !magnitude.bytes().all(|c| c.is_ascii_digit() || c == b'.') {
+            return None;
+        }
+
+        let seconds = if let Some((integral, fractional)) = magnitude.split_once('.') {
+            // the sudoers parser limits 'input' to 18 characters, so none of these
+            // operations can overflow in practice; but checked arithmetic is used anyway
+            let shift = 10u64.checked_pow(fractional.len().try_into().ok()?)?;
+            let scaled = integral
+                .parse::<u64>()
+                .ok()?
+                .checked_mul(shift)?
+                .checked_add(fractional.parse::<u64>().ok()?)?;
+
+            scaled.checked_mul(60)? / shift
+        } else {
+            magnitude.parse::<u64>().ok()?.checked_mul(60)?
+        };
+
+        Some(if negative {
+            Self::infinite()
+        } else {
+            Self::from_secs(seconds)
+        })
+    }
+
+    /// Parse the notation used for command timeouts: a number of seconds, or a sequence of
+    /// numbers that are each followed by a unit (`d`, `h`, `m` or `s`), such as "1h30m".
+    /// Every unit may occur at most once, in that order.
+    pub fn parse_timeout(input: &str) -> Option<Self> {
+        if !input.is_empty() && input.bytes().all(|c| c.is_ascii_digit()) {
+            return input.parse().ok().map(Self::from_secs);
+        }
+
+        let mut units: &[(u8, u64)] =
+            &[(b'd', 24 * 60 * 60), (b'h', 60 * 60), (b'm', 60), (b's', 1)];
+        let mut seconds: u64 = 0;
+        let mut rest = input;
+        while !rest.is_empty() {
+            let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+            let (number, remainder) = rest.split_at(digits);
+            let unit = remainder.bytes().next()?.to_ascii_lowercase();
+
+            let position = units.iter().position(|(name, _)| *name == unit)?;
+            let factor = units[position].1;
+            units = &units[position + 1..];
+
+            let amount = number.parse::<u64>().ok()?.checked_mul(factor)?;
+            seconds = seconds.checked_add(amount)?;
+            rest = &remainder[1..];
+        }
+
+        (!input.is_empty()).then_some(Self::from_secs(seconds))
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.seconds == Some(0)
+    }
+
+    /// The equivalent standard duration, or `None` if this duration is unlimited.
+    pub fn to_std(self) -> Option<Duration> {
+        self.seconds.map(Duration::from_secs)
+    }
+
+    /// What remains of this duration after `elapsed` has passed, or `None` if it has run out.
+    pub fn checked_sub(self, elapsed: Duration) -> Option<Self> {
+        match self.to_std() {
+            None => Some(Self::infinite()),
+            Some(duration) => duration
+                .checked_sub(elapsed)
+                .map(|remaining| Self::from_secs(remaining.as_secs())),
+        }
+    }
+}
+
+impl fmt::Display for SudoersDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(seconds) = self.seconds else {
+            return f.write_str("unlimited");
+        };
+
+        let (minutes, rest) = (seconds / 60, seconds % 60);
+        if rest == 0 {
+            let plural = if minutes == 1 { "" } else { "s" };
+            write!(f, "{minutes} minute{plural}")
+        } else if rest % 3 == 0 {
+            // a multiple of 3 seconds is a multiple of 1/20th of a minute, so this
+            // can be written using two decimals without losing precision
+            let hundredths = rest * 100 / 60;
+            let decimals = format!("{hundredths:02}");
+            write!(f, "{minutes}.{} minutes", decimals.trim_end_matches('0'))
+        } else {
+            let plural = if seconds == 1 { "" } else { "s" };
+            write!(f, "{seconds} second{plural}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_minutes() {
+        let parse = |input| SudoersDuration::parse_minutes(input).and_then(SudoersDuration::to_std);
+        let secs = |secs| Some(Duration::from_secs(secs));
+
+        assert_eq!(parse("0"), secs(0));
+        assert_eq!(parse("15"), secs(15 * 60));
+        assert_eq!(parse("25.25"), secs(25 * 60 + 15));
+        assert_eq!(parse("0.0001"), secs(0));
+        assert_eq!(parse("99999999999999999.9"), None);
+
+        let infinite = Some(SudoersDuration::infinite());
+        assert_eq!(SudoersDuration::parse_minutes("-1"), infinite);
+        assert_eq!(SudoersDuration::parse_minutes("-0.5"), infinite);
+
+        for invalid in [
+            "", ".", ".5", "1.", "+1", "1.+5", "--1", "-", "1.2.3", "1e3", "ten",
+        ] {
+            assert_eq!(SudoersDuration::parse_minutes(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn parse_timeout() {
+        let parse = SudoersDuration::parse_timeout;
+        let secs = |secs| Some(SudoersDuration::from_secs(secs));
+
+        assert_eq!(parse("0"), secs(0));
+        assert_eq!(parse("90"), secs(90));
+        assert_eq!(parse("90s"), secs(90));
+        assert_eq!(parse("5m"), secs(5 * 60));
+        assert_eq!(parse("1h30m"), secs(90 * 60));
+        assert_eq!(
+            parse("7d8h30m10s"),
+            secs(((7 * 24 + 8) * 60 + 30) * 60 + 10)
+        );
+        assert_eq!(parse("2H5S"), secs(2 * 60 * 60 + 5));
+
+        for invalid in [
+            "",
+            "s",
+            "1x",
+            "m5",
+            "5m30",
+            "30s5m",
+            "1m1m",
+            "-1",
+            "+1",
+            "1.5m",
+            "1 m",
+            "99999999999999999999",
+            "9999999999999999d",
+        ] {
+            assert_eq!(parse(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn display() {
+        let show = |secs| SudoersDuration::from_secs(secs).to_string();
+
+        assert_eq!(show(0), "0 minutes");
+        assert_eq!(show(60), "1 minute");
+        assert_eq!(show(5 * 60), "5 minutes");
+        assert_eq!(show(150), "2.5 minutes");
+        assert_eq!(show(25 * 60 + 15), "25.25 minutes");
+        assert_eq!(show(6), "0.1 minutes");
+        assert_eq!(show(1), "1 second");
+        assert_eq!(show(61), "61 seconds");
+        assert_eq!(SudoersDuration::infinite().to_string(), "unlimited");
+    }
+
+    #[test]
+    fn printing_round_trips() {
+        for input in ["0", "1", "5", "2.5", "25.25", "0.1", "0.05", "1440"] {
+            let duration = SudoersDuration::parse_minutes(input).unwrap();
+            let printed = duration.to_string();
+            let minutes = printed.split(' ').next().unwrap();
+
+            assert_eq!(minutes, input);
+            assert_eq!(SudoersDuration::parse_minutes(minutes), Some(duration));
+        }
+    }
+
+    #[test]
+    fn sentinels() {
+        let zero = SudoersDuration::zero();
+        let infinite = SudoersDuration::infinite();
+
+        assert!(zero.is_zero());
+        assert!(!infinite.is_zero());
+        assert_ne!(zero, infinite);
+        assert_eq!(zero.to_std(), Some(Duration::ZERO));
+        assert_eq!(infinite.to_std(), None);
+
+        let year = Duration::from_secs(365 * 24 * 60 * 60);
+        assert_eq!(infinite.checked_sub(year), Some(infinite));
+        assert_eq!(zero.checked_sub(Duration::ZERO), Some(zero));
+        assert_eq!(zero.checked_sub(Duration::from_secs(1)), None);
+        assert_eq!(
+            SudoersDuration::from_secs(90).checked_sub(Duration::from_millis(30_500)),
+            Some(SudoersDuration::from_secs(59))
+        );
+    }
+}
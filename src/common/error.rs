@@ -20,10 +20,16 @@ pub enum Error {
         chdir: SudoPath,
         command: PathBuf,
     },
+    ChRootNotAllowed {
+        chroot: SudoPath,
+        command: PathBuf,
+    },
+    TimeoutNotAllowed,
     UserNotFound(String),
     GroupNotFound(String),
     Authorization(String),
     InteractionRequired,
+    NoCommand,
     EnvironmentVar(Vec<String>),
     Configuration(String),
     Options(String),
@@ -77,7 +83,18 @@ impl fmt::Display for Error {
                 )
             }
             Error::CommandNotFound(p) => {
-                xlat_write!(f, "'{path}': command not found", path = p.display())
+                xlat_write!(f, "'{path}': command not found", path = p.display())?;
+                if super::command::is_shell_builtin(p) {
+                    writeln!(f)?;
+                    xlat_write!(
+                        f,
+                        "'{command}' is a shell builtin, so it cannot be run directly\n\
+                        use the -s option to run a privileged shell, or run it in a shell \
+                        like this: sudo sh -c '{command} ...'",
+                        command = p.display()
+                    )?;
+                }
+                Ok(())
             }
             Error::InvalidCommand(p) => {
                 xlat_write!(f, "'{path}': invalid command", path = p.display())
@@ -89,6 +106,7 @@ impl fmt::Display for Error {
                 xlat_write!(f, "I'm sorry {user}. I'm afraid I can't do that", user = u)
             }
             Error::InteractionRequired => xlat_write!(f, "interactive authentication is required"),
+            Error::NoCommand => xlat_write!(f, "no command given"),
             Error::EnvironmentVar(vs) => {
                 xlat_write!(
                     f,
@@ -129,6 +147,15 @@ impl fmt::Display for Error {
                 path = chdir.display(),
                 command = command.display()
             ),
+            Error::ChRootNotAllowed { chroot, command } => xlat_write!(
+                f,
+                "you are not allowed to use '--chroot {path}' with '{command}'",
+                path = chroot.display(),
+                command = command.display()
+            ),
+            Error::TimeoutNotAllowed => {
+                xlat_write!(f, "you are not allowed to set a command timeout")
+            }
             Error::StringValidation(string) => {
                 write!(
                     f,
@@ -5,6 +5,7 @@ use std::fmt::{self, Write};
 
 pub use command::CommandAndArguments;
 pub use context::Context;
+pub use duration::SudoersDuration;
 pub use error::Error;
 pub use path::SudoPath;
 pub use string::SudoString;
@@ -12,6 +13,7 @@ pub use string::SudoString;
 pub mod bin_serde;
 pub mod command;
 pub mod context;
+mod duration;
 pub mod error;
 mod path;
 pub mod resolve;
@@ -1,5 +1,8 @@
 use crate::system::interface::UserId;
-use crate::system::{Group, User};
+use crate::system::{
+    Group, User,
+    file::{InRoot, open_in_root, resolve_in_root},
+};
 use core::fmt;
 use std::{
     env,
@@ -174,6 +177,12 @@ pub(crate) fn resolve_target_user_and_group(
     }
 }
 
+/// Resolve a user name or #<id>, as accepted by `-u`, to a user
+pub(crate) fn resolve_user(name_or_id: &SudoString) -> Result<User, Error> {
+    resolve_from_name_or_id(&Some(name_or_id.clone()), User::from_name, User::from_uid)?
+        .ok_or_else(|| Error::UserNotFound(name_or_id.to_string()))
+}
+
 fn resolve_from_name_or_id<T, I, E>(
     input: &Option<SudoString>,
     from_name: impl FnOnce(&CStr) -> Result<Option<T>, E>,
@@ -219,6 +228,24 @@ pub(crate) fn resolve_path(command: &Path, path: &str) -> Option<PathBuf> {
         .find(|arg| is_valid_executable(arg))
 }
 
+/// Resolve a executable name based in the PATH environment variable, as seen by a command
+/// that will be executed with `root` as its root directory; the resulting path is also the
+/// path as seen from inside `root`.
+pub(crate) fn resolve_path_in_root(command: &Path, path: &str, root: &Path) -> Option<PathBuf> {
+    path.split(':')
+        .map(Path::new)
+        .filter(|path| path.is_absolute())
+        .map(|path| path.join(command))
+        .find(|arg| {
+            resolve_in_root(root, arg).is_ok_and(|real| is_valid_executable(&on_host(root, &real)))
+        })
+}
+
+/// Translate a path inside `root` to the path of the same file outside of it.
+pub(crate) fn on_host(root: &Path, path: &Path) -> PathBuf {
+    root.join(path.strip_prefix("/").unwrap_or(path))
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::Path;
@@ -335,6 +362,36 @@ pub fn canonicalize_newfile<P: AsRef<Path>>(path: P) -> io::Result<PathBuf> {
     Ok(reconstructed_path)
 }
 
+/// Like [canonicalize], but treating `root` as the root directory; the resulting path is the
+/// path as seen from inside `root`. Only absolute paths are accepted. If possible, the file
+/// that the path leads to is also kept open.
+pub fn canonicalize_in_root<P: AsRef<Path>>(root: &Path, path: P) -> io::Result<InRoot> {
+    let path = path.as_ref();
+    if !path.is_absolute() {
+        return Err(io::ErrorKind::InvalidInput.into());
+    }
+    let Some(parent) = path.parent() else {
+        // path is "/"
+        return open_in_root(root, path);
+    };
+
+    let canon_path = resolve_in_root(root, parent)?;
+
+    let reconstructed_path = if let Some(file_name) = path.file_name() {
+        canon_path.join(file_name)
+    } else {
+        canon_path
+    };
+
+    // access the object to generate the regular error if it does not exist
+    let found = open_in_root(root, &reconstructed_path)?;
+
+    Ok(InRoot {
+        path: reconstructed_path,
+        file: found.file,
+    })
+}
+
 #[cfg(test)]
 mod test {
     use super::canonicalize;
@@ -1,4 +1,5 @@
 use std::{
+    borrow::Cow,
     ffi::{CStr, OsStr, OsString, c_char, c_int, c_long},
     os::{
         fd::{AsRawFd, BorrowedFd},
@@ -109,6 +110,28 @@ pub fn is_fifo_or_sock(fildes: BorrowedFd) -> bool {
     fstat_mode_any::<{ libc::S_IFIFO | libc::S_IFSOCK }>(&fildes)
 }
 
+/// Make text that may come from outside of sudo safe to write to a terminal, by escaping
+/// control characters (other than newlines and tabs) so they show up as e.g. `\u{1b}`; this
+/// prevents escape sequences in it from changing the terminal title, moving the cursor to
+/// overwrite earlier output, and so on.
+pub fn escape_control_chars(text: &str) -> Cow<'_, str> {
+    let is_unsafe = |c: char| c.is_control() && c != '\n' && c != '\t';
+    if !text.contains(is_unsafe) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut escaped = String::with_capacity(text.len() + 8);
+    for c in text.chars() {
+        if is_unsafe(c) {
+            escaped.extend(c.escape_default());
+        } else {
+            escaped.push(c);
+        }
+    }
+
+    Cow::Owned(escaped)
+}
+
 /// Dynamically obtain the correct buffer size (within bounds)
 pub fn dynamic_fill<T: Default + Copy, E>(
     range: std::ops::Range<usize>,
@@ -140,7 +163,7 @@ pub fn dynamic_fill<T: Default + Copy, E>(
 #[cfg(test)]
 mod test {
 
-    use super::{dynamic_fill, os_string_from_ptr, string_from_ptr};
+    use super::{dynamic_fill, escape_control_chars, os_string_from_ptr, string_from_ptr};
 
     #[test]
     fn miri_test_str_to_ptr() {
@@ -158,6 +181,20 @@ mod test {
         assert_eq!(strp(c"hello".as_ptr()), "hello");
     }
 
+    #[test]
+    fn test_escape_control_chars() {
+        assert_eq!(escape_control_chars("plain text"), "plain text");
+        assert_eq!(escape_control_chars("rüdiger\tlines\n"), "rüdiger\tlines\n");
+        assert_eq!(
+            escape_control_chars("\x1b]0;pwned\x07Password: "),
+            "\\u{1b}]0;pwned\\u{7}Password: "
+        );
+        assert_eq!(
+            escape_control_chars("a\rb\x7f\u{9b}2J"),
+            "a\\rb\\u{7f}\\u{9b}2J"
+        );
+    }
+
     #[test]
     fn test_tty() {
         use crate::system::term::Pty;
@@ -1,5 +1,7 @@
 #![forbid(unsafe_code)]
-pub type SettingsModifier = Box<dyn FnOnce(&mut Settings)>;
+use crate::common::SudoersDuration;
+
+pub type SettingsModifier = Box<dyn Fn(&mut Settings)>;
 
 pub enum ListMode {
     Set,
@@ -31,25 +33,44 @@ defaults! {
     always_query_group_plugin = false  #ignored
     always_set_home           = false  #ignored
     env_reset                 = true   #ignored
-    fqdn                      = false  #ignored
+    fqdn                      = false
     ignore_dot                = true   #ignored
     lecture                   = never (!= never) [always, once, never] #ignored
     mailerpath                = None (!= None) #ignored
     mail_badpass              = true   #ignored
     match_group_by_gid        = false  #ignored
     use_pty                   = true
+    use_netgroups             = true
     visiblepw                 = false  #ignored
     pwfeedback                = true
     rootpw                    = false
+    runaspw                   = false
     targetpw                  = false
+    authenticate              = true
     noexec                    = false
+    sudoedit_follow           = false
     noninteractive_auth       = false
+    pam_acct_mgmt             = true
+    pam_session               = true
+    pam_setcred               = true
+    shell_noargs              = false
 
     insults                   = false  #ignored
 
     setenv                    = false
+    set_logname               = true
     runcwd                    = None (!= None)
+    runchroot                 = None (!= None)
     apparmor_profile          = None (!= None)
+    logfile                   = None (!= None)
+    admin_flag                = None (!= None)
+    admin_flag_groups         = ["admin", "sudo"]
+    log_host                  = false
+    log_year                  = false
+    log_backend               = syslog (!= syslog) [syslog, journald, file]
+    syslog_pid                = false
+    loglinelen                = 80 (!= 0) [0..=100000]
+    sudoers_locale            = "C"
     umask                     = 0o022 (!= 0o777) {octal_mode}
     umask_override            = false
 
@@ -57,14 +78,19 @@ defaults! {
 
     secure_path               = None (!= None)
 
-    verifypw                  = all (!= never) [all, always, any, never] #ignored
+    verifypw                  = all (!= never) [all, always, any, never]
 
-    passwd_timeout            = (5*60) (!= 0) {fractional_minutes}
-    timestamp_timeout         = (15*60) (!= 0) {fractional_minutes}
+    pam_auth_timeout          = (SudoersDuration::from_secs(5*60)) (!= (SudoersDuration::zero())) {SudoersDuration::parse_minutes => SudoersDuration}
+    passwd_timeout            = (SudoersDuration::from_secs(5*60)) (!= (SudoersDuration::zero())) {SudoersDuration::parse_minutes => SudoersDuration}
+    timestamp_timeout         = (SudoersDuration::from_secs(15*60)) (!= (SudoersDuration::zero())) {SudoersDuration::parse_minutes => SudoersDuration}
+    command_timeout           = (SudoersDuration::zero()) (!= (SudoersDuration::zero())) {SudoersDuration::parse_timeout => SudoersDuration}
+    user_command_timeouts     = false
 
     editor                    = SYSTEM_EDITOR
     env_editor                = true
 
+    env_max                   = 1048576 [0..=1073741824]
+
     env_keep                  = ["COLORS", "DISPLAY", "HOSTNAME", "KRB5CCNAME", "LS_COLORS", "PATH",
                                  "PS1", "PS2", "XAUTHORITY", "XAUTHORIZATION", "XDG_CURRENT_DESKTOP"]
 
@@ -85,24 +111,235 @@ fn octal_mode(input: &str) -> Option<u64> {
         .map(Into::into)
 }
 
-/// A custom parser to parse seconds as fractional "minutes", the format used by
-/// passwd_timeout and timestamp_timeout.
-fn fractional_minutes(input: &str) -> Option<u64> {
-    if let Some((integral, fractional)) = input.split_once('.') {
-        // - 'input' is maximally 18 characters, making fractional.len() at most 17;
-        //   1e17 < 2**63, so the definition of 'shift' will not overflow.
-        // - for the same reason, if both parses in the definition of 'seconds' succeed,
-        //   we will have constructed an integer < 1e17.
-        //-  1e17 * 60 = 6e18 < 9e18 < 2**63, so the final line also will not overflow
-        let shift = 10u64.pow(fractional.len().try_into().ok()?);
-        let seconds = integral.parse::<u64>().ok()? * shift + fractional.parse::<u64>().ok()?;
-
-        Some(seconds * 60 / shift)
-    } else {
-        input.parse::<u64>().ok()?.checked_mul(60)
+/// How the value of a setting is shown by `sudo -V` and `sudo -l`
+enum Value {
+    Flag(bool),
+    Text(String),
+    List(Vec<String>),
+    Unset,
+}
+
+trait ToValue {
+    fn to_value(&self) -> Value;
+}
+
+impl ToValue for bool {
+    fn to_value(&self) -> Value {
+        Value::Flag(*self)
+    }
+}
+
+impl ToValue for u64 {
+    fn to_value(&self) -> Value {
+        Value::Text(self.to_string())
     }
 }
 
+impl ToValue for Box<str> {
+    fn to_value(&self) -> Value {
+        Value::Text(self.to_string())
+    }
+}
+
+impl ToValue for Option<Box<str>> {
+    fn to_value(&self) -> Value {
+        self.as_ref().map_or(Value::Unset, ToValue::to_value)
+    }
+}
+
+impl ToValue for std::collections::HashSet<String> {
+    fn to_value(&self) -> Value {
+        let mut items = self.iter().cloned().collect::<Vec<_>>();
+        items.sort();
+        Value::List(items)
+    }
+}
+
+impl ToValue for SudoersDuration {
+    fn to_value(&self) -> Value {
+        Value::Text(self.to_string())
+    }
+}
+
+/// What `sudo -V` says about each setting; every setting in the table above that is not
+/// ignored must be listed here.
+const DESCRIPTIONS: &[(&str, &str)] = &[
+    (
+        "fqdn",
+        "Require fully-qualified hostnames in the sudoers file",
+    ),
+    ("use_pty", "Always run commands in a pseudo-terminal"),
+    ("use_netgroups", "Enable sudoers netgroup support"),
+    (
+        "pwfeedback",
+        "Provide visual feedback at the password prompt when there is user input",
+    ),
+    ("rootpw", "Prompt for root's password, not the user's"),
+    (
+        "runaspw",
+        "Prompt for the runas_default user's password, not the user's",
+    ),
+    (
+        "targetpw",
+        "Prompt for the target user's password, not the user's",
+    ),
+    ("authenticate", "Require users to authenticate by default"),
+    ("noexec", "Prevent commands from executing other programs"),
+    (
+        "sudoedit_follow",
+        "Follow symbolic links when editing files",
+    ),
+    (
+        "noninteractive_auth",
+        "Attempt authentication even when in non-interactive mode",
+    ),
+    ("pam_acct_mgmt", "Perform PAM account validation management"),
+    (
+        "pam_session",
+        "Create a new PAM session for the command to run in",
+    ),
+    (
+        "pam_setcred",
+        "Establish PAM credentials for the target user",
+    ),
+    (
+        "shell_noargs",
+        "If sudo is invoked with no arguments it acts as if the -s option had been given",
+    ),
+    (
+        "setenv",
+        "Allow users to set arbitrary environment variables",
+    ),
+    ("set_logname", "Set LOGNAME and USER to the target user"),
+    (
+        "runcwd",
+        "Working directory to change to before executing the command",
+    ),
+    (
+        "runchroot",
+        "Root directory to change to before executing the command",
+    ),
+    (
+        "apparmor_profile",
+        "AppArmor profile to use in the new security context",
+    ),
+    ("logfile", "Path to log file"),
+    (
+        "admin_flag",
+        "Path to the file that is created after an administrator runs a command",
+    ),
+    (
+        "admin_flag_groups",
+        "Groups whose members count as administrators for admin_flag",
+    ),
+    ("log_host", "Log the hostname in the (non-syslog) log file"),
+    ("log_year", "Log the year in the (non-syslog) log file"),
+    (
+        "log_backend",
+        "Where to log commands that are run or denied",
+    ),
+    (
+        "syslog_pid",
+        "Include the process ID when logging via syslog",
+    ),
+    (
+        "loglinelen",
+        "Length at which to wrap log file lines (0 for no wrap)",
+    ),
+    (
+        "sudoers_locale",
+        "Locale to use for time stamps in the log file",
+    ),
+    ("umask", "Umask to use or 0777 to use user's"),
+    (
+        "umask_override",
+        "The umask specified in sudoers will override the user's, even if it is more permissive",
+    ),
+    ("passwd_tries", "Number of tries to enter a password"),
+    ("secure_path", "Value to override user's $PATH with"),
+    ("verifypw", "When to require a password for 'sudo -v'"),
+    (
+        "pam_auth_timeout",
+        "Time limit for PAM modules during authentication",
+    ),
+    ("passwd_timeout", "Password prompt timeout"),
+    ("timestamp_timeout", "Authentication timestamp timeout"),
+    ("command_timeout", "Timeout for commands"),
+    (
+        "user_command_timeouts",
+        "Allow the user to specify a timeout on the command line",
+    ),
+    (
+        "editor",
+        "Path to the editor for use by sudoedit and visudo",
+    ),
+    (
+        "env_editor",
+        "Allow the editor to be chosen with SUDO_EDITOR, VISUAL and EDITOR",
+    ),
+    (
+        "env_max",
+        "Maximum size in bytes of the environment preserved from the user",
+    ),
+    ("env_keep", "Environment variables to preserve"),
+    ("env_check", "Environment variables to check for safety"),
+];
+
+/// The lines that `sudo -V` shows for the given settings, in the order of the table above;
+/// flags that are off and settings that are not set are left out.
+pub fn describe(settings: &Settings) -> Vec<String> {
+    let mut lines = Vec::new();
+    for (name, value) in values(settings) {
+        let description = DESCRIPTIONS
+            .iter()
+            .find_map(|(key, description)| (*key == name).then_some(*description))
+            .unwrap_or(name);
+
+        match value {
+            Value::Flag(true) => lines.push(description.to_string()),
+            Value::Flag(false) | Value::Unset => {}
+            // the umask is the only integer that is written in octal
+            Value::Text(_) if name == "umask" => {
+                lines.push(format!("{description}: 0{:03o}", settings.umask()))
+            }
+            Value::Text(text) => lines.push(format!("{description}: {text}")),
+            Value::List(items) => {
+                lines.push(format!("{description}:"));
+                lines.extend(items.into_iter().map(|item| format!("\t{item}")));
+            }
+        }
+    }
+
+    lines
+}
+
+/// The settings that have an effect, with their value written as in a Defaults line; this
+/// is how `sudo -l` shows the Defaults that apply.
+pub fn entries(settings: &Settings) -> Vec<(&'static str, String)> {
+    let quoted = |text: String| {
+        if text.contains(|c: char| c.is_whitespace() || c == ',') {
+            format!("\"{text}\"")
+        } else {
+            text
+        }
+    };
+
+    values(settings)
+        .into_iter()
+        .map(|(name, value)| {
+            let entry = match value {
+                Value::Flag(true) => name.to_string(),
+                Value::Flag(false) | Value::Unset => format!("!{name}"),
+                Value::Text(_) if name == "umask" => format!("{name}=0{:03o}", settings.umask()),
+                Value::Text(text) => format!("{name}={}", quoted(text)),
+                Value::List(items) if items.is_empty() => format!("!{name}"),
+                Value::List(items) => format!("{name}={}", quoted(items.join(" "))),
+            };
+            (name, entry)
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -119,15 +356,21 @@ mod test {
         assert_eq! { def.use_pty, true };
         assert_eq! { def.visiblepw, false };
         assert_eq! { def.env_editor, true };
+        assert_eq! { def.set_logname, true };
         assert_eq! { def.passwd_tries, 3 };
         assert_eq! { def.secure_path, None };
         assert_eq! { def.env_check, ["COLORTERM", "LANG", "LANGUAGE", "LC_*", "LINGUAS", "TERM", "TZ"].iter().map(|s| s.to_string()).collect() };
         assert_eq! { def.verifypw, enums::verifypw::all };
+        assert_eq! { def.logfile, None };
+        assert_eq! { def.loglinelen, 80 };
+        assert_eq! { &*def.sudoers_locale, "C" };
 
         negate("env_check").unwrap()(&mut def);
         negate("env_reset").unwrap()(&mut def);
         negate("secure_path").unwrap()(&mut def);
         negate("verifypw").unwrap()(&mut def);
+        negate("set_logname").unwrap()(&mut def);
+        negate("loglinelen").unwrap()(&mut def);
         assert_eq! { def.always_query_group_plugin, false };
         assert_eq! { def.always_set_home, false };
         assert_eq! { def.env_reset, false };
@@ -136,10 +379,12 @@ mod test {
         assert_eq! { def.use_pty, true };
         assert_eq! { def.visiblepw, false };
         assert_eq! { def.env_editor, true };
+        assert_eq! { def.set_logname, false };
         assert_eq! { def.passwd_tries, 3 };
         assert_eq! { def.secure_path, None };
         assert! { def.env_check.is_empty() };
         assert_eq! { def.verifypw, enums::verifypw::never };
+        assert_eq! { def.loglinelen, 0 };
 
         let SettingKind::Text(f) = set("lecture").unwrap() else {
             panic!()
@@ -153,6 +398,16 @@ mod test {
         negate("lecture").unwrap()(&mut def);
         assert_eq! { def.lecture, enums::lecture::never };
 
+        assert_eq! { def.log_backend, enums::log_backend::syslog };
+        let SettingKind::Text(f) = set("log_backend").unwrap() else {
+            panic!()
+        };
+        f("journald").unwrap()(&mut def);
+        assert_eq! { def.log_backend, enums::log_backend::journald };
+        assert!(f("json").is_none());
+        negate("log_backend").unwrap()(&mut def);
+        assert_eq! { def.log_backend, enums::log_backend::syslog };
+
         let SettingKind::Flag(f) = set("env_reset").unwrap() else {
             panic!()
         };
@@ -182,7 +437,7 @@ mod test {
         assert_eq! { def.visiblepw, false };
         assert_eq! { def.env_editor, true };
         assert_eq! { def.passwd_tries, 5 };
-        assert_eq! { def.timestamp_timeout, 25*60 + 60/4 };
+        assert_eq! { def.timestamp_timeout, SudoersDuration::from_secs(25*60 + 60/4) };
         assert_eq! { def.secure_path, Some("/bin".into()) };
         assert! { def.env_check.is_empty() };
         assert_eq! { def.verifypw, enums::verifypw::any };
@@ -190,4 +445,115 @@ mod test {
         assert!(set("notanoption").is_none());
         assert!(f("notanoption").is_none());
     }
+
+    #[test]
+    fn every_setting_has_a_description() {
+        let shown = values(&Settings::default())
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+
+        for name in &shown {
+            assert!(
+                DESCRIPTIONS.iter().any(|(key, _)| key == name),
+                "add a description of '{name}' for sudo -V"
+            );
+        }
+        for (key, _) in DESCRIPTIONS {
+            assert!(
+                shown.contains(key),
+                "'{key}' is not a setting that is shown"
+            );
+        }
+    }
+
+    #[test]
+    fn describe_settings() {
+        let mut def = Settings::default();
+        for name in ["use_pty", "use_netgroups", "pwfeedback", "env_editor"] {
+            negate(name).unwrap()(&mut def);
+        }
+        let SettingKind::Text(f) = set("secure_path").unwrap() else {
+            panic!()
+        };
+        f("/usr/bin:/bin").unwrap()(&mut def);
+        let SettingKind::Integer(f) = set("umask").unwrap() else {
+            panic!()
+        };
+        f("027").unwrap()(&mut def);
+        let SettingKind::List(f) = set("env_keep").unwrap() else {
+            panic!()
+        };
+        f(ListMode::Set, vec!["TZ".to_string(), "LANG".to_string()])(&mut def);
+        negate("env_check").unwrap()(&mut def);
+        negate("admin_flag_groups").unwrap()(&mut def);
+
+        assert_eq!(
+            describe(&def),
+            [
+                "Require users to authenticate by default",
+                "Perform PAM account validation management",
+                "Create a new PAM session for the command to run in",
+                "Establish PAM credentials for the target user",
+                "Set LOGNAME and USER to the target user",
+                "Groups whose members count as administrators for admin_flag:",
+                "Where to log commands that are run or denied: syslog",
+                "Length at which to wrap log file lines (0 for no wrap): 80",
+                "Locale to use for time stamps in the log file: C",
+                "Umask to use or 0777 to use user's: 0027",
+                "Number of tries to enter a password: 3",
+                "Value to override user's $PATH with: /usr/bin:/bin",
+                "When to require a password for 'sudo -v': all",
+                "Time limit for PAM modules during authentication: 5 minutes",
+                "Password prompt timeout: 5 minutes",
+                "Authentication timestamp timeout: 15 minutes",
+                "Timeout for commands: 0 minutes",
+                &format!("Path to the editor for use by sudoedit and visudo: {SYSTEM_EDITOR}"),
+                "Maximum size in bytes of the environment preserved from the user: 1048576",
+                "Environment variables to preserve:",
+                "\tLANG",
+                "\tTZ",
+                "Environment variables to check for safety:",
+            ]
+        );
+    }
+
+    #[test]
+    fn settings_as_defaults_entries() {
+        let mut def = Settings::default();
+        negate("use_pty").unwrap()(&mut def);
+        let SettingKind::Integer(f) = set("umask").unwrap() else {
+            panic!()
+        };
+        f("027").unwrap()(&mut def);
+        let SettingKind::Text(f) = set("secure_path").unwrap() else {
+            panic!()
+        };
+        f("/usr/bin:/bin").unwrap()(&mut def);
+        let SettingKind::List(f) = set("env_keep").unwrap() else {
+            panic!()
+        };
+        f(ListMode::Set, vec!["TZ".to_string(), "LANG".to_string()])(&mut def);
+        negate("env_check").unwrap()(&mut def);
+
+        let entries = entries(&def);
+        let entry = |name| {
+            entries
+                .iter()
+                .find_map(|(key, entry)| (*key == name).then_some(entry.as_str()))
+        };
+
+        assert_eq!(entry("use_pty"), Some("!use_pty"));
+        assert_eq!(entry("authenticate"), Some("authenticate"));
+        assert_eq!(entry("umask"), Some("umask=0027"));
+        assert_eq!(entry("secure_path"), Some("secure_path=/usr/bin:/bin"));
+        assert_eq!(entry("runcwd"), Some("!runcwd"));
+        assert_eq!(entry("env_keep"), Some("env_keep=\"LANG TZ\""));
+        assert_eq!(entry("env_check"), Some("!env_check"));
+        assert_eq!(
+            entry("timestamp_timeout"),
+            Some("timestamp_timeout=\"15 minutes\"")
+        );
+        assert_eq!(entry("env_reset"), None);
+    }
 }
@@ -1,4 +1,5 @@
 macro_rules! storage_of {
+    ($id:ident, =typed $ty: ty; $($_: tt)*) => { $ty };
     ($id:ident, true) => { bool };
     ($id:ident, false) => { bool };
     ($id:ident, [ $($value: expr),* ]) => { std::collections::HashSet<String> };
@@ -9,6 +10,7 @@ macro_rules! storage_of {
 }
 
 macro_rules! referent_of {
+    ($id:ident, =typed $ty: ty; $($_: tt)*) => { $ty };
     ($id:ident, true) => { bool };
     ($id:ident, false) => { bool };
     ($id:ident, [ $($value: expr),* ]) => { &std::collections::HashSet<String> };
@@ -60,11 +62,11 @@ macro_rules! modifier_of {
     ($id:ident, [ $($value: expr),* ]) => {
         $crate::defaults::SettingKind::List(|mode, list| {
             Box::new(move |obj: &mut Settings| match mode {
-                ListMode::Set => obj.$id = list.into_iter().collect(),
-                ListMode::Add => obj.$id.extend(list),
+                ListMode::Set => obj.$id = list.iter().cloned().collect(),
+                ListMode::Add => obj.$id.extend(list.iter().cloned()),
                 ListMode::Del => {
-                    for key in list {
-                        obj.$id.remove(&key);
+                    for key in &list {
+                        obj.$id.remove(key);
                     }
                 }
             })
@@ -102,13 +104,13 @@ macro_rules! modifier_of {
     ($id:ident, None) => {
         $crate::defaults::SettingKind::Text(|text| {
             let text = text.into();
-            Some(Box::new(move |obj: &mut Settings| obj.$id = Some(text)))
+            Some(Box::new(move |obj: &mut Settings| obj.$id = Some(Clone::clone(&text))))
         })
     };
     ($id:ident, $value: expr) => {
         $crate::defaults::SettingKind::Text(|text| {
             let text = text.into();
-            Some(Box::new(move |obj: &mut Settings| obj.$id = text))
+            Some(Box::new(move |obj: &mut Settings| obj.$id = Clone::clone(&text)))
         })
     };
 }
@@ -145,32 +147,50 @@ macro_rules! emit {
 }
 
 macro_rules! defaults {
-    ($($name:ident = $value:tt $((!= $negate:tt))? $([$($key:ident),*])? $([$first:literal ..= $last:literal$(; radix: $radix: expr)?])? $({$fn: expr})? $(#$attribute:ident)?)*) => {
+    ($($name:ident = $value:tt $((!= $negate:tt))? $([$($key:ident),*])? $([$first:literal ..= $last:literal$(; radix: $radix: expr)?])? $({$fn: expr $(=> $ty: ty)?})? $(#$attribute:ident)?)*) => {
         #[allow(non_camel_case_types)]
-        mod enums {
+        pub mod enums {
             $($(
                 #[derive(Clone,Copy,Debug,Default)]
                 #[cfg_attr(test, derive(PartialEq, Eq))]
                 pub enum $name { #[default] $($key),* }
+
+                impl super::ToValue for $name {
+                    fn to_value(&self) -> super::Value {
+                        super::Value::Text(format!("{self:?}"))
+                    }
+                }
             )?)*
         }
 
         #[derive(Clone)]
         pub struct Settings {
-            $($name: storage_of!($name, $(=int $fn;)?$(=int $first;)?$($(=enum $key;)*)? $value)),*
+            $($name: storage_of!($name, $($(=typed $ty;)?)? $(=int $fn;)?$(=int $first;)?$($(=enum $key;)*)? $value)),*
         }
 
         // we add setters to make sure the settings-object is read only, and to generate 'unused variable' warnings
         impl Settings {
             $(
             emit! { $($attribute)?;
-                pub fn $name(&self) -> referent_of!($name, $(=int $fn;)?$(=int $first;)?$($(=enum $key;)*)? $value) {
+                pub fn $name(&self) -> referent_of!($name, $($(=typed $ty;)?)? $(=int $fn;)?$(=int $first;)?$($(=enum $key;)*)? $value) {
                     result_of!(self.$name, $(=value $fn;)?$(=value $first;)?$($(=value $key;)*)? $value)
                 }
             }
             )*
         }
 
+        // the settings that have an effect, in the order of this table; used by 'fn describe'
+        #[allow(clippy::vec_init_then_push)]
+        fn values(settings: &Settings) -> Vec<(&'static str, Value)> {
+            let mut values = Vec::new();
+            $(
+            emit! { $($attribute)?;
+                values.push((stringify!($name), settings.$name.to_value()));
+            }
+            )*
+            values
+        }
+
         impl Default for Settings {
             fn default() -> Self {
                 Self {
@@ -183,13 +203,15 @@ macro_rules! defaults {
             match name {
                 $(
                 stringify!($name) if ifdef!($($negate)?; true; has_standard_negator!($value)) => {
+                    // an explicit negation that is not a literal has to be parenthesized
+                    #[allow(unused_parens)]
                     let value = ifdef!($($negate)?;
                         // this setting has an explicit negation; use that
                         initializer_of!($name, $(=int $fn;)?$(=int $first;)?$($(=enum $key;)*)? $($negate)?);
                         // for bool and sets, false/empty works (for other types this is dead code)
                         Default::default()
                     );
-                    Some(Box::new(move |obj: &mut Settings| obj.$name = value))
+                    Some(Box::new(move |obj: &mut Settings| obj.$name = Clone::clone(&value)))
                 },
                 )*
                 _ => None
@@ -5,7 +5,7 @@ use std::{
     os::fd::{AsFd, AsRawFd, RawFd},
 };
 
-use libc::{POLLIN, POLLOUT, pollfd};
+use libc::{POLLERR, POLLHUP, POLLIN, POLLOUT, pollfd};
 
 use crate::common::{HARDENED_ENUM_VALUE_0, HARDENED_ENUM_VALUE_1};
 use crate::{cutils::cerr, log::dev_debug};
@@ -194,7 +194,11 @@ impl<T: Process> EventRegistry<T> {
         // Remove the ids that correspond to file descriptors that were not ready.
         for (i, fd) in fds.iter().enumerate().rev() {
             let events = fd.events & fd.revents;
-            if !((events & POLLIN != 0) || (events & POLLOUT != 0)) {
+            // `POLLHUP` and `POLLERR` are reported even if they were not requested. For a read
+            // handle they are handled as readability, so the next read reports the hangup or
+            // error; writers only get the events they asked for.
+            let hangup = fd.events & POLLIN != 0 && fd.revents & (POLLHUP | POLLERR) != 0;
+            if !((events & POLLIN != 0) || (events & POLLOUT != 0) || hangup) {
                 ids.remove(i);
             }
         }
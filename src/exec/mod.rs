@@ -3,17 +3,19 @@ mod io_util;
 mod no_pty;
 #[cfg(target_os = "linux")]
 mod noexec;
+mod prepare;
 mod use_pty;
 
 use std::{
     borrow::Cow,
     convert::Infallible,
-    env,
-    ffi::{OsStr, OsString, c_int},
+    ffi::{OsString, c_int},
+    fs::File,
     io,
-    os::unix::{ffi::OsStrExt, process::CommandExt},
+    os::fd::{AsFd, OwnedFd},
+    os::unix::ffi::{OsStrExt, OsStringExt},
     path::{Path, PathBuf},
-    process::{self, Command},
+    process,
     time::Duration,
 };
 
@@ -21,12 +23,14 @@ use crate::{
     common::{
         HARDENED_ENUM_VALUE_0, HARDENED_ENUM_VALUE_1, HARDENED_ENUM_VALUE_2, bin_serde::BinPipe,
     },
+    cutils::safe_isatty,
     exec::no_pty::exec_no_pty,
-    log::{dev_info, dev_warn, user_error},
+    log::{dev_info, dev_warn},
+    sudo::Environment,
     system::{
-        _exit, ForkResult, Group, User, fork,
+        ForkResult, Group, User, fork,
         interface::ProcessId,
-        kill, killpg, mark_fds_as_cloexec, set_target_user, setpgid,
+        kill, killpg, setpgid,
         signal::{SignalNumber, SignalSet, SignalsState, consts::*, exit_with_signal, signal_name},
         term::UserTerm,
         wait::{Wait, WaitError, WaitOptions},
@@ -36,17 +40,33 @@ use crate::{
 use self::{
     event::{EventRegistry, Process},
     io_util::was_interrupted,
+    prepare::{ChildError, PreparedCommand},
     use_pty::{SIGCONT_BG, SIGCONT_FG, exec_pty},
 };
 
 #[cfg(target_os = "linux")]
-use self::noexec::SpawnNoexecHandler;
+use self::noexec::{NoexecFilter, SpawnNoexecHandler, add_noexec_filter};
 #[cfg(not(target_os = "linux"))]
 enum SpawnNoexecHandler {}
 #[cfg(not(target_os = "linux"))]
 impl SpawnNoexecHandler {
     fn spawn(self) {}
 }
+#[cfg(not(target_os = "linux"))]
+enum NoexecFilter {}
+#[cfg(not(target_os = "linux"))]
+impl NoexecFilter {
+    fn install(&self) -> io::Result<()> {
+        match *self {}
+    }
+}
+#[cfg(not(target_os = "linux"))]
+fn add_noexec_filter() -> io::Result<(NoexecFilter, SpawnNoexecHandler)> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "NOEXEC is currently only supported on Linux",
+    ))
+}
 
 #[derive(Debug, Copy, Clone)]
 #[cfg_attr(test, derive(PartialEq))]
@@ -60,29 +80,59 @@ pub enum Umask {
     Override(libc::mode_t) = HARDENED_ENUM_VALUE_2,
 }
 
-pub struct RunOptions<'a> {
-    pub command: &'a Path,
-    pub arguments: &'a [OsString],
-    pub arg0: Option<&'a Path>,
-    pub chdir: Option<PathBuf>,
-    pub is_login: bool,
-    pub user: &'a User,
-    pub group: &'a Group,
+/// Everything that determines what gets executed, and how. This is assembled in a single place
+/// once the policy has been consulted; the exec layer does not look at anything else.
+#[derive(Debug)]
+pub struct FinalCommand {
+    /// The (fully qualified) binary that is executed.
+    pub command: PathBuf,
+    /// The binary itself, if it was opened when it was looked up inside `chroot`; it is then
+    /// executed through this, rather than by looking up `command` once more.
+    pub command_file: Option<OwnedFd>,
+    /// The argument vector, starting with the name the command is invoked with.
+    pub argv: Vec<OsString>,
+    pub env: Environment,
+    pub cwd: Option<WorkingDir>,
+    pub chroot: Option<PathBuf>,
+    pub user: User,
+    pub group: Group,
     pub umask: Umask,
+    /// The AppArmor profile the command should be confined by.
+    #[cfg(feature = "apparmor")]
+    pub apparmor_profile: Option<String>,
 
     pub background: bool,
     pub use_pty: bool,
     pub noexec: bool,
+    /// How long the command may run before it is terminated, if there is a limit.
+    pub timeout: Option<Duration>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorkingDir {
+    /// The command is not run if the working directory cannot be changed to this directory.
+    Required(PathBuf),
+    /// If changing to this directory fails, the command is run in the current directory.
+    Preferred(PathBuf),
+}
+
+/// The name a login shell is invoked with: its file name, prefixed with a "-" to signal to it
+/// that it is a login shell.
+pub(crate) fn login_shell_arg0(arg0: &Path) -> OsString {
+    let mut process_name = arg0
+        .file_name()
+        .map(|osstr| osstr.as_bytes().to_vec())
+        .unwrap_or_default();
+    process_name.insert(0, b'-');
+
+    OsString::from_vec(process_name)
 }
 
 /// Based on `ogsudo`s `exec_pty` function.
 ///
 /// Returns the [`ExitReason`] of the command and a function that restores the default handler for
 /// signals once its called.
-pub fn run_command(
-    options: RunOptions<'_>,
-    env: impl IntoIterator<Item = (impl AsRef<OsStr>, impl AsRef<OsStr>)>,
-) -> io::Result<ExitReason> {
+pub fn run_command(options: FinalCommand) -> io::Result<ExitReason> {
     if options.background {
         // SAFETY: There should be no other threads at this point.
         match unsafe { fork() }? {
@@ -96,117 +146,41 @@ pub fn run_command(
         }
     }
 
-    // FIXME: should we pipe the stdio streams?
-    let qualified_path = options.command;
-    let mut command = Command::new(qualified_path);
-    // reset env and set filtered environment
-    command.args(options.arguments).env_clear().envs(env);
-    // set the arg0 to the requested string
-    // TODO: this mechanism could perhaps also be used to set the arg0 for login shells, as below
-    if let Some(arg0) = options.arg0 {
-        command.arg0(arg0);
-    }
-
-    if options.is_login {
-        // signal to the operating system that the command is a login shell by prefixing "-"
-        let mut process_name = qualified_path
-            .file_name()
-            .map(|osstr| osstr.as_bytes().to_vec())
-            .unwrap_or_default();
-        process_name.insert(0, b'-');
-        command.arg0(OsStr::from_bytes(&process_name));
-    }
-
-    let spawn_noexec_handler = if options.noexec {
-        #[cfg(not(target_os = "linux"))]
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            "NOEXEC is currently only supported on Linux",
-        ));
+    let background = options.background;
+    let use_pty = options.use_pty;
+    let timeout = options.timeout;
+    let pty_owner = options.user.clone();
 
-        #[cfg(target_os = "linux")]
-        Some(noexec::add_noexec_filter(&mut command)?)
-    } else {
-        None
-    };
-
-    // Decide if the pwd should be changed. `--chdir` takes precedence over `-i`.
-    let path = options
-        .chdir
-        .as_ref()
-        .map(|chdir| chdir.to_owned())
-        .or_else(|| options.is_login.then(|| options.user.home.clone().into()))
-        .clone();
-
-    // set target user and groups
-    set_target_user(&mut command, options.user.clone(), options.group.clone());
-
-    // change current directory if necessary.
-    if let Some(path) = path {
-        let is_chdir = options.chdir.is_some();
-
-        // SAFETY: Chdir as used internally by set_current_dir is async-signal-safe. The logger we
-        // use is also async-signal-safe.
-        unsafe {
-            command.pre_exec(move || {
-                if let Err(err) = env::set_current_dir(&path) {
-                    user_error!(
-                        "unable to change directory to {path}: {error}",
-                        path = path.display(),
-                        error = err
-                    );
-                    if is_chdir {
-                        return Err(err);
-                    }
-                }
-
-                Ok(())
-            });
-        }
-    }
-
-    // SAFETY: Umask is async-signal-safe.
-    unsafe {
-        let umask = options.umask;
-
-        command.pre_exec(move || {
-            match umask {
-                Umask::Preserve => {}
-                Umask::Extend(umask) => {
-                    // The only options to get the existing umask are overwriting it or
-                    // parsing a /proc file. Given that this is a single-threaded context,
-                    // overwrite it with a safe value is fine and the simpler option.
-                    let existing_umask = libc::umask(0o777);
-                    libc::umask(existing_umask | umask);
-                }
-                Umask::Override(umask) => {
-                    libc::umask(umask);
-                }
-            }
+    // FIXME: should we pipe the stdio streams?
+    let (mut command, spawn_noexec_handler) = PreparedCommand::new(options)?;
 
-            Ok(())
-        });
+    if background && safe_isatty(io::stdin().as_fd()) {
+        // The command must not read what the user types into their shell, so it reads from
+        // /dev/null instead of failing with EIO. Its output still goes to the terminal. (When
+        // a pty is used, this is replaced by an equivalent half-closed pipe.)
+        command.stdin = Some(File::open("/dev/null")?.into());
     }
 
     let sudo_pid = ProcessId::new(std::process::id() as i32);
 
-    if options.use_pty {
+    if use_pty {
         match UserTerm::open() {
             Ok(user_tty) => exec_pty(
                 sudo_pid,
                 spawn_noexec_handler,
                 command,
                 user_tty,
-                options.user,
-                options.background,
+                &pty_owner,
+                background,
+                timeout,
             ),
             Err(err) => {
                 dev_info!("Could not open user's terminal, not allocating a pty: {err}");
-                exec_no_pty(sudo_pid, spawn_noexec_handler, command)
+                exec_no_pty(sudo_pid, spawn_noexec_handler, command, timeout)
             }
         }
     } else {
-        exec_no_pty(sudo_pid, spawn_noexec_handler, command)
+        exec_no_pty(sudo_pid, spawn_noexec_handler, command, timeout)
     }
 }
 
@@ -226,46 +200,28 @@ impl ExitReason {
     }
 }
 
+/// Runs in the command process, right after it was forked; like [`PreparedCommand::exec`], this
+/// may only call async-signal-safe functions.
 fn exec_command(
-    mut command: Command,
+    command: &PreparedCommand,
     original_set: Option<SignalSet>,
     mut original_signal: SignalsState,
-    mut errpipe_tx: BinPipe<i32>,
+    mut errpipe_tx: BinPipe<ChildError>,
 ) -> ! {
-    // Restore the signal handlers of modified signals
-    if let Err(err) = original_signal.restore() {
-        dev_warn!("cannot restore signal states: {err}");
-    }
+    // Restore the signal handlers of modified signals. Failing to do so is not fatal, and cannot
+    // be logged here.
+    original_signal.restore().ok();
+
+    // The standard library ignores SIGPIPE, which should not be passed on to the command.
+    // SAFETY: signal is async-signal-safe, and SIGPIPE is a valid signal.
+    unsafe { libc::signal(SIGPIPE, libc::SIG_DFL) };
 
     // Restore the signal mask now that the handlers have been setup.
     if let Some(set) = original_set {
-        if let Err(err) = set.set_mask() {
-            dev_warn!("cannot restore signal mask: {err}");
-        }
-    }
-
-    if let Err(err) = mark_fds_as_cloexec() {
-        dev_warn!("failed to close the universe: {err}");
-        // Send the error to the monitor using the pipe.
-        if let Some(error_code) = err.raw_os_error() {
-            errpipe_tx.write(&error_code).ok();
-        }
-
-        // We call `_exit` instead of `exit` to avoid flushing the parent's IO streams by accident.
-        _exit(1);
-    }
-
-    let err = command.exec();
-
-    dev_warn!("failed to execute command: {err}");
-    // If `exec` returns, it means that executing the command failed. Send the error to the
-    // monitor using the pipe.
-    if let Some(error_code) = err.raw_os_error() {
-        errpipe_tx.write(&error_code).ok();
+        set.set_mask().ok();
     }
 
-    // We call `_exit` instead of `exit` to avoid flushing the parent's IO streams by accident.
-    _exit(1);
+    command.exec(&mut errpipe_tx)
 }
 
 // Kill the process with increasing urgency.
@@ -279,6 +235,20 @@ fn terminate_process(pid: ProcessId, use_killpg: bool) {
     kill_fn(pid, SIGKILL).ok();
 }
 
+// Like `terminate_process`, but for several process groups at once; processes in these groups
+// that survive `SIGHUP` and `SIGTERM` get `SIGKILL` after the same grace period.
+fn terminate_process_groups(pgrps: &[ProcessId]) {
+    for signal in [SIGHUP, SIGTERM] {
+        for &pgrp in pgrps {
+            killpg(pgrp, signal).ok();
+        }
+    }
+    std::thread::sleep(Duration::from_secs(2));
+    for &pgrp in pgrps {
+        killpg(pgrp, SIGKILL).ok();
+    }
+}
+
 trait HandleSigchld: Process {
     const OPTIONS: WaitOptions;
 
@@ -287,47 +257,56 @@ trait HandleSigchld: Process {
     fn on_stop(&mut self, signal: SignalNumber, registry: &mut EventRegistry<Self>);
 }
 
+/// Handle every status change of `child_pid` that is pending. Only that child is waited for, so
+/// the exit of any other child of this process is never mistaken for it, and as several
+/// `SIGCHLD`s may be delivered as one, this keeps waiting until there is nothing left to report.
+/// Once the child has exited or was terminated, it has been reaped and is not waited for again.
 fn handle_sigchld<T: HandleSigchld>(
     handler: &mut T,
     registry: &mut EventRegistry<T>,
     child_name: &'static str,
     child_pid: ProcessId,
 ) {
-    let status = loop {
-        match child_pid.wait(T::OPTIONS) {
-            Err(WaitError::Io(err)) if was_interrupted(&err) => {}
+    let mut reported = false;
+    loop {
+        let status = match child_pid.wait(T::OPTIONS) {
+            Err(WaitError::Io(err)) if was_interrupted(&err) => continue,
             // This only happens if we receive `SIGCHLD` but there's no status update from the
             // monitor.
             Err(WaitError::Io(err)) => {
                 return dev_info!("cannot wait for {child_pid} ({child_name}): {err}");
             }
+            // All status changes have been handled.
+            Err(WaitError::NotReady) if reported => return,
             // This only happens if the monitor exited and any process already waited for the
             // monitor.
             Err(WaitError::NotReady) => {
                 return dev_info!("{child_pid} ({child_name}) has no status report");
             }
-            Ok((_pid, status)) => break status,
+            Ok((_pid, status)) => status,
+        };
+        reported = true;
+
+        if let Some(exit_code) = status.exit_status() {
+            dev_info!("{child_pid} ({child_name}) exited with status code {exit_code}");
+            return handler.on_exit(exit_code, registry);
+        } else if let Some(signal) = status.stop_signal() {
+            dev_info!(
+                "{child_pid} ({child_name}) was stopped by {}",
+                signal_fmt(signal),
+            );
+            handler.on_stop(signal, registry)
+        } else if let Some(signal) = status.term_signal() {
+            dev_info!(
+                "{child_pid} ({child_name}) was terminated by {}",
+                signal_fmt(signal),
+            );
+            return handler.on_term(signal, registry);
+        } else if status.did_continue() {
+            dev_info!("{child_pid} ({child_name}) continued execution");
+        } else {
+            dev_warn!("unexpected wait status for {child_pid} ({child_name})")
         }
-    };
-    if let Some(exit_code) = status.exit_status() {
-        dev_info!("{child_pid} ({child_name}) exited with status code {exit_code}");
-        handler.on_exit(exit_code, registry)
-    } else if let Some(signal) = status.stop_signal() {
-        dev_info!(
-            "{child_pid} ({child_name}) was stopped by {}",
-            signal_fmt(signal),
-        );
-        handler.on_stop(signal, registry)
-    } else if let Some(signal) = status.term_signal() {
-        dev_info!(
-            "{child_pid} ({child_name}) was terminated by {}",
-            signal_fmt(signal),
-        );
-        handler.on_term(signal, registry)
-    } else if status.did_continue() {
-        dev_info!("{child_pid} ({child_name}) continued execution");
-    } else {
-        dev_warn!("unexpected wait status for {child_pid} ({child_name})")
     }
 }
 
@@ -349,3 +328,103 @@ const fn cond_fmt<'a>(cond: bool, true_s: &'a str, false_s: &'a str) -> &'a str
 const fn opt_fmt(cond: bool, s: &str) -> &str {
     cond_fmt(cond, s, "")
 }
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder {
+        exits: Vec<c_int>,
+        stops: Vec<SignalNumber>,
+    }
+
+    impl Process for Recorder {
+        type Event = ();
+        type Break = ();
+        type Exit = c_int;
+
+        fn on_event(&mut self, _event: (), _registry: &mut EventRegistry<Self>) {}
+    }
+
+    impl HandleSigchld for Recorder {
+        const OPTIONS: WaitOptions = WaitOptions::new().all().untraced().no_hang();
+
+        fn on_exit(&mut self, exit_code: c_int, registry: &mut EventRegistry<Self>) {
+            registry.set_exit(exit_code);
+            self.exits.push(exit_code);
+        }
+
+        fn on_term(&mut self, signal: SignalNumber, registry: &mut EventRegistry<Self>) {
+            registry.set_exit(-signal);
+            self.exits.push(-signal);
+        }
+
+        fn on_stop(&mut self, signal: SignalNumber, _registry: &mut EventRegistry<Self>) {
+            self.stops.push(signal);
+        }
+    }
+
+    #[test]
+    fn other_children_do_not_count_as_the_command() {
+        let spawn = |script: &str| Command::new("sh").args(["-c", script]).spawn().unwrap();
+        let pid_of = |child: &std::process::Child| ProcessId::new(child.id() as i32);
+
+        // helpers that exit right before, at the same time as, and after the command
+        let mut helpers = vec![spawn("exit 1"), spawn("sleep 0.2; exit 2")];
+        let mut command = spawn("sleep 0.2; exit 42");
+        helpers.push(spawn("sleep 0.3; exit 3"));
+
+        let mut recorder = Recorder::default();
+        let mut registry = EventRegistry::new();
+        let command_pid = pid_of(&command);
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        while recorder.exits.is_empty() {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "the command was not reaped"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+            handle_sigchld(&mut recorder, &mut registry, "command", command_pid);
+        }
+        // a later SIGCHLD (for one of the helpers) does not report the command again
+        handle_sigchld(&mut recorder, &mut registry, "command", command_pid);
+        assert_eq!(recorder.exits, [42]);
+        assert!(recorder.stops.is_empty());
+
+        // the helpers were left for whoever waits for them
+        for (helper, code) in helpers.iter_mut().zip([1, 2, 3]) {
+            assert_eq!(helper.wait().unwrap().code(), Some(code));
+        }
+        assert!(command.try_wait().is_err());
+    }
+
+    #[test]
+    fn all_pending_status_changes_are_handled() {
+        #[allow(clippy::zombie_processes)]
+        let command = Command::new("sh")
+            .args(["-c", "kill -STOP $$; exit 5"])
+            .spawn()
+            .unwrap();
+        let command_pid = ProcessId::new(command.id() as i32);
+
+        let mut recorder = Recorder::default();
+        let mut registry = EventRegistry::new();
+        while recorder.stops.is_empty() {
+            std::thread::sleep(Duration::from_millis(10));
+            handle_sigchld(&mut recorder, &mut registry, "command", command_pid);
+        }
+        assert_eq!(recorder.stops, [SIGSTOP]);
+        assert!(recorder.exits.is_empty());
+
+        kill(command_pid, SIGCONT).unwrap();
+        while recorder.exits.is_empty() {
+            std::thread::sleep(Duration::from_millis(10));
+            handle_sigchld(&mut recorder, &mut registry, "command", command_pid);
+        }
+        assert_eq!(recorder.exits, [5]);
+        assert_eq!(recorder.stops, [SIGSTOP]);
+    }
+}
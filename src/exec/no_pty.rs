@@ -1,14 +1,16 @@
-use std::{ffi::c_int, io, process::Command};
+use std::{ffi::c_int, io, os::fd::AsFd, time::Duration};
 
 use super::{
     ExitReason, HandleSigchld,
     event::PollEvent,
     event::{EventRegistry, Process, StopReason},
     io_util::was_interrupted,
+    prepare::{ChildError, ChildOutcome, PreparedCommand},
     terminate_process,
 };
 use crate::{
     common::bin_serde::BinPipe,
+    cutils::safe_isatty,
     system::signal::{
         SignalHandler, SignalHandlerBehavior, SignalNumber, SignalSet, SignalStream, SignalsState,
         consts::*, register_handlers,
@@ -16,11 +18,12 @@ use crate::{
 };
 use crate::{
     exec::{SpawnNoexecHandler, exec_command, handle_sigchld, signal_fmt},
+    log::status::{self, Phase},
     log::{dev_error, dev_info, dev_warn},
     system::{
-        ForkResult, fork, getpgid, getpgrp,
+        ForkResult, alarm, fork, getpgid, getpgrp,
         interface::ProcessId,
-        kill, killpg,
+        kill, killpg, setpgid,
         term::{Terminal, UserTerm},
         wait::WaitOptions,
     },
@@ -29,7 +32,8 @@ use crate::{
 pub(super) fn exec_no_pty(
     sudo_pid: ProcessId,
     spawn_noexec_handler: Option<SpawnNoexecHandler>,
-    command: Command,
+    command: PreparedCommand,
+    timeout: Option<Duration>,
 ) -> io::Result<ExitReason> {
     // FIXME (ogsudo): Initialize the policy plugin's session here.
 
@@ -47,6 +51,18 @@ pub(super) fn exec_no_pty(
 
     // FIXME (ogsudo): Some extra config happens here if selinux is available.
 
+    // The command stays in sudo's process group, so if sudo is in the foreground of the user's
+    // terminal the command is as well. Remember the terminal so the foreground can be given
+    // back to sudo if the command hands it to another process group and does not restore it.
+    let foreground_tty = UserTerm::open()
+        .ok()
+        .filter(|tty| tty.is_terminal_for_pgrp(getpgrp()));
+
+    // Unless the command has a timeout: then it gets a process group of its own, so that any
+    // processes it started in that group can be terminated together with it. Sudo's process
+    // group might include the user's shell, so it cannot be used for this.
+    let own_pgrp = timeout.is_some();
+
     // Use a pipe to get the IO error if `exec` fails.
     let (errpipe_tx, errpipe_rx) = BinPipe::pair()?;
 
@@ -56,25 +72,62 @@ pub(super) fn exec_no_pty(
         err
     })?
     else {
-        exec_command(command, original_set, original_signals, errpipe_tx);
+        if own_pgrp {
+            setpgid(ProcessId::new(0), ProcessId::new(0)).ok();
+        }
+
+        exec_command(&command, original_set, original_signals, errpipe_tx);
     };
 
+    if own_pgrp {
+        // This is also done by the command process itself, as it is not known which of the
+        // two gets to run first.
+        if let Err(err) = setpgid(command_pid, command_pid) {
+            dev_info!("cannot set process group of command ({command_pid}): {err}");
+        }
+
+        // Hand the terminal to the command; it is given back to sudo once the command is done.
+        // Not if sudo is part of a pipeline (one of its standard streams is not a terminal):
+        // the other processes of the pipeline share sudo's process group, so they would lose
+        // the terminal while they might need it.
+        let in_pipeline = ![
+            io::stdin().as_fd(),
+            io::stdout().as_fd(),
+            io::stderr().as_fd(),
+        ]
+        .into_iter()
+        .all(safe_isatty);
+
+        if let Some(tty) = foreground_tty.as_ref().filter(|_| !in_pipeline) {
+            if let Err(err) = tty.tcsetpgrp(command_pid) {
+                dev_warn!("cannot set foreground process group to {command_pid} (command): {err}");
+            }
+        }
+    }
+
     if let Some(spawner) = spawn_noexec_handler {
         spawner.spawn();
     }
 
     dev_info!("executed command with pid {command_pid}");
+    status::report(Phase::Exec { pid: command_pid });
 
     let mut registry = EventRegistry::new();
 
     let mut closure = ExecClosure::new(
         command_pid,
         sudo_pid,
+        command,
         errpipe_rx,
         &mut registry,
         original_signals,
+        own_pgrp,
     )?;
 
+    if let Some(timeout) = timeout {
+        alarm(timeout);
+    }
+
     // Restore the signal mask now that the handlers have been setup.
     if let Some(set) = original_set {
         if let Err(err) = set.set_mask() {
@@ -87,6 +140,15 @@ pub(super) fn exec_no_pty(
         StopReason::Exit(reason) => reason,
     };
 
+    // The command is done, so it can no longer time out.
+    if timeout.is_some() {
+        alarm(Duration::ZERO);
+    }
+
+    if let Some(tty) = foreground_tty {
+        closure.restore_foreground(&tty, command_pid);
+    }
+
     // Restore signal handlers
     drop(closure.signal_handlers);
 
@@ -97,7 +159,10 @@ struct ExecClosure {
     command_pid: Option<ProcessId>,
     sudo_pid: ProcessId,
     parent_pgrp: ProcessId,
-    errpipe_rx: BinPipe<i32>,
+    /// Whether the command leads a process group of its own.
+    own_pgrp: bool,
+    command: PreparedCommand,
+    errpipe_rx: BinPipe<ChildError>,
     original_signals: SignalsState,
     signal_stream: &'static SignalStream,
     signal_handlers: [SignalHandler; ExecClosure::SIGNALS.len()],
@@ -112,9 +177,11 @@ impl ExecClosure {
     fn new(
         command_pid: ProcessId,
         sudo_pid: ProcessId,
-        errpipe_rx: BinPipe<i32>,
+        command: PreparedCommand,
+        errpipe_rx: BinPipe<ChildError>,
         registry: &mut EventRegistry<Self>,
         mut original_signals: SignalsState,
+        own_pgrp: bool,
     ) -> io::Result<Self> {
         registry.register_event(&errpipe_rx, PollEvent::Readable, |_| ExecEvent::ErrPipe);
 
@@ -126,9 +193,11 @@ impl ExecClosure {
 
         Ok(Self {
             command_pid: Some(command_pid),
+            command,
             errpipe_rx,
             sudo_pid,
             parent_pgrp: getpgrp(),
+            own_pgrp,
             original_signals,
             signal_stream,
             signal_handlers,
@@ -156,6 +225,44 @@ impl ExecClosure {
         false
     }
 
+    /// Make sudo's process group the foreground process group of `tty` again, if the command
+    /// left it to a process group of its own: the one that it leads, or one that it created
+    /// and that has no processes left. Any other process group got the terminal from someone
+    /// else (e.g. the shell), so it is left alone.
+    fn restore_foreground(&mut self, tty: &UserTerm, command_pid: ProcessId) {
+        let Ok(foreground_pgrp) = tty.tcgetpgrp() else {
+            return;
+        };
+
+        if foreground_pgrp == self.parent_pgrp {
+            return;
+        }
+
+        let left_by_command = foreground_pgrp == command_pid
+            || killpg(foreground_pgrp, 0).is_err_and(|err| err.raw_os_error() == Some(libc::ESRCH));
+        if !left_by_command {
+            dev_info!(
+                "not restoring the foreground: it is held by process group {foreground_pgrp}"
+            );
+            return;
+        }
+
+        // Sudo is in the background now, so `tcsetpgrp` would stop it with `SIGTTOU`.
+        let sigttou_handler = SignalHandler::register(
+            SIGTTOU,
+            SignalHandlerBehavior::Ignore,
+            &mut self.original_signals,
+        )
+        .map_err(|err| dev_warn!("cannot set handler for SIGTTOU: {err}"))
+        .ok();
+
+        if let Err(err) = tty.tcsetpgrp(self.parent_pgrp) {
+            dev_warn!("cannot restore foreground process group: {err}");
+        }
+
+        drop(sigttou_handler);
+    }
+
     /// Suspend the main process.
     fn suspend_parent(&mut self, signal: SignalNumber) {
         let mut opt_tty = UserTerm::open().ok();
@@ -256,7 +363,7 @@ impl ExecClosure {
                 }
 
                 if signal == SIGALRM {
-                    terminate_process(command_pid, false);
+                    terminate_process(command_pid, self.own_pgrp);
                 } else {
                     kill(command_pid, signal).ok();
                 }
@@ -283,10 +390,16 @@ impl Process for ExecClosure {
                 match self.errpipe_rx.read() {
                     Err(err) if was_interrupted(&err) => { /* Retry later */ }
                     Err(err) => registry.set_break(err),
-                    Ok(error_code) => {
-                        // Received error code from the command, forward it to the parent.
-                        registry.set_break(io::Error::from_raw_os_error(error_code));
-                    }
+                    Ok(error) => match self.command.report(error) {
+                        // Received an error from the command, forward it to the parent.
+                        ChildOutcome::Fatal(err) => registry.set_break(err),
+                        // Let the command process know that it can continue.
+                        ChildOutcome::Continues => {
+                            self.errpipe_rx.write(&error).ok();
+                        }
+                        // The command process exits with a status reflecting the error.
+                        ChildOutcome::Exits => {}
+                    },
                 }
             }
         }
@@ -7,8 +7,6 @@ use std::ffi::{c_int, c_uint, c_ulong, c_void};
 use std::mem::{align_of, offset_of, size_of, zeroed};
 use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
 use std::os::unix::net::UnixStream;
-use std::os::unix::process::CommandExt;
-use std::process::Command;
 use std::ptr::{self, addr_of};
 use std::{cmp, io, thread};
 
@@ -237,7 +235,7 @@ fn receive_fd(rx_fd: UnixStream) -> OwnedFd {
     }
 }
 
-fn send_fd(tx_fd: UnixStream, notify_fd: OwnedFd) -> io::Result<()> {
+fn send_fd(tx_fd: &UnixStream, notify_fd: OwnedFd) -> io::Result<()> {
     let mut data = [0u8; 1];
     let mut iov = iovec {
         iov_base: &mut data as *mut [u8; 1] as *mut c_void,
@@ -361,98 +359,98 @@ const GUEST_SYSCALL: (i64, i64) = if cfg!(target_arch = "aarch64") {
 // Bit that is set on syscalls when using the X32 ABI; see man seccomp.
 const __X32_SYSCALL_BIT: u32 = 0x40000000;
 
-pub(crate) fn add_noexec_filter(command: &mut Command) -> io::Result<SpawnNoexecHandler> {
+/// The half of the seccomp setup that is performed by the command process: installing the filter
+/// and passing its notification fd back to sudo.
+pub(crate) struct NoexecFilter(UnixStream);
+
+pub(crate) fn add_noexec_filter() -> io::Result<(NoexecFilter, SpawnNoexecHandler)> {
     let (tx_fd, rx_fd) = UnixStream::pair()?;
 
-    // wrap tx_fd so it can be moved into the closure
-    let mut tx_fd = Some(tx_fd);
+    Ok((NoexecFilter(tx_fd), SpawnNoexecHandler(rx_fd)))
+}
 
-    // SAFETY: See individual SAFETY comments
-    unsafe {
-        // SAFETY: The closure only calls async-signal-safe functions.
-        command.pre_exec(move || {
-            let tx_fd = tx_fd.take().unwrap();
-
-            let nr_offset = offset_of!(seccomp_data, nr);
-            let arch_offset = offset_of!(seccomp_data, arch);
-
-            // SAFETY: libc unnecessarily marks these functions as unsafe
-            #[rustfmt::skip]
-            let exec_filter = [
-                // Load architecture number into the accumulator
-                BPF_STMT((BPF_LD | BPF_ABS) as _, arch_offset as _),
-                // Check if we are any of the recognized architectures
-                BPF_JUMP((BPF_JMP | BPF_JEQ | BPF_K) as _, HOST_ARCH as _, 7, 0),
-                BPF_JUMP((BPF_JMP | BPF_JEQ | BPF_K) as _, GUEST_ARCH as _, 1, 0),
-                // Not a recognized architecture, forbid all syscalls
-                BPF_STMT((BPF_RET | BPF_K) as _, SECCOMP_RET_KILL_PROCESS as _),
-
-                // Guest architecture section
-                // Load syscall number into the accumulator
-                BPF_STMT((BPF_LD | BPF_W | BPF_ABS) as _, nr_offset as _),
-                // Jump to user notify for execve/execveat
-                BPF_JUMP((BPF_JMP | BPF_JEQ | BPF_K) as _, GUEST_SYSCALL.0 as _, 2, 0),
-                BPF_JUMP((BPF_JMP | BPF_JEQ | BPF_K) as _, GUEST_SYSCALL.1 as _, 1, 0),
-                // Allow non-matching syscalls
-                BPF_STMT((BPF_RET | BPF_K) as _, SECCOMP_RET_ALLOW),
-                // Notify sudo about execve/execveat syscall
-                BPF_STMT((BPF_RET | BPF_K) as _, SECCOMP_RET_USER_NOTIF as _),
-
-                // Host architecture section
-                // Load syscall number into the accumulator
-                BPF_STMT((BPF_LD | BPF_W | BPF_ABS) as _, nr_offset as _),
-                // Unset the X32_SYSCALL bit (only necessary on x86_64)
-                #[cfg(target_arch = "x86_64")]
-                BPF_STMT((BPF_ALU | BPF_AND | BPF_K) as _, !__X32_SYSCALL_BIT),
-                // On x86-64 only: check the x32 "design error" syscall numbers
-                #[cfg(target_arch = "x86_64")]
-                BPF_JUMP((BPF_JMP | BPF_JEQ | BPF_K) as _, SYS_execve_x32 as _, 4, 0),
-                #[cfg(target_arch = "x86_64")]
-                BPF_JUMP((BPF_JMP | BPF_JEQ | BPF_K) as _, SYS_execveat_x32 as _, 3, 0),
-                // Jump to user notify for execve/execveat
-                BPF_JUMP((BPF_JMP | BPF_JEQ | BPF_K) as _, SYS_execve as _, 2, 0),
-                BPF_JUMP((BPF_JMP | BPF_JEQ | BPF_K) as _, SYS_execveat as _, 1, 0),
-                // Allow non-matching syscalls
-                BPF_STMT((BPF_RET | BPF_K) as _, SECCOMP_RET_ALLOW),
-                // Notify sudo about execve/execveat syscall
-                BPF_STMT((BPF_RET | BPF_K) as _, SECCOMP_RET_USER_NOTIF as _),
-            ];
-
-            // this is used since we can't yet use "let exec_filter: [sock_filter; _] above"
-            const fn check_type<const N: usize>(_arr: &[sock_filter; N]) {}
-            check_type(&exec_filter);
-
-            let exec_fprog = sock_fprog {
-                len: exec_filter.len() as u16,
-                filter: addr_of!(exec_filter) as *mut sock_filter,
-            };
-
-            // SAFETY: Trivially safe as it doesn't touch any memory.
-            // SECCOMP_SET_MODE_FILTER will fail unless the process has
-            // CAP_SYS_ADMIN or the no_new_privs bit is set.
-            if prctl(PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) == -1 {
-                return Err(io::Error::last_os_error());
-            }
+impl NoexecFilter {
+    /// Install the filter in the current process. This only calls async-signal-safe functions,
+    /// so it can be used between `fork` and `exec`.
+    pub(crate) fn install(&self) -> io::Result<()> {
+        let nr_offset = offset_of!(seccomp_data, nr);
+        let arch_offset = offset_of!(seccomp_data, arch);
+
+        // SAFETY: libc unnecessarily marks these functions as unsafe
+        #[rustfmt::skip]
+        let exec_filter = unsafe { [
+            // Load architecture number into the accumulator
+            BPF_STMT((BPF_LD | BPF_ABS) as _, arch_offset as _),
+            // Check if we are any of the recognized architectures
+            BPF_JUMP((BPF_JMP | BPF_JEQ | BPF_K) as _, HOST_ARCH as _, 7, 0),
+            BPF_JUMP((BPF_JMP | BPF_JEQ | BPF_K) as _, GUEST_ARCH as _, 1, 0),
+            // Not a recognized architecture, forbid all syscalls
+            BPF_STMT((BPF_RET | BPF_K) as _, SECCOMP_RET_KILL_PROCESS as _),
+
+            // Guest architecture section
+            // Load syscall number into the accumulator
+            BPF_STMT((BPF_LD | BPF_W | BPF_ABS) as _, nr_offset as _),
+            // Jump to user notify for execve/execveat
+            BPF_JUMP((BPF_JMP | BPF_JEQ | BPF_K) as _, GUEST_SYSCALL.0 as _, 2, 0),
+            BPF_JUMP((BPF_JMP | BPF_JEQ | BPF_K) as _, GUEST_SYSCALL.1 as _, 1, 0),
+            // Allow non-matching syscalls
+            BPF_STMT((BPF_RET | BPF_K) as _, SECCOMP_RET_ALLOW),
+            // Notify sudo about execve/execveat syscall
+            BPF_STMT((BPF_RET | BPF_K) as _, SECCOMP_RET_USER_NOTIF as _),
+
+            // Host architecture section
+            // Load syscall number into the accumulator
+            BPF_STMT((BPF_LD | BPF_W | BPF_ABS) as _, nr_offset as _),
+            // Unset the X32_SYSCALL bit (only necessary on x86_64)
+            #[cfg(target_arch = "x86_64")]
+            BPF_STMT((BPF_ALU | BPF_AND | BPF_K) as _, !__X32_SYSCALL_BIT),
+            // On x86-64 only: check the x32 "design error" syscall numbers
+            #[cfg(target_arch = "x86_64")]
+            BPF_JUMP((BPF_JMP | BPF_JEQ | BPF_K) as _, SYS_execve_x32 as _, 4, 0),
+            #[cfg(target_arch = "x86_64")]
+            BPF_JUMP((BPF_JMP | BPF_JEQ | BPF_K) as _, SYS_execveat_x32 as _, 3, 0),
+            // Jump to user notify for execve/execveat
+            BPF_JUMP((BPF_JMP | BPF_JEQ | BPF_K) as _, SYS_execve as _, 2, 0),
+            BPF_JUMP((BPF_JMP | BPF_JEQ | BPF_K) as _, SYS_execveat as _, 1, 0),
+            // Allow non-matching syscalls
+            BPF_STMT((BPF_RET | BPF_K) as _, SECCOMP_RET_ALLOW),
+            // Notify sudo about execve/execveat syscall
+            BPF_STMT((BPF_RET | BPF_K) as _, SECCOMP_RET_USER_NOTIF as _),
+        ] };
+
+        // this is used since we can't yet use "let exec_filter: [sock_filter; _] above"
+        const fn check_type<const N: usize>(_arr: &[sock_filter; N]) {}
+        check_type(&exec_filter);
+
+        let exec_fprog = sock_fprog {
+            len: exec_filter.len() as u16,
+            filter: addr_of!(exec_filter) as *mut sock_filter,
+        };
+
+        // SAFETY: Trivially safe as it doesn't touch any memory.
+        // SECCOMP_SET_MODE_FILTER will fail unless the process has
+        // CAP_SYS_ADMIN or the no_new_privs bit is set.
+        if unsafe { prctl(PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
 
-            // While the man page warns against using seccomp_unotify as security
-            // mechanism, the TOCTOU problem that is described there isn't
-            // relevant here. We only SECCOMP_USER_NOTIF_FLAG_CONTINUE the first
-            // execve which is done by ourself and thus trusted.
-            // SAFETY: Passes a valid sock_fprog as argument.
-            let notify_fd = seccomp(
+        // While the man page warns against using seccomp_unotify as security
+        // mechanism, the TOCTOU problem that is described there isn't
+        // relevant here. We only SECCOMP_USER_NOTIF_FLAG_CONTINUE the first
+        // execve which is done by ourself and thus trusted.
+        // SAFETY: Passes a valid sock_fprog as argument.
+        let notify_fd = unsafe {
+            seccomp(
                 SECCOMP_SET_MODE_FILTER,
                 SECCOMP_FILTER_FLAG_NEW_LISTENER as _,
                 addr_of!(exec_fprog).cast_mut(),
-            );
-            if notify_fd < 0 {
-                return Err(io::Error::last_os_error());
-            }
-
-            send_fd(tx_fd, OwnedFd::from_raw_fd(notify_fd))?;
+            )
+        };
+        if notify_fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
 
-            Ok(())
-        });
+        // SAFETY: seccomp returned a new file descriptor that is not owned by anything else.
+        send_fd(&self.0, unsafe { OwnedFd::from_raw_fd(notify_fd) })
     }
-
-    Ok(SpawnNoexecHandler(rx_fd))
 }
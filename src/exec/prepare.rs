@@ -0,0 +1,823 @@
+//! Everything the command process does between `fork` and `execve`.
+//!
+//! At the moment sudo forks, another thread (a name resolver that was given up on, or one started
+//! by a PAM module) may be holding a lock, e.g. the one of the allocator, which would then never
+//! be released in the child. So until the command is executed, the child may only call
+//! async-signal-safe functions: everything that needs allocating or formatting is done up front by
+//! [`PreparedCommand::new`], and [`PreparedCommand::exec`] only performs system calls. Failures
+//! are sent to the parent as a [`ChildError`], and are reported by the parent.
+
+use std::{
+    ffi::{CString, OsStr, c_char, c_int},
+    fs::{self, File, OpenOptions},
+    io::{self, Read},
+    iter,
+    os::{
+        fd::{AsRawFd, OwnedFd},
+        unix::{
+            ffi::{OsStrExt, OsStringExt},
+            fs::OpenOptionsExt,
+        },
+    },
+    path::Path,
+    ptr,
+};
+
+use crate::{
+    common::{
+        bin_serde::{BinPipe, DeSerialize},
+        resolve::on_host,
+    },
+    cutils::cerr,
+    log::user_error,
+    system::{
+        _exit, ProcFs,
+        file::resolve_in_root,
+        inject_group,
+        interface::{GroupId, UserId},
+        mark_fds_as_cloexec_below, open_max,
+    },
+};
+
+use super::{FinalCommand, NoexecFilter, SpawnNoexecHandler, Umask, WorkingDir, add_noexec_filter};
+
+/// The step in which the command process failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub(super) enum ChildStep {
+    /// Setting up file descriptors, seccomp, the root directory or the user and groups.
+    Setup = 0,
+    /// Changing to the working directory.
+    Chdir = 1,
+    /// Executing the command itself.
+    Exec = 2,
+}
+
+/// A failure in the command process, as sent to its parent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct ChildError {
+    pub(super) step: ChildStep,
+    pub(super) errno: c_int,
+}
+
+impl ChildError {
+    fn new(step: ChildStep, err: io::Error) -> Self {
+        Self {
+            step,
+            errno: err.raw_os_error().unwrap_or(libc::EIO),
+        }
+    }
+
+    fn last(step: ChildStep) -> Self {
+        Self::new(step, io::Error::last_os_error())
+    }
+
+    /// The status the command process exits with after this error. Like a shell, use 126 for a
+    /// command that is not allowed to be executed.
+    fn exit_status(&self) -> c_int {
+        if self.step == ChildStep::Exec && self.errno == libc::EACCES {
+            126
+        } else {
+            1
+        }
+    }
+}
+
+/// What happens after the command process reported a [`ChildError`].
+#[derive(Debug)]
+pub(super) enum ChildOutcome {
+    /// The command process waits for the report to be echoed back, and then carries on.
+    Continues,
+    /// The command process exits, with a status that reflects the error.
+    Exits,
+    /// The command cannot be run at all, and sudo should stop with this error.
+    Fatal(io::Error),
+}
+
+impl DeSerialize for ChildError {
+    type Bytes = [u8; 8];
+
+    fn serialize(&self) -> Self::Bytes {
+        let mut buf = [0; 8];
+        let (step_buf, errno_buf) = buf.split_at_mut(4);
+        step_buf.copy_from_slice(&(self.step as i32).to_ne_bytes());
+        errno_buf.copy_from_slice(&self.errno.to_ne_bytes());
+        buf
+    }
+
+    fn deserialize(bytes: Self::Bytes) -> Self {
+        let (step_buf, errno_buf) = bytes.split_at(4);
+        let step = match i32::from_ne_bytes(step_buf.try_into().unwrap()) {
+            1 => ChildStep::Chdir,
+            2 => ChildStep::Exec,
+            _ => ChildStep::Setup,
+        };
+        let errno = c_int::from_ne_bytes(errno_buf.try_into().unwrap());
+
+        Self { step, errno }
+    }
+}
+
+/// A NULL-terminated array of C strings, as expected by `execve`.
+struct CStringArray {
+    _strings: Vec<CString>,
+    pointers: Vec<*const c_char>,
+}
+
+impl CStringArray {
+    fn new(strings: impl IntoIterator<Item = Vec<u8>>, what: &'static str) -> io::Result<Self> {
+        let strings = strings
+            .into_iter()
+            .map(|string| nul_free(string, what))
+            .collect::<io::Result<Vec<_>>>()?;
+        let pointers = strings
+            .iter()
+            .map(|string| string.as_ptr())
+            .chain(iter::once(ptr::null()))
+            .collect();
+
+        Ok(Self {
+            _strings: strings,
+            pointers,
+        })
+    }
+
+    fn as_ptr(&self) -> *const *const c_char {
+        self.pointers.as_ptr()
+    }
+}
+
+fn nul_free(bytes: Vec<u8>, what: &'static str) -> io::Result<CString> {
+    CString::new(bytes).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{what} contains a nul byte"),
+        )
+    })
+}
+
+/// A [`FinalCommand`], translated into the raw values that the system calls in the command
+/// process need.
+pub(super) struct PreparedCommand {
+    path: CString,
+    /// The command itself, which is then executed instead of `path` (see
+    /// [`FinalCommand::command_file`]). Scripts are always executed by their path, since that is
+    /// how their interpreter opens them; the file that runs is then not pinned down.
+    file: Option<OwnedFd>,
+    argv: CStringArray,
+    envp: CStringArray,
+    /// The arguments for `/bin/sh`, in case the command turns out to be a script without `#!`;
+    /// this is not done for commands that may not execute other programs.
+    sh_argv: Option<CStringArray>,
+    /// The file descriptors that become the IO streams of the command; `None` means that the
+    /// stream is inherited from sudo.
+    pub(super) stdin: Option<OwnedFd>,
+    pub(super) stdout: Option<OwnedFd>,
+    pub(super) stderr: Option<OwnedFd>,
+    chroot: Option<CString>,
+    cwd: Option<(CString, WorkingDir)>,
+    groups: Vec<GroupId>,
+    gid: GroupId,
+    uid: UserId,
+    umask: Umask,
+    noexec: Option<NoexecFilter>,
+    /// Upper bound for the file descriptors that may be open, in case they cannot be marked as
+    /// CLOEXEC all at once.
+    open_max: c_int,
+}
+
+impl PreparedCommand {
+    pub(super) fn new(command: FinalCommand) -> io::Result<(Self, Option<SpawnNoexecHandler>)> {
+        let path = nul_free(command.command.into_os_string().into_vec(), "command")?;
+        let file = command.command_file.filter(|file| !is_script(file));
+
+        let sh_argv = iter::once(b"sh".to_vec())
+            .chain(iter::once(path.as_bytes().to_vec()))
+            .chain(
+                command
+                    .argv
+                    .iter()
+                    .skip(1)
+                    .map(|arg| arg.as_bytes().to_vec()),
+            );
+        let sh_argv = if command.noexec {
+            None
+        } else {
+            Some(CStringArray::new(sh_argv, "argument")?)
+        };
+        let argv = CStringArray::new(
+            command.argv.into_iter().map(|arg| arg.into_vec()),
+            "argument",
+        )?;
+
+        let envp = CStringArray::new(
+            command.env.into_iter().map(|(key, value)| {
+                let mut var = key.into_vec();
+                var.push(b'=');
+                var.extend_from_slice(value.as_bytes());
+                var
+            }),
+            "environment variable",
+        )?;
+
+        let chroot = command
+            .chroot
+            .map(|root| nul_free(root.into_os_string().into_vec(), "root directory"))
+            .transpose()?;
+
+        let cwd = command
+            .cwd
+            .map(|cwd| {
+                let (WorkingDir::Required(path) | WorkingDir::Preferred(path)) = &cwd;
+                let path = nul_free(path.as_os_str().as_bytes().to_vec(), "working directory")?;
+                Ok::<_, io::Error>((path, cwd))
+            })
+            .transpose()?;
+
+        let mut groups = command.user.groups;
+        inject_group(command.group.gid, &mut groups);
+        if c_int::try_from(groups.len()).is_err() {
+            return Err(io::Error::other("too many groups"));
+        }
+
+        let (noexec, spawn_noexec_handler) = if command.noexec {
+            let (filter, handler) = add_noexec_filter()?;
+            (Some(filter), Some(handler))
+        } else {
+            (None, None)
+        };
+
+        let open_max = open_max();
+
+        let prepared = Self {
+            path,
+            file,
+            argv,
+            envp,
+            sh_argv,
+            stdin: None,
+            stdout: None,
+            stderr: None,
+            chroot,
+            cwd,
+            groups,
+            gid: command.group.gid,
+            uid: command.user.uid,
+            umask: command.umask,
+            noexec,
+            open_max,
+        };
+
+        Ok((prepared, spawn_noexec_handler))
+    }
+
+    /// Set up the current (just forked) process and replace it by the command. If that fails,
+    /// the error is sent over `errpipe` and the process exits.
+    ///
+    /// Only async-signal-safe functions may be called from here.
+    pub(super) fn exec(&self, errpipe: &mut BinPipe<ChildError>) -> ! {
+        let error = match self.setup(errpipe) {
+            Ok(()) => self.execve(),
+            Err(error) => error,
+        };
+
+        errpipe.write(&error).ok();
+
+        // We call `_exit` instead of `exit` to avoid flushing the parent's IO streams by accident.
+        _exit(error.exit_status());
+    }
+
+    fn setup(&self, errpipe: &mut BinPipe<ChildError>) -> Result<(), ChildError> {
+        let setup_failed = |err| ChildError::new(ChildStep::Setup, err);
+
+        mark_fds_as_cloexec_below(self.open_max).map_err(setup_failed)?;
+
+        for (target, fd) in [&self.stdin, &self.stdout, &self.stderr]
+            .into_iter()
+            .enumerate()
+        {
+            if let Some(fd) = fd {
+                redirect(fd.as_raw_fd(), target as c_int).map_err(setup_failed)?;
+            }
+        }
+
+        if let Some(filter) = &self.noexec {
+            filter.install().map_err(setup_failed)?;
+        }
+
+        // change the root directory if necessary; this has to happen while we are still
+        // privileged, and after all lookups of users and groups (which could otherwise read files
+        // in the new root)
+        if let Some(root) = &self.chroot {
+            // SAFETY: chroot and chdir are passed valid C strings
+            unsafe {
+                cerr(libc::chroot(root.as_ptr())).map_err(setup_failed)?;
+                // do not leave the working directory outside of the new root
+                cerr(libc::chdir(c"/".as_ptr())).map_err(setup_failed)?;
+            }
+        }
+
+        // SAFETY: setgroups is passed a valid pointer to the number of groups that is passed
+        // along, which was checked to fit; `GroupId` is transparent so it can be cast to gid_t.
+        // setgid and setuid set the real, effective and saved version of the gid and uid
+        // respectively rather than just the real gid and uid. The original sudo uses setresgid
+        // and setresuid instead with all three arguments equal, but as this does the same as
+        // setgid and setuid using the latter is fine too.
+        unsafe {
+            cerr(libc::setgroups(
+                self.groups.len() as _,
+                self.groups.as_ptr().cast::<libc::gid_t>(),
+            ))
+            .map_err(setup_failed)?;
+            cerr(libc::setgid(self.gid.inner())).map_err(setup_failed)?;
+            cerr(libc::setuid(self.uid.inner())).map_err(setup_failed)?;
+        }
+
+        if let Some((path, cwd)) = &self.cwd {
+            // SAFETY: chdir is passed a valid C string
+            if unsafe { libc::chdir(path.as_ptr()) } == -1 {
+                let error = ChildError::last(ChildStep::Chdir);
+                match cwd {
+                    WorkingDir::Required(_) => return Err(error),
+                    // The parent reports this; wait until it has done so, to not have the
+                    // message appear in the middle of the output of the command.
+                    WorkingDir::Preferred(_) => {
+                        if errpipe.write(&error).is_ok() {
+                            errpipe.read().ok();
+                        }
+                    }
+                }
+            }
+        }
+
+        // SAFETY: umask is always safe to call
+        unsafe {
+            match self.umask {
+                Umask::Preserve => {}
+                Umask::Extend(umask) => {
+                    // The only options to get the existing umask are overwriting it or
+                    // parsing a /proc file. Given that this is a single-threaded context,
+                    // overwrite it with a safe value is fine and the simpler option.
+                    let existing_umask = libc::umask(0o777);
+                    libc::umask(existing_umask | umask);
+                }
+                Umask::Override(umask) => {
+                    libc::umask(umask);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Only returns if the command could not be executed.
+    fn execve(&self) -> ChildError {
+        match &self.file {
+            // SAFETY: fexecve is passed a file descriptor (which is valid as it is owned) and
+            // NULL-terminated arrays of C strings
+            Some(file) => unsafe {
+                libc::fexecve(file.as_raw_fd(), self.argv.as_ptr(), self.envp.as_ptr())
+            },
+            // SAFETY: execve is passed a valid C string and NULL-terminated arrays of C strings
+            None => unsafe {
+                libc::execve(self.path.as_ptr(), self.argv.as_ptr(), self.envp.as_ptr())
+            },
+        };
+        let error = ChildError::last(ChildStep::Exec);
+
+        if let (libc::ENOEXEC, Some(sh_argv)) = (error.errno, &self.sh_argv) {
+            // like execvp, run a script without `#!` line using the shell
+            // SAFETY: idem
+            unsafe { libc::execve(c"/bin/sh".as_ptr(), sh_argv.as_ptr(), self.envp.as_ptr()) };
+        }
+
+        error
+    }
+
+    /// Report a failure of the command process to the user, and tell what happens next.
+    pub(super) fn report(&self, error: ChildError) -> ChildOutcome {
+        let err = io::Error::from_raw_os_error(error.errno);
+
+        match (error.step, &self.cwd) {
+            (ChildStep::Chdir, Some((_, cwd))) => {
+                let (WorkingDir::Required(path) | WorkingDir::Preferred(path)) = cwd;
+                user_error!(
+                    "unable to change directory to {path}: {error}",
+                    path = path.display(),
+                    error = err
+                );
+
+                match cwd {
+                    WorkingDir::Required(_) => ChildOutcome::Exits,
+                    WorkingDir::Preferred(_) => ChildOutcome::Continues,
+                }
+            }
+            (ChildStep::Exec, _) => {
+                let path = self.command_path();
+                let interpreter = (error.errno == libc::ENOENT)
+                    .then(|| self.missing_interpreter())
+                    .flatten();
+
+                if let Some(interpreter) = interpreter {
+                    user_error!(
+                        "unable to execute {path}: {interpreter}: bad interpreter: {error}",
+                        path = path.display(),
+                        interpreter = interpreter,
+                        error = err
+                    );
+                } else {
+                    user_error!(
+                        "unable to execute {path}: {error}",
+                        path = path.display(),
+                        error = err
+                    );
+                }
+
+                ChildOutcome::Exits
+            }
+            _ => ChildOutcome::Fatal(err),
+        }
+    }
+
+    fn command_path(&self) -> &Path {
+        Path::new(OsStr::from_bytes(self.path.as_bytes()))
+    }
+
+    /// If the command exists, executing it can only have failed with ENOENT because it is a
+    /// script whose interpreter is missing; return the interpreter from its `#!` line.
+    ///
+    /// Inside a chroot, symbolic links are resolved as the command would see them, so that a
+    /// link pointing outside of it cannot be used to read files on the host.
+    fn missing_interpreter(&self) -> Option<String> {
+        let command = self.command_path();
+        let path = match &self.chroot {
+            Some(root) => {
+                let root = Path::new(OsStr::from_bytes(root.as_bytes()));
+                on_host(root, &resolve_in_root(root, command).ok()?)
+            }
+            None => command.to_path_buf(),
+        };
+
+        if !fs::metadata(&path).ok()?.is_file() {
+            return None;
+        }
+
+        let mut line = [0; 256];
+        let len = File::open(&path).ok()?.read(&mut line).ok()?;
+
+        shebang_interpreter(&line[..len])
+    }
+}
+
+/// Whether `file` starts with `#!`. A script cannot be executed through a file descriptor that
+/// is closed on exec, as its interpreter would be told to open that file descriptor. If this
+/// cannot be determined, the file is assumed not to be a script.
+fn is_script(file: &OwnedFd) -> bool {
+    let Ok(path) = ProcFs::path(format!("self/fd/{}", file.as_raw_fd())) else {
+        return false;
+    };
+    // never wait for a writer, should the file have been replaced by a fifo
+    let Ok(mut script) = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)
+    else {
+        return false;
+    };
+
+    let mut start = [0; 2];
+    script.read_exact(&mut start).is_ok() && start == *b"#!"
+}
+
+/// The interpreter named on the `#!` line at the start of `script`.
+fn shebang_interpreter(script: &[u8]) -> Option<String> {
+    let line = script.strip_prefix(b"#!")?.split(|&c| c == b'\n').next()?;
+    let interpreter = line
+        .split(|c| c.is_ascii_whitespace())
+        .find(|word| !word.is_empty())?;
+
+    Some(String::from_utf8_lossy(interpreter).into_owned())
+}
+
+/// Make `fd` available to the command as `target`.
+fn redirect(fd: c_int, target: c_int) -> io::Result<()> {
+    // SAFETY: dup2 and fcntl cannot cause UB, even for invalid file descriptors
+    unsafe {
+        if fd == target {
+            // dup2 would do nothing, leaving the CLOEXEC flag in place
+            cerr(libc::fcntl(fd, libc::F_SETFD, 0))?;
+        } else {
+            cerr(libc::dup2(fd, target))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::OsString,
+        fs,
+        io::Read,
+        os::unix::{
+            fs::{PermissionsExt, symlink},
+            net::UnixStream,
+        },
+        path::{Path, PathBuf},
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+        },
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        common::bin_serde::BinPipe,
+        exec::{FinalCommand, Umask},
+        system::{
+            ForkResult, Group, User, fork, kill,
+            signal::consts::SIGKILL,
+            wait::{Wait, WaitOptions},
+        },
+    };
+
+    use super::{ChildError, ChildStep, PreparedCommand, shebang_interpreter};
+
+    fn script(name: &str, content: &str, mode: u32) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("sudo-{name}-{}", std::process::id()));
+        fs::write(&path, content).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        path
+    }
+
+    fn final_command(command: &str, args: &[&str], env: &[(&str, &str)]) -> FinalCommand {
+        let user = User::real().unwrap().unwrap();
+        let group = Group::from_gid(User::real_gid()).unwrap().unwrap();
+
+        FinalCommand {
+            command: command.into(),
+            command_file: None,
+            argv: std::iter::once(command)
+                .chain(args.iter().copied())
+                .map(OsString::from)
+                .collect(),
+            env: env
+                .iter()
+                .map(|&(key, value)| (key.into(), value.into()))
+                .collect(),
+            cwd: None,
+            chroot: None,
+            user,
+            group,
+            umask: Umask::Preserve,
+            #[cfg(feature = "apparmor")]
+            apparmor_profile: None,
+            background: false,
+            use_pty: false,
+            noexec: false,
+            timeout: None,
+        }
+    }
+
+    /// Run the command in a child process, and return its exit code and whatever it reported
+    /// over the error pipe. Fails if the child does not finish in time.
+    fn run(command: &PreparedCommand) -> (Option<i32>, Option<ChildError>) {
+        let (errpipe_tx, mut errpipe_rx) = BinPipe::pair().unwrap();
+
+        // SAFETY: the child only calls `PreparedCommand::exec`
+        let pid = match unsafe { fork() }.unwrap() {
+            ForkResult::Child => {
+                let mut errpipe_tx = errpipe_tx;
+                command.exec(&mut errpipe_tx)
+            }
+            ForkResult::Parent(pid) => pid,
+        };
+        drop(errpipe_tx);
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let status = loop {
+            match pid.wait(WaitOptions::new().no_hang()) {
+                Ok((_, status)) => break status,
+                Err(_) if Instant::now() < deadline => thread::sleep(Duration::from_millis(1)),
+                Err(_) => {
+                    kill(pid, SIGKILL).ok();
+                    panic!("the command process got stuck before executing the command");
+                }
+            }
+        };
+
+        (status.exit_status(), errpipe_rx.read().ok())
+    }
+
+    /// Changing users is only allowed for root, so otherwise the command process is expected to
+    /// stop right there.
+    fn expected_outcome() -> (Option<i32>, Option<ChildError>) {
+        if User::effective_uid().inner() == 0 {
+            (Some(0), None)
+        } else {
+            let error = ChildError {
+                step: ChildStep::Setup,
+                errno: libc::EPERM,
+            };
+            (Some(1), Some(error))
+        }
+    }
+
+    #[test]
+    fn child_errors_survive_the_pipe() {
+        let (mut tx, mut rx) = BinPipe::<ChildError>::pair().unwrap();
+        for step in [ChildStep::Setup, ChildStep::Chdir, ChildStep::Exec] {
+            let error = ChildError {
+                step,
+                errno: libc::ENOENT,
+            };
+            tx.write(&error).unwrap();
+            assert_eq!(rx.read().unwrap(), error);
+        }
+    }
+
+    #[test]
+    fn environment_is_passed_in_order() {
+        let command = final_command(
+            "/usr/bin/env",
+            &[],
+            &[("ZZZ", "1"), ("AAA", "2"), ("MMM", "3")],
+        );
+        let (mut prepared, _) = PreparedCommand::new(command).unwrap();
+        let (mut output, stdout) = UnixStream::pair().unwrap();
+        prepared.stdout = Some(stdout.into());
+
+        let outcome = run(&prepared);
+        drop(prepared);
+        assert_eq!(outcome, expected_outcome());
+
+        if outcome.1.is_none() {
+            let mut stdout = String::new();
+            output.read_to_string(&mut stdout).unwrap();
+            assert_eq!(stdout, "ZZZ=1\nAAA=2\nMMM=3\n");
+        }
+    }
+
+    #[test]
+    fn nul_bytes_are_rejected() {
+        let command = final_command("/usr/bin/env", &[], &[("FOO", "B\0R")]);
+        assert!(PreparedCommand::new(command).is_err());
+
+        let command = final_command("/usr/bin/env", &["B\0R"], &[]);
+        assert!(PreparedCommand::new(command).is_err());
+    }
+
+    #[test]
+    fn missing_command_is_reported() {
+        if User::effective_uid().inner() != 0 {
+            return;
+        }
+
+        let command = final_command("/nonexistent/command", &[], &[]);
+        let (prepared, _) = PreparedCommand::new(command).unwrap();
+
+        let error = ChildError {
+            step: ChildStep::Exec,
+            errno: libc::ENOENT,
+        };
+        assert_eq!(run(&prepared), (Some(1), Some(error)));
+    }
+
+    #[test]
+    fn script_without_shebang_is_run_by_the_shell() {
+        if User::effective_uid().inner() != 0 {
+            return;
+        }
+
+        let path = script("no-shebang", "exit 3\n", 0o755);
+        let command = final_command(path.to_str().unwrap(), &[], &[]);
+        let (prepared, _) = PreparedCommand::new(command).unwrap();
+        let outcome = run(&prepared);
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(outcome, (Some(3), None));
+    }
+
+    #[test]
+    fn non_executable_file_is_reported() {
+        if User::effective_uid().inner() != 0 {
+            return;
+        }
+
+        let path = script("not-executable", "#!/bin/sh\n", 0o644);
+        let command = final_command(path.to_str().unwrap(), &[], &[]);
+        let (prepared, _) = PreparedCommand::new(command).unwrap();
+        let outcome = run(&prepared);
+        fs::remove_file(path).unwrap();
+
+        let error = ChildError {
+            step: ChildStep::Exec,
+            errno: libc::EACCES,
+        };
+        assert_eq!(outcome, (Some(126), Some(error)));
+    }
+
+    #[test]
+    fn opened_command_is_executed_instead_of_its_path() {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let open = |path: &Path| {
+            fs::OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_PATH)
+                .open(path)
+                .unwrap()
+        };
+
+        // the path has been made to lead to another program since the command was looked up
+        let replaced = script("replaced", "exit 3\n", 0o755);
+        let mut command = final_command(replaced.to_str().unwrap(), &[], &[]);
+        command.command_file = Some(open(Path::new("/bin/true")).into());
+        let (prepared, _) = PreparedCommand::new(command).unwrap();
+        let outcome = run(&prepared);
+        fs::remove_file(replaced).unwrap();
+        assert_eq!(outcome, expected_outcome());
+
+        // but the interpreter of a script can only open it by its path
+        let pinned = script("pinned", "#!/bin/sh\nexit 4\n", 0o755);
+        let mut command = final_command(pinned.to_str().unwrap(), &[], &[]);
+        command.command_file = Some(open(&pinned).into());
+        let (prepared, _) = PreparedCommand::new(command).unwrap();
+        let outcome = run(&prepared);
+        fs::remove_file(pinned).unwrap();
+        if User::effective_uid().inner() == 0 {
+            assert_eq!(outcome, (Some(4), None));
+        } else {
+            assert_eq!(outcome, expected_outcome());
+        }
+    }
+
+    #[test]
+    fn missing_interpreter_is_named() {
+        let path = script("bad-interpreter", "#! /nonexistent/sh -e\nexit 0\n", 0o755);
+        let command = final_command(path.to_str().unwrap(), &[], &[]);
+        let (prepared, _) = PreparedCommand::new(command).unwrap();
+        let interpreter = prepared.missing_interpreter();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(interpreter.as_deref(), Some("/nonexistent/sh"));
+    }
+
+    #[test]
+    fn missing_interpreter_stays_inside_the_chroot() {
+        let root = std::env::temp_dir().join(format!("sudo-jail-{}", std::process::id()));
+        fs::create_dir_all(root.join("bin")).unwrap();
+        let outside = script("escaped", "#!/host/only/sh\n", 0o755);
+        symlink(&outside, root.join("bin/escape")).unwrap();
+
+        let mut command = final_command("/bin/escape", &[], &[]);
+        command.chroot = Some(root.clone());
+        let (prepared, _) = PreparedCommand::new(command).unwrap();
+        let interpreter = prepared.missing_interpreter();
+        fs::remove_dir_all(&root).unwrap();
+        fs::remove_file(outside).unwrap();
+
+        assert_eq!(interpreter, None);
+    }
+
+    #[test]
+    fn shebang_lines() {
+        assert_eq!(shebang_interpreter(b"#!/bin/sh"), Some("/bin/sh".into()));
+        assert_eq!(
+            shebang_interpreter(b"#!/usr/bin/env python3\nprint()"),
+            Some("/usr/bin/env".into())
+        );
+        assert_eq!(shebang_interpreter(b"#!\n/bin/sh"), None);
+        assert_eq!(shebang_interpreter(b"echo #!/bin/sh"), None);
+        assert_eq!(shebang_interpreter(b"\x7fELF"), None);
+    }
+
+    #[test]
+    fn forking_while_other_threads_allocate() {
+        let stop = Arc::new(AtomicBool::new(false));
+        let threads = (0..4)
+            .map(|_| {
+                let stop = stop.clone();
+                thread::spawn(move || {
+                    while !stop.load(Ordering::Relaxed) {
+                        let strings = (0..100).map(|n| n.to_string()).collect::<Vec<_>>();
+                        std::hint::black_box(strings);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let command = final_command("/bin/sh", &["-c", "exit 0"], &[("PATH", "/bin")]);
+        let (prepared, _) = PreparedCommand::new(command).unwrap();
+        for _ in 0..200 {
+            assert_eq!(run(&prepared), expected_outcome());
+        }
+
+        stop.store(true, Ordering::Relaxed);
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::{convert::Infallible, ffi::c_int, io, process::Command};
+use std::{convert::Infallible, ffi::c_int, io};
 
 use crate::exec::{opt_fmt, signal_fmt};
 use crate::system::signal::{
@@ -11,11 +11,12 @@ use crate::{
         event::{EventRegistry, Process},
         exec_command,
         io_util::{retry_while_interrupted, was_interrupted},
+        prepare::{ChildError, ChildOutcome, PreparedCommand},
         use_pty::backchannel::{MonitorBackchannel, MonitorMessage, ParentMessage},
     },
 };
 use crate::{
-    exec::{HandleSigchld, handle_sigchld, terminate_process},
+    exec::{HandleSigchld, handle_sigchld, terminate_process, terminate_process_groups},
     system::{
         _exit, ForkResult, fork, getpgid, getpgrp,
         interface::ProcessId,
@@ -36,7 +37,7 @@ use super::CommandStatus;
 
 pub(super) fn exec_monitor(
     pty_follower: PtyFollower,
-    command: Command,
+    command: PreparedCommand,
     foreground: bool,
     backchannel: &mut MonitorBackchannel,
     original_set: Option<SignalSet>,
@@ -112,7 +113,7 @@ pub(super) fn exec_monitor(
         // Done with the pty follower.
         drop(pty_follower);
 
-        exec_command(command, original_set, original_signals, errpipe_tx)
+        exec_command(&command, original_set, original_signals, errpipe_tx)
     };
 
     // Send the command's PID to the parent.
@@ -125,6 +126,7 @@ pub(super) fn exec_monitor(
     let mut closure = MonitorClosure::new(
         command_pid,
         pty_follower,
+        command,
         errpipe_rx,
         backchannel,
         &mut registry,
@@ -217,7 +219,8 @@ struct MonitorClosure<'a> {
     command_pgrp: ProcessId,
     monitor_pgrp: ProcessId,
     pty_follower: PtyFollower,
-    errpipe_rx: BinPipe<i32>,
+    command: PreparedCommand,
+    errpipe_rx: BinPipe<ChildError>,
     backchannel: &'a mut MonitorBackchannel,
     signal_stream: &'static SignalStream,
     _signal_handlers: [SignalHandler; MonitorClosure::SIGNALS.len()],
@@ -231,7 +234,8 @@ impl<'a> MonitorClosure<'a> {
     fn new(
         command_pid: ProcessId,
         pty_follower: PtyFollower,
-        errpipe_rx: BinPipe<i32>,
+        command: PreparedCommand,
+        errpipe_rx: BinPipe<ChildError>,
         backchannel: &'a mut MonitorBackchannel,
         registry: &mut EventRegistry<Self>,
         original_signals: &mut SignalsState,
@@ -269,6 +273,7 @@ impl<'a> MonitorClosure<'a> {
             command_pgrp,
             monitor_pgrp,
             pty_follower,
+            command,
             errpipe_rx,
             backchannel,
             signal_stream,
@@ -306,10 +311,16 @@ impl<'a> MonitorClosure<'a> {
         match self.errpipe_rx.read() {
             Err(err) if was_interrupted(&err) => { /* Retry later */ }
             Err(err) => registry.set_break(err),
-            Ok(error_code) => {
-                // Received error code from the command, forward it to the parent.
-                registry.set_break(io::Error::from_raw_os_error(error_code));
-            }
+            Ok(error) => match self.command.report(error) {
+                // Received an error from the command, forward it to the parent.
+                ChildOutcome::Fatal(err) => registry.set_break(err),
+                // Let the command process know that it can continue.
+                ChildOutcome::Continues => {
+                    self.errpipe_rx.write(&error).ok();
+                }
+                // The command process exits with a status reflecting the error.
+                ChildOutcome::Exits => {}
+            },
         }
     }
 
@@ -322,7 +333,15 @@ impl<'a> MonitorClosure<'a> {
         );
         match signal {
             SIGALRM => {
-                terminate_process(command_pid, false);
+                // The command has timed out. Besides its own process group, terminate the one
+                // in the foreground of the pty, in case the command started a job there.
+                let mut pgrps = vec![self.command_pgrp];
+                if let Ok(foreground) = self.pty_follower.tcgetpgrp() {
+                    if foreground != self.command_pgrp && foreground != self.monitor_pgrp {
+                        pgrps.push(foreground);
+                    }
+                }
+                terminate_process_groups(&pgrps);
             }
             SIGCONT_FG => {
                 // Continue with the command as the foreground process group
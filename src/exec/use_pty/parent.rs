@@ -2,7 +2,7 @@ use std::collections::VecDeque;
 use std::ffi::c_int;
 use std::io;
 use std::os::fd::{FromRawFd, OwnedFd};
-use std::process::{Command, Stdio};
+use std::time::Duration;
 
 use libc::{O_CLOEXEC, close};
 
@@ -13,9 +13,11 @@ use crate::exec::use_pty::monitor::exec_monitor;
 use crate::exec::{
     ExitReason, SpawnNoexecHandler,
     io_util::retry_while_interrupted,
+    prepare::PreparedCommand,
     use_pty::backchannel::{BackchannelPair, MonitorMessage, ParentBackchannel, ParentMessage},
 };
 use crate::exec::{HandleSigchld, cond_fmt, handle_sigchld, signal_fmt, terminate_process};
+use crate::log::status::{self, Phase};
 use crate::log::{dev_error, dev_info, dev_warn};
 use crate::system::signal::{
     SignalHandler, SignalHandlerBehavior, SignalNumber, SignalSet, SignalStream, SignalsState,
@@ -23,7 +25,7 @@ use crate::system::signal::{
 };
 use crate::system::term::{Pty, PtyFollower, PtyLeader, TermSize, Terminal, UserTerm};
 use crate::system::wait::WaitOptions;
-use crate::system::{_exit, ForkResult, Group, User, chown, fork, getpgrp, kill, killpg};
+use crate::system::{_exit, ForkResult, Group, User, alarm, chown, fork, getpgrp, kill, killpg};
 use crate::system::{getpgid, interface::ProcessId};
 
 use super::pipe::Pipe;
@@ -32,10 +34,11 @@ use super::{CommandStatus, SIGCONT_BG};
 pub(in crate::exec) fn exec_pty(
     sudo_pid: ProcessId,
     spawn_noexec_handler: Option<SpawnNoexecHandler>,
-    mut command: Command,
+    mut command: PreparedCommand,
     user_tty: UserTerm,
     pty_owner: &User,
     background: bool,
+    timeout: Option<Duration>,
 ) -> io::Result<ExitReason> {
     // Allocate a pseudoterminal.
     let pty = get_pty(pty_owner)?;
@@ -81,9 +84,9 @@ pub(in crate::exec) fn exec_pty(
         })
     };
 
-    command.stdin(clone_follower()?);
-    command.stdout(clone_follower()?);
-    command.stderr(clone_follower()?);
+    command.stdin = Some(clone_follower()?.into());
+    command.stdout = Some(clone_follower()?.into());
+    command.stderr = Some(clone_follower()?.into());
 
     let mut registry = EventRegistry::<ParentClosure>::new();
 
@@ -141,7 +144,7 @@ pub(in crate::exec) fn exec_pty(
             }
             // SAFETY: pipe2 created two owned pipe fds.
             unsafe {
-                command.stdin(OwnedFd::from_raw_fd(pipes[0]));
+                command.stdin = Some(OwnedFd::from_raw_fd(pipes[0]));
                 close(pipes[1]);
             }
         } else {
@@ -149,7 +152,7 @@ pub(in crate::exec) fn exec_pty(
             if io::stdin().is_pipe_or_socket() {
                 exec_bg = true;
             }
-            command.stdin(Stdio::inherit());
+            command.stdin = None;
         }
 
         if foreground && parent_pgrp != sudo_pid {
@@ -166,12 +169,12 @@ pub(in crate::exec) fn exec_pty(
             exec_bg = true;
             preserve_oflag = true;
         }
-        command.stdout(Stdio::inherit());
+        command.stdout = None;
     }
 
     if !io::stderr().is_terminal_for_pgrp(parent_pgrp) {
         dev_info!("stderr is not a terminal, command will inherit it");
-        command.stderr(Stdio::inherit());
+        command.stderr = None;
     }
 
     // If there is another process later in the pipeline, don't interfere
@@ -282,6 +285,11 @@ pub(in crate::exec) fn exec_pty(
         original_signals,
     )?;
 
+    // The resulting `SIGALRM` is forwarded to the monitor, which terminates the command.
+    if let Some(timeout) = timeout {
+        alarm(timeout);
+    }
+
     // Restore the signal mask now that the handlers have been setup.
     if let Some(set) = original_set {
         if let Err(err) = set.set_mask() {
@@ -290,6 +298,11 @@ pub(in crate::exec) fn exec_pty(
     }
 
     let exit_reason = closure.run(registry);
+
+    // The command is done, so it can no longer time out.
+    if timeout.is_some() {
+        alarm(Duration::ZERO);
+    }
     // FIXME (ogsudo): Retry if `/dev/tty` is revoked.
 
     // Flush the terminal
@@ -347,6 +360,8 @@ struct ParentClosure {
     foreground: bool,
     term_raw: bool,
     preserve_oflag: bool,
+    /// Whether a hangup of the user's terminal was already passed on to the command.
+    hangup_forwarded: bool,
     backchannel: ParentBackchannel,
     message_queue: VecDeque<MonitorMessage>,
     backchannel_write_handle: EventHandle,
@@ -401,6 +416,7 @@ impl ParentClosure {
             foreground,
             term_raw,
             preserve_oflag,
+            hangup_forwarded: false,
             backchannel,
             message_queue: VecDeque::new(),
             backchannel_write_handle,
@@ -454,6 +470,7 @@ impl ParentClosure {
                     // executing.
                     ParentMessage::CommandPid(pid) => {
                         dev_info!("received command PID ({pid}) from monitor");
+                        status::report(Phase::Exec { pid });
                         self.command_pid = pid.into();
                     }
                     ParentMessage::CommandStatus(status) => {
@@ -730,6 +747,12 @@ impl ParentClosure {
                         // Skip the signal if it was sent by the user and it is self-terminating.
                         return;
                     }
+                } else if signal == SIGHUP {
+                    // The kernel sent this because the user's terminal was hung up.
+                    if self.hangup_forwarded {
+                        return;
+                    }
+                    self.hangup_forwarded = true;
                 }
 
                 // FIXME: check `send_command_status`
@@ -738,6 +761,21 @@ impl ParentClosure {
         }
     }
 
+    /// Stop relaying data between the user's terminal and the pty once the former is gone.
+    ///
+    /// The kernel only sends `SIGHUP` to the session leader and the foreground process group of
+    /// the terminal, which might not include sudo. Make sure the command hears about the hangup
+    /// anyway, but only once.
+    fn on_tty_hangup(&mut self, registry: &mut EventRegistry<Self>) {
+        dev_warn!("tty gone (hung up/closed), discarding command output");
+        self.tty_pipe.detach_left(registry);
+
+        if !self.hangup_forwarded {
+            self.hangup_forwarded = true;
+            self.schedule_signal(SIGHUP, registry);
+        }
+    }
+
     fn handle_sigwinch(&mut self) -> io::Result<()> {
         let new_size = self.tty_pipe.left().get_size()?;
 
@@ -795,8 +833,7 @@ impl Process for ParentClosure {
             ParentEvent::Tty(poll_event) => {
                 // Check if tty which existed is now gone.
                 if self.tty_pipe.left().tcgetsid().is_err() {
-                    dev_warn!("tty gone (closed/detached), ignoring future events");
-                    self.tty_pipe.ignore_events(registry);
+                    self.on_tty_hangup(registry);
                 } else {
                     self.tty_pipe.on_left_event(poll_event, registry).ok();
                 }
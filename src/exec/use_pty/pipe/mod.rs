@@ -17,6 +17,7 @@ pub(super) struct Pipe<L, R> {
     buffer_lr: Buffer<L, R>,
     buffer_rl: Buffer<R, L>,
     background: bool,
+    left_detached: bool,
 }
 
 impl<L: Read + Write + AsFd, R: Read + Write + AsFd> Pipe<L, R> {
@@ -42,6 +43,7 @@ impl<L: Read + Write + AsFd, R: Read + Write + AsFd> Pipe<L, R> {
             left,
             right,
             background: false,
+            left_detached: false,
         }
     }
 
@@ -68,6 +70,16 @@ impl<L: Read + Write + AsFd, R: Read + Write + AsFd> Pipe<L, R> {
         self.buffer_rl.write_handle.ignore(registry);
     }
 
+    /// Stop exchanging data with the left end of this pipe, for example because it was hung up.
+    ///
+    /// The right end is still read from so it does not block when its buffer fills up, but
+    /// everything read from it is discarded.
+    pub(super) fn detach_left<T: Process>(&mut self, registry: &mut EventRegistry<T>) {
+        self.disable_input(registry);
+        self.buffer_rl.discard(registry);
+        self.left_detached = true;
+    }
+
     /// Stop the poll events of the left end of this pipe.
     pub(super) fn disable_input<T: Process>(&mut self, registry: &mut EventRegistry<T>) {
         self.buffer_lr.read_handle.ignore(registry);
@@ -87,7 +99,9 @@ impl<L: Read + Write + AsFd, R: Read + Write + AsFd> Pipe<L, R> {
         }
         self.buffer_lr.write_handle.resume(registry);
         self.buffer_rl.read_handle.resume(registry);
-        self.buffer_rl.write_handle.resume(registry);
+        if !self.left_detached {
+            self.buffer_rl.write_handle.resume(registry);
+        }
     }
 
     /// Handle a poll event for the left side of the pipe.
@@ -114,7 +128,14 @@ impl<L: Read + Write + AsFd, R: Read + Write + AsFd> Pipe<L, R> {
         registry: &mut EventRegistry<T>,
     ) -> io::Result<()> {
         match poll_event {
-            PollEvent::Readable => self.buffer_rl.read(&mut self.right, registry),
+            PollEvent::Readable => {
+                self.buffer_rl.read(&mut self.right, registry)?;
+                if self.left_detached {
+                    self.buffer_rl.discard(registry);
+                }
+
+                Ok(())
+            }
             PollEvent::Writable => {
                 if self.buffer_lr.write(&mut self.right, registry)? && !self.background {
                     self.buffer_lr.read_handle.resume(registry);
@@ -202,6 +223,12 @@ impl<R: Read, W: Write> Buffer<R, W> {
         Ok(())
     }
 
+    /// Drop the bytes in the buffer and stop writing.
+    fn discard<T: Process>(&mut self, registry: &mut EventRegistry<T>) {
+        self.internal.clear();
+        self.write_handle.ignore(registry);
+    }
+
     /// Write bytes from the buffer.
     ///
     /// Calling this function will block until `write` is ready to be written.
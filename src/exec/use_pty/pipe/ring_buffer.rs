@@ -61,6 +61,12 @@ impl RingBuffer {
         self.len == 0
     }
 
+    /// Drop the contents of the buffer.
+    pub(super) fn clear(&mut self) {
+        self.start = 0;
+        self.len = 0;
+    }
+
     pub(super) fn remove<W: Write>(&mut self, write: &mut W) -> io::Result<usize> {
         let removed_len = if self.is_full() {
             // Case 2.1. The buffer is full, meaning that there are two filled slices in `storage`:
@@ -132,6 +138,19 @@ mod tests {
         assert_eq!(expected, found);
     }
 
+    #[test]
+    fn cleared_buffer_is_empty() {
+        let mut buf = RingBuffer::new();
+
+        buf.insert(&mut [0x45; RingBuffer::LEN].as_slice()).unwrap();
+        buf.clear();
+        assert!(buf.is_empty());
+
+        let mut found = vec![];
+        assert_eq!(buf.remove(&mut found).unwrap(), 0);
+        assert!(found.is_empty());
+    }
+
     #[test]
     fn insert_into_empty_buffer_with_offset() {
         const HALF_LEN: usize = RingBuffer::LEN / 2;
@@ -16,11 +16,20 @@ pub(crate) mod system;
 
 mod su;
 mod sudo;
+mod sudo_ts;
 mod visudo;
 
 pub use su::main as su_main;
 pub use sudo::main as sudo_main;
+pub use sudo_ts::main as sudo_ts_main;
 pub use visudo::main as visudo_main;
 
+/// The entry point of the fuzz target in fuzz/
+#[cfg(fuzzing)]
+#[doc(hidden)]
+pub fn fuzz_parse_sudoers(input: &str) {
+    let _ = sudoers::Sudoers::parse_without_includes(input);
+}
+
 #[cfg(feature = "do-not-use-all-features")]
 compile_error!("Refusing to compile using 'cargo --all-features' --- please read the README");
@@ -0,0 +1,354 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use crate::system::audit::secure_open_log_file;
+use crate::system::file::FileLock;
+use crate::system::time::format_local_time;
+
+/// The prefix of every continuation line of a wrapped log entry.
+const INDENT: &str = "    ";
+
+/// How often the log file is opened again when it turns out to have been replaced (e.g. by
+/// logrotate) while waiting for the lock on it.
+const MAX_REOPEN: usize = 3;
+
+/// A single event, in the form in which it is reported to syslog and the log file.
+pub struct Entry<'a> {
+    pub user: &'a str,
+    pub host: Option<&'a str>,
+    pub message: &'a str,
+}
+
+impl fmt::Display for Entry<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} : ", self.user)?;
+        if let Some(host) = self.host {
+            write!(f, "HOST={host} : ")?;
+        }
+        f.write_str(self.message)
+    }
+}
+
+/// The log file set by `Defaults logfile`, along with the options that determine its format.
+pub struct LogFile {
+    pub path: PathBuf,
+    pub log_year: bool,
+    pub log_host: bool,
+    pub line_len: usize,
+    pub locale: String,
+}
+
+impl LogFile {
+    pub fn log(&self, user: &str, host: &str, message: &str) {
+        let Ok(now) = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) else {
+            return;
+        };
+        let Some(text) = self.format(now.as_secs() as i64, user, host, message) else {
+            return;
+        };
+
+        // like syslog, logging to a file is done on a best-effort basis
+        let _ = self.append(text.as_bytes(), |path| secure_open_log_file(path));
+    }
+
+    /// Append `text` (a complete, possibly wrapped, entry) to the log file in a single write
+    /// while holding a lock on it, so that entries of concurrent invocations never get mixed
+    /// up. The file is opened with `open`; if it was rotated before the lock was obtained, the
+    /// file that now exists at the path is used instead.
+    fn append(&self, text: &[u8], open: impl Fn(&Path) -> io::Result<File>) -> io::Result<()> {
+        for _ in 0..MAX_REOPEN {
+            let file = open(&self.path)?;
+            let _lock = FileLock::exclusive(&file, false)?;
+            if self.is_current(&file)? {
+                return (&file).write_all(text);
+            }
+        }
+
+        Err(io::Error::other(format!(
+            "{} keeps being replaced",
+            self.path.display()
+        )))
+    }
+
+    /// Whether `file` is (still) the file at the configured path.
+    fn is_current(&self, file: &File) -> io::Result<bool> {
+        let opened = file.metadata()?;
+        match fs::symlink_metadata(&self.path) {
+            Ok(meta) => Ok(meta.dev() == opened.dev() && meta.ino() == opened.ino()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn format(&self, time: i64, user: &str, host: &str, message: &str) -> Option<String> {
+        let time_format = if self.log_year {
+            c"%h %e %T %Y"
+        } else {
+            c"%h %e %T"
+        };
+        let timestamp = format_local_time(time, time_format, &self.locale)?;
+
+        let entry = Entry {
+            user,
+            host: self.log_host.then_some(host),
+            message,
+        };
+
+        Some(wrap(&format!("{timestamp} : {entry}"), self.line_len))
+    }
+}
+
+/// Break `line` on spaces into lines that are at most `max_len` bytes long where possible,
+/// prefixing every continuation line with an indentation that counts towards that length.
+/// A `max_len` that is too short to hold the indentation disables wrapping.
+fn wrap(line: &str, mut max_len: usize) -> String {
+    let mut result = String::new();
+    let mut indent = "";
+    let mut rest = line;
+
+    if max_len > INDENT.len() {
+        while rest.len() > max_len {
+            let bytes = rest.as_bytes();
+            // the last space that fits; failing that, the first space after it
+            let end = match bytes[..=max_len].iter().rposition(|&c| c == b' ') {
+                Some(pos) if pos > 0 => pos,
+                _ => match bytes[max_len..].iter().position(|&c| c == b' ') {
+                    Some(pos) => max_len + pos,
+                    None => break,
+                },
+            };
+
+            result.push_str(indent);
+            result.push_str(&rest[..end]);
+            result.push('\n');
+            rest = &rest[end + 1..];
+
+            if indent.is_empty() {
+                indent = INDENT;
+                max_len -= INDENT.len();
+            }
+        }
+    }
+
+    if !rest.is_empty() {
+        result.push_str(indent);
+        result.push_str(rest);
+    }
+    result.push('\n');
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGE: &str = "TTY=pts/0 ; PWD=/home/ferris ; USER=root ; COMMAND=/usr/bin/ls -l --color=auto /etc/sudoers.d /var/log/extremely/long/path/name/that/does/not/fit/anywhere";
+
+    // September 9th 2001, 01:46:40 UTC
+    const TIME: i64 = 1_000_000_000;
+
+    fn logfile(log_year: bool, log_host: bool, line_len: usize) -> LogFile {
+        LogFile {
+            path: PathBuf::new(),
+            log_year,
+            log_host,
+            line_len,
+            locale: "C".to_string(),
+        }
+    }
+
+    fn format(log_year: bool, log_host: bool, line_len: usize) -> String {
+        logfile(log_year, log_host, line_len)
+            .format(TIME, "ferris", "crab", MESSAGE)
+            .unwrap()
+    }
+
+    /// Remove the timestamp (which depends on the local timezone) from the first line
+    fn strip_timestamp(text: &str) -> &str {
+        text.split_once(" : ").unwrap().1
+    }
+
+    #[test]
+    fn entries() {
+        let entry = Entry {
+            user: "ferris",
+            host: None,
+            message: "hello",
+        };
+        assert_eq!(entry.to_string(), "ferris : hello");
+
+        let entry = Entry {
+            host: Some("crab"),
+            ..entry
+        };
+        assert_eq!(entry.to_string(), "ferris : HOST=crab : hello");
+    }
+
+    #[test]
+    fn timestamps() {
+        let text = format(false, false, 0);
+        let (timestamp, _) = text.split_once(" : ").unwrap();
+        assert_eq!(timestamp.len(), "Sep  9 01:46:40".len());
+        assert!(timestamp.starts_with("Sep "));
+
+        let text = format(true, false, 0);
+        let (timestamp, _) = text.split_once(" : ").unwrap();
+        assert_eq!(timestamp.len(), "Sep  9 01:46:40 2001".len());
+        assert!(timestamp.ends_with(" 2001"));
+    }
+
+    #[test]
+    fn no_wrapping() {
+        for line_len in [0, 1, 4] {
+            assert_eq!(
+                strip_timestamp(&format(false, false, line_len)),
+                format!("ferris : {MESSAGE}\n")
+            );
+        }
+        assert_eq!(
+            strip_timestamp(&format(true, true, 0)),
+            format!("ferris : HOST=crab : {MESSAGE}\n")
+        );
+        assert_eq!(
+            strip_timestamp(&format(false, false, 1000)),
+            format!("ferris : {MESSAGE}\n")
+        );
+    }
+
+    #[test]
+    fn wrapping() {
+        let text = format(false, false, 80);
+        assert_eq!(text.lines().next().unwrap().len(), 69);
+        assert_eq!(
+            strip_timestamp(&text),
+            "ferris : TTY=pts/0 ; PWD=/home/ferris ; USER=root ;
+    COMMAND=/usr/bin/ls -l --color=auto /etc/sudoers.d
+    /var/log/extremely/long/path/name/that/does/not/fit/anywhere
+"
+        );
+
+        let text = format(true, true, 80);
+        assert_eq!(
+            strip_timestamp(&text),
+            "ferris : HOST=crab : TTY=pts/0 ; PWD=/home/ferris ;
+    USER=root ; COMMAND=/usr/bin/ls -l --color=auto /etc/sudoers.d
+    /var/log/extremely/long/path/name/that/does/not/fit/anywhere
+"
+        );
+
+        let text = format(false, false, 40);
+        assert_eq!(
+            strip_timestamp(&text),
+            "ferris : TTY=pts/0 ;
+    PWD=/home/ferris ; USER=root ;
+    COMMAND=/usr/bin/ls -l --color=auto
+    /etc/sudoers.d
+    /var/log/extremely/long/path/name/that/does/not/fit/anywhere
+"
+        );
+    }
+
+    fn open_for_appending(path: &Path) -> io::Result<File> {
+        fs::OpenOptions::new().append(true).create(true).open(path)
+    }
+
+    fn temporary_logfile(line_len: usize) -> LogFile {
+        LogFile {
+            path: crate::system::file::create_temporary_dir()
+                .unwrap()
+                .join("sudo.log"),
+            ..logfile(false, false, line_len)
+        }
+    }
+
+    #[test]
+    fn concurrent_entries_are_not_interleaved() {
+        let log = &temporary_logfile(40);
+        let entry = &|thread: usize, n: usize| {
+            log.format(
+                TIME,
+                &format!("user{thread}"),
+                "crab",
+                &format!("#{n} {MESSAGE}"),
+            )
+            .unwrap()
+        };
+
+        std::thread::scope(|scope| {
+            for thread in 0..8 {
+                scope.spawn(move || {
+                    for n in 0..50 {
+                        let text = entry(thread, n);
+                        log.append(text.as_bytes(), open_for_appending).unwrap();
+                    }
+                });
+            }
+        });
+
+        let contents = fs::read_to_string(&log.path).unwrap();
+        let mut entries = Vec::new();
+        for line in contents.lines() {
+            if line.starts_with(INDENT) {
+                let last: &mut String = entries.last_mut().unwrap();
+                last.push_str(line);
+            } else {
+                entries.push(line.to_string());
+            }
+            entries.last_mut().unwrap().push('\n');
+        }
+
+        let mut expected = (0..8)
+            .flat_map(|thread| (0..50).map(move |n| entry(thread, n)))
+            .collect::<Vec<_>>();
+        expected.sort();
+        entries.sort();
+        assert_eq!(entries, expected);
+
+        fs::remove_dir_all(log.path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn rotated_file_is_reopened() {
+        let log = temporary_logfile(0);
+        let rotated = log.path.with_extension("log.1");
+        fs::write(&log.path, "old\n").unwrap();
+
+        // keep the current file locked, so that the entry below has to wait until it has
+        // been rotated away
+        let file = open_for_appending(&log.path).unwrap();
+        let lock = FileLock::exclusive(&file, false).unwrap();
+
+        let (opened, has_opened) = std::sync::mpsc::channel();
+        std::thread::scope(|scope| {
+            let writer = scope.spawn(|| {
+                log.append(b"new\n", |path| {
+                    let file = open_for_appending(path);
+                    let _ = opened.send(());
+                    file
+                })
+            });
+
+            has_opened.recv().unwrap();
+            fs::rename(&log.path, &rotated).unwrap();
+            drop(lock);
+
+            writer.join().unwrap().unwrap();
+        });
+
+        assert_eq!(fs::read_to_string(&rotated).unwrap(), "old\n");
+        assert_eq!(fs::read_to_string(&log.path).unwrap(), "new\n");
+
+        fs::remove_dir_all(log.path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn wrapping_without_spaces() {
+        assert_eq!(wrap("abcdefghij", 5), "abcdefghij\n");
+        assert_eq!(wrap("abcdefghij klm", 5), "abcdefghij\n    klm\n");
+        assert_eq!(wrap("abc def ghi jkl", 8), "abc def\n    ghi\n    jkl\n");
+    }
+}
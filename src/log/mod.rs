@@ -4,9 +4,27 @@ use std::fmt;
 use std::ops::Deref;
 use std::sync::OnceLock;
 
+mod journald;
+mod logfile;
 mod simple_logger;
+pub(crate) mod status;
 mod syslog;
 
+pub use journald::JournalEntry;
+pub use logfile::{Entry, LogFile};
+pub use syslog::include_pid as syslog_include_pid;
+
+/// Where commands that are run or denied are recorded (`Defaults log_backend`), besides the
+/// log file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogBackend {
+    Syslog,
+    /// Structured entries in the systemd journal; syslog is used if journald is not running.
+    Journald,
+    /// Only the log file; syslog is used if no log file has been set.
+    File,
+}
+
 macro_rules! logger_macro {
     ($name:ident is $rule_level:ident to $target:literal with $filter:ident, $d:tt) => {
         macro_rules! $name {
@@ -28,7 +46,7 @@ macro_rules! logger_macro {
 // logger_macro!(auth_error is Error to "sudo::auth");
 logger_macro!(auth_warn is Warn to "sudo::auth" with format_args);
 logger_macro!(auth_info is Info to "sudo::auth" with format_args);
-// logger_macro!(auth_debug is Debug to "sudo::auth" with format_args);
+logger_macro!(auth_debug is Debug to "sudo::auth" with format_args);
 
 logger_macro!(user_error is Error to "sudo::user" with xlat);
 logger_macro!(user_warn is Warn to "sudo::user" with xlat);
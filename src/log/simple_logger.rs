@@ -4,6 +4,7 @@ use std::io::Write;
 #[cfg(feature = "dev")]
 use std::{fs::File, path::Path};
 
+use crate::cutils::escape_control_chars;
 use crate::log::{Level, Log};
 
 pub struct SimpleLogger<W: Send + Sync>
@@ -19,7 +20,11 @@ where
     for<'a> &'a W: Write,
 {
     fn log(&self, _level: Level, args: &dyn fmt::Display) {
-        let s = format!("{}{}\n", self.prefix, args);
+        let s = format!(
+            "{}{}\n",
+            self.prefix,
+            escape_control_chars(&args.to_string())
+        );
         let _ = (&self.target).write_all(s.as_bytes());
         let _ = (&self.target).flush();
     }
@@ -94,4 +99,21 @@ mod tests {
         assert_eq!(value, "[test] Hello World!\nflushed");
         drop(value);
     }
+
+    #[test]
+    fn control_characters_are_escaped() {
+        let target = MyString::default();
+        let logger = SimpleLogger {
+            target: target.clone(),
+            prefix: "[test] ",
+        };
+
+        let user = "\x1b]0;root\x07ferris";
+        logger.log(Level::Warn, &format_args!("user {user} not found"));
+
+        assert_eq!(
+            target.read(),
+            "[test] user \\u{1b}]0;root\\u{7}ferris not found\nflushed"
+        );
+    }
 }
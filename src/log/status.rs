@@ -0,0 +1,183 @@
+//! Machine-readable progress of sudo (`--status-json`), for tools that run commands through
+//! sudo and want to know how far it got: every phase is written as a JSON object on a line of
+//! its own. Nothing that the user typed or that the command gets to see (such as passwords or
+//! environment variables) is ever included.
+
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::fd::{FromRawFd, RawFd};
+use std::sync::OnceLock;
+
+use crate::cutils::cerr;
+use crate::system::interface::ProcessId;
+use crate::system::signal::{SignalNumber, signal_name};
+
+static STATUS_FILE: OnceLock<File> = OnceLock::new();
+
+pub(crate) enum Phase<'a> {
+    PolicyLoaded,
+    Judged {
+        allowed: bool,
+        must_authenticate: bool,
+    },
+    Authenticated,
+    SessionOpened,
+    Exec {
+        pid: ProcessId,
+    },
+    Exited {
+        code: i32,
+    },
+    Killed {
+        signal: SignalNumber,
+    },
+    Failed {
+        error: &'a str,
+    },
+}
+
+impl Phase<'_> {
+    fn to_json(&self) -> String {
+        let (phase, fields) = match self {
+            Phase::PolicyLoaded => ("policy-loaded", vec![]),
+            Phase::Judged {
+                allowed,
+                must_authenticate,
+            } => (
+                "judged",
+                vec![
+                    ("allowed", allowed.to_string()),
+                    ("must_authenticate", must_authenticate.to_string()),
+                ],
+            ),
+            Phase::Authenticated => ("authenticated", vec![]),
+            Phase::SessionOpened => ("session-opened", vec![]),
+            Phase::Exec { pid } => ("exec", vec![("pid", pid.to_string())]),
+            Phase::Exited { code } => ("exited", vec![("code", code.to_string())]),
+            Phase::Killed { signal } => (
+                "exited",
+                vec![("signal", json_string(&signal_name(*signal)))],
+            ),
+            Phase::Failed { error } => ("failed", vec![("error", json_string(error))]),
+        };
+
+        let mut json = format!("{{\"phase\":{}", json_string(phase));
+        for (name, value) in fields {
+            let _ = write!(json, ",{}:{value}", json_string(name));
+        }
+        json.push('}');
+
+        json
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+/// Write the progress of sudo to `fd`, which must have been open for writing when sudo was
+/// started: any descriptor that sudo opens later on (to read the sudoers file, for instance)
+/// could otherwise end up with this number. This must be called before sudo opens any files.
+pub(crate) fn enable(fd: RawFd) -> io::Result<()> {
+    // SAFETY: F_GETFL can be used on any descriptor, and fails if it is not open
+    let flags = cerr(unsafe { libc::fcntl(fd, libc::F_GETFL) })?;
+    if flags & libc::O_ACCMODE == libc::O_RDONLY {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "not open for writing",
+        ));
+    }
+
+    // the command does not get to write to it
+    // SAFETY: the descriptor is open, and only its close-on-exec flag is changed
+    cerr(unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) })?;
+
+    // SAFETY: the descriptor is open; the invoking process handed it to sudo for this purpose,
+    // and it is not in use by anything else in sudo, so sudo may take ownership of it
+    let file = unsafe { File::from_raw_fd(fd) };
+    if STATUS_FILE.set(file).is_err() {
+        panic!("the status file descriptor was already set");
+    }
+
+    Ok(())
+}
+
+/// Report that sudo has reached `phase`, if `--status-json` was given; like logging, this
+/// never fails.
+pub(crate) fn report(phase: Phase) {
+    if let Some(mut file) = STATUS_FILE.get() {
+        let _ = file.write_all(format!("{}\n", phase.to_json()).as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::fd::AsRawFd;
+
+    use super::*;
+
+    #[test]
+    fn phases_as_json() {
+        assert_eq!(
+            Phase::PolicyLoaded.to_json(),
+            r#"{"phase":"policy-loaded"}"#
+        );
+        assert_eq!(
+            Phase::Judged {
+                allowed: true,
+                must_authenticate: false
+            }
+            .to_json(),
+            r#"{"phase":"judged","allowed":true,"must_authenticate":false}"#
+        );
+        assert_eq!(
+            Phase::Exec {
+                pid: ProcessId::new(1234)
+            }
+            .to_json(),
+            r#"{"phase":"exec","pid":1234}"#
+        );
+        assert_eq!(
+            Phase::Exited { code: 3 }.to_json(),
+            r#"{"phase":"exited","code":3}"#
+        );
+        assert_eq!(
+            Phase::Killed {
+                signal: libc::SIGTERM
+            }
+            .to_json(),
+            r#"{"phase":"exited","signal":"SIGTERM"}"#
+        );
+        assert_eq!(
+            Phase::Failed {
+                error: "a \"quoted\"\nerror\x1b"
+            }
+            .to_json(),
+            r#"{"phase":"failed","error":"a \"quoted\"\nerror\u001b"}"#
+        );
+    }
+
+    #[test]
+    fn only_writable_descriptors_are_accepted() {
+        let read_only = File::open("/dev/null").unwrap();
+        assert!(enable(read_only.as_raw_fd()).is_err());
+
+        assert!(enable(-1).is_err());
+    }
+}
@@ -130,6 +130,11 @@ impl Write for SysLogMessageWriter {
 
 const FACILITY: c_int = libc::LOG_AUTH;
 
+/// Include the process ID in every message that is sent to syslog from now on.
+pub fn include_pid() {
+    crate::system::openlog(libc::LOG_PID, FACILITY);
+}
+
 impl Log for Syslog {
     fn log(&self, level: Level, args: &dyn fmt::Display) {
         let priority = match level {
@@ -1,18 +1,21 @@
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
 use std::ffi::{c_int, c_void};
+use std::io;
+use std::sync::Arc;
 use std::time::Duration;
 
-use crate::cutils::string_from_ptr;
+use crate::cutils::{escape_control_chars, string_from_ptr};
 use crate::pam::rpassword::Hidden;
 use crate::system::signal::{self, SignalSet};
 
 use super::sys::*;
 
+use super::watchdog::Watchdog;
 use super::{PamError, PamErrorType, error::PamResult, rpassword, securemem::PamBuffer};
 
 /// Each message in a PAM conversation will have a message style. Each of these
 /// styles must be handled separately.
-#[derive(Clone, Copy, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum PamMessageStyle {
     /// Prompt for input using a message. The input should considered secret
     /// and should be hidden from view.
@@ -41,6 +44,10 @@ impl PamMessageStyle {
     }
 }
 
+/// The maximum number of messages in a single call of the conversation function; this
+/// limit is the same in Linux-PAM and OpenPAM.
+const PAM_MAX_NUM_MSG: c_int = 32;
+
 pub trait Converser {
     /// Handle a normal prompt, i.e. present some message and ask for a value.
     /// The value is not considered a secret.
@@ -59,6 +66,24 @@ pub trait Converser {
     fn handle_info(&self, msg: &str) -> PamResult<()>;
 }
 
+impl<C: Converser + ?Sized> Converser for Box<C> {
+    fn handle_normal_prompt(&self, msg: &str) -> PamResult<PamBuffer> {
+        (**self).handle_normal_prompt(msg)
+    }
+
+    fn handle_hidden_prompt(&self, msg: &str) -> PamResult<PamBuffer> {
+        (**self).handle_hidden_prompt(msg)
+    }
+
+    fn handle_error(&self, msg: &str) -> PamResult<()> {
+        (**self).handle_error(msg)
+    }
+
+    fn handle_info(&self, msg: &str) -> PamResult<()> {
+        (**self).handle_info(msg)
+    }
+}
+
 /// Handle a single message in a conversation.
 fn handle_message<C: Converser>(
     app_data: &ConverserData<C>,
@@ -68,8 +93,6 @@ fn handle_message<C: Converser>(
     use PamMessageStyle::*;
 
     match style {
-        PromptEchoOn | PromptEchoOff if app_data.no_interact => Err(PamError::InteractionRequired),
-
         PromptEchoOn => app_data.converser.handle_normal_prompt(msg).map(Some),
         PromptEchoOff => {
             let final_prompt = match app_data.auth_prompt.as_deref() {
@@ -96,7 +119,6 @@ fn handle_message<C: Converser>(
 /// input from the user.
 pub struct CLIConverser {
     pub(super) name: String,
-    pub(super) use_askpass: bool,
     pub(super) use_stdin: bool,
     pub(super) bell: Cell<bool>,
     pub(super) password_feedback: bool,
@@ -130,9 +152,7 @@ impl Drop for SignalGuard {
 
 impl CLIConverser {
     fn open(&self) -> PamResult<(Terminal<'_>, SignalGuard)> {
-        let term = if self.use_askpass {
-            Terminal::open_askpass()?
-        } else if self.use_stdin {
+        let term = if self.use_stdin {
             Terminal::open_stdie()?
         } else {
             let mut tty = Terminal::open_tty()?;
@@ -149,19 +169,22 @@ impl CLIConverser {
 
 impl Converser for CLIConverser {
     fn handle_normal_prompt(&self, msg: &str) -> PamResult<PamBuffer> {
+        let msg = escape_control_chars(msg);
         let (mut tty, _guard) = self.open()?;
         let input_needed = xlat!("input needed");
+        // like in original sudo, passwd_timeout applies to every question from PAM
         tty.read_input(
             &format!("[{}: {input_needed} {msg} ", self.name),
-            None,
+            self.password_timeout,
             Hidden::No,
         )
     }
 
     fn handle_hidden_prompt(&self, msg: &str) -> PamResult<PamBuffer> {
+        let msg = escape_control_chars(msg);
         let (mut tty, _guard) = self.open()?;
         tty.read_input(
-            msg,
+            &msg,
             self.password_timeout,
             if self.password_feedback {
                 Hidden::WithFeedback(())
@@ -172,27 +195,126 @@ impl Converser for CLIConverser {
     }
 
     fn handle_error(&self, msg: &str) -> PamResult<()> {
+        let msg = escape_control_chars(msg);
         let (mut tty, _) = self.open()?;
-        Ok(tty.prompt(&format!("[{} error] {msg}\n", self.name))?)
+        let newline = line_end(&msg);
+        Ok(tty.prompt(&format!("[{} error] {msg}{newline}", self.name))?)
     }
 
     fn handle_info(&self, msg: &str) -> PamResult<()> {
+        let msg = escape_control_chars(msg);
         let (mut tty, _) = self.open()?;
-        Ok(tty.prompt(&format!("[{}] {msg}\n", self.name))?)
+        let newline = line_end(&msg);
+        Ok(tty.prompt(&format!("[{}] {msg}{newline}", self.name))?)
+    }
+}
+
+/// A converser for when the user may not be asked for anything (`sudo -n`): every prompt fails
+/// with [PamError::InteractionRequired], and messages are written to `output` (stderr, outside
+/// of tests), so that the terminal is never opened. This also works when there is no terminal
+/// at all, e.g. in cron.
+pub struct NonInteractiveConverser<W = io::Stderr> {
+    pub(super) name: String,
+    pub(super) output: RefCell<W>,
+}
+
+impl<W: io::Write> NonInteractiveConverser<W> {
+    fn show(&self, text: std::fmt::Arguments) {
+        // like eprintln_ignore_io_error!, failing to show a message does not fail the conversation
+        let _ = writeln!(self.output.borrow_mut(), "{text}");
+    }
+}
+
+impl<W: io::Write> Converser for NonInteractiveConverser<W> {
+    fn handle_normal_prompt(&self, _msg: &str) -> PamResult<PamBuffer> {
+        Err(PamError::InteractionRequired)
+    }
+
+    fn handle_hidden_prompt(&self, _msg: &str) -> PamResult<PamBuffer> {
+        Err(PamError::InteractionRequired)
+    }
+
+    fn handle_error(&self, msg: &str) -> PamResult<()> {
+        let msg = escape_control_chars(msg);
+        let msg = msg.strip_suffix('\n').unwrap_or(&msg);
+        self.show(format_args!("[{} error] {msg}", self.name));
+        Ok(())
+    }
+
+    fn handle_info(&self, msg: &str) -> PamResult<()> {
+        let msg = escape_control_chars(msg);
+        let msg = msg.strip_suffix('\n').unwrap_or(&msg);
+        self.show(format_args!("[{}] {msg}", self.name));
+        Ok(())
+    }
+}
+
+/// A converser for `sudo -A`: every question is answered by the program in `SUDO_ASKPASS`,
+/// which gets the prompt as its only argument and writes the answer to its standard output.
+/// Messages are shown in the same way as by [NonInteractiveConverser], since the helper is
+/// typically used when there is no terminal to show them on.
+pub struct AskpassConverser<W = io::Stderr> {
+    pub(super) messages: NonInteractiveConverser<W>,
+}
+
+impl<W> AskpassConverser<W> {
+    fn ask(&self, prompt: &str) -> PamResult<PamBuffer> {
+        Terminal::open_askpass()?.read_input(prompt, None, Hidden::No)
+    }
+}
+
+impl<W: io::Write> Converser for AskpassConverser<W> {
+    fn handle_normal_prompt(&self, msg: &str) -> PamResult<PamBuffer> {
+        let msg = escape_control_chars(msg);
+        let input_needed = xlat!("input needed");
+        self.ask(&format!("[{}: {input_needed} {msg} ", self.messages.name))
+    }
+
+    fn handle_hidden_prompt(&self, msg: &str) -> PamResult<PamBuffer> {
+        self.ask(&escape_control_chars(msg))
+    }
+
+    fn handle_error(&self, msg: &str) -> PamResult<()> {
+        self.messages.handle_error(msg)
     }
+
+    fn handle_info(&self, msg: &str) -> PamResult<()> {
+        self.messages.handle_info(msg)
+    }
+}
+
+/// The newline needed to end a message from a PAM module; some modules already end their
+/// messages with one, others leave that to the application.
+fn line_end(msg: &str) -> &'static str {
+    if msg.ends_with('\n') { "" } else { "\n" }
+}
+
+/// PAM modules differ in whether they tolerate a trailing newline in a response, so never
+/// hand them one; at most a single newline is removed, any others are part of the input.
+fn strip_trailing_newline(mut response: PamBuffer) -> PamBuffer {
+    let len = response
+        .iter()
+        .position(|&b| b == 0)
+        .unwrap_or(response.len());
+    if len > 0 && response[len - 1] == b'\n' {
+        response[len - 1] = 0;
+    }
+
+    response
 }
 
 /// Helper struct that contains the converser as well as panic boolean
 pub(super) struct ConverserData<C> {
     pub(super) converser: C,
     pub(super) converser_name: String,
-    pub(super) no_interact: bool,
     pub(super) auth_prompt: Option<String>,
     // pam_authenticate does not return error codes returned by the conversation
     // function; these are set by the conversation function instead of returning
     // multiple error codes.
     pub(super) error: Option<PamError>,
     pub(super) panicked: bool,
+    /// Set while the modules authenticate under a time limit.
+    pub(super) watchdog: Option<Arc<Watchdog>>,
 }
 
 /// This function implements the conversation function of `pam_conv`.
@@ -215,6 +337,19 @@ pub(super) unsafe extern "C" fn converse<C: Converser>(
     response: *mut *mut pam_response,
     appdata_ptr: *mut c_void,
 ) -> c_int {
+    // a buggy module could pass a count that would make us read past the end of `msg`,
+    // or allocate an absurd amount of memory for the responses
+    if !(0..=PAM_MAX_NUM_MSG).contains(&num_msg) {
+        return PamErrorType::ConversationError.as_int();
+    }
+
+    if num_msg == 0 {
+        // there is nothing to respond to, so don't allocate a (zero-sized) response array
+        // SAFETY: PAM contract says that we are passed a valid, non-null, writeable pointer here.
+        unsafe { *response = std::ptr::null_mut() };
+        return PamErrorType::Success.as_int();
+    }
+
     let result = std::panic::catch_unwind(|| {
         let mut resp_bufs = Vec::with_capacity(num_msg as usize);
         for i in 0..num_msg as usize {
@@ -245,9 +380,21 @@ pub(super) unsafe extern "C" fn converse<C: Converser>(
                 return PamErrorType::ConversationError;
             }
 
-            match handle_message(app_data, style, &msg) {
+            // past the time limit, the modules may not wait for the user anymore; but while
+            // they do wait for the user, the time limit does not apply
+            let watchdog = app_data.watchdog.clone();
+            if watchdog.as_ref().is_some_and(|watchdog| watchdog.expired()) {
+                app_data.error = Some(PamError::ModulesTimedOut);
+                return PamErrorType::ConversationError;
+            }
+            let paused = watchdog.as_ref().map(|watchdog| watchdog.pause());
+
+            let result = handle_message(app_data, style, &msg);
+            drop(paused);
+
+            match result {
                 Ok(resp_buf) => {
-                    resp_bufs.push(resp_buf);
+                    resp_bufs.push(resp_buf.map(strip_trailing_newline));
                 }
                 Err(err) => {
                     app_data.error = Some(err);
@@ -307,32 +454,16 @@ pub(super) unsafe extern "C" fn converse<C: Converser>(
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::pam::mock::MockConverser;
     use PamMessageStyle::*;
     use std::pin::Pin;
 
+    #[derive(Clone)]
     struct PamMessage {
         msg: String,
         style: PamMessageStyle,
     }
 
-    impl Converser for String {
-        fn handle_normal_prompt(&self, msg: &str) -> PamResult<PamBuffer> {
-            Ok(PamBuffer::new(format!("{self} says {msg}").into_bytes()))
-        }
-
-        fn handle_hidden_prompt(&self, msg: &str) -> PamResult<PamBuffer> {
-            Ok(PamBuffer::new(msg.as_bytes().to_vec()))
-        }
-
-        fn handle_error(&self, msg: &str) -> PamResult<()> {
-            panic!("{msg}")
-        }
-
-        fn handle_info(&self, _msg: &str) -> PamResult<()> {
-            Ok(())
-        }
-    }
-
     // essentially do the inverse of the "conversation function"
     fn dummy_pam(msgs: &[PamMessage], talkie: &pam_conv) -> Vec<Option<String>> {
         let pam_msgs = msgs
@@ -425,12 +556,19 @@ mod test {
     #[test]
     fn miri_pam_gpt() {
         let mut hello = Box::pin(ConverserData {
-            converser: "tux".to_string(),
+            converser: MockConverser::new([
+                (PromptEchoOn, "hello", Some("tux")),
+                (PromptEchoOff, "[tux: authenticate] fish", Some("chips")),
+                (TextInfo, "mars", None),
+                (PromptEchoOff, "banging the rocks together", Some("ow")),
+                (TextInfo, "", None),
+                (PromptEchoOn, "", Some("")),
+            ]),
             converser_name: "tux".to_string(),
-            no_interact: false,
             auth_prompt: Some("authenticate".to_owned()),
             error: None,
             panicked: false,
+            watchdog: None,
         });
         let cookie = PamConvBorrow::new(hello.as_mut());
         let pam_conv = cookie.borrow();
@@ -439,12 +577,12 @@ mod test {
 
         assert_eq!(
             dummy_pam(&[msg(PromptEchoOn, "hello")], pam_conv),
-            vec![Some("tux says hello".to_string())]
+            vec![Some("tux".to_string())]
         );
 
         assert_eq!(
             dummy_pam(&[msg(PromptEchoOff, "fish")], pam_conv),
-            vec![Some("[tux: authenticate] fish".to_string())]
+            vec![Some("chips".to_string())]
         );
 
         assert_eq!(dummy_pam(&[msg(TextInfo, "mars")], pam_conv), vec![None]);
@@ -458,19 +596,192 @@ mod test {
                 ],
                 pam_conv
             ),
-            vec![
-                Some("[tux: authenticate] banging the rocks together".to_string()),
-                None,
-                Some("tux says ".to_string()),
-            ]
+            vec![Some("ow".to_string()), None, Some("".to_string())]
         );
 
         //assert!(!hello.panicked); // not allowed by borrow checker
-        let real_hello = unsafe { &mut *(pam_conv.appdata_ptr as *mut ConverserData<String>) };
+        let real_hello =
+            unsafe { &mut *(pam_conv.appdata_ptr as *mut ConverserData<MockConverser>) };
         assert!(!real_hello.panicked);
+        // the hidden prompts are prefixed, and the other messages are passed on as they are
+        real_hello.converser.assert_received(&[
+            (PromptEchoOn, "hello"),
+            (PromptEchoOff, "[tux: authenticate] fish"),
+            (TextInfo, "mars"),
+            (
+                PromptEchoOff,
+                "[tux: authenticate] banging the rocks together",
+            ),
+            (TextInfo, ""),
+            (PromptEchoOn, ""),
+        ]);
 
+        // a message that was not expected
         assert_eq!(dummy_pam(&[msg(ErrorMessage, "oops")], pam_conv), vec![]);
 
         assert!(hello.panicked); // allowed now
     }
+
+    #[test]
+    fn miri_num_msg_out_of_range() {
+        let mut hello = Box::pin(ConverserData {
+            converser: MockConverser::new(vec![(TextInfo, "mars", None); 32]),
+            converser_name: "tux".to_string(),
+            auth_prompt: Some("authenticate".to_owned()),
+            error: None,
+            panicked: false,
+            watchdog: None,
+        });
+        let cookie = PamConvBorrow::new(hello.as_mut());
+        let pam_conv = cookie.borrow();
+
+        let message = pam_message {
+            msg: c"hello".as_ptr(),
+            msg_style: PromptEchoOn as i32,
+        };
+        let mut ptrs = vec![&message as *const pam_message; 10_000];
+
+        let call = |num_msg: c_int, msg: *mut *const pam_message| {
+            let mut raw_response = std::ptr::dangling_mut::<pam_response>();
+            let result = unsafe {
+                pam_conv.conv.unwrap()(num_msg, msg, &mut raw_response, pam_conv.appdata_ptr)
+            };
+            (PamErrorType::from_int(result), raw_response)
+        };
+
+        // no messages: nothing is allocated, and there is no response
+        let (result, response) = call(0, std::ptr::null_mut());
+        assert_eq!(result, PamErrorType::Success);
+        assert!(response.is_null());
+
+        // more than PAM_MAX_NUM_MSG messages, or a negative amount, are refused outright
+        for num_msg in [PAM_MAX_NUM_MSG + 1, 10_000, -1, c_int::MIN] {
+            let (result, response) = call(num_msg, ptrs.as_mut_ptr());
+            assert_eq!(result, PamErrorType::ConversationError, "{num_msg}");
+            assert_eq!(response, std::ptr::dangling_mut(), "{num_msg}");
+        }
+
+        // the largest allowed amount is still handled
+        assert_eq!(
+            dummy_pam(&vec![msg(TextInfo, "mars"); 32], pam_conv),
+            vec![None; 32]
+        );
+
+        let real_hello =
+            unsafe { &mut *(pam_conv.appdata_ptr as *mut ConverserData<MockConverser>) };
+        assert!(!real_hello.panicked);
+        assert!(real_hello.error.is_none());
+        real_hello
+            .converser
+            .assert_received(&[(TextInfo, "mars"); 32]);
+    }
+
+    #[test]
+    fn miri_responses_have_no_trailing_newline() {
+        let mut hello = Box::pin(ConverserData {
+            converser: MockConverser::new([
+                (PromptEchoOn, "name?", Some("tux\n")),
+                (PromptEchoOff, "password:", Some("hunter2\n")),
+                (PromptEchoOff, "twice", Some("twice\n\n")),
+            ]),
+            converser_name: "tux".to_string(),
+            auth_prompt: Some("authenticate".to_owned()),
+            error: None,
+            panicked: false,
+            watchdog: None,
+        });
+        let cookie = PamConvBorrow::new(hello.as_mut());
+        let pam_conv = cookie.borrow();
+
+        assert_eq!(
+            dummy_pam(
+                &[
+                    msg(PromptEchoOn, "name?\n"),
+                    msg(PromptEchoOff, "password:\n"),
+                    msg(PromptEchoOff, "twice\n"),
+                ],
+                pam_conv
+            ),
+            vec![
+                Some("tux".to_string()),
+                Some("hunter2".to_string()),
+                Some("twice\n".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn miri_non_interactive_converser_never_prompts() {
+        let mut hello = Box::pin(ConverserData {
+            converser: NonInteractiveConverser {
+                name: "tux".to_string(),
+                output: RefCell::new(Vec::new()),
+            },
+            converser_name: "tux".to_string(),
+            auth_prompt: Some("authenticate".to_owned()),
+            error: None,
+            panicked: false,
+            watchdog: None,
+        });
+        let cookie = PamConvBorrow::new(hello.as_mut());
+        let pam_conv = cookie.borrow();
+
+        // messages are shown without needing a terminal
+        assert_eq!(
+            dummy_pam(
+                &[msg(TextInfo, "mars"), msg(ErrorMessage, "venus\n")],
+                pam_conv
+            ),
+            vec![None, None]
+        );
+
+        for style in [PromptEchoOn, PromptEchoOff] {
+            assert!(
+                dummy_pam(
+                    &[msg(TextInfo, "\x1b[2J"), msg(style, "password:")],
+                    pam_conv
+                )
+                .is_empty()
+            );
+
+            let real_hello = unsafe {
+                &mut *(pam_conv.appdata_ptr as *mut ConverserData<NonInteractiveConverser<Vec<u8>>>)
+            };
+            assert!(matches!(
+                real_hello.error.take(),
+                Some(PamError::InteractionRequired)
+            ));
+            assert!(!real_hello.panicked);
+        }
+
+        assert_eq!(
+            String::from_utf8(hello.converser.output.take()).unwrap(),
+            "[tux] mars\n\
+             [tux error] venus\n\
+             [tux] \\u{1b}[2J\n\
+             [tux] \\u{1b}[2J\n"
+        );
+    }
+
+    #[test]
+    fn strip_only_one_newline() {
+        let strip = |input: &str| {
+            let response = strip_trailing_newline(PamBuffer::new(input.as_bytes().to_vec()));
+            let len = response.iter().position(|&b| b == 0).unwrap();
+            String::from_utf8(response[..len].to_vec()).unwrap()
+        };
+
+        assert_eq!(strip("hunter2"), "hunter2");
+        assert_eq!(strip("hunter2\n"), "hunter2");
+        assert_eq!(strip("hunter2\n\n"), "hunter2\n");
+        assert_eq!(strip("\n"), "");
+        assert_eq!(strip(""), "");
+    }
+
+    #[test]
+    fn messages_end_with_one_newline() {
+        assert_eq!(line_end("Your password will expire in 3 days"), "\n");
+        assert_eq!(line_end("Your password will expire in 3 days\n"), "");
+        assert_eq!(line_end(""), "\n");
+    }
 }
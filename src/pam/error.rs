@@ -182,6 +182,7 @@ pub enum PamError {
     NoPasswordProvided,
     IncorrectPasswordAttempt,
     TimedOut,
+    ModulesTimedOut,
     InvalidUser(String, String),
     NoAskpassProgram,
     InvalidAskpassProgram(PathBuf),
@@ -239,6 +240,9 @@ impl fmt::Display for PamError {
                 xlat_write!(f, "Incorrect authentication attempt")
             }
             PamError::TimedOut => xlat_write!(f, "timed out"),
+            PamError::ModulesTimedOut => {
+                xlat_write!(f, "PAM modules did not finish authenticating in time")
+            }
             PamError::InvalidUser(username, other_user) => {
                 xlat_write!(
                     f,
@@ -0,0 +1,96 @@
+//! A scripted [Converser] for tests: it expects the PAM modules to send a given sequence of
+//! messages, answers the prompts among them, and records everything it received.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+
+use super::converse::{Converser, PamMessageStyle};
+use super::{PamResult, securemem::PamBuffer};
+
+pub(super) struct MockConverser {
+    /// The expected messages: their style, a fragment of their text, and the answer to give
+    /// if they are prompts.
+    script: RefCell<VecDeque<(PamMessageStyle, String, Option<String>)>>,
+    received: RefCell<Vec<(PamMessageStyle, String)>>,
+}
+
+impl MockConverser {
+    /// Expect the messages in `script`, in that order; any other message makes the converser
+    /// panic, which the conversation function turns into a conversation error.
+    pub(super) fn new<'a>(
+        script: impl IntoIterator<Item = (PamMessageStyle, &'a str, Option<&'a str>)>,
+    ) -> Self {
+        MockConverser {
+            script: RefCell::new(
+                script
+                    .into_iter()
+                    .map(|(style, fragment, answer)| {
+                        (style, fragment.to_string(), answer.map(str::to_string))
+                    })
+                    .collect(),
+            ),
+            received: RefCell::default(),
+        }
+    }
+
+    fn receive(&self, style: PamMessageStyle, msg: &str) -> Option<String> {
+        self.received.borrow_mut().push((style, msg.to_string()));
+
+        let Some((expected_style, fragment, answer)) = self.script.borrow_mut().pop_front() else {
+            panic!("unexpected {style:?} message: {msg:?}");
+        };
+        assert_eq!(style, expected_style, "for message {msg:?}");
+        assert!(
+            msg.contains(&fragment),
+            "expected {fragment:?} in {style:?} message {msg:?}"
+        );
+
+        answer
+    }
+
+    fn answer(&self, style: PamMessageStyle, msg: &str) -> PamResult<PamBuffer> {
+        let answer = self
+            .receive(style, msg)
+            .unwrap_or_else(|| panic!("no answer was scripted for {msg:?}"));
+
+        Ok(PamBuffer::new(answer.into_bytes()))
+    }
+
+    /// Check that the messages received so far match `expected`, each given by its style
+    /// and a fragment of its text; this also fails if part of the script was not used.
+    pub(super) fn assert_received(&self, expected: &[(PamMessageStyle, &str)]) {
+        let received = self.received.borrow();
+        assert_eq!(
+            received.len(),
+            expected.len(),
+            "received {received:?}, expected {expected:?}"
+        );
+        for ((style, msg), (expected_style, fragment)) in received.iter().zip(expected) {
+            assert_eq!(style, expected_style, "for message {msg:?}");
+            assert!(msg.contains(fragment), "expected {fragment:?} in {msg:?}");
+        }
+
+        let script = self.script.borrow();
+        assert!(script.is_empty(), "never received {script:?}");
+    }
+}
+
+impl Converser for MockConverser {
+    fn handle_normal_prompt(&self, msg: &str) -> PamResult<PamBuffer> {
+        self.answer(PamMessageStyle::PromptEchoOn, msg)
+    }
+
+    fn handle_hidden_prompt(&self, msg: &str) -> PamResult<PamBuffer> {
+        self.answer(PamMessageStyle::PromptEchoOff, msg)
+    }
+
+    fn handle_error(&self, msg: &str) -> PamResult<()> {
+        self.receive(PamMessageStyle::ErrorMessage, msg);
+        Ok(())
+    }
+
+    fn handle_info(&self, msg: &str) -> PamResult<()> {
+        self.receive(PamMessageStyle::TextInfo, msg);
+        Ok(())
+    }
+}
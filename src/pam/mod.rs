@@ -4,10 +4,15 @@ use std::{
     os::raw::c_char,
     os::unix::prelude::OsStrExt,
     ptr::NonNull,
+    sync::Arc,
+    thread,
     time::Duration,
 };
 
-use crate::system::signal::{self, SignalSet};
+use crate::system::{
+    self,
+    signal::{self, SignalSet},
+};
 
 use converse::ConverserData;
 use error::pam_err;
@@ -17,8 +22,11 @@ use sys::*;
 mod askpass;
 mod converse;
 mod error;
+#[cfg(test)]
+mod mock;
 mod rpassword;
 mod securemem;
+mod watchdog;
 
 #[cfg_attr(target_os = "linux", path = "sys_linuxpam.rs")]
 #[cfg_attr(target_os = "freebsd", path = "sys_openpam.rs")]
@@ -32,18 +40,26 @@ unsafe extern "C" {}
 const PAM_DATA_SILENT: std::ffi::c_int = 0;
 
 pub use converse::CLIConverser;
+use converse::{AskpassConverser, Converser, NonInteractiveConverser};
+use watchdog::Watchdog;
 
 pub struct PamContext {
-    data_ptr: *mut ConverserData<CLIConverser>,
+    data_ptr: *mut ConverserData<Box<dyn Converser>>,
     pamh: *mut pam_handle_t,
+    service_name: String,
+    module_timeout: Option<Duration>,
     silent: bool,
     allow_null_auth_token: bool,
     last_pam_status: Option<c_int>,
     session_started: bool,
+    credentials_established: bool,
 }
 
 impl PamContext {
-    /// Build the PamContext with the CLI conversation function.
+    /// Build the PamContext with the CLI conversation function; if `no_interact` is set, the
+    /// user is never prompted, and messages from PAM modules are written to stderr instead.
+    /// The same goes for those messages if `use_askpass` is set, in which case the user is
+    /// prompted by the program in `SUDO_ASKPASS`.
     ///
     /// The target user is optional and may also be set after the context was
     /// constructed or not set at all in which case PAM will ask for a
@@ -62,13 +78,24 @@ impl PamContext {
         password_timeout: Option<Duration>,
         target_user: Option<&str>,
     ) -> PamResult<PamContext> {
-        let converser = CLIConverser {
-            bell: bell.into(),
+        let messages = || NonInteractiveConverser {
             name: converser_name.to_owned(),
-            use_askpass,
-            use_stdin,
-            password_feedback,
-            password_timeout,
+            output: std::io::stderr().into(),
+        };
+        let converser: Box<dyn Converser> = if no_interact {
+            Box::new(messages())
+        } else if use_askpass {
+            Box::new(AskpassConverser {
+                messages: messages(),
+            })
+        } else {
+            Box::new(CLIConverser {
+                bell: bell.into(),
+                name: converser_name.to_owned(),
+                use_stdin,
+                password_feedback,
+                password_timeout,
+            })
         };
 
         let c_service_name = CString::new(service_name)?;
@@ -82,10 +109,10 @@ impl PamContext {
         let data_ptr = Box::into_raw(Box::new(ConverserData {
             converser,
             converser_name: converser_name.to_owned(),
-            no_interact,
             auth_prompt: Some(xlat!("authenticate").to_owned()),
             error: None,
             panicked: false,
+            watchdog: None,
         }));
 
         let mut pamh = std::ptr::null_mut();
@@ -96,7 +123,7 @@ impl PamContext {
                 c_service_name.as_ptr(),
                 c_user_ptr,
                 &pam_conv {
-                    conv: Some(converse::converse::<CLIConverser>),
+                    conv: Some(converse::converse::<Box<dyn Converser>>),
                     appdata_ptr: data_ptr as *mut c_void,
                 },
                 &mut pamh,
@@ -110,10 +137,13 @@ impl PamContext {
         Ok(PamContext {
             data_ptr,
             pamh,
+            service_name: service_name.to_owned(),
+            module_timeout: None,
             silent: false,
             allow_null_auth_token: true,
             last_pam_status: None,
             session_started: false,
+            credentials_established: false,
         })
     }
 
@@ -136,6 +166,13 @@ impl PamContext {
         self.allow_null_auth_token = allow;
     }
 
+    /// Limit the time that the modules may spend authenticating, not counting the time spent
+    /// waiting for the user; by default there is no limit. Since a module that hangs cannot
+    /// be interrupted, the process exits if the modules do not return shortly after this.
+    pub fn set_module_timeout(&mut self, timeout: Option<Duration>) {
+        self.module_timeout = timeout;
+    }
+
     /// Get the PAM flag value for the silent flag
     fn silent_flag(&self) -> i32 {
         if self.silent { PAM_SILENT as _ } else { 0 }
@@ -163,8 +200,11 @@ impl PamContext {
             set.block()
         });
 
-        // SAFETY: `self.pamh` contains a correct handle (obtained from `pam_start`)
-        let auth_res = pam_err(unsafe { pam_authenticate(self.pamh, flags) });
+        let auth_res = match self.module_timeout {
+            // SAFETY: `self.pamh` contains a correct handle (obtained from `pam_start`)
+            None => pam_err(unsafe { pam_authenticate(self.pamh, flags) }),
+            Some(limit) => self.authenticate_with_watchdog(flags, limit),
+        };
 
         // Restore signals
         if let Ok(set) = cur_signals {
@@ -270,9 +310,13 @@ impl PamContext {
         pam_err(unsafe { pam_set_item(self.pamh, PAM_RUSER as _, data.as_ptr() as *const c_void) })
     }
 
-    /// Re-initialize the credentials stored in PAM
-    pub fn credentials_reinitialize(&mut self) -> PamResult<()> {
-        self.credentials(PAM_REINITIALIZE_CRED as c_int)
+    /// Establish the credentials of the user; this should only be done once the user is
+    /// allowed to proceed, i.e. after they were authenticated (if that was required at all)
+    /// and their account was validated.
+    pub fn credentials_establish(&mut self) -> PamResult<()> {
+        self.credentials(PAM_ESTABLISH_CRED as c_int)?;
+        self.credentials_established = true;
+        Ok(())
     }
 
     /// Updates to the credentials stored in PAM
@@ -309,7 +353,7 @@ impl PamContext {
         Ok(())
     }
 
-    /// End the user session.
+    /// End the user session, and delete the credentials that were established for it.
     pub fn close_session(&mut self) {
         // closing the pam session is best effort, if any error occurs we cannot
         // do anything with it
@@ -318,6 +362,11 @@ impl PamContext {
             let _ = pam_err(unsafe { pam_close_session(self.pamh, self.silent_flag()) });
             self.session_started = false;
         }
+
+        if self.credentials_established {
+            let _ = self.credentials(PAM_DELETE_CRED as c_int);
+            self.credentials_established = false;
+        }
     }
 
     /// Get a full listing of the current PAM environment
@@ -371,6 +420,52 @@ impl PamContext {
         Ok(res)
     }
 
+    fn authenticate_with_watchdog(&mut self, flags: c_int, limit: Duration) -> PamResult<()> {
+        let watchdog = Arc::new(Watchdog::new(limit));
+        // SAFETY: self.data_ptr was created by Box::into_raw
+        unsafe { (*self.data_ptr).watchdog = Some(watchdog.clone()) };
+
+        // a module that hangs may do so while holding a lock (of the memory allocator, say),
+        // so the diagnostic is prepared beforehand and written with nothing but write(2)
+        let service_name = &self.service_name;
+        // SAFETY: self.data_ptr was created by Box::into_raw
+        let name = unsafe { &(*self.data_ptr).converser_name };
+        let modules = watchdog::auth_modules(service_name).join(", ");
+        let diagnostic = format!(
+            "{name}: authentication timed out: the auth modules of PAM service '{service_name}' did not return ({modules})\n\
+             {name}: {}\n",
+            xlat!("PAM modules did not finish authenticating in time")
+        );
+
+        let result = thread::scope(|scope| {
+            scope.spawn(|| {
+                watchdog.watch(|| {
+                    // the terminal is left as it was found, since the conversation function
+                    // (which is the only thing that changes its settings) cannot be running
+                    // SAFETY: the pointer and length describe the bytes of `diagnostic`
+                    unsafe {
+                        libc::write(
+                            libc::STDERR_FILENO,
+                            diagnostic.as_ptr().cast(),
+                            diagnostic.len(),
+                        )
+                    };
+                    system::_exit(1);
+                })
+            });
+
+            // SAFETY: `self.pamh` contains a correct handle (obtained from `pam_start`)
+            let result = pam_err(unsafe { pam_authenticate(self.pamh, flags) });
+            watchdog.finish();
+            result
+        });
+
+        // SAFETY: self.data_ptr was created by Box::into_raw
+        unsafe { (*self.data_ptr).watchdog = None };
+
+        result
+    }
+
     /// Check if anything panicked since the last call.
     pub fn has_panicked(&self) -> bool {
         // SAFETY: self.data_ptr was created by Box::into_raw
@@ -26,7 +26,8 @@ use libc::{
 };
 
 use crate::cutils::{cerr, safe_isatty};
-use crate::pam::{PamError, PamResult, askpass};
+use crate::pam::{PamError, PamErrorType, PamResult, askpass};
+use crate::system::paths;
 use crate::system::signal::{
     self, SignalHandler, SignalHandlerBehavior, SignalsState, exit_with_signal,
 };
@@ -113,6 +114,8 @@ fn prompt_password(
     hidden: Hidden<()>,
 ) -> PamResult<PamBuffer> {
     'getpass: loop {
+        // with echo on, the terminal itself shows the newline that ends the input
+        let echoes_newline = matches!(hidden, Hidden::No) && safe_isatty(source);
         let hide_input = match hidden.clone() {
             // If input is not a tty, we can't hide feedback.
             _ if !safe_isatty(source) => Hidden::No,
@@ -145,6 +148,9 @@ fn prompt_password(
         };
 
         let res = read_unbuffered(&mut reader, sink, &hide_input);
+        if !(echoes_newline && res.is_ok()) {
+            let _ = sink.write(b"\n");
+        }
 
         drop(hide_input);
         drop(handlers);
@@ -222,7 +228,6 @@ fn read_unbuffered(
     impl Drop for Bullets<'_> {
         fn drop(&mut self) {
             self.clear();
-            let _ = self.sink.write(b"\n");
         }
     }
 
@@ -405,10 +410,11 @@ impl Terminal<'_> {
     }
 
     pub fn open_askpass() -> PamResult<Self> {
-        let Some(program) = std::env::var_os("SUDO_ASKPASS") else {
-            return Err(PamError::NoAskpassProgram);
+        // the administrator may configure a helper for when the user has not chosen one
+        let program = match std::env::var_os("SUDO_ASKPASS").filter(|path| !path.is_empty()) {
+            Some(program) => PathBuf::from(program),
+            None => paths::askpass()?.ok_or(PamError::NoAskpassProgram)?,
         };
-        let program = PathBuf::from(program);
 
         if program.is_absolute() {
             Ok(Terminal::Askpass(program, io::sink()))
@@ -437,13 +443,19 @@ impl Terminal<'_> {
                 let mut reader = TimeoutRead::new(askpass_stdout.as_fd(), None);
                 let password = read_unbuffered(&mut reader, sink, &Hidden::No)?;
 
-                loop {
+                let status = loop {
                     match command_pid.wait(WaitOptions::new()) {
-                        Ok(_) => break,
+                        Ok((_, status)) => break status,
                         Err(WaitError::Io(err)) if err.kind() == io::ErrorKind::Interrupted => {}
                         Err(WaitError::Io(err)) => return Err(PamError::IoError(err)),
                         Err(WaitError::NotReady) => unreachable!(),
                     }
+                };
+
+                // a helper that fails (e.g. because the user cancelled its dialog) did not
+                // answer, whatever it may have written
+                if status.exit_status() != Some(0) {
+                    return Err(PamError::Pam(PamErrorType::AuthError));
                 }
 
                 Ok(password)
@@ -545,6 +557,64 @@ mod test {
         );
     }
 
+    #[test]
+    fn miri_test_eof() {
+        // sudo -S with nothing on stdin does not wait, nor ask again
+        let mut stdout = Vec::new();
+        let (rx, tx) = make_pipe();
+        drop(tx);
+        assert!(matches!(
+            read_unbuffered(
+                &mut TimeoutRead::new(rx.as_fd(), None),
+                &mut stdout,
+                &Hidden::No
+            ),
+            Err(PamError::NoPasswordProvided)
+        ));
+    }
+
+    #[test]
+    fn hidden_input_is_never_echoed() {
+        use crate::system::term::Pty;
+
+        for feedback in [false, true] {
+            let Pty {
+                mut leader,
+                follower,
+                ..
+            } = Pty::open().unwrap();
+            let mut sink = File::from(follower.as_fd().try_clone_to_owned().unwrap());
+
+            // the user types after the prompt has hidden the input
+            let hidden_input = HiddenInput::new(follower.as_fd()).unwrap();
+            leader.write_all(b"password123\n").unwrap();
+            let hidden = if feedback {
+                Hidden::WithFeedback(hidden_input)
+            } else {
+                Hidden::Yes(hidden_input)
+            };
+            let password = read_unbuffered(
+                &mut TimeoutRead::new(follower.as_fd(), None),
+                &mut sink,
+                &hidden,
+            )
+            .unwrap();
+            assert_eq!(&password[..11], b"password123");
+            drop(hidden);
+            drop((sink, follower));
+
+            // everything the terminal shows; once the follower is closed, reading fails
+            let mut shown = Vec::new();
+            let mut buf = [0; 64];
+            while let Ok(n @ 1..) = leader.read(&mut buf) {
+                shown.extend_from_slice(&buf[..n]);
+            }
+            let shown = String::from_utf8_lossy(&shown);
+            assert!(!shown.contains("password"), "{shown:?}");
+            assert_eq!(shown.contains('*'), feedback, "{shown:?}");
+        }
+    }
+
     #[test]
     fn miri_test_write() {
         let mut data = Vec::new();
@@ -58,7 +58,8 @@ pub const PAM_MODULE_UNKNOWN: u32 = 28;
 pub const PAM_BAD_ITEM: u32 = 29;
 pub const PAM_SILENT: u32 = 32768;
 pub const PAM_DISALLOW_NULL_AUTHTOK: u32 = 1;
-pub const PAM_REINITIALIZE_CRED: u32 = 8;
+pub const PAM_ESTABLISH_CRED: u32 = 2;
+pub const PAM_DELETE_CRED: u32 = 4;
 pub const PAM_CHANGE_EXPIRED_AUTHTOK: u32 = 32;
 pub const PAM_USER: u32 = 2;
 pub const PAM_TTY: u32 = 3;
@@ -67,7 +67,8 @@ pub const PAM_TEXT_INFO: _bindgen_ty_2 = 4;
 pub const PAM_MAX_RESP_SIZE: _bindgen_ty_2 = 512;
 pub const PAM_SILENT: _bindgen_ty_3 = -2147483648;
 pub const PAM_DISALLOW_NULL_AUTHTOK: _bindgen_ty_3 = 1;
-pub const PAM_REINITIALIZE_CRED: _bindgen_ty_3 = 4;
+pub const PAM_ESTABLISH_CRED: _bindgen_ty_3 = 1;
+pub const PAM_DELETE_CRED: _bindgen_ty_3 = 2;
 pub const PAM_CHANGE_EXPIRED_AUTHTOK: _bindgen_ty_3 = 4;
 pub const PAM_USER: _bindgen_ty_4 = 2;
 pub const PAM_TTY: _bindgen_ty_4 = 3;
@@ -0,0 +1,234 @@
+//! A time limit for the PAM modules that authenticate the user, so that a module that hangs
+//! (for instance while contacting an authentication server that does not respond) cannot
+//! leave sudo stuck forever.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// How long the modules may still take to return after the time limit has passed, before sudo
+/// gives up on them.
+pub(super) const GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+pub(super) struct Watchdog {
+    limit: Duration,
+    state: Mutex<State>,
+    changed: Condvar,
+    expired: AtomicBool,
+}
+
+struct State {
+    /// When the modules last got control; `None` while the conversation function is waiting
+    /// for the user, since that is already limited by `passwd_timeout`.
+    running_since: Option<Instant>,
+    finished: bool,
+}
+
+impl Watchdog {
+    pub(super) fn new(limit: Duration) -> Self {
+        Watchdog {
+            limit,
+            state: Mutex::new(State {
+                running_since: Some(Instant::now()),
+                finished: false,
+            }),
+            changed: Condvar::new(),
+            expired: AtomicBool::new(false),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // the state is consistent after every update, so a panic elsewhere does not matter
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Whether the time limit has passed; the conversation function refuses to prompt once
+    /// this is the case.
+    pub(super) fn expired(&self) -> bool {
+        self.expired.load(Ordering::SeqCst)
+    }
+
+    /// Stop the clock until the returned guard is dropped, after which the modules get the
+    /// full time limit again.
+    pub(super) fn pause(&self) -> Paused<'_> {
+        self.lock().running_since = None;
+        self.changed.notify_all();
+
+        Paused(self)
+    }
+
+    /// Called when the modules have returned.
+    pub(super) fn finish(&self) {
+        self.lock().finished = true;
+        self.changed.notify_all();
+    }
+
+    /// Wait until `finish` is called; if the time limit passes first, the watchdog expires,
+    /// and `on_hang` is called if the modules have still not returned after [GRACE_PERIOD].
+    pub(super) fn watch(&self, on_hang: impl FnOnce()) {
+        let mut state = self.lock();
+        loop {
+            if state.finished {
+                return;
+            }
+
+            state = match state.running_since {
+                None => self
+                    .changed
+                    .wait(state)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner()),
+                Some(since) => match self.limit.checked_sub(since.elapsed()) {
+                    Some(left) if !left.is_zero() => {
+                        self.changed
+                            .wait_timeout(state, left)
+                            .unwrap_or_else(|poisoned| poisoned.into_inner())
+                            .0
+                    }
+                    _ => break,
+                },
+            };
+        }
+
+        self.expired.store(true, Ordering::SeqCst);
+
+        let (state, _) = self
+            .changed
+            .wait_timeout_while(state, GRACE_PERIOD, |state| !state.finished)
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if !state.finished {
+            drop(state);
+            on_hang();
+        }
+    }
+}
+
+pub(super) struct Paused<'a>(&'a Watchdog);
+
+impl Drop for Paused<'_> {
+    fn drop(&mut self) {
+        self.0.lock().running_since = Some(Instant::now());
+        self.0.changed.notify_all();
+    }
+}
+
+/// The modules in the auth stack of `service`, as far as they can be found out, to tell the
+/// administrator where to look; included files are listed as such, but not expanded.
+pub(super) fn auth_modules(service: &str) -> Vec<String> {
+    ["/etc/pam.d", "/usr/lib/pam.d"]
+        .iter()
+        .find_map(|dir| std::fs::read_to_string(Path::new(dir).join(service)).ok())
+        .map(|config| parse_auth_modules(&config))
+        .unwrap_or_default()
+}
+
+fn parse_auth_modules(config: &str) -> Vec<String> {
+    let mut modules = Vec::new();
+    for line in config.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+
+        match fields.next() {
+            Some("@include") => {
+                modules.extend(fields.next().map(|file| format!("@include {file}")));
+            }
+            // a leading '-' means that the module is skipped if it is not installed
+            Some("auth" | "-auth") => {
+                // the control field is either a single keyword, or a bracketed list of them
+                if let Some(control) = fields.next() {
+                    if control.starts_with('[') && !control.ends_with(']') {
+                        fields.by_ref().find(|field| field.ends_with(']'));
+                    }
+                }
+                modules.extend(fields.next().map(str::to_string));
+            }
+            _ => {}
+        }
+    }
+
+    modules
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn watchdog_stays_quiet_for_modules_that_return() {
+        let watchdog = Watchdog::new(Duration::from_secs(60));
+        thread::scope(|scope| {
+            scope.spawn(|| watchdog.watch(|| panic!("the modules did return")));
+            thread::sleep(Duration::from_millis(10));
+            watchdog.finish();
+        });
+        assert!(!watchdog.expired());
+    }
+
+    #[test]
+    fn watchdog_does_not_count_prompts() {
+        let watchdog = Watchdog::new(Duration::from_millis(100));
+        thread::scope(|scope| {
+            scope.spawn(|| watchdog.watch(|| panic!("the modules did return")));
+            {
+                let _paused = watchdog.pause();
+                // a user that takes a while to type their password
+                thread::sleep(Duration::from_millis(300));
+            }
+            assert!(!watchdog.expired());
+            watchdog.finish();
+        });
+        assert!(!watchdog.expired());
+    }
+
+    #[test]
+    fn watchdog_expires_for_modules_that_hang() {
+        let hangs = AtomicUsize::new(0);
+
+        // modules that return during the grace period
+        let watchdog = Watchdog::new(Duration::from_millis(10));
+        thread::scope(|scope| {
+            scope.spawn(|| watchdog.watch(|| _ = hangs.fetch_add(1, Ordering::SeqCst)));
+            while !watchdog.expired() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            watchdog.finish();
+        });
+        assert_eq!(hangs.load(Ordering::SeqCst), 0);
+
+        // modules that do not
+        let watchdog = Watchdog::new(Duration::from_millis(10));
+        watchdog.watch(|| _ = hangs.fetch_add(1, Ordering::SeqCst));
+        assert!(watchdog.expired());
+        assert_eq!(hangs.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn auth_modules_are_listed() {
+        let config = "\
+            #%PAM-1.0\n\
+            auth [success=1 default=ignore] pam_unix.so nullok\n\
+            auth   [success=done new_authtok_reqd=done default=die]   pam_sss.so use_first_pass\n\
+            -auth optional pam_gnome_keyring.so # not always installed\n\
+            auth requisite pam_deny.so\n\
+            account required pam_unix.so\n\
+            @include common-auth\n\
+            # auth required pam_commented_out.so\n\
+            session required pam_limits.so\n";
+
+        assert_eq!(
+            parse_auth_modules(config),
+            [
+                "pam_unix.so",
+                "pam_sss.so",
+                "pam_gnome_keyring.so",
+                "pam_deny.so",
+                "@include common-auth"
+            ]
+        );
+    }
+}
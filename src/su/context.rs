@@ -1,5 +1,4 @@
 use std::{
-    collections::HashMap,
     env,
     ffi::OsString,
     fs,
@@ -7,13 +6,12 @@ use std::{
 };
 
 use crate::common::{error::Error, resolve::CurrentUser};
-use crate::exec::{RunOptions, Umask};
+use crate::exec::{FinalCommand, Umask, WorkingDir, login_shell_arg0};
 use crate::log::user_warn;
+use crate::sudo::Environment;
 use crate::system::{Group, User};
 use crate::{common::resolve::is_valid_executable, system::interface::UserId};
 
-type Environment = HashMap<OsString, OsString>;
-
 use super::cli::SuRunOptions;
 
 const VALID_LOGIN_SHELLS_LIST: &str = "/etc/shells";
@@ -196,20 +194,35 @@ impl SuContext {
 }
 
 impl SuContext {
-    pub(crate) fn as_run_options(&self) -> RunOptions<'_> {
-        RunOptions {
-            command: &self.command,
-            arguments: &self.arguments,
-            arg0: None,
-            chdir: None,
-            is_login: self.options.login,
-            user: &self.user,
-            group: &self.group,
+    pub(crate) fn final_command(&self, environment: Environment) -> FinalCommand {
+        let arg0 = if self.options.login {
+            login_shell_arg0(&self.command)
+        } else {
+            self.command.clone().into_os_string()
+        };
+
+        FinalCommand {
+            command: self.command.clone(),
+            command_file: None,
+            argv: std::iter::once(arg0)
+                .chain(self.arguments.iter().cloned())
+                .collect(),
+            env: environment,
+            cwd: self
+                .options
+                .login
+                .then(|| WorkingDir::Preferred(self.user.home.clone().into())),
+            chroot: None,
+            user: self.user.clone(),
+            group: self.group.clone(),
             umask: Umask::Preserve,
+            #[cfg(feature = "apparmor")]
+            apparmor_profile: None,
 
             background: false,
             use_pty: true,
             noexec: false,
+            timeout: None,
         }
     }
 }
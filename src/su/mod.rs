@@ -105,7 +105,7 @@ fn run(options: SuRunOptions) -> Result<(), Error> {
     environment.extend(pam.env()?);
 
     // run command and return corresponding exit code
-    let command_exit_reason = crate::exec::run_command(context.as_run_options(), environment);
+    let command_exit_reason = crate::exec::run_command(context.final_command(environment));
 
     pam.close_session();
 
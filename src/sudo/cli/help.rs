@@ -2,7 +2,7 @@ pub fn usage_msg() -> &'static str {
     xlat!(
         "\
 usage: sudo -h | -K | -k | -V
-usage: sudo [-ABbknS] [-p prompt] [-D directory] [-g group] [-u user] [-i | -s] [command [arg ...]]
+usage: sudo [-ABbknS] [-p prompt] [-D directory] [-R directory] [-T timeout] [-g group] [-u user] [-i | -s] [command [arg ...]]
 usage: sudo -v [-ABknS] [-p prompt] [-g group] [-u user]
 usage: sudo -l [-ABknS] [-p prompt] [-U user] [-g group] [-u user] [command [arg ...]]
 usage: sudo -e [-ABknS] [-p prompt] [-D directory] [-g group] [-u user] file ..."
@@ -28,13 +28,16 @@ fn help_msg() -> &'static str {
   -l, --list                    list user's privileges or check a specific command; use twice for longer format
   -n, --non-interactive         non-interactive mode, no prompts are used
   -p, --prompt=prompt           use the specified password prompt
+  -R, --chroot=directory        change the root directory before running command
   -S, --stdin                   read password from standard input
+  -T, --command-timeout=timeout terminate command after the specified time limit
   -s, --shell                   run shell as the target user; a command may also be specified
   -U, --other-user=user         in list mode, display privileges for user
   -u, --user=user               run command (or edit file) as specified user name or ID
   -V, --version                 display version information and exit
   -v, --validate                update user's timestamp without running a command
       --preserve-env=list       preserve specific environment variables
+      --status-json=fd          report progress as JSON objects on file descriptor fd
   --                            stop processing command line arguments")
 }
 
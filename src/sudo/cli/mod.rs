@@ -1,10 +1,12 @@
 #![forbid(unsafe_code)]
 
 use std::ffi::OsStr;
+use std::os::fd::RawFd;
+use std::os::unix::ffi::OsStrExt;
 use std::str;
 use std::{borrow::Cow, ffi::OsString, mem};
 
-use crate::common::{DisplayOsStr, SudoPath, SudoString};
+use crate::common::{DisplayOsStr, SudoPath, SudoString, SudoersDuration};
 use crate::log::user_warn;
 
 pub mod help;
@@ -78,7 +80,10 @@ impl TryFrom<SudoOptions> for SudoVersionOptions {
 }
 
 // sudo -h | -K | -k | -V
-pub struct SudoRemoveTimestampOptions {}
+pub struct SudoRemoveTimestampOptions {
+    // -u
+    pub user: Option<SudoString>,
+}
 
 impl TryFrom<SudoOptions> for SudoRemoveTimestampOptions {
     type Error = String;
@@ -88,14 +93,19 @@ impl TryFrom<SudoOptions> for SudoRemoveTimestampOptions {
         let remove_timestamp = mem::take(&mut opts.remove_timestamp);
         debug_assert!(remove_timestamp);
 
+        let user = mem::take(&mut opts.user);
+
         reject_all("--remove-timestamp", opts)?;
 
-        Ok(Self {})
+        Ok(Self { user })
     }
 }
 
 // sudo -h | -K | -k | -V
-pub struct SudoResetTimestampOptions {}
+pub struct SudoResetTimestampOptions {
+    // -u
+    pub user: Option<SudoString>,
+}
 
 impl TryFrom<SudoOptions> for SudoResetTimestampOptions {
     type Error = String;
@@ -105,9 +115,11 @@ impl TryFrom<SudoOptions> for SudoResetTimestampOptions {
         let reset_timestamp = mem::take(&mut opts.reset_timestamp);
         debug_assert!(reset_timestamp);
 
+        let user = mem::take(&mut opts.user);
+
         reject_all("--reset-timestamp", opts)?;
 
-        Ok(Self {})
+        Ok(Self { user })
     }
 }
 
@@ -250,6 +262,8 @@ pub struct SudoListOptions {
     pub bell: bool,
     // -l OR -l -l
     pub list: List,
+    // --suggest (hidden, for shell completion)
+    pub suggest: Option<String>,
 
     // -k
     pub reset_timestamp: bool,
@@ -275,7 +289,8 @@ impl TryFrom<SudoOptions> for SudoListOptions {
     fn try_from(mut opts: SudoOptions) -> Result<Self, Self::Error> {
         let askpass = mem::take(&mut opts.askpass);
         let bell = mem::take(&mut opts.bell);
-        let list = opts.list.take().unwrap();
+        let list = opts.list.take().unwrap_or(List::Once);
+        let suggest = mem::take(&mut opts.suggest);
         let reset_timestamp = mem::take(&mut opts.reset_timestamp);
         let non_interactive = mem::take(&mut opts.non_interactive);
         let stdin = mem::take(&mut opts.stdin);
@@ -304,12 +319,17 @@ impl TryFrom<SudoOptions> for SudoListOptions {
             ));
         }
 
+        if suggest.is_some() {
+            ensure_is_absent("--suggest", &positional_args, xlat!("command"))?;
+        }
+
         reject_all("--list", opts)?;
 
         Ok(Self {
             askpass,
             bell,
             list,
+            suggest,
             reset_timestamp,
             non_interactive,
             stdin,
@@ -342,17 +362,25 @@ pub struct SudoRunOptions {
     pub prompt: Option<String>,
     // -D
     pub chdir: Option<SudoPath>,
+    // -R
+    pub chroot: Option<SudoPath>,
+    // -T
+    pub command_timeout: Option<SudoersDuration>,
     // -g
     pub group: Option<SudoString>,
     // -u
     pub user: Option<SudoString>,
     // VAR=value
-    pub env_var_list: Vec<(String, String)>,
+    pub env_var_list: Vec<(String, OsString)>,
     // -i
     pub login: bool,
     // -s
     pub shell: bool,
+    // --status-json
+    pub status_json: Option<RawFd>,
     pub positional_args: Vec<OsString>,
+    // neither -i, -s nor a command was given
+    pub implied_shell: bool,
 }
 
 impl TryFrom<SudoOptions> for SudoRunOptions {
@@ -367,12 +395,16 @@ impl TryFrom<SudoOptions> for SudoRunOptions {
         let stdin = mem::take(&mut opts.stdin);
         let prompt = mem::take(&mut opts.prompt);
         let chdir = mem::take(&mut opts.chdir);
+        let chroot = mem::take(&mut opts.chroot);
+        let command_timeout = mem::take(&mut opts.command_timeout);
         let group = mem::take(&mut opts.group);
         let user = mem::take(&mut opts.user);
         let env_var_list = mem::take(&mut opts.env_var_list);
         let login = mem::take(&mut opts.login);
         let shell = mem::take(&mut opts.shell);
+        let status_json = mem::take(&mut opts.status_json);
         let positional_args = mem::take(&mut opts.positional_args);
+        let implied_shell = !login && !shell && positional_args.is_empty();
 
         if bell && stdin {
             return Err(xlat!(
@@ -394,17 +426,8 @@ impl TryFrom<SudoOptions> for SudoRunOptions {
                     option = "--shell"
                 ));
             }
-            (false, false, true) => {
-                if cfg!(debug_assertions) {
-                    // see `SudoOptions::validate`
-                    panic!();
-                } else {
-                    return Err(xlat!(
-                        "expected one of: --login, --shell, a command as a positional argument"
-                    )
-                    .into());
-                }
-            }
+            // no command at all: this is `sudo --shell` if `shell_noargs` is set
+            (false, false, true) => "--shell",
         };
 
         reject_all(context, opts)?;
@@ -418,12 +441,16 @@ impl TryFrom<SudoOptions> for SudoRunOptions {
             stdin,
             prompt,
             chdir,
+            chroot,
+            command_timeout,
             group,
             user,
             env_var_list,
             login,
             shell,
+            status_json,
             positional_args,
+            implied_shell,
         })
     }
 }
@@ -438,6 +465,10 @@ struct SudoOptions {
     background: bool,
     // -D
     chdir: Option<SudoPath>,
+    // -R
+    chroot: Option<SudoPath>,
+    // -T
+    command_timeout: Option<SudoersDuration>,
     // -g
     group: Option<SudoString>,
     // -i
@@ -456,9 +487,11 @@ struct SudoOptions {
     prompt: Option<String>,
     // -u
     user: Option<SudoString>,
+    // --status-json
+    status_json: Option<RawFd>,
 
     // additional environment
-    env_var_list: Vec<(String, String)>,
+    env_var_list: Vec<(String, OsString)>,
 
     /* actions */
     // -e
@@ -467,6 +500,8 @@ struct SudoOptions {
     help: bool,
     // -l
     list: Option<List>,
+    // --suggest
+    suggest: Option<String>,
     // -K
     remove_timestamp: bool,
     // -k
@@ -496,7 +531,7 @@ impl List {
 enum SudoArg {
     Flag(String),
     Argument(String, String),
-    Environment(String, String),
+    Environment(String, OsString),
     Rest(Vec<OsString>),
 }
 
@@ -508,15 +543,18 @@ fn demand_utf8(arg: &OsStr) -> String {
 }
 
 impl SudoArg {
-    const TAKES_ARGUMENT_SHORT: &'static [char] = &['D', 'g', 'h', 'p', 'R', 'U', 'u'];
+    const TAKES_ARGUMENT_SHORT: &'static [char] = &['D', 'g', 'h', 'p', 'R', 'T', 'U', 'u'];
     const TAKES_ARGUMENT: &'static [&'static str] = &[
         "chdir",
         "group",
         "host",
         "chroot",
+        "command-timeout",
         "other-user",
         "user",
         "prompt",
+        "suggest",
+        "status-json",
     ];
 
     /// argument assignments and shorthand options preprocessing
@@ -542,7 +580,7 @@ impl SudoArg {
                     if !Self::TAKES_ARGUMENT.contains(&key) && key != "preserve-env" {
                         Err(xlat!(
                             "'{option}' does not take any arguments",
-                            option = key
+                            option = format!("--{key}")
                         ))?;
                     }
                     processed.push(SudoArg::Argument("--".to_string() + key, value.to_string()));
@@ -559,7 +597,11 @@ impl SudoArg {
                     let arg = String::from_utf8(arg.into_encoded_bytes()).expect("already checked");
                     processed.push(SudoArg::Flag(arg));
                 }
-            } else if let Some(unprefixed) = arg.as_encoded_bytes().strip_prefix(b"-") {
+            } else if let Some(unprefixed) = arg
+                .as_encoded_bytes()
+                .strip_prefix(b"-")
+                .filter(|unprefixed| !unprefixed.is_empty())
+            {
                 let unprefixed = str::from_utf8(unprefixed).map_err(|_| demand_utf8(&arg))?;
 
                 // split combined shorthand options
@@ -578,13 +620,14 @@ impl SudoArg {
                         }
                         if next.is_some() {
                             processed.push(SudoArg::Argument(flag, rest.to_string()));
+                        } else if curr == 'h' {
+                            // '-h' only takes an argument when it is attached, otherwise it is
+                            // the short version of --help
+                            processed.push(SudoArg::Flag(flag));
                         } else if let Some(next) = arg_iter.next() {
                             let next = str::from_utf8(next.as_encoded_bytes())
                                 .map_err(|_| demand_utf8(&next))?;
                             processed.push(SudoArg::Argument(flag, next.to_owned()));
-                        } else if curr == 'h' {
-                            // short version of --help has no arguments
-                            processed.push(SudoArg::Flag(flag));
                         } else {
                             Err(xlat!("'{option}' expects an argument", option = flag))?;
                         }
@@ -617,19 +660,19 @@ impl SudoOptions {
             SudoAction::RemoveTimestamp(self.try_into()?)
         } else if self.validate {
             SudoAction::Validate(self.try_into()?)
-        } else if self.list.is_some() {
+        } else if self.list.is_some() || self.suggest.is_some() {
             SudoAction::List(self.try_into()?)
         } else if self.edit {
             SudoAction::Edit(self.try_into()?)
         } else {
             let is_run = self.login | self.shell | !self.positional_args.is_empty();
 
-            if is_run {
-                SudoAction::Run(self.try_into()?)
-            } else if self.reset_timestamp {
+            if !is_run && self.reset_timestamp {
                 SudoAction::ResetTimestamp(self.try_into()?)
             } else {
-                return Err(xlat!("expected one of these actions: --help, --version, --remove-timestamp, --validate, --list, --edit, --login, --shell, a command as a positional argument, --reset-timestamp").into());
+                // without a command, a shell is run if the policy sets `shell_noargs`; whether
+                // it does is only known once the policy has been read, see `Context`
+                SudoAction::Run(self.try_into()?)
             }
         };
 
@@ -712,21 +755,28 @@ impl SudoOptions {
                     "-v" | "--validate" => {
                         options.validate = true;
                     }
-                    _option => {
-                        Err(xlat!("invalid option provided"))?;
+                    option => {
+                        Err(xlat!("invalid option '{option}'", option = option))?;
                     }
                 },
                 SudoArg::Argument(option, value) => match option.as_str() {
                     "-D" | "--chdir" => {
                         options.chdir = Some(SudoPath::from_cli_string(value));
                     }
+                    "-R" | "--chroot" => {
+                        options.chroot = Some(SudoPath::from_cli_string(value));
+                    }
+                    "-T" | "--command-timeout" => {
+                        let timeout = SudoersDuration::parse_timeout(&value).ok_or_else(|| {
+                            xlat!("invalid timeout value '{value}'", value = value)
+                        })?;
+                        options.command_timeout = Some(timeout);
+                    }
                     "-E" | "--preserve-env" => {
                         options
                             .env_var_list
                             .extend(value.split(',').filter_map(|var| {
-                                std::env::var(var)
-                                    .ok()
-                                    .map(|value| (var.to_string(), value))
+                                std::env::var_os(var).map(|value| (var.to_string(), value))
                             }));
                     }
                     "-g" | "--group" => {
@@ -741,8 +791,23 @@ impl SudoOptions {
                     "-u" | "--user" => {
                         options.user = Some(SudoString::from_cli_string(value));
                     }
-                    _option => {
-                        Err(xlat!("invalid option provided"))?;
+                    "--suggest" => {
+                        options.suggest = Some(value);
+                    }
+                    "--status-json" => {
+                        // the standard streams are not meant for this, and the command may
+                        // write to them as well
+                        let fd = value
+                            .parse::<RawFd>()
+                            .ok()
+                            .filter(|&fd| fd > 2)
+                            .ok_or_else(|| {
+                                xlat!("invalid file descriptor '{value}'", value = value)
+                            })?;
+                        options.status_json = Some(fd);
+                    }
+                    option => {
+                        Err(xlat!("invalid option '{option}'", option = option))?;
                     }
                 },
                 SudoArg::Environment(key, value) => {
@@ -775,19 +840,18 @@ impl SudoOptions {
     }
 }
 
-fn try_to_env_var(arg: &OsStr) -> Result<Option<(String, String)>, String> {
-    if !arg.as_encoded_bytes().contains(&b'=') {
+fn try_to_env_var(arg: &OsStr) -> Result<Option<(String, OsString)>, String> {
+    let bytes = arg.as_bytes();
+    let Some(equals) = bytes.iter().position(|&c| c == b'=') else {
         return Ok(None);
-    }
-
-    let arg = str::from_utf8(arg.as_encoded_bytes()).map_err(|_| demand_utf8(arg))?;
+    };
+    let (name, value) = (&bytes[..equals], &bytes[equals + 1..]);
 
-    let (name, value) = arg
-        .split_once('=')
-        .expect("checked above that an = is present");
+    // only the name has to be valid UTF-8; the value is passed on as-is
+    let name = str::from_utf8(name).map_err(|_| demand_utf8(arg))?;
 
     if name.chars().all(|c| c.is_alphanumeric() || c == '_') {
-        Ok(Some((name.to_owned(), value.to_owned())))
+        Ok(Some((name.to_owned(), OsStr::from_bytes(value).to_owned())))
     } else {
         Ok(None)
     }
@@ -857,6 +921,8 @@ fn reject_all(context: &str, opts: SudoOptions) -> Result<(), String> {
         bell,
         background,
         chdir,
+        chroot,
+        command_timeout,
         edit,
         group,
         help,
@@ -869,6 +935,8 @@ fn reject_all(context: &str, opts: SudoOptions) -> Result<(), String> {
         shell,
         stdin,
         prompt,
+        status_json,
+        suggest,
         user,
         validate,
         version,
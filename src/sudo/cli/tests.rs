@@ -1,4 +1,4 @@
-use crate::common::SudoPath;
+use crate::common::{SudoPath, SudoersDuration};
 
 use super::{SudoAction, SudoOptions, SudoRunOptions};
 
@@ -69,7 +69,7 @@ fn short_preserve_env_with_var_fails() {
 fn preserve_env_with_var() {
     let cmd = SudoOptions::try_parse_from(["sudo", "--preserve-env=HOME"]).unwrap();
     assert_eq!(
-        [("HOME".to_string(), std::env::var("HOME").unwrap())],
+        [("HOME".to_string(), std::env::var_os("HOME").unwrap())],
         cmd.env_var_list.as_slice()
     );
 }
@@ -80,8 +80,8 @@ fn preserve_env_with_several_vars() {
     let cmd = SudoOptions::try_parse_from(["sudo", "--preserve-env=PATH,HOME"]).unwrap();
     assert_eq!(
         [
-            ("PATH".to_string(), std::env::var("PATH").unwrap()),
-            ("HOME".to_string(), std::env::var("HOME").unwrap()),
+            ("PATH".to_string(), std::env::var_os("PATH").unwrap()),
+            ("HOME".to_string(), std::env::var_os("HOME").unwrap()),
         ],
         cmd.env_var_list.as_slice()
     );
@@ -97,7 +97,7 @@ fn preserve_env_boolean_and_list() {
     for args in argss {
         let cmd = SudoOptions::try_parse_from(args).unwrap();
         assert_eq!(
-            [("HOME".to_string(), std::env::var("HOME").unwrap())],
+            [("HOME".to_string(), std::env::var_os("HOME").unwrap())],
             cmd.env_var_list.as_slice()
         );
     }
@@ -124,7 +124,7 @@ fn env_variable() {
     let cmd = SudoOptions::try_parse_from(["sudo", "ENV=with_a_value"]).unwrap();
     assert_eq!(
         cmd.env_var_list,
-        vec![("ENV".to_owned(), "with_a_value".to_owned())]
+        vec![("ENV".to_owned(), "with_a_value".into())]
     );
     assert!(cmd.positional_args.is_empty());
 }
@@ -143,21 +143,40 @@ fn several_env_variables() {
     assert_eq!(
         cmd.env_var_list,
         vec![
-            ("ENV".to_owned(), "with_a_value".to_owned()),
-            ("another_var".to_owned(), "otherval".to_owned()),
-            ("more".to_owned(), "this_is_a_val".to_owned())
+            ("ENV".to_owned(), "with_a_value".into()),
+            ("another_var".to_owned(), "otherval".into()),
+            ("more".to_owned(), "this_is_a_val".into())
         ]
     );
     assert!(cmd.positional_args.is_empty());
 }
 
+/// The value of an env variable can be any sequence of bytes; only its name must be UTF-8.
+#[test]
+fn env_variable_with_non_utf8_value() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    let arg = OsStr::from_bytes(b"LATIN=caf\xe9\xff");
+    let cmd = SudoOptions::try_parse_from([OsStr::new("sudo"), arg]).unwrap();
+    assert_eq!(
+        cmd.env_var_list,
+        vec![(
+            "LATIN".to_owned(),
+            OsStr::from_bytes(b"caf\xe9\xff").to_owned()
+        )]
+    );
+
+    let arg = OsStr::from_bytes(b"caf\xe9=latin");
+    assert!(SudoOptions::try_parse_from([OsStr::new("sudo"), arg]).is_err());
+}
+
 /// Mix env variables and trailing arguments that just pass through sudo
 /// Divided by hyphens.
 #[test]
 fn mix_env_variables_with_trailing_args_divided_by_hyphens() {
     let cmd = SudoOptions::try_parse_from(["sudo", "env=var", "--", "external=args", "something"])
         .unwrap();
-    assert_eq!(cmd.env_var_list, vec![("env".to_owned(), "var".to_owned())]);
+    assert_eq!(cmd.env_var_list, vec![("env".to_owned(), "var".into())]);
     assert_eq!(cmd.positional_args, vec!["external=args", "something"]);
 }
 
@@ -168,7 +187,7 @@ fn mix_env_variables_with_trailing_args_divided_by_known_flag() {
     let cmd = SudoOptions::try_parse_from(["sudo", "-i", "external=args", "something"]).unwrap();
     assert_eq!(
         cmd.env_var_list,
-        vec![("external".to_owned(), "args".to_owned())]
+        vec![("external".to_owned(), "args".into())]
     );
     assert!(cmd.login);
     assert_eq!(cmd.positional_args, vec!["something"]);
@@ -218,7 +237,7 @@ fn first_trailing_env_var_is_not_an_external_arg() {
     } else {
         panic!()
     };
-    assert_eq!(opts.env_var_list, vec![("FOO".to_owned(), "1".to_owned()),]);
+    assert_eq!(opts.env_var_list, vec![("FOO".to_owned(), "1".into()),]);
     assert_eq!(opts.positional_args, ["command", "BAR=2"],);
 }
 
@@ -233,8 +252,8 @@ fn trailing_env_vars_are_external_args() {
     assert_eq!(
         cmd.env_var_list,
         vec![
-            ("FOO".to_owned(), "1".to_owned()),
-            ("BAR".to_owned(), "2".to_owned())
+            ("FOO".to_owned(), "1".into()),
+            ("BAR".to_owned(), "2".into())
         ]
     );
     assert_eq!(
@@ -248,7 +267,7 @@ fn trailing_env_vars_are_external_args() {
 #[test]
 fn single_env_var_declaration() {
     let cmd = SudoOptions::try_parse_from(["sudo", "FOO=1", "command"]).unwrap();
-    assert_eq!(cmd.env_var_list, vec![("FOO".to_owned(), "1".to_owned())]);
+    assert_eq!(cmd.env_var_list, vec![("FOO".to_owned(), "1".into())]);
     assert_eq!(cmd.positional_args, ["command"]);
 }
 
@@ -309,6 +328,66 @@ fn directory() {
     assert_eq!(cmd.chdir, Some(SudoPath::from("/some/path")));
 }
 
+#[test]
+fn root_directory() {
+    let cmd = SudoOptions::try_parse_from(["sudo", "-R/srv/jail"]).unwrap();
+    assert_eq!(cmd.chroot, Some(SudoPath::from("/srv/jail")));
+
+    let cmd = SudoOptions::try_parse_from(["sudo", "--chroot", "/srv/jail"]).unwrap();
+    assert_eq!(cmd.chroot, Some(SudoPath::from("/srv/jail")));
+
+    let cmd = SudoOptions::try_parse_from(["sudo", "--chroot=/srv/jail"]).unwrap();
+    assert_eq!(cmd.chroot, Some(SudoPath::from("/srv/jail")));
+
+    let cmd = SudoAction::try_parse_from(["sudo", "-R/srv/jail", "ls"])
+        .unwrap()
+        .try_into_run()
+        .ok()
+        .unwrap();
+    assert_eq!(cmd.chroot, Some(SudoPath::from("/srv/jail")));
+
+    assert!(SudoAction::try_parse_from(["sudo", "-R/srv/jail", "-v"]).is_err());
+}
+
+#[test]
+fn command_timeout() {
+    let expected = Some(SudoersDuration::from_secs(90));
+
+    let cmd = SudoOptions::try_parse_from(["sudo", "-T90"]).unwrap();
+    assert_eq!(cmd.command_timeout, expected);
+
+    let cmd = SudoOptions::try_parse_from(["sudo", "--command-timeout", "1m30s"]).unwrap();
+    assert_eq!(cmd.command_timeout, expected);
+
+    let cmd = SudoAction::try_parse_from(["sudo", "--command-timeout=1m30s", "sleep", "100"])
+        .unwrap()
+        .try_into_run()
+        .ok()
+        .unwrap();
+    assert_eq!(cmd.command_timeout, expected);
+
+    assert!(SudoOptions::try_parse_from(["sudo", "-T", "soon"]).is_err());
+    assert!(SudoAction::try_parse_from(["sudo", "-T5", "-v"]).is_err());
+}
+
+#[test]
+fn status_json() {
+    let cmd = SudoAction::try_parse_from(["sudo", "--status-json=3", "true"])
+        .unwrap()
+        .try_into_run()
+        .ok()
+        .unwrap();
+    assert_eq!(cmd.status_json, Some(3));
+
+    let cmd = SudoOptions::try_parse_from(["sudo", "--status-json", "9"]).unwrap();
+    assert_eq!(cmd.status_json, Some(9));
+
+    for fd in ["0", "1", "2", "-1", "stderr", ""] {
+        assert!(SudoOptions::try_parse_from(["sudo", "--status-json", fd, "true"]).is_err());
+    }
+    assert!(SudoAction::try_parse_from(["sudo", "--status-json=3", "-v"]).is_err());
+}
+
 #[test]
 fn group() {
     let cmd = SudoOptions::try_parse_from(["sudo", "-grustaceans"]).unwrap();
@@ -405,6 +484,26 @@ fn conflicting_arguments() {
     assert!(cmd.is_reset_timestamp());
 }
 
+#[test]
+fn timestamp_actions_for_other_user() {
+    let Ok(SudoAction::RemoveTimestamp(options)) =
+        SudoAction::try_parse_from(["sudo", "-K", "-u", "ferris"])
+    else {
+        panic!("expected a remove timestamp action");
+    };
+    assert_eq!(options.user.as_deref(), Some("ferris"));
+
+    let Ok(SudoAction::ResetTimestamp(options)) =
+        SudoAction::try_parse_from(["sudo", "-k", "--user=ferris"])
+    else {
+        panic!("expected a reset timestamp action");
+    };
+    assert_eq!(options.user.as_deref(), Some("ferris"));
+
+    assert!(SudoAction::try_parse_from(["sudo", "-K", "-g", "ferris"]).is_err());
+    assert!(SudoAction::try_parse_from(["sudo", "-k", "-U", "ferris"]).is_err());
+}
+
 #[test]
 fn list() {
     let valid: &[&[_]] = &[
@@ -433,6 +532,31 @@ fn list() {
     }
 }
 
+#[test]
+fn suggest() {
+    for args in [
+        &["sudo", "--suggest", "/usr/bin/"][..],
+        &["sudo", "--suggest=/usr/bin/"],
+        &["sudo", "-l", "--suggest", "/usr/bin/"],
+        &["sudo", "-U", "ferris", "--suggest", "/usr/bin/"],
+    ] {
+        let SudoAction::List(opts) = SudoAction::try_parse_from(args.iter().copied()).unwrap()
+        else {
+            panic!("expected list mode");
+        };
+        assert_eq!(opts.suggest.as_deref(), Some("/usr/bin/"));
+    }
+
+    let SudoAction::List(opts) = SudoAction::try_parse_from(["sudo", "-l"]).unwrap() else {
+        panic!("expected list mode");
+    };
+    assert_eq!(opts.suggest, None);
+
+    assert!(SudoAction::try_parse_from(["sudo", "--suggest"]).is_err());
+    assert!(SudoAction::try_parse_from(["sudo", "--suggest", "/usr", "true"]).is_err());
+    assert!(SudoAction::try_parse_from(["sudo", "--suggest", "/usr", "-v"]).is_err());
+}
+
 #[test]
 fn validate() {
     let cmd = SudoAction::try_parse_from(["sudo", "-v"]).unwrap();
@@ -488,7 +612,26 @@ fn run_reset_timestamp_shell() {
 
 #[test]
 fn run_no_command() {
-    assert!(SudoAction::try_parse_from(["sudo", "-u", "root"]).is_err());
+    // whether this is allowed depends on `Defaults shell_noargs`
+    for args in [&["sudo"][..], &["sudo", "-u", "root"]] {
+        let action = SudoAction::try_parse_from(args)
+            .unwrap()
+            .try_into_run()
+            .ok()
+            .unwrap();
+        assert!(action.implied_shell);
+        assert!(!action.shell);
+    }
+
+    let action = SudoAction::try_parse_from(["sudo", "-s"])
+        .unwrap()
+        .try_into_run()
+        .ok()
+        .unwrap();
+    assert!(!action.implied_shell);
+
+    // options that do not go with running a shell are still rejected
+    assert!(SudoAction::try_parse_from(["sudo", "-U", "root"]).is_err());
 }
 
 #[test]
@@ -500,3 +643,126 @@ fn run_login() {
 fn run_shell() {
     assert!(SudoAction::try_parse_from(["sudo", "-s"]).unwrap().is_run());
 }
+
+#[test]
+fn option_syntax() {
+    // (arguments, user, login, shell, positional arguments)
+    type Case = (
+        &'static [&'static str],
+        Option<&'static str>,
+        bool,
+        bool,
+        &'static [&'static str],
+    );
+    let table: &[Case] = &[
+        (
+            &["-Hiu", "admin", "cmd"],
+            Some("admin"),
+            true,
+            false,
+            &["cmd"],
+        ),
+        (&["-iuadmin", "cmd"], Some("admin"), true, false, &["cmd"]),
+        (
+            &["-Hi", "-uadmin", "cmd"],
+            Some("admin"),
+            true,
+            false,
+            &["cmd"],
+        ),
+        (&["--user=admin", "-s"], Some("admin"), false, true, &[]),
+        (&["--user", "admin", "-s"], Some("admin"), false, true, &[]),
+        (&["--user=", "-s"], Some(""), false, true, &[]),
+        (&["--user==", "-s"], Some("="), false, true, &[]),
+        (&["-u", "-i", "cmd"], Some("-i"), false, false, &["cmd"]),
+        (
+            &["-u", "admin", "ls", "-l"],
+            Some("admin"),
+            false,
+            false,
+            &["ls", "-l"],
+        ),
+        (
+            &["-i", "--", "-u", "admin"],
+            None,
+            true,
+            false,
+            &["-u", "admin"],
+        ),
+        (&["-s", "--", "--"], None, false, true, &["--"]),
+        (
+            &["-u", "bob", "--", "-weird-binary", "--help"],
+            Some("bob"),
+            false,
+            false,
+            &["-weird-binary", "--help"],
+        ),
+        (
+            &["--", "ls", "-l", "--", "-i"],
+            None,
+            false,
+            false,
+            &["ls", "-l", "--", "-i"],
+        ),
+        (
+            &["-i", "--", "cmd", "--", "-x"],
+            None,
+            true,
+            false,
+            &["cmd", "--", "-x"],
+        ),
+        (&["-", "-i"], None, false, false, &["-", "-i"]),
+        (
+            &["ls", "--user=admin"],
+            None,
+            false,
+            false,
+            &["ls", "--user=admin"],
+        ),
+    ];
+
+    for (args, user, login, shell, positional) in table {
+        let cmd = SudoOptions::try_parse_from(std::iter::once(&"sudo").chain(args.iter()))
+            .unwrap_or_else(|err| panic!("{args:?}: {err}"));
+        assert_eq!(cmd.user.as_deref(), *user, "{args:?}");
+        assert_eq!(cmd.login, *login, "{args:?}");
+        assert_eq!(cmd.shell, *shell, "{args:?}");
+        assert_eq!(cmd.positional_args, *positional, "{args:?}");
+    }
+}
+
+#[test]
+fn option_errors_name_the_option() {
+    let table: &[(&[&str], &str)] = &[
+        (&["-x"], "invalid option '-x'"),
+        (&["-ixs"], "invalid option '-x'"),
+        (&["--wololo"], "invalid option '--wololo'"),
+        (&["--wololo=1"], "'--wololo' does not take any arguments"),
+        (&["--login=yes"], "'--login' does not take any arguments"),
+        (&["-hhost", "-v"], "invalid option '-h'"),
+        (&["--host=host", "-v"], "invalid option '--host'"),
+        (&["-u"], "'-u' expects an argument"),
+        (&["-iu"], "'-u' expects an argument"),
+        (&["--user"], "'--user' expects an argument"),
+        (&["-u=root", "ls"], "invalid option '='"),
+    ];
+
+    for (args, message) in table {
+        let Err(err) = SudoOptions::try_parse_from(std::iter::once(&"sudo").chain(args.iter()))
+        else {
+            panic!("{args:?} should not parse");
+        };
+        assert_eq!(err, *message, "{args:?}");
+    }
+}
+
+#[test]
+fn help_does_not_take_the_next_argument() {
+    let cmd = SudoOptions::try_parse_from(["sudo", "-h", "-V"]).unwrap();
+    assert!(cmd.help);
+    assert!(cmd.version);
+
+    let cmd = SudoOptions::try_parse_from(["sudo", "-h", "ls"]).unwrap();
+    assert!(cmd.help);
+    assert_eq!(cmd.positional_args, ["ls"]);
+}
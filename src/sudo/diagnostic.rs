@@ -43,13 +43,13 @@ macro_rules! diagnostic {
             $crate::sudo::diagnostic::cited_error(&format!($str), range, $path);
         } else {
             for line in format!($str).split('\n') {
-                eprintln_ignore_io_error!("sudo: {}", line);
+                eprintln_ignore_io_error!("sudo: {}", $crate::cutils::escape_control_chars(line));
             }
         }
     };
     ($str:expr) => {{
         for line in format!($str).split('\n') {
-            eprintln_ignore_io_error!("sudo: {}", line);
+            eprintln_ignore_io_error!("sudo: {}", $crate::cutils::escape_control_chars(line));
         }
     }};
 }
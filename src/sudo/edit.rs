@@ -4,7 +4,11 @@ use std::ffi::OsString;
 use std::fs::File;
 use std::io::{Read, Seek, Write};
 use std::net::Shutdown;
-use std::os::unix::{fs::OpenOptionsExt, net::UnixStream, process::ExitStatusExt};
+use std::os::unix::{
+    fs::{DirBuilderExt, OpenOptionsExt},
+    net::UnixStream,
+    process::ExitStatusExt,
+};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::{io, process};
@@ -218,13 +222,16 @@ fn handle_child_inner(
     for (i, file) in files.iter_mut().enumerate() {
         // Create temp file
         let dir = tempdir.0.join(format!("{i}"));
-        std::fs::create_dir(&dir).map_err(|e| {
-            xlat!(
-                "failed to create temporary directory {path}: {error}",
-                path = dir.display(),
-                error = e
-            )
-        })?;
+        std::fs::DirBuilder::new()
+            .mode(0o700)
+            .create(&dir)
+            .map_err(|e| {
+                xlat!(
+                    "failed to create temporary directory {path}: {error}",
+                    path = dir.display(),
+                    error = e
+                )
+            })?;
         let tempfile_path = dir.join(file.path.file_name().expect("file must have filename"));
         let mut tempfile = std::fs::OpenOptions::new()
             .read(true)
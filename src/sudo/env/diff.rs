@@ -0,0 +1,248 @@
+//! A record of the decisions made while building the environment of the command, so that the
+//! development logs can answer "why did my variable disappear?".
+
+use std::collections::{BTreeSet, HashMap};
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+
+use crate::system::escape_os_str_lossy;
+
+use super::environment::Environment;
+
+/// Values of variables whose names contain one of these are never logged.
+const SENSITIVE_NAMES: &[&str] = &["PASS", "TOKEN", "KEY", "SECRET"];
+
+/// Why a variable of the invoking user is not passed on to the command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Removal {
+    LoginShell,
+    ShellFunction,
+    SecurePath,
+    EnvCheck,
+    NotKept,
+    EnvMax,
+}
+
+impl fmt::Display for Removal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            Removal::LoginShell => "reset for a login shell",
+            Removal::ShellFunction => "value looks like a shell function",
+            Removal::SecurePath => "secure_path is set",
+            Removal::EnvCheck => "unsafe value for env_check",
+            Removal::NotKept => "not in env_keep or env_check",
+            Removal::EnvMax => "exceeds env_max",
+        };
+        f.write_str(reason)
+    }
+}
+
+/// The step of building the environment that set a variable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layer {
+    Pam,
+    Sudo,
+    CommandLine,
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let layer = match self {
+            Layer::Pam => "PAM",
+            Layer::Sudo => "sudo",
+            Layer::CommandLine => "the command line",
+        };
+        f.write_str(layer)
+    }
+}
+
+#[derive(Default)]
+pub struct EnvTrace {
+    removed: HashMap<OsString, Removal>,
+    set: HashMap<OsString, (Layer, OsString)>,
+}
+
+impl EnvTrace {
+    pub fn remove(&mut self, key: &OsStr, reason: Removal) {
+        self.removed.insert(key.to_owned(), reason);
+    }
+
+    /// Record that `layer` set `key` to `value`; variables that end up with a value that was
+    /// not recorded are attributed to sudo itself.
+    pub fn set(&mut self, key: &OsStr, value: &OsStr, layer: Layer) {
+        self.set.insert(key.to_owned(), (layer, value.to_owned()));
+    }
+
+    fn layer(&self, key: &OsStr, value: &OsStr) -> Layer {
+        match self.set.get(key) {
+            Some((layer, recorded)) if recorded == value => *layer,
+            _ => Layer::Sudo,
+        }
+    }
+
+    /// Describe how `after` differs from `before`, one variable per line in the order of their
+    /// names, for example (this example is checked by `documented_example` below):
+    ///
+    /// ```text
+    /// -DISPLAY=:0 (not in env_keep or env_check)
+    /// -GITHUB_TOKEN=<redacted> (not in env_keep or env_check)
+    /// ~PATH=/home/ferris/bin:/usr/bin -> /usr/sbin:/usr/bin (set by sudo)
+    /// +SUDO_USER=ferris (set by sudo)
+    /// ```
+    pub fn diff(&self, before: &Environment, after: &Environment) -> String {
+        let names = before.keys().chain(after.keys()).collect::<BTreeSet<_>>();
+
+        let mut lines = Vec::new();
+        for name in names {
+            let show = |value: &OsStr| {
+                if is_sensitive(name) {
+                    "<redacted>".to_string()
+                } else {
+                    escape_os_str_lossy(value)
+                }
+            };
+            let key = escape_os_str_lossy(name);
+
+            match (before.get(name), after.get(name)) {
+                (Some(old), None) => {
+                    let reason = self
+                        .removed
+                        .get(name)
+                        .map_or("removed".to_string(), ToString::to_string);
+                    lines.push(format!("-{key}={} ({reason})", show(old)));
+                }
+                (None, Some(new)) => {
+                    let layer = self.layer(name, new);
+                    lines.push(format!("+{key}={} (set by {layer})", show(new)));
+                }
+                (Some(old), Some(new)) if old != new => {
+                    let layer = self.layer(name, new);
+                    lines.push(format!(
+                        "~{key}={} -> {} (set by {layer})",
+                        show(old),
+                        show(new)
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        lines.join("\n")
+    }
+}
+
+fn is_sensitive(name: &OsStr) -> bool {
+    let name = name.to_string_lossy().to_ascii_uppercase();
+    SENSITIVE_NAMES.iter().any(|pattern| name.contains(pattern))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> Environment {
+        vars.iter()
+            .map(|(key, value)| (key.into(), value.into()))
+            .collect()
+    }
+
+    #[test]
+    fn diff_is_sorted_by_name() {
+        let before = env(&[
+            ("TERM", "xterm"),
+            ("PATH", "/home/ferris/bin"),
+            ("DISPLAY", ":0"),
+        ]);
+        let after = env(&[
+            ("TERM", "xterm"),
+            ("PATH", "/usr/bin"),
+            ("SUDO_USER", "ferris"),
+        ]);
+
+        let mut trace = EnvTrace::default();
+        trace.remove(OsStr::new("DISPLAY"), Removal::NotKept);
+
+        assert_eq!(
+            trace.diff(&before, &after),
+            "-DISPLAY=:0 (not in env_keep or env_check)\n\
+             ~PATH=/home/ferris/bin -> /usr/bin (set by sudo)\n\
+             +SUDO_USER=ferris (set by sudo)"
+        );
+        assert_eq!(trace.diff(&after, &after), "");
+    }
+
+    #[test]
+    fn documented_example() {
+        let before = env(&[
+            ("DISPLAY", ":0"),
+            ("GITHUB_TOKEN", "ghp_123"),
+            ("PATH", "/home/ferris/bin:/usr/bin"),
+        ]);
+        let after = env(&[("PATH", "/usr/sbin:/usr/bin"), ("SUDO_USER", "ferris")]);
+
+        let mut trace = EnvTrace::default();
+        trace.remove(OsStr::new("DISPLAY"), Removal::NotKept);
+        trace.remove(OsStr::new("GITHUB_TOKEN"), Removal::NotKept);
+
+        let doc = include_str!("diff.rs")
+            .lines()
+            .skip_while(|line| line.trim() != "/// ```text")
+            .skip(1)
+            .take_while(|line| line.trim() != "/// ```")
+            .map(|line| line.trim().trim_start_matches("/// "))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(trace.diff(&before, &after), doc);
+    }
+
+    #[test]
+    fn variables_are_attributed_to_the_layer_that_set_their_value() {
+        let before = env(&[]);
+        let after = env(&[("FOO", "cli"), ("LANG", "C"), ("PATH", "/usr/bin")]);
+
+        let mut trace = EnvTrace::default();
+        trace.set(OsStr::new("LANG"), OsStr::new("C"), Layer::Pam);
+        // overwritten later by secure_path
+        trace.set(OsStr::new("PATH"), OsStr::new("/opt/bin"), Layer::Pam);
+        trace.set(OsStr::new("FOO"), OsStr::new("cli"), Layer::CommandLine);
+
+        assert_eq!(
+            trace.diff(&before, &after),
+            "+FOO=cli (set by the command line)\n\
+             +LANG=C (set by PAM)\n\
+             +PATH=/usr/bin (set by sudo)"
+        );
+    }
+
+    #[test]
+    fn sensitive_values_are_redacted() {
+        let before = env(&[
+            ("GITHUB_TOKEN", "ghp_123"),
+            ("my_password", "hunter2"),
+            ("SSH_AUTH_SOCK", "/tmp/agent"),
+            ("AWS_SECRET_ACCESS_KEY", "abc"),
+        ]);
+        let after = env(&[("SSH_AUTH_SOCK", "/tmp/agent.2"), ("API_KEY", "xyz")]);
+
+        let diff = EnvTrace::default().diff(&before, &after);
+        assert_eq!(
+            diff,
+            "+API_KEY=<redacted> (set by sudo)\n\
+             -AWS_SECRET_ACCESS_KEY=<redacted> (removed)\n\
+             -GITHUB_TOKEN=<redacted> (removed)\n\
+             ~SSH_AUTH_SOCK=/tmp/agent -> /tmp/agent.2 (set by sudo)\n\
+             -my_password=<redacted> (removed)"
+        );
+    }
+
+    #[test]
+    fn names_and_values_are_escaped() {
+        let before = env(&[]);
+        let after = env(&[("EVIL", "\x1b[2Jgone")]);
+
+        assert_eq!(
+            EnvTrace::default().diff(&before, &after),
+            "+EVIL=\\u{1b}[2Jgone (set by sudo)"
+        );
+    }
+}
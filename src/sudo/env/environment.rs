@@ -4,20 +4,166 @@ use std::{
     os::unix::prelude::OsStrExt,
 };
 
-use crate::common::{CommandAndArguments, Context, Error, context::LaunchType};
+use crate::common::{CommandAndArguments, Context, DisplayOsStr, Error, context::LaunchType};
+use crate::log::{dev_debug, user_warn};
 use crate::sudoers::Restrictions;
-use crate::system::{PATH_MAX, audit::zoneinfo_path};
+use crate::system::{PATH_MAX, audit::zoneinfo_path, login_defs::LoginDefs};
 
+use super::diff::{EnvTrace, Layer, Removal};
 use super::wildcard_match::wildcard_match;
 
 // TODO: use _PATH_STDPATH from paths.h
 pub(crate) const PATH_DEFAULT: &str = "/usr/bin:/bin:/usr/sbin:/sbin";
 
-pub type Environment = HashMap<OsString, OsString>;
+/// The largest environment variable (name and value) that sudo will accept, regardless of the
+/// sudoers file; this is also the largest string that Linux allows to be passed to execve.
+pub(crate) const MAX_ENV_VAR_SIZE: usize = 128 * 1024;
 
-/// obtain the system environment
-pub fn system_environment() -> Environment {
-    std::env::vars_os().collect()
+/// A set of environment variables that remembers the order in which they were added.
+///
+/// Replacing the value of a variable keeps it in its original position, so that the
+/// environment of the command does not depend on hashing or on the order of the sudoers file.
+#[derive(Clone, Debug, Default)]
+pub struct Environment {
+    vars: Vec<(OsString, OsString)>,
+    index: HashMap<OsString, usize>,
+}
+
+impl Environment {
+    pub fn get(&self, key: &OsStr) -> Option<&OsString> {
+        self.index.get(key).map(|&i| &self.vars[i].1)
+    }
+
+    pub fn contains_key(&self, key: &OsStr) -> bool {
+        self.index.contains_key(key)
+    }
+
+    /// Set a variable; if it was already present, its value is replaced in-place and the
+    /// previous value is returned.
+    pub fn insert(&mut self, key: OsString, value: OsString) -> Option<OsString> {
+        if let Some(&i) = self.index.get(&key) {
+            Some(std::mem::replace(&mut self.vars[i].1, value))
+        } else {
+            self.index.insert(key.clone(), self.vars.len());
+            self.vars.push((key, value));
+            None
+        }
+    }
+
+    /// Set a variable only if it is not already present.
+    pub fn insert_if_absent(&mut self, key: OsString, value: impl FnOnce() -> OsString) {
+        if !self.contains_key(&key) {
+            self.insert(key, value());
+        }
+    }
+
+    /// The names of the variables, in the order in which they were added.
+    pub fn keys(&self) -> impl Iterator<Item = &OsStr> {
+        self.vars.iter().map(|(key, _)| key.as_os_str())
+    }
+}
+
+impl FromIterator<(OsString, OsString)> for Environment {
+    fn from_iter<T: IntoIterator<Item = (OsString, OsString)>>(iter: T) -> Self {
+        let mut environment = Environment::default();
+        environment.extend(iter);
+        environment
+    }
+}
+
+impl Extend<(OsString, OsString)> for Environment {
+    fn extend<T: IntoIterator<Item = (OsString, OsString)>>(&mut self, iter: T) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl IntoIterator for Environment {
+    type Item = (OsString, OsString);
+    type IntoIter = std::vec::IntoIter<(OsString, OsString)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.vars.into_iter()
+    }
+}
+
+impl std::ops::Index<&OsStr> for Environment {
+    type Output = OsString;
+
+    fn index(&self, key: &OsStr) -> &OsString {
+        self.get(key).expect("environment variable not present")
+    }
+}
+
+/// obtain the system environment; variables larger than [MAX_ENV_VAR_SIZE] are dropped
+pub fn system_environment(settings: &Restrictions) -> Environment {
+    admit_variables(std::env::vars_os(), settings)
+}
+
+/// Collect `vars`, dropping those larger than [MAX_ENV_VAR_SIZE] as they are read; the user is
+/// only warned about the ones that `settings` would otherwise have passed on to the command.
+pub(super) fn admit_variables(
+    vars: impl IntoIterator<Item = (OsString, OsString)>,
+    settings: &Restrictions,
+) -> Environment {
+    let mut limit = SizeLimit::per_variable(MAX_ENV_VAR_SIZE);
+    vars.into_iter()
+        .filter(|(key, value)| {
+            let admitted = limit.admit(key, value);
+            if !admitted && should_keep(key, value, settings) {
+                warn_too_large(key);
+            }
+            admitted
+        })
+        .collect()
+}
+
+/// The number of bytes an environment variable takes up in the environment of a process
+fn env_var_size(key: &OsStr, value: &OsStr) -> usize {
+    // "KEY=VALUE" plus a null terminator
+    key.len() + value.len() + 2
+}
+
+/// Keeps track of how large environment variables are allowed to be; variables that are too
+/// large are never truncated (since that could change their meaning) but dropped altogether.
+struct SizeLimit {
+    per_variable: usize,
+    remaining: usize,
+}
+
+impl SizeLimit {
+    fn per_variable(max: usize) -> Self {
+        Self {
+            per_variable: max,
+            remaining: usize::MAX,
+        }
+    }
+
+    fn total(max: usize) -> Self {
+        Self {
+            per_variable: MAX_ENV_VAR_SIZE,
+            remaining: max,
+        }
+    }
+
+    /// Check whether a variable fits (and account for it if it does).
+    fn admit(&mut self, key: &OsStr, value: &OsStr) -> bool {
+        let size = env_var_size(key, value);
+        if size <= self.per_variable && size <= self.remaining {
+            self.remaining -= size;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn warn_too_large(key: &OsStr) {
+    user_warn!(
+        "environment variable {name} is too large and has been removed",
+        name = DisplayOsStr(key)
+    );
 }
 
 /// check byte slice contains with given byte slice
@@ -47,6 +193,7 @@ fn format_command(command_and_arguments: &CommandAndArguments) -> OsString {
 fn add_extra_env(
     context: &Context,
     cfg: &Restrictions,
+    login_defs: &LoginDefs,
     sudo_ps1: Option<OsString>,
     environment: &mut Environment,
 ) {
@@ -63,27 +210,38 @@ fn add_extra_env(
     environment.insert("SUDO_USER".into(), context.current_user.name.clone().into());
     environment.insert("SUDO_HOME".into(), context.current_user.home.clone().into());
     // The current SHELL variable should determine the shell to run when -s is passed, if none set use passwd entry
-    environment
-        .entry("SHELL".into())
-        .or_insert_with(|| context.target_user.shell.clone().into());
+    environment.insert_if_absent("SHELL".into(), || context.target_user.shell.clone().into());
     // HOME: Set to the home directory of the target user if -i or -H are specified, env_reset or always_set_home are
     // set in sudoers, or when the -s option is specified and set_home is set in sudoers.
     // In sudo-rs env_reset is mandatory, so we always set HOME unless it's in the env_keep list.
-    environment
-        .entry("HOME".into())
-        .or_insert_with(|| context.target_user.home.clone().into());
+    environment.insert_if_absent("HOME".into(), || context.target_user.home.clone().into());
+    // MAIL: Set to the mail spool of the target user if -i is specified, or if MAIL is not in
+    // the env_keep list. Where that spool is depends on the MAIL_DIR setting in login.defs;
+    // system accounts do not get mail, so a login shell for one of those gets no MAIL at all.
+    let system_login = context.launch == LaunchType::Login
+        && login_defs.is_system_account(context.target_user.uid);
+    if !system_login {
+        environment.insert_if_absent("MAIL".into(), || {
+            login_defs.mailbox(&context.target_user.name).into()
+        });
+    }
+
+    // Set to the login name of the target user when the -i option is specified, or
+    // when the set_logname option is enabled in sudoers (unless LOGNAME is present in the
+    // env_keep list). With `!set_logname`, these keep referring to the invoking user.
+    let logname = if cfg.set_logname || context.launch == LaunchType::Login {
+        &context.target_user.name
+    } else {
+        &context.current_user.name
+    };
 
     match (
         environment.get(OsStr::new("LOGNAME")),
         environment.get(OsStr::new("USER")),
     ) {
-        // Set to the login name of the target user when the -i option is specified,
-        // when the set_logname option is enabled in sudoers, or when the env_reset option
-        // is enabled in sudoers (unless LOGNAME is present in the env_keep list).
-        // Since we always want to do env_reset -> always set these except when present in env
         (None, None) => {
-            environment.insert("LOGNAME".into(), context.target_user.name.clone().into());
-            environment.insert("USER".into(), context.target_user.name.clone().into());
+            environment.insert("LOGNAME".into(), logname.clone().into());
+            environment.insert("USER".into(), logname.clone().into());
         }
         // LOGNAME should be set to the same value as USER if the latter is preserved.
         (None, Some(user)) => {
@@ -96,19 +254,22 @@ fn add_extra_env(
         (Some(_), Some(_)) => {}
     }
 
+    // LOGIN is only present if it was preserved, but set_logname controls it together
+    // with LOGNAME and USER, so it should never name a different user than those.
+    if cfg.set_logname && environment.contains_key(OsStr::new("LOGIN")) {
+        let logname = environment[OsStr::new("LOGNAME")].clone();
+        environment.insert("LOGIN".into(), logname);
+    }
+
     // Overwrite PATH when secure_path is set
     if let Some(secure_path) = &cfg.path {
         // assign path by env path or secure_path configuration
         environment.insert("PATH".into(), secure_path.into());
     }
     // If the PATH and TERM variables are not preserved from the user's environment, they will be set to default value
-    environment
-        .entry("PATH".into())
-        .or_insert_with(|| PATH_DEFAULT.into());
+    environment.insert_if_absent("PATH".into(), || PATH_DEFAULT.into());
     // If the TERM variable is not preserved from the user's environment, it will be set to default value
-    environment
-        .entry("TERM".into())
-        .or_insert_with(|| "unknown".into());
+    environment.insert_if_absent("TERM".into(), || "unknown".into());
     // The SUDO_PS1 variable requires special treatment as the PS1 variable must be set in the
     // target environment to the same value of SUDO_PS1 if the latter is set.
     if let Some(sudo_ps1_value) = sudo_ps1 {
@@ -167,31 +328,46 @@ fn in_table(needle: (&OsStr, &OsStr), haystack: &HashSet<String>) -> bool {
 
 /// Determine whether a specific environment variable should be kept
 fn should_keep(key: &OsStr, value: &OsStr, cfg: &Restrictions) -> bool {
+    removal_reason(key, value, cfg).is_none()
+}
+
+/// Determine why a specific environment variable should not be kept, if it should not
+fn removal_reason(key: &OsStr, value: &OsStr, cfg: &Restrictions) -> Option<Removal> {
+    let removed_unless = |keep: bool, reason| if keep { None } else { Some(reason) };
+
     if value.as_bytes().starts_with("()".as_bytes()) {
-        return false;
+        return Some(Removal::ShellFunction);
     }
 
     if cfg.path.is_some() && key == "PATH" {
-        return false;
+        return Some(Removal::SecurePath);
     }
 
     if key == "TZ" {
-        return in_table((key, value), cfg.env_keep)
-            || (in_table((key, value), cfg.env_check) && is_safe_tz(value.as_bytes()));
+        if in_table((key, value), cfg.env_keep) {
+            return None;
+        }
+        if !in_table((key, value), cfg.env_check) {
+            return Some(Removal::NotKept);
+        }
+        return removed_unless(is_safe_tz(value.as_bytes()), Removal::EnvCheck);
     }
 
     if in_table((key, value), cfg.env_check) {
-        return !value.as_bytes().iter().any(|c| *c == b'%' || *c == b'/');
+        return removed_unless(
+            !value.as_bytes().iter().any(|c| *c == b'%' || *c == b'/'),
+            Removal::EnvCheck,
+        );
     }
 
-    in_table((key, value), cfg.env_keep)
+    removed_unless(in_table((key, value), cfg.env_keep), Removal::NotKept)
 }
 
 /// Construct the final environment from the current one and a sudo context
 /// see <https://github.com/sudo-project/sudo/blob/main/plugins/sudoers/env.c> for the original implementation
 /// see <https://www.sudo.ws/docs/man/sudoers.man/#Command_environment> for the original documentation
 ///
-/// The HOME, SHELL, LOGNAME and USER environment variables are initialized based on the target user
+/// The HOME, MAIL, SHELL, LOGNAME and USER environment variables are initialized based on the target user
 /// and the SUDO_* variables are set based on the invoking user.
 ///
 /// Additional variables, such as DISPLAY, PATH and TERM, are preserved from the invoking user's
@@ -199,37 +375,76 @@ fn should_keep(key: &OsStr, value: &OsStr, cfg: &Restrictions) -> bool {
 ///
 /// If the PATH and TERM variables are not preserved from the user's environment, they will be set to default value
 ///
-/// Environment variables with a value beginning with ‘()’ are removed
+/// Environment variables with a value beginning with ‘()’ are removed, as are variables that
+/// would make the environment preserved from the invoking user exceed the env_max limit
+///
+/// The resulting environment is ordered as follows: first the variables preserved from the
+/// invoking user's environment (in their original order), then those set by PAM, then the
+/// variables that sudo sets itself (in the order of `add_extra_env`), and finally the variables
+/// that were set on the command line. Replacing a variable does not change its position.
 pub fn get_target_environment(
     current_env: Environment,
     additional_env: impl IntoIterator<Item = (OsString, OsString)>,
-    user_override: Vec<(String, String)>,
+    user_override: Vec<(String, OsString)>,
     context: &Context,
     settings: &Restrictions,
+    login_defs: &LoginDefs,
 ) -> Result<Environment, Error> {
     // retrieve SUDO_PS1 value to set a PS1 value as additional environment
     let sudo_ps1 = current_env.get(OsStr::new("SUDO_PS1")).cloned();
 
-    // variables preserved from the invoking user's environment by the
-    // env_keep list take precedence over those in the PAM environment
-    let mut environment: HashMap<_, _> = additional_env.into_iter().collect();
-
     let login_vars: &[_] = if context.launch == LaunchType::Login {
-        &["HOME", "SHELL", "USER", "LOGNAME"].map(OsStr::new)
+        &["HOME", "MAIL", "SHELL", "USER", "LOGNAME"].map(OsStr::new)
     } else {
         &[]
     };
 
-    environment.extend(current_env.into_iter().filter(|(key, value)| {
-        !login_vars.contains(&key.as_os_str()) && should_keep(key, value, settings)
-    }));
+    // only variables that are passed on from the user count towards the env_max limit
+    let mut limit = SizeLimit::total(settings.env_max);
+
+    let mut trace = EnvTrace::default();
+    let original_env = cfg!(feature = "dev").then(|| current_env.clone());
+
+    let mut environment: Environment = current_env
+        .into_iter()
+        .filter(|(key, value)| {
+            let removal = if login_vars.contains(&key.as_os_str()) {
+                Some(Removal::LoginShell)
+            } else if let Some(reason) = removal_reason(key, value, settings) {
+                Some(reason)
+            } else if !limit.admit(key, value) {
+                warn_too_large(key);
+                Some(Removal::EnvMax)
+            } else {
+                None
+            };
+            if let Some(reason) = removal {
+                trace.remove(key, reason);
+            }
+            removal.is_none()
+        })
+        .collect();
+
+    // variables preserved from the invoking user's environment by the
+    // env_keep list take precedence over those in the PAM environment
+    for (key, value) in additional_env {
+        if !environment.contains_key(&key) {
+            trace.set(&key, &value, Layer::Pam);
+            environment.insert(key, value);
+        }
+    }
 
-    add_extra_env(context, settings, sudo_ps1, &mut environment);
+    add_extra_env(context, settings, login_defs, sudo_ps1, &mut environment);
 
     let mut rejected_vars = Vec::new();
     for (key, value) in user_override {
-        if should_keep(OsStr::new(&key), OsStr::new(&value), settings) {
-            environment.insert(key.into(), value.into());
+        if should_keep(OsStr::new(&key), &value, settings) {
+            if limit.admit(OsStr::new(&key), &value) {
+                trace.set(OsStr::new(&key), &value, Layer::CommandLine);
+                environment.insert(key.into(), value);
+            } else {
+                warn_too_large(OsStr::new(&key));
+            }
         } else {
             rejected_vars.push(key);
         }
@@ -238,13 +453,23 @@ pub fn get_target_environment(
         return Err(Error::EnvironmentVar(rejected_vars));
     }
 
+    if let Some(original_env) = original_env {
+        dev_debug!(
+            "changes to the environment:\n{}",
+            trace.diff(&original_env, &environment)
+        );
+    }
+
     Ok(environment)
 }
 
 /// Extend the environment with user-supplied info
-pub fn dangerous_extend<S>(env: &mut Environment, user_override: impl IntoIterator<Item = (S, S)>)
-where
-    S: Into<OsString>,
+pub fn dangerous_extend<K, V>(
+    env: &mut Environment,
+    user_override: impl IntoIterator<Item = (K, V)>,
+) where
+    K: Into<OsString>,
+    V: Into<OsString>,
 {
     env.extend(
         user_override
@@ -255,7 +480,7 @@ where
 
 #[cfg(test)]
 mod tests {
-    use super::{is_safe_tz, should_keep, zoneinfo_path};
+    use super::{Environment, Removal, is_safe_tz, removal_reason, should_keep, zoneinfo_path};
     use std::{collections::HashSet, ffi::OsStr};
 
     struct TestConfiguration {
@@ -271,16 +496,8 @@ mod tests {
                     OsStr::new(key),
                     OsStr::new(value),
                     &crate::sudoers::Restrictions {
-                        env_keep: &self.keep,
-                        env_check: &self.check,
                         path: self.path.as_deref(),
-                        chdir: crate::sudoers::DirChange::Strict(None),
-                        trust_environment: false,
-                        use_pty: true,
-                        umask: crate::exec::Umask::Preserve,
-                        #[cfg(feature = "apparmor")]
-                        apparmor_profile: None,
-                        noexec: false,
+                        ..crate::sudoers::Restrictions::with_env_lists(&self.keep, &self.check)
                     }
                 ),
                 expected,
@@ -313,6 +530,27 @@ mod tests {
         config.check_should_keep("PATH", "FOO", true);
     }
 
+    #[test]
+    fn tz_removal_reasons() {
+        let reason = |keep: &[&str], check: &[&str], value: &str| {
+            let to_set = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+            let (keep, check): (HashSet<String>, HashSet<String>) = (to_set(keep), to_set(check));
+            removal_reason(
+                OsStr::new("TZ"),
+                OsStr::new(value),
+                &crate::sudoers::Restrictions::with_env_lists(&keep, &check),
+            )
+        };
+
+        assert_eq!(reason(&[], &[], "Europe/Amsterdam"), Some(Removal::NotKept));
+        assert_eq!(
+            reason(&[], &["TZ"], "../Europe/Berlin"),
+            Some(Removal::EnvCheck)
+        );
+        assert_eq!(reason(&[], &["TZ"], "Europe/Amsterdam"), None);
+        assert_eq!(reason(&["TZ"], &[], "../Europe/Berlin"), None);
+    }
+
     #[allow(clippy::bool_assert_comparison)]
     #[test]
     fn test_tzinfo() {
@@ -337,4 +575,23 @@ mod tests {
             false
         );
     }
+
+    #[test]
+    fn test_environment_order() {
+        let mut env: Environment = [("B", "1"), ("A", "2"), ("C", "3")]
+            .into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect();
+
+        assert_eq!(env.insert("A".into(), "4".into()), Some("2".into()));
+        env.insert_if_absent("C".into(), || "5".into());
+        env.insert_if_absent("D".into(), || "6".into());
+
+        assert_eq!(env[OsStr::new("A")], "4");
+        assert_eq!(env[OsStr::new("C")], "3");
+        assert_eq!(
+            env.into_iter().collect::<Vec<_>>(),
+            [("B", "1"), ("A", "4"), ("C", "3"), ("D", "6")].map(|(k, v)| (k.into(), v.into()))
+        );
+    }
 }
@@ -1,5 +1,6 @@
 #![forbid(unsafe_code)]
 
+mod diff;
 pub mod environment;
 pub mod wildcard_match;
 
@@ -2,11 +2,12 @@ use crate::common::resolve::CurrentUser;
 use crate::common::{CommandAndArguments, Context};
 use crate::sudo::{
     cli::{SudoAction, SudoRunOptions},
-    env::environment::{Environment, get_target_environment},
+    env::environment::{Environment, PATH_DEFAULT, admit_variables, get_target_environment},
 };
 use crate::system::interface::{GroupId, UserId};
-use crate::system::{Group, Hostname, User};
+use crate::system::{Group, Hostname, User, login_defs::LoginDefs};
 use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
 
 const TESTS: &str = "
 > env
@@ -36,6 +37,7 @@ const TESTS: &str = "
     SUDO_USER=test
     SUDO_HOME=/home/test
     HOME=/root
+    MAIL=/var/spool/mail/root
     LOGNAME=root
     USER=root
     TERM=xterm
@@ -53,11 +55,17 @@ const TESTS: &str = "
     SUDO_USER=test
     SUDO_HOME=/home/test
     HOME=/home/test
+    MAIL=/var/spool/mail/test
     LOGNAME=test
     USER=test
     TERM=xterm
 ";
 
+/// The login.defs of the tests, so that they do not depend on that of the system
+fn login_defs() -> LoginDefs {
+    LoginDefs::parse("MAIL_DIR /var/spool/mail\nUID_MIN 1000\n")
+}
+
 fn parse_env_commands(input: &str) -> Vec<(&str, Environment)> {
     input
         .trim()
@@ -79,7 +87,8 @@ fn parse_env_commands(input: &str) -> Vec<(&str, Environment)> {
 
 fn create_test_context(sudo_options: SudoRunOptions) -> Context {
     let path = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin".to_string();
-    let command = CommandAndArguments::build_from_args(None, sudo_options.positional_args, &path);
+    let command =
+        CommandAndArguments::build_from_args(None, sudo_options.positional_args, &path, None);
 
     let current_user = CurrentUser::fake(User {
         uid: UserId::new(1000),
@@ -113,6 +122,7 @@ fn create_test_context(sudo_options: SudoRunOptions) -> Context {
     Context {
         hostname: Hostname::fake("test-ubuntu"),
         command,
+        secure_path: None,
         current_user: current_user.clone(),
         target_user: if sudo_options.user.as_deref() == Some("test") {
             current_user.into()
@@ -126,6 +136,8 @@ fn create_test_context(sudo_options: SudoRunOptions) -> Context {
         },
         launch: crate::common::context::LaunchType::Direct,
         chdir: sudo_options.chdir,
+        chroot: sudo_options.chroot,
+        command_timeout: sudo_options.command_timeout,
         askpass: sudo_options.askpass,
         stdin: sudo_options.stdin,
         prompt: sudo_options.prompt,
@@ -137,6 +149,15 @@ fn create_test_context(sudo_options: SudoRunOptions) -> Context {
     }
 }
 
+/// The restrictions that follow from the default settings.
+fn default_restrictions(settings: &crate::defaults::Settings) -> crate::sudoers::Restrictions<'_> {
+    crate::sudoers::Restrictions {
+        env_max: settings.env_max().try_into().unwrap(),
+        path: settings.secure_path(),
+        ..crate::sudoers::Restrictions::with_env_lists(settings.env_keep(), settings.env_check())
+    }
+}
+
 fn environment_to_set(environment: Environment) -> HashSet<String> {
     HashSet::from_iter(
         environment
@@ -163,18 +184,8 @@ fn test_environment_variable_filtering() {
             HashMap::new(),
             Vec::new(),
             &context,
-            &crate::sudoers::Restrictions {
-                env_keep: settings.env_keep(),
-                env_check: settings.env_check(),
-                path: settings.secure_path(),
-                use_pty: true,
-                chdir: crate::sudoers::DirChange::Strict(None),
-                trust_environment: false,
-                umask: crate::exec::Umask::Preserve,
-                #[cfg(feature = "apparmor")]
-                apparmor_profile: None,
-                noexec: false,
-            },
+            &default_restrictions(&settings),
+            &login_defs(),
         )
         .unwrap();
 
@@ -192,3 +203,294 @@ fn test_environment_variable_filtering() {
         );
     }
 }
+
+#[test]
+fn test_set_logname_disabled() {
+    let mut parts = parse_env_commands(TESTS);
+    let initial_env = parts.remove(0).1;
+
+    let options = SudoAction::try_parse_from(["sudo", "env"])
+        .unwrap()
+        .try_into_run()
+        .ok()
+        .unwrap();
+    let settings = crate::defaults::Settings::default();
+    let context = create_test_context(options);
+    let resulting_env = get_target_environment(
+        initial_env,
+        HashMap::new(),
+        Vec::new(),
+        &context,
+        &crate::sudoers::Restrictions {
+            set_logname: false,
+            ..default_restrictions(&settings)
+        },
+        &login_defs(),
+    )
+    .unwrap();
+
+    // the target user is root, but LOGNAME and USER keep referring to the invoking user
+    assert_eq!(resulting_env[OsStr::new("HOME")], "/root");
+    assert_eq!(resulting_env[OsStr::new("LOGNAME")], "test");
+    assert_eq!(resulting_env[OsStr::new("USER")], "test");
+}
+
+#[test]
+fn test_environment_ordering() {
+    let mut parts = parse_env_commands(TESTS);
+    let initial_env = parts.remove(0).1;
+
+    let build = || {
+        let options = SudoAction::try_parse_from(["sudo", "env"])
+            .unwrap()
+            .try_into_run()
+            .ok()
+            .unwrap();
+        let settings = crate::defaults::Settings::default();
+        let context = create_test_context(options);
+        get_target_environment(
+            initial_env.clone(),
+            [("PAM_VAR".into(), "1".into()), ("LANG".into(), "C".into())],
+            vec![("LANGUAGE".to_string(), "nl_NL".into())],
+            &context,
+            &default_restrictions(&settings),
+            &login_defs(),
+        )
+        .unwrap()
+        .into_iter()
+        .map(|(k, v)| format!("{}={}", k.to_str().unwrap(), v.to_str().unwrap()))
+        .collect::<Vec<_>>()
+    };
+
+    let resulting_env = build();
+    assert_eq!(resulting_env, build());
+
+    // inherited variables first (a replaced value keeps its position), then PAM variables,
+    // then the variables set by sudo itself
+    assert_eq!(
+        resulting_env,
+        [
+            "HOSTNAME=test-ubuntu",
+            "LANG=en_US.UTF-8",
+            "LANGUAGE=nl_NL",
+            "LC_ALL=en_US.UTF-8",
+            "LS_COLORS=cd=40;33;01:*.jpg=01;35:*.mp3=00;36:",
+            "PATH=/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin",
+            "TERM=xterm",
+            "PAM_VAR=1",
+            "SUDO_COMMAND=/usr/bin/env",
+            "SUDO_UID=1000",
+            "SUDO_GID=1000",
+            "SUDO_USER=test",
+            "SUDO_HOME=/home/test",
+            "SHELL=/bin/bash",
+            "HOME=/root",
+            "MAIL=/var/spool/mail/root",
+            "LOGNAME=root",
+            "USER=root",
+        ]
+    );
+}
+
+#[test]
+fn test_oversized_variables_are_dropped() {
+    let mut parts = parse_env_commands(TESTS);
+    let mut initial_env = parts.remove(0).1;
+    initial_env.insert("HUGE".into(), "x".repeat(10 * 1024 * 1024).into());
+    initial_env.insert("LARGE".into(), "x".repeat(100 * 1024).into());
+
+    let settings = crate::defaults::Settings::default();
+    let env_keep = settings
+        .env_keep()
+        .iter()
+        .cloned()
+        .chain(["HUGE", "LARGE", "BIG"].map(str::to_string))
+        .collect::<HashSet<_>>();
+
+    let build = |env_max: usize| {
+        let options = SudoAction::try_parse_from(["sudo", "env"])
+            .unwrap()
+            .try_into_run()
+            .ok()
+            .unwrap();
+        let context = create_test_context(options);
+        get_target_environment(
+            initial_env.clone(),
+            HashMap::new(),
+            vec![("BIG".to_string(), "x".repeat(50 * 1024).into())],
+            &context,
+            &crate::sudoers::Restrictions {
+                env_keep: &env_keep,
+                env_max,
+                ..default_restrictions(&settings)
+            },
+            &login_defs(),
+        )
+        .unwrap()
+    };
+
+    // a variable can never exceed the fixed limit, not even if env_max would allow it
+    let resulting_env = build(settings.env_max().try_into().unwrap());
+    assert!(!resulting_env.contains_key(OsStr::new("HUGE")));
+    assert_eq!(resulting_env[OsStr::new("LARGE")].len(), 100 * 1024);
+    assert_eq!(resulting_env[OsStr::new("BIG")].len(), 50 * 1024);
+
+    // variables are dropped (never truncated) once env_max is reached
+    let resulting_env = build(120 * 1024);
+    assert!(!resulting_env.contains_key(OsStr::new("HUGE")));
+    assert_eq!(resulting_env[OsStr::new("LARGE")].len(), 100 * 1024);
+    assert!(!resulting_env.contains_key(OsStr::new("BIG")));
+
+    // variables set by sudo itself are not affected
+    let resulting_env = build(0);
+    assert!(!resulting_env.contains_key(OsStr::new("LARGE")));
+    assert!(!resulting_env.contains_key(OsStr::new("HOSTNAME")));
+    assert_eq!(resulting_env[OsStr::new("SUDO_COMMAND")], "/usr/bin/env");
+    assert_eq!(resulting_env[OsStr::new("PATH")], PATH_DEFAULT);
+
+    let total_size = resulting_env
+        .into_iter()
+        .map(|(key, value)| key.len() + value.len() + 2)
+        .sum::<usize>();
+    assert!(total_size < 1024);
+}
+
+/// The largest amount of memory this process has had in use, in KiB.
+#[cfg(target_os = "linux")]
+fn peak_rss() -> u64 {
+    let status = std::fs::read_to_string("/proc/self/status").unwrap();
+    let line = status
+        .lines()
+        .find(|line| line.starts_with("VmHWM:"))
+        .unwrap();
+    line.split_whitespace().nth(1).unwrap().parse().unwrap()
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_oversized_variables_are_not_kept_in_memory() {
+    const MEASURE: &str = "SUDO_RS_TEST_MEASURE_PEAK_MEMORY";
+    const VARS: usize = 64;
+    const SIZE: usize = 1024 * 1024;
+
+    if std::env::var_os(MEASURE).is_none() {
+        // the other tests in this process would affect its peak memory use, so the measurement is
+        // done by running this test on its own in a new process
+        let (_crate, module) = module_path!().split_once("::").unwrap();
+        let name = format!("{module}::test_oversized_variables_are_not_kept_in_memory");
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", &name, "--test-threads=1", "--nocapture"])
+            .env(MEASURE, "1")
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            output.status.success() && stdout.contains("1 passed"),
+            "{stdout}{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return;
+    }
+
+    let settings = crate::defaults::Settings::default();
+    let env_keep = HashSet::from(["HUGE_*".to_string()]);
+    let restrictions = crate::sudoers::Restrictions {
+        env_keep: &env_keep,
+        ..default_restrictions(&settings)
+    };
+
+    // like the real environment, the variables are read one at a time
+    let huge_vars = (0..VARS).map(|i| (format!("HUGE_{i}").into(), "x".repeat(SIZE).into()));
+    let small_vars = [("SMALL".into(), "x".into())];
+
+    let peak_before = peak_rss();
+    let environment = admit_variables(huge_vars.chain(small_vars), &restrictions);
+    let peak_after = peak_rss();
+
+    assert!(environment.contains_key(OsStr::new("SMALL")));
+    assert_eq!(environment.into_iter().count(), 1);
+    // holding on to all of them would take 64 MiB
+    assert!(
+        peak_after - peak_before < (VARS * SIZE / 1024 / 2) as u64,
+        "peak memory grew from {peak_before} KiB to {peak_after} KiB"
+    );
+}
+
+#[test]
+fn test_non_utf8_values_are_preserved() {
+    use std::os::unix::ffi::OsStrExt;
+
+    let latin1 = OsStr::from_bytes(b"caf\xe9\xff");
+
+    let mut parts = parse_env_commands(TESTS);
+    let mut initial_env = parts.remove(0).1;
+    initial_env.insert("LATIN_KEPT".into(), latin1.into());
+    initial_env.insert("LC_LATIN".into(), latin1.into());
+    initial_env.insert("LATIN_DROPPED".into(), latin1.into());
+
+    let settings = crate::defaults::Settings::default();
+    let env_keep = settings
+        .env_keep()
+        .iter()
+        .cloned()
+        .chain(["LATIN_K*", "LATIN_SET"].map(str::to_string))
+        .collect::<HashSet<_>>();
+
+    let options = SudoAction::try_parse_from(["sudo", "env"])
+        .unwrap()
+        .try_into_run()
+        .ok()
+        .unwrap();
+    let context = create_test_context(options);
+    let resulting_env = get_target_environment(
+        initial_env,
+        HashMap::new(),
+        vec![("LATIN_SET".to_string(), latin1.into())],
+        &context,
+        &crate::sudoers::Restrictions {
+            env_keep: &env_keep,
+            ..default_restrictions(&settings)
+        },
+        &login_defs(),
+    )
+    .unwrap();
+
+    // env_keep, env_check and variables from the command line all pass the bytes on unchanged
+    assert_eq!(resulting_env[OsStr::new("LATIN_KEPT")], latin1);
+    assert_eq!(resulting_env[OsStr::new("LC_LATIN")], latin1);
+    assert_eq!(resulting_env[OsStr::new("LATIN_SET")], latin1);
+    assert!(!resulting_env.contains_key(OsStr::new("LATIN_DROPPED")));
+}
+
+#[test]
+fn test_login_shell_for_system_account_has_no_mail() {
+    let mut parts = parse_env_commands(TESTS);
+    let mut initial_env = parts.remove(0).1;
+    initial_env.insert("MAIL".into(), "/var/spool/mail/test".into());
+
+    let settings = crate::defaults::Settings::default();
+    let build = |uid| {
+        let options = SudoAction::try_parse_from(["sudo", "env"])
+            .unwrap()
+            .try_into_run()
+            .ok()
+            .unwrap();
+        let mut context = create_test_context(options);
+        context.launch = crate::common::context::LaunchType::Login;
+        context.target_user.uid = UserId::new(uid);
+        context.target_user.name = "daemon".into();
+        get_target_environment(
+            initial_env.clone(),
+            HashMap::new(),
+            Vec::new(),
+            &context,
+            &default_restrictions(&settings),
+            &login_defs(),
+        )
+        .unwrap()
+    };
+
+    // the invoking user's MAIL is never passed on to a login shell
+    assert!(!build(1).contains_key(OsStr::new("MAIL")));
+    assert_eq!(build(1000)[OsStr::new("MAIL")], "/var/spool/mail/daemon");
+}
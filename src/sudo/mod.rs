@@ -1,8 +1,9 @@
 #![deny(unsafe_code)]
 
-use crate::common::Error;
-use crate::common::resolve::CurrentUser;
+use crate::common::resolve::{CurrentUser, resolve_user};
+use crate::common::{Error, SudoString};
 use crate::log::dev_info;
+use crate::log::status::{self, Phase};
 use crate::system::User;
 use crate::system::interface::UserId;
 use crate::system::timestamp::RecordScope;
@@ -11,7 +12,7 @@ use crate::system::{Process, timestamp::SessionRecordFile};
 pub(crate) use cli::SudoAction;
 #[cfg(not(test))]
 use cli::SudoAction;
-use std::{path::PathBuf, time::Duration};
+use std::path::PathBuf;
 
 mod cli;
 pub(crate) use cli::{SudoEditOptions, SudoListOptions, SudoRunOptions, SudoValidateOptions};
@@ -19,7 +20,7 @@ mod edit;
 
 pub(crate) mod diagnostic;
 mod env;
-pub(crate) use env::environment::PATH_DEFAULT;
+pub(crate) use env::environment::{Environment, PATH_DEFAULT};
 mod pam;
 mod pipeline;
 
@@ -61,6 +62,48 @@ pub(crate) fn candidate_sudoers_file() -> PathBuf {
     path
 }
 
+/// What `sudo -V` additionally shows when it is run by root: the built-in configuration,
+/// and the value of every setting before the sudoers file is applied.
+fn configuration_details() -> String {
+    use crate::common::context::LaunchType;
+    use crate::system::paths;
+
+    // helpers are only shown if sudo would really run them
+    let helper = |description: &str, path: std::io::Result<Option<PathBuf>>| match path {
+        Ok(Some(path)) => format!("{description}: {}", path.display()),
+        Ok(None) => format!("{description}: none"),
+        Err(error) => format!("{description}: none ({error})"),
+    };
+
+    let mut lines = vec![
+        format!("Sudoers path: {}", candidate_sudoers_file().display()),
+        "Authentication methods: 'pam'".to_string(),
+        format!(
+            "PAM service name to use: {}",
+            pam::service_name(LaunchType::Direct)
+        ),
+        format!(
+            "PAM service name to use for login shells: {}",
+            pam::service_name(LaunchType::Login)
+        ),
+        format!(
+            "Path to authentication timestamp dir: {}",
+            SessionRecordFile::BASE_PATH
+        ),
+        format!("PATH to use if the user has none: {PATH_DEFAULT}"),
+        helper("Path to the askpass helper", paths::askpass()),
+        helper(
+            "Path to the non-unix group helper",
+            paths::nonunix_group_helper(),
+        ),
+        String::new(),
+        "Sudoers defaults:".to_string(),
+    ];
+    lines.extend(crate::defaults::describe(&Default::default()));
+
+    lines.join("\n")
+}
+
 fn sudo_process() -> Result<(), Error> {
     crate::log::SudoLogger::new("sudo: ").into_global_logger();
 
@@ -90,20 +133,30 @@ fn sudo_process() -> Result<(), Error> {
             }
             SudoAction::Version(_) => {
                 println_ignore_io_error!("sudo-rs {VERSION}");
+                if User::real_uid() == UserId::ROOT {
+                    println_ignore_io_error!("\n{}", configuration_details());
+                    if let Err(error) = pipeline::check_sudoers() {
+                        diagnostic::diagnostic!("{error}");
+                    }
+                }
                 std::process::exit(0);
             }
-            SudoAction::RemoveTimestamp(_) => {
+            SudoAction::RemoveTimestamp(options) => {
                 let user = CurrentUser::resolve()?;
-                let mut record_file = SessionRecordFile::open_for_user(&user, Duration::default())?;
-                record_file.reset()?;
+                let owner = records_owner(&user, options.user.as_ref())?;
+                SessionRecordFile::remove_for_uid(user.uid, owner)?;
                 Ok(())
             }
-            SudoAction::ResetTimestamp(_) => {
-                if let Some(scope) = RecordScope::for_process(&Process::new()) {
-                    let user = CurrentUser::resolve()?;
-                    let mut record_file =
-                        SessionRecordFile::open_for_user(&user, Duration::default())?;
-                    record_file.disable(scope)?;
+            SudoAction::ResetTimestamp(options) => {
+                let user = CurrentUser::resolve()?;
+                let owner = records_owner(&user, options.user.as_ref())?;
+                if owner == user.uid {
+                    if let Some(scope) = RecordScope::for_process(&Process::new()) {
+                        SessionRecordFile::disable_for_uid(user.uid, owner, Some(scope))?;
+                    }
+                } else {
+                    // the current session is not one of theirs, so invalidate all of them
+                    SessionRecordFile::disable_for_uid(user.uid, owner, None)?;
                 }
                 Ok(())
             }
@@ -113,7 +166,22 @@ fn sudo_process() -> Result<(), Error> {
                 unstable_warning();
 
                 // SudoAction::from_env() should already ensure this
-                assert!(!options.positional_args.is_empty() || options.shell || options.login);
+                assert!(
+                    !options.positional_args.is_empty()
+                        || options.shell
+                        || options.login
+                        || options.implied_shell
+                );
+
+                if let Some(fd) = options.status_json {
+                    status::enable(fd).map_err(|error| {
+                        Error::Options(xlat!(
+                            "cannot report progress on file descriptor {fd}: {error}",
+                            fd = fd,
+                            error = error
+                        ))
+                    })?;
+                }
 
                 pipeline::run(options)
             }
@@ -121,12 +189,26 @@ fn sudo_process() -> Result<(), Error> {
             SudoAction::Edit(options) => pipeline::run_edit(options),
         },
         Err(e) => {
-            eprintln_ignore_io_error!("{e}\n{}", usage_msg);
+            eprintln_ignore_io_error!("{}\n{}", crate::cutils::escape_control_chars(&e), usage_msg);
             std::process::exit(1);
         }
     }
 }
 
+/// Determine whose session records `-K` or `-k` act upon: those of the invoking user, or of
+/// the user given with `-u`, which only root may do for other users.
+fn records_owner(user: &CurrentUser, other: Option<&SudoString>) -> Result<UserId, Error> {
+    let Some(name_or_id) = other else {
+        return Ok(user.uid);
+    };
+
+    let owner = resolve_user(name_or_id)?.uid;
+    SessionRecordFile::authorize(user.uid, owner)
+        .map_err(|_| Error::Authorization(user.name.to_string()))?;
+
+    Ok(owner)
+}
+
 fn self_check() -> Result<(), Error> {
     if User::effective_uid() != UserId::ROOT {
         #[cfg(target_os = "linux")]
@@ -144,7 +226,13 @@ pub fn main() {
     match sudo_process() {
         Ok(()) => (),
         Err(error) => {
-            if !error.is_silent() {
+            status::report(Phase::Failed {
+                error: &error.to_string(),
+            });
+            if let Error::NoCommand = error {
+                // `sudo` by itself, without `Defaults shell_noargs`
+                eprintln_ignore_io_error!("{}", cli::help::usage_msg());
+            } else if !error.is_silent() {
                 diagnostic::diagnostic!("{error}");
             }
             std::process::exit(1);
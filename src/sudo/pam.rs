@@ -15,6 +15,7 @@ pub(super) struct InitPamArgs<'a> {
     pub(super) non_interactive: bool,
     pub(super) password_feedback: bool,
     pub(super) password_timeout: Option<Duration>,
+    pub(super) module_timeout: Option<Duration>,
     pub(super) auth_prompt: Option<String>,
     pub(super) auth_user: &'a str,
     pub(super) requesting_user: &'a str,
@@ -22,6 +23,14 @@ pub(super) struct InitPamArgs<'a> {
     pub(super) hostname: &'a str,
 }
 
+/// The PAM service that is used to authenticate for running a command in this way
+pub(super) fn service_name(launch: LaunchType) -> &'static str {
+    match launch {
+        LaunchType::Login if cfg!(feature = "pam-login") => "sudo-i",
+        LaunchType::Login | LaunchType::Shell | LaunchType::Direct => "sudo",
+    }
+}
+
 pub(super) fn init_pam(
     InitPamArgs {
         launch,
@@ -31,6 +40,7 @@ pub(super) fn init_pam(
         non_interactive,
         password_feedback,
         password_timeout,
+        module_timeout,
         auth_prompt,
         auth_user,
         requesting_user,
@@ -38,13 +48,9 @@ pub(super) fn init_pam(
         hostname,
     }: InitPamArgs,
 ) -> PamResult<PamContext> {
-    let service_name = match launch {
-        LaunchType::Login if cfg!(feature = "pam-login") => "sudo-i",
-        LaunchType::Login | LaunchType::Shell | LaunchType::Direct => "sudo",
-    };
     let mut pam = PamContext::new_cli(
         "sudo",
-        service_name,
+        service_name(launch),
         use_askpass,
         use_stdin,
         bell,
@@ -55,6 +61,7 @@ pub(super) fn init_pam(
     )?;
     pam.mark_silent(matches!(launch, LaunchType::Direct));
     pam.mark_allow_null_auth_token(false);
+    pam.set_module_timeout(module_timeout);
     pam.set_requesting_user(requesting_user)?;
 
     match auth_prompt.as_deref() {
@@ -112,6 +119,11 @@ pub(super) fn attempt_authenticate(
             // there was no error, so authentication succeeded
             Ok(_) => break,
 
+            // a PAM module wanted to prompt the user, which the -n flag does not allow
+            Err(PamError::InteractionRequired) => {
+                return Err(Error::InteractionRequired);
+            }
+
             // maxtries was reached, pam does not allow any more tries
             Err(PamError::Pam(PamErrorType::MaxTries)) => {
                 return Err(Error::MaxAuthAttempts(current_try));
@@ -138,27 +150,39 @@ pub(super) fn attempt_authenticate(
     Ok(())
 }
 
+/// Prepare PAM for running the command as `target_user`; establishing credentials and opening
+/// a session can each be turned off, and `PamContext::close_session` only undoes what was done.
 pub(super) fn pre_exec(
     pam: &mut PamContext,
     target_user: &str,
+    setcred: bool,
+    session: bool,
 ) -> Result<Vec<(OsString, OsString)>, Error> {
     // check what the current user in PAM is
     let user = pam.get_user()?;
     if user != target_user {
         // switch pam over to the target user
         pam.set_user(target_user)?;
+    }
 
-        // make sure that credentials are loaded for the target user
-        // errors are ignored because not all modules support this functionality
-        if let Err(e) = pam.credentials_reinitialize() {
+    // this is only reached once the policy allowed the command and the user was authenticated
+    // if that was required (no password may have been asked at all, because of NOPASSWD or
+    // a valid session record), and their account was validated if pam_acct_mgmt is set; so
+    // this is the point at which PAM expects credentials to be established (and modules that
+    // count failed attempts may reset their counters); errors are ignored because not all
+    // modules support this functionality
+    if setcred {
+        if let Err(e) = pam.credentials_establish() {
             dev_info!(
-                "PAM gave an error while trying to re-initialize credentials: {:?}",
+                "PAM gave an error while trying to establish credentials: {:?}",
                 e
             );
         }
     }
 
-    pam.open_session()?;
+    if session {
+        pam.open_session()?;
+    }
 
     let env_vars = pam.env()?;
 
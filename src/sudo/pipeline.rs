@@ -1,18 +1,24 @@
 use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
 use std::time::Duration;
 
 use super::cli::{SudoRunOptions, SudoValidateOptions};
 use super::diagnostic;
 use crate::common::resolve::{AuthUser, CurrentUser};
-use crate::common::{Context, Error};
-use crate::log::{auth_info, auth_warn};
+use crate::common::{Context, Error, SudoersDuration};
+use crate::exec::ExitReason;
+use crate::log::status::{self, Phase};
+use crate::log::{Entry, JournalEntry, LogBackend, auth_info, auth_warn, user_info, user_warn};
 use crate::pam::PamContext;
 use crate::sudo::env::environment;
 use crate::sudo::pam::{InitPamArgs, attempt_authenticate, init_pam, pre_exec};
 use crate::sudoers::{AuthenticatingUser, Authentication, Authorization, Judgement, Sudoers};
+use crate::system::login_defs::LoginDefs;
 use crate::system::term::current_tty_name;
 use crate::system::timestamp::{RecordScope, SessionRecordFile, TouchResult};
-use crate::system::{Process, escape_os_str_lossy};
+use crate::system::{Process, escape_os_str_lossy, paths};
 
 mod list;
 pub(super) use list::run_list;
@@ -20,10 +26,93 @@ pub(super) use list::run_list;
 mod edit;
 pub(super) use edit::run_edit;
 
+mod admin_flag;
+use admin_flag::create_admin_flag;
+
+mod seen_warnings;
+
 fn read_sudoers() -> Result<Sudoers, Error> {
     let sudoers_path = &super::candidate_sudoers_file();
 
-    let (sudoers, syntax_errors) = Sudoers::open(sudoers_path).map_err(|e| {
+    let (sudoers, syntax_errors) = open_sudoers(sudoers_path)?;
+
+    // mistakes in the sudoers files are reported every time; warnings about features that
+    // are not supported are only reported once, until they (or the files they are in) change
+    let warnings = syntax_errors
+        .iter()
+        .filter(|problem| problem.unsupported_feature);
+    let report_warnings = seen_warnings::are_new(warnings, sudoers_path);
+    let problems = syntax_errors
+        .into_iter()
+        .filter(|problem| report_warnings || !problem.unsupported_feature)
+        .collect();
+    report_problems(sudoers_path, problems);
+
+    Ok(sudoers)
+}
+
+/// Report all problems in the sudoers files, including the ones that were reported before;
+/// `sudo -V` does this when it is run by root.
+pub(super) fn check_sudoers() -> Result<(), Error> {
+    let sudoers_path = &super::candidate_sudoers_file();
+
+    let (_, syntax_errors) = open_sudoers(sudoers_path)?;
+    report_problems(sudoers_path, syntax_errors);
+
+    Ok(())
+}
+
+fn report_problems(sudoers_path: &std::path::Path, syntax_errors: Vec<crate::sudoers::Error>) {
+    for crate::sudoers::Error {
+        source,
+        location,
+        message,
+        ..
+    } in syntax_errors
+    {
+        let path = source.as_deref().unwrap_or(sudoers_path);
+        diagnostic::diagnostic!("{message}", path @ location);
+    }
+}
+
+/// Run `job`, which reads the policy and builds the context. These steps involve name service
+/// lookups that can hang (for instance on an unreachable LDAP server), before any prompt is
+/// shown; a notice is printed so that users do not assume they missed the password prompt.
+/// How long that takes can be set in sudo.conf.
+fn with_slowness_notice<T>(job: impl FnOnce() -> T) -> T {
+    match paths::slow_lookup_notice() {
+        Some(delay) => watchdog(
+            delay,
+            || user_info!("resolving user and group information is taking longer than expected"),
+            job,
+        ),
+        None => job(),
+    }
+}
+
+/// Run `job` on the current thread, while a helper thread calls `on_timeout` if `job` has not
+/// finished within `delay`. The helper thread is joined before this returns, so anything it
+/// prints cannot end up in the middle of output (such as a prompt) that follows.
+fn watchdog<T>(delay: Duration, on_timeout: impl FnOnce() + Send, job: impl FnOnce() -> T) -> T {
+    let (done, finished) = mpsc::channel::<()>();
+
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            if let Err(RecvTimeoutError::Timeout) = finished.recv_timeout(delay) {
+                on_timeout();
+            }
+        });
+
+        let result = job();
+        drop(done);
+        result
+    })
+}
+
+fn open_sudoers(
+    sudoers_path: &std::path::Path,
+) -> Result<(Sudoers, Vec<crate::sudoers::Error>), Error> {
+    Sudoers::open(sudoers_path).map_err(|e| {
         // Provide a more helpful error message when the sudoers file is missing
         if e.kind() == std::io::ErrorKind::NotFound {
             Error::Configuration(xlat!(
@@ -40,23 +129,11 @@ fn read_sudoers() -> Result<Sudoers, Error> {
         } else {
             Error::Configuration(xlat!("invalid configuration: {error}", error = e))
         }
-    })?;
-
-    for crate::sudoers::Error {
-        source,
-        location,
-        message,
-    } in syntax_errors
-    {
-        let path = source.as_deref().unwrap_or(sudoers_path);
-        diagnostic::diagnostic!("{message}", path @ location);
-    }
-
-    Ok(sudoers)
+    })
 }
 
-fn judge(mut policy: Sudoers, context: &Context) -> Result<Judgement, Error> {
-    Ok(policy.check(
+fn judge(policy: &mut Sudoers, context: &Context) -> Result<Judgement, Error> {
+    let judgement = policy.check(
         &*context.current_user,
         &context.hostname,
         crate::sudoers::Request {
@@ -65,28 +142,72 @@ fn judge(mut policy: Sudoers, context: &Context) -> Result<Judgement, Error> {
             command: &context.command.command,
             arguments: &context.command.arguments,
         },
-    ))
+    );
+
+    if judgement.syslog_pid() {
+        crate::log::syslog_include_pid();
+    }
+
+    Ok(judgement)
 }
 
 pub fn run(mut cmd_opts: SudoRunOptions) -> Result<(), Error> {
-    let mut policy = read_sudoers()?;
-
     let user_requested_env_vars = std::mem::take(&mut cmd_opts.env_var_list);
 
-    let context = Context::from_run_opts(cmd_opts, &mut policy)?;
+    let (mut sudoers, mut context) = with_slowness_notice(|| {
+        let mut sudoers = read_sudoers()?;
+        let context = Context::from_run_opts(cmd_opts, &mut sudoers)?;
+        Ok::<_, Error>((sudoers, context))
+    })?;
+    status::report(Phase::PolicyLoaded);
 
-    let policy = judge(policy, &context)?;
+    let mut policy = judge(&mut sudoers, &context)?;
+
+    // the command must be looked up (and judged) inside the root directory it will run in, and
+    // using the secure_path that applies to it
+    if let Some((chroot, path)) = relocation_needed(&policy, &context) {
+        context.resolve_command_in(chroot, path.as_deref())?;
+
+        // judge the relocated command using the same policy, not a copy read again from disk
+        policy = judge(&mut sudoers, &context)?;
+
+        if relocation_needed(&policy, &context).is_some() {
+            report_judgement(None);
+            log_command_denial(&context, &policy);
+            return Err(Error::Authorization(context.current_user.name.to_string()));
+        }
+    }
 
     let Authorization::Allowed(auth, controls) = policy.authorization() else {
+        report_judgement(None);
+        log_command_denial(&context, &policy);
         return Err(Error::Authorization(context.current_user.name.to_string()));
     };
+    report_judgement(Some(&auth));
+
+    if policy.intercept() {
+        user_warn!(
+            "INTERCEPT is not supported by sudo-rs, commands run by this command are not checked"
+        );
+    }
 
     let mut pam_context = auth_and_update_record_file(&context, auth)?;
+    status::report(Phase::Authenticated);
+
+    create_admin_flag(&context, &policy);
 
     // build environment
-    let additional_env = pre_exec(&mut pam_context, &context.target_user.name)?;
+    let additional_env = pre_exec(
+        &mut pam_context,
+        &context.target_user.name,
+        policy.pam_setcred(),
+        policy.pam_session(),
+    )?;
+    if policy.pam_session() {
+        status::report(Phase::SessionOpened);
+    }
 
-    let current_env = environment::system_environment();
+    let current_env = environment::system_environment(&controls);
     let (checked_vars, trusted_vars) = if controls.trust_environment {
         (vec![], user_requested_env_vars)
     } else {
@@ -99,35 +220,71 @@ pub fn run(mut cmd_opts: SudoRunOptions) -> Result<(), Error> {
         checked_vars,
         &context,
         &controls,
+        LoginDefs::system(),
     )?;
 
     environment::dangerous_extend(&mut target_env, trusted_vars);
 
+    let command = context.final_command(&controls, target_env)?;
+
     // prepare switch of apparmor profile
     #[cfg(feature = "apparmor")]
-    if let Some(profile) = &controls.apparmor_profile {
+    if let Some(profile) = &command.apparmor_profile {
         crate::apparmor::set_profile_for_next_exec(profile)
             .map_err(|err| Error::AppArmor(profile.clone(), err))?;
     }
 
-    let options = context.try_as_run_options(&controls)?;
-
-    // Log after try_as_run_options to avoid logging if the command is not resolved
-    log_command_execution(&context);
+    // Log after final_command to avoid logging if the command is not resolved
+    log_command_execution(&context, &policy);
 
     // run command and return corresponding exit code
-    let command_exit_reason = crate::exec::run_command(options, target_env)
+    let command_exit_reason = crate::exec::run_command(command)
         .map_err(|io_error| Error::Io(Some(context.command.command), io_error));
 
     pam_context.close_session();
 
-    match command_exit_reason?.exit_process()? {}
+    let command_exit_reason = command_exit_reason?;
+    status::report(match command_exit_reason {
+        ExitReason::Code(code) => Phase::Exited { code },
+        ExitReason::Signal(signal) => Phase::Killed { signal },
+    });
+
+    match command_exit_reason.exit_process()? {}
 }
 
-pub fn run_validate(cmd_opts: SudoValidateOptions) -> Result<(), Error> {
-    let mut policy = read_sudoers()?;
+/// The `judged` phase of `--status-json`; `auth` is `None` if the command is not allowed.
+fn report_judgement(auth: Option<&Authentication>) {
+    status::report(Phase::Judged {
+        allowed: auth.is_some(),
+        must_authenticate: auth.is_some_and(|auth| auth.must_authenticate),
+    });
+}
 
-    let context = Context::from_validate_opts(cmd_opts)?;
+/// If the policy wants the command to run in a root directory other than the one in which it
+/// was looked up, or wants it to be looked up using another secure_path (which can be set for
+/// specific commands), determine that root directory and the search path to use inside of it.
+fn relocation_needed(
+    policy: &Judgement,
+    context: &Context,
+) -> Option<(Option<std::path::PathBuf>, Option<String>)> {
+    let Authorization::Allowed(_, controls) = policy.authorization() else {
+        return None;
+    };
+
+    // if the requested root directory is not permitted, that is reported later on
+    let chroot = context.chroot_dir(&controls).ok()?;
+
+    let path = controls.path.map(str::to_string);
+
+    (chroot != context.command.chroot || path != context.secure_path).then_some((chroot, path))
+}
+
+pub fn run_validate(cmd_opts: SudoValidateOptions) -> Result<(), Error> {
+    let (mut policy, context) = with_slowness_notice(|| {
+        let mut policy = read_sudoers()?;
+        let context = Context::from_validate_opts(cmd_opts, &mut policy)?;
+        Ok::<_, Error>((policy, context))
+    })?;
 
     match policy.check_validate_permission(&*context.current_user, &context.hostname) {
         Authorization::Forbidden => {
@@ -148,9 +305,11 @@ fn auth_and_update_record_file(
         prior_validity,
         allowed_attempts,
         password_timeout,
+        module_timeout,
         ref credential,
         pwfeedback,
         noninteractive_auth,
+        pam_acct_mgmt,
     }: Authentication,
 ) -> Result<PamContext, Error> {
     let auth_user = match credential {
@@ -180,7 +339,8 @@ fn auth_and_update_record_file(
         bell: context.bell,
         non_interactive: context.non_interactive,
         password_feedback: pwfeedback,
-        password_timeout,
+        password_timeout: password_timeout.to_std(),
+        module_timeout: module_timeout.to_std(),
         auth_prompt: context.prompt.clone(),
         auth_user: &auth_user.name,
         requesting_user: &context.current_user.name,
@@ -208,7 +368,9 @@ fn auth_and_update_record_file(
         }
     }
 
-    pam_context.validate_account_or_change_auth_token()?;
+    if pam_acct_mgmt {
+        pam_context.validate_account_or_change_auth_token()?;
+    }
 
     Ok(pam_context)
 }
@@ -221,7 +383,7 @@ fn determine_auth_status(
     record_for: Option<RecordScope>,
     current_user: &CurrentUser,
     auth_user: &AuthUser,
-    prior_validity: Duration,
+    prior_validity: SudoersDuration,
 ) -> AuthStatus {
     if !must_policy_authenticate {
         AuthStatus::new(false, None)
@@ -265,7 +427,93 @@ impl AuthStatus {
     }
 }
 
-fn log_command_execution(context: &Context) {
+fn log_command_execution(context: &Context, policy: &Judgement) {
+    let message = command_log_details(context);
+    log_to_system(context, policy, &message, true);
+    log_to_file(context, policy, &message);
+}
+
+fn log_command_denial(context: &Context, policy: &Judgement) {
+    let message = format!("command not allowed ; {}", command_log_details(context));
+    log_to_system(context, policy, &message, false);
+    log_to_file(context, policy, &message);
+}
+
+/// Record a command that is run (or denied) in syslog, or in the journal if `log_backend`
+/// asks for that; if it asks for the log file only, syslog is still used if there is none.
+fn log_to_system(context: &Context, policy: &Judgement, message: &str, allowed: bool) {
+    let entry = entry(context, message);
+    match policy.log_backend() {
+        LogBackend::Syslog => {}
+        LogBackend::Journald => {
+            if journal_entry(context, policy, &entry, allowed)
+                .send()
+                .is_ok()
+            {
+                return;
+            }
+        }
+        LogBackend::File => {
+            if policy.logfile().is_some() {
+                return;
+            }
+        }
+    }
+
+    if allowed {
+        auth_info!("{entry}");
+    } else {
+        auth_warn!("{entry}");
+    }
+}
+
+fn journal_entry(
+    context: &Context,
+    policy: &Judgement,
+    entry: &Entry,
+    allowed: bool,
+) -> JournalEntry {
+    let tty = current_tty_name().unwrap_or_default();
+
+    let mut journal_entry = JournalEntry::new();
+    journal_entry
+        .field("MESSAGE", entry.to_string())
+        // the same priorities and facility as the message would have in syslog
+        .field("PRIORITY", if allowed { "6" } else { "4" })
+        .field("SYSLOG_FACILITY", "4")
+        .field("SYSLOG_IDENTIFIER", "sudo")
+        .field("SUDO_USER", context.current_user.name.as_str())
+        .field("SUDO_TTY", tty.as_bytes())
+        .field("SUDO_RUNAS_USER", context.target_user.name.as_str())
+        .field("SUDO_COMMAND", logged_command(context))
+        .field("SUDO_RESULT", if allowed { "allowed" } else { "denied" });
+    if policy.syslog_pid() {
+        journal_entry.field("SYSLOG_PID", std::process::id().to_string());
+    }
+
+    journal_entry
+}
+
+fn entry<'a>(context: &'a Context, message: &'a str) -> Entry<'a> {
+    Entry {
+        user: &context.current_user.name,
+        // syslog records the host name by itself
+        host: None,
+        message,
+    }
+}
+
+fn log_to_file(context: &Context, policy: &Judgement, message: &str) {
+    if let Some(logfile) = policy.logfile() {
+        logfile.log(&context.current_user.name, &context.hostname, message);
+    }
+}
+
+/// Very long command lines are cut short in the logs, just like SUDO_COMMAND; this only affects
+/// what is logged, not the command that is run.
+const MAX_LOGGED_COMMAND_LEN: usize = 4096;
+
+fn command_log_details(context: &Context) -> String {
     let tty_info = if let Ok(tty_name) = current_tty_name() {
         format!("TTY={} ;", escape_os_str_lossy(&tty_name))
     } else {
@@ -278,12 +526,129 @@ fn log_command_execution(context: &Context) {
             .unwrap_or_else(|_| OsStr::new("unknown")),
     );
     let user = context.target_user.name.escape_debug().collect::<String>();
-    auth_info!(
-        "{} : {} PWD={} ; USER={} ; COMMAND={}",
-        &context.current_user.name,
+    let chroot_info = if let Some(chroot) = &context.command.chroot {
+        format!("CHROOT={} ; ", escape_os_str_lossy(chroot.as_os_str()))
+    } else {
+        String::from("")
+    };
+    format!(
+        "{} {}PWD={} ; USER={} ; COMMAND={}",
         tty_info,
+        chroot_info,
         pwd,
         user,
-        &context.command
-    );
+        logged_command(context)
+    )
+}
+
+fn logged_command(context: &Context) -> String {
+    let mut command = context.command.to_string();
+    if command.len() > MAX_LOGGED_COMMAND_LEN {
+        let mut end = MAX_LOGGED_COMMAND_LEN;
+        while !command.is_char_boundary(end) {
+            end -= 1;
+        }
+        command.truncate(end);
+        command.push_str(" [...]");
+    }
+
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+    use std::path::Path;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+    use crate::sudoers::Request;
+    use crate::system::group_provider::GroupProvider;
+    use crate::system::interface::{GroupId, UnixUser, UserId};
+    use crate::system::{Group, Hostname, User};
+
+    /// A user whose group memberships are looked up on a name service that does not respond
+    /// until it is released through the channel (or gives up after a minute).
+    struct SlowUser(User, mpsc::Receiver<()>);
+
+    impl PartialEq for SlowUser {
+        fn eq(&self, other: &Self) -> bool {
+            self.0 == other.0
+        }
+    }
+
+    impl UnixUser for SlowUser {
+        fn has_name(&self, name: &str) -> bool {
+            self.0.has_name(name)
+        }
+        fn has_uid(&self, uid: UserId) -> bool {
+            self.0.has_uid(uid)
+        }
+        fn is_root(&self) -> bool {
+            self.0.is_root()
+        }
+        fn in_group_by_name(&self, name: &CStr) -> bool {
+            _ = self.1.recv_timeout(Duration::from_secs(60));
+            self.0.in_group_by_name(name)
+        }
+        fn in_group_by_gid(&self, gid: GroupId) -> bool {
+            self.0.in_group_by_gid(gid)
+        }
+        fn in_netgroup(&self, netgroup: &CStr) -> Option<bool> {
+            self.0.in_netgroup(netgroup)
+        }
+        fn in_nonunix_group(&self, group: &str, provider: &dyn GroupProvider) -> Option<bool> {
+            self.0.in_nonunix_group(group, provider)
+        }
+
+        type Group = Group;
+        fn group(&self) -> Group {
+            self.0.group()
+        }
+    }
+
+    #[test]
+    fn watchdog_does_not_fire_for_fast_jobs() {
+        let fired = AtomicBool::new(false);
+        let notify = || fired.store(true, Ordering::SeqCst);
+
+        assert_eq!(watchdog(Duration::from_secs(10), notify, || 42), 42);
+        assert!(!fired.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn slow_name_service_triggers_the_notice() {
+        let (mut sudoers, _) = Sudoers::read(
+            "%sudo-rs-nonexistent ALL=(ALL:ALL) ALL\n".as_bytes(),
+            "/etc/sudoers",
+        )
+        .unwrap();
+        let (release, released) = mpsc::channel();
+        let user = SlowUser(User::real().unwrap().unwrap(), released);
+        let group = Group::from_gid(User::real_gid()).unwrap().unwrap();
+
+        let fired = AtomicBool::new(false);
+        let judgement = watchdog(
+            Duration::from_millis(10),
+            || {
+                fired.store(true, Ordering::SeqCst);
+                release.send(()).unwrap();
+            },
+            || {
+                let request = Request {
+                    user: &user,
+                    group: &group,
+                    command: Path::new("/bin/true"),
+                    arguments: &[],
+                };
+                sudoers.check(&user, &Hostname::fake("server"), request)
+            },
+        );
+
+        assert!(fired.load(Ordering::SeqCst));
+        assert!(matches!(
+            judgement.authorization(),
+            Authorization::Forbidden
+        ));
+    }
 }
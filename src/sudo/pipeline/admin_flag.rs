@@ -0,0 +1,75 @@
+use std::ffi::CString;
+use std::fs::{OpenOptions, Permissions};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use crate::common::Context;
+use crate::sudoers::Judgement;
+use crate::system::{User, audit::sudo_call, interface::UnixUser};
+
+/// Create the file set by `Defaults admin_flag` (Ubuntu uses `~/.sudo_as_admin_successful`,
+/// which makes bash stop suggesting to use sudo), as the invoking user, if they are a member
+/// of one of the `admin_flag_groups`. This is merely a courtesy, so errors are ignored.
+pub(super) fn create_admin_flag(context: &Context, policy: &Judgement) {
+    let Some((template, groups)) = policy.admin_flag() else {
+        return;
+    };
+
+    let user: &User = &context.current_user;
+    let is_admin = groups
+        .iter()
+        .any(|group| CString::new(group.as_str()).is_ok_and(|name| user.in_group_by_name(&name)));
+    if !is_admin {
+        return;
+    }
+
+    let (Some(path), Ok(group)) = (expand_home(template, &user.home), user.primary_group()) else {
+        return;
+    };
+
+    let _ = sudo_call(user, &group, || {
+        // an existing file (or symbolic link) is never touched
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o644)
+            .open(&path)?;
+        // the mode passed to open is subject to the umask
+        file.set_permissions(Permissions::from_mode(0o644))
+    });
+}
+
+/// Expand a leading `~` to the home directory of the invoking user; other paths must be
+/// absolute.
+fn expand_home(template: &str, home: &Path) -> Option<PathBuf> {
+    if template == "~" {
+        Some(home.to_owned())
+    } else if let Some(rest) = template.strip_prefix("~/") {
+        Some(home.join(rest))
+    } else {
+        Some(PathBuf::from(template)).filter(|path| path.is_absolute())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn home_directory_is_expanded() {
+        let home = Path::new("/home/ferris");
+        let expand = |template| expand_home(template, home);
+
+        assert_eq!(
+            expand("~/.sudo_as_admin_successful").unwrap(),
+            Path::new("/home/ferris/.sudo_as_admin_successful")
+        );
+        assert_eq!(
+            expand("/var/lib/admin").unwrap(),
+            Path::new("/var/lib/admin")
+        );
+        assert_eq!(expand("~").unwrap(), home);
+        assert_eq!(expand("~ferris/flag"), None);
+        assert_eq!(expand("relative/flag"), None);
+    }
+}
@@ -5,18 +5,22 @@ use crate::sudoers::Authorization;
 use crate::system::audit;
 
 pub fn run_edit(edit_opts: SudoEditOptions) -> Result<(), Error> {
-    let policy = super::read_sudoers()?;
+    let (mut sudoers, context) = super::with_slowness_notice(|| {
+        let mut sudoers = super::read_sudoers()?;
+        let context = Context::from_edit_opts(edit_opts, &mut sudoers)?;
+        Ok::<_, Error>((sudoers, context))
+    })?;
 
-    let context = Context::from_edit_opts(edit_opts)?;
-
-    let policy = super::judge(policy, &context)?;
+    let policy = super::judge(&mut sudoers, &context)?;
 
     let Authorization::Allowed(auth, _controls) = policy.authorization() else {
+        super::log_command_denial(&context, &policy);
         return Err(Error::Authorization(context.current_user.name.to_string()));
     };
 
     let mut pam_context = super::auth_and_update_record_file(&context, auth)?;
 
+    let follow = policy.sudoedit_follow();
     let mut opened_files = Vec::with_capacity(context.files_to_edit.len());
     for (path, arg) in context.files_to_edit.iter().zip(&context.command.arguments) {
         if let Some(path) = path {
@@ -25,10 +29,11 @@ pub fn run_edit(edit_opts: SudoEditOptions) -> Result<(), Error> {
                 &context.current_user,
                 &context.target_user,
                 &context.target_group,
+                follow,
             ) {
                 Ok(file) => opened_files.push((path, file)),
                 // ErrorKind::FilesystemLoop was only stabilized in 1.83
-                Err(error) if error.raw_os_error() == Some(libc::ELOOP) => {
+                Err(error) if error.raw_os_error() == Some(libc::ELOOP) && !follow => {
                     user_error!(
                         "{path}: editing symbolic links is not permitted",
                         path = DisplayOsStr(arg),
@@ -54,7 +59,7 @@ pub fn run_edit(edit_opts: SudoEditOptions) -> Result<(), Error> {
 
     // run command and return corresponding exit code
     let command_exit_reason = {
-        super::log_command_execution(&context);
+        super::log_command_execution(&context, &policy);
 
         let editor = policy.preferred_editor();
 
@@ -26,16 +26,23 @@ pub(in crate::sudo) fn run_list(cmd_opts: SudoListOptions) -> Result<(), Error>
         .transpose()?;
 
     let original_command = cmd_opts.positional_args.first().cloned();
+    let suggest = cmd_opts.suggest.clone();
 
-    let mut sudoers = super::read_sudoers()?;
-
-    let context = Context::from_list_opts(cmd_opts, &mut sudoers)?;
+    let (mut sudoers, context) = super::with_slowness_notice(|| {
+        let mut sudoers = super::read_sudoers()?;
+        let context = Context::from_list_opts(cmd_opts, &mut sudoers)?;
+        Ok::<_, Error>((sudoers, context))
+    })?;
 
     if auth_invoking_user(&context, &mut sudoers, &original_command, &other_user)?.is_break() {
         return Ok(());
     }
 
-    if let Some(original_command) = original_command {
+    if let Some(prefix) = suggest {
+        for command in suggest_commands(&prefix, &context, &other_user, &mut sudoers) {
+            println_ignore_io_error!("{command}");
+        }
+    } else if let Some(original_command) = original_command {
         check_sudo_command_perms(&original_command, context, &other_user, &mut sudoers)?;
     } else {
         let inspected_user = other_user.as_ref().unwrap_or(&context.current_user);
@@ -44,6 +51,11 @@ pub(in crate::sudo) fn run_list(cmd_opts: SudoListOptions) -> Result<(), Error>
             .peekable();
 
         if matching_entries.peek().is_some() {
+            // the Defaults were applied for the invoking user, so they are not shown for another
+            if other_user.is_none() {
+                print_matching_defaults(&sudoers, &context, verbose_list_mode);
+            }
+
             xlat_println!(
                 "User {user} may run the following commands on {hostname}:",
                 user = inspected_user.name,
@@ -70,6 +82,74 @@ pub(in crate::sudo) fn run_list(cmd_opts: SudoListOptions) -> Result<(), Error>
     Ok(())
 }
 
+/// Show the settings that the Defaults which apply have changed; in verbose mode, every
+/// setting is shown on a line of its own, together with the place where it was set.
+fn print_matching_defaults(sudoers: &Sudoers, context: &Context, verbose_list_mode: bool) {
+    let defaults = sudoers.matching_defaults();
+    if defaults.is_empty() {
+        return;
+    }
+
+    xlat_println!(
+        "Matching Defaults entries for {user} on {hostname}:",
+        user = context.current_user.name,
+        hostname = context.hostname
+    );
+    if verbose_list_mode {
+        for (entry, source) in defaults {
+            println_ignore_io_error!(
+                "    {entry}  # {}:{}",
+                source.path.display(),
+                source.location.start.0
+            );
+        }
+    } else {
+        let entries = defaults
+            .into_iter()
+            .map(|(entry, _)| entry)
+            .collect::<Vec<_>>();
+        println_ignore_io_error!("    {}", entries.join(", "));
+    }
+    println_ignore_io_error!();
+}
+
+/// The literal commands starting with `prefix` that the inspected user may run as the target
+/// user; every candidate is judged like a real request, so that rules further down the policy
+/// (which may forbid it again) are taken into account.
+fn suggest_commands(
+    prefix: &str,
+    context: &Context,
+    other_user: &Option<User>,
+    sudoers: &mut Sudoers,
+) -> Vec<String> {
+    let inspected_user = other_user.as_ref().unwrap_or(&context.current_user);
+    let candidates = sudoers
+        .matching_entries(inspected_user, &context.hostname)
+        .flat_map(|entry| entry.literal_commands())
+        .filter(|(command, _)| command.starts_with(prefix))
+        .collect::<Vec<_>>();
+
+    let mut commands = candidates
+        .into_iter()
+        .filter(|(command, arguments)| {
+            let request = Request {
+                user: &context.target_user,
+                group: &context.target_group,
+                command: Path::new(command),
+                arguments,
+            };
+            let judgement = sudoers.check(inspected_user, &context.hostname, request);
+
+            matches!(judgement.authorization(), Authorization::Allowed(..))
+        })
+        .map(|(command, _)| command)
+        .collect::<Vec<_>>();
+    commands.sort();
+    commands.dedup();
+
+    commands
+}
+
 fn auth_invoking_user(
     context: &Context,
     sudoers: &mut Sudoers,
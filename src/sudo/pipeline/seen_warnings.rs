@@ -0,0 +1,158 @@
+use std::collections::BTreeSet;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{self, Read, Seek, Write};
+use std::path::Path;
+
+use crate::sudoers::Error;
+use crate::system::audit::SecureDir;
+use crate::system::file::FileLock;
+
+/// Where the fingerprint of the last reported problems is kept; since this is cleared when
+/// the system boots, the problems are reported again after a reboot.
+const STATE_DIR: &str = "/var/run/sudo-rs";
+const STATE_FILE: &str = "sudoers-warnings";
+
+/// Whether the warnings about the sudoers files (which are otherwise printed by every
+/// invocation of sudo) differ from the ones that were reported last; they are remembered as
+/// reported by calling this. If the state file cannot be used, everything counts as new.
+pub(super) fn are_new<'a>(
+    warnings: impl IntoIterator<Item = &'a Error>,
+    sudoers_path: &Path,
+) -> bool {
+    record(Path::new(STATE_DIR), fingerprint(warnings, sudoers_path)).unwrap_or(true)
+}
+
+/// Summarize the problems independently of the order in which they were found, together with
+/// the contents of the files that they are about, so that editing such a file makes them
+/// count as new. `DefaultHasher` is not stable across Rust releases, which can only cause
+/// the problems to be reported one extra time after sudo is upgraded.
+fn fingerprint<'a>(warnings: impl IntoIterator<Item = &'a Error>, sudoers_path: &Path) -> u64 {
+    let canonical = warnings
+        .into_iter()
+        .map(
+            |Error {
+                 source,
+                 location,
+                 message,
+                 ..
+             }| {
+                (
+                    source.as_deref().unwrap_or(sudoers_path),
+                    location.map(|span| (span.start, span.end)),
+                    message.as_str(),
+                )
+            },
+        )
+        .collect::<BTreeSet<_>>();
+
+    let mut hasher = DefaultHasher::new();
+    canonical.hash(&mut hasher);
+    let files = canonical
+        .iter()
+        .map(|(path, _, _)| *path)
+        .collect::<BTreeSet<_>>();
+    for file in files {
+        std::fs::read(file).ok().hash(&mut hasher);
+    }
+
+    hasher.finish()
+}
+
+/// Store `fingerprint` in `dir`, returning whether it differs from the one stored before.
+fn record(dir: &Path, fingerprint: u64) -> io::Result<bool> {
+    let mut file =
+        SecureDir::open(dir, true)?.open_file(STATE_FILE, libc::O_RDWR | libc::O_CREAT)?;
+
+    // lock the file so that another sudo does not write to it between reading and writing it
+    let lock = FileLock::exclusive(&file, false)?;
+
+    let mut recorded = String::new();
+    file.read_to_string(&mut recorded)?;
+    let fingerprint = format!("{fingerprint:016x}\n");
+    if recorded == fingerprint {
+        lock.unlock()?;
+        return Ok(false);
+    }
+
+    file.set_len(0)?;
+    file.rewind()?;
+    file.write_all(fingerprint.as_bytes())?;
+
+    lock.unlock()?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::MetadataExt;
+
+    use super::*;
+    use crate::sudoers::Span;
+    use crate::system::file::create_temporary_dir;
+
+    fn warning(line: usize, message: &str) -> Error {
+        Error {
+            source: None,
+            location: Some(Span {
+                start: (line, 1),
+                end: (line, 10),
+            }),
+            message: message.to_string(),
+            unsupported_feature: true,
+        }
+    }
+
+    #[test]
+    fn fingerprint_covers_the_problems_and_the_files() {
+        let tmp = create_temporary_dir().unwrap();
+        let sudoers = tmp.join("sudoers");
+        std::fs::write(&sudoers, "Defaults bogus\n").unwrap();
+
+        let problems = || {
+            vec![
+                warning(1, "unknown setting: 'bogus'"),
+                warning(2, "this tag is ignored by sudo-rs"),
+            ]
+        };
+        let before = fingerprint(&problems(), &sudoers);
+
+        // the order in which the problems were found does not matter, but every problem does
+        let mut reversed = problems();
+        reversed.reverse();
+        assert_eq!(fingerprint(&reversed, &sudoers), before);
+        assert_ne!(fingerprint(&problems()[..1], &sudoers), before);
+
+        // editing the file that the problems are about makes them new
+        std::fs::write(&sudoers, "Defaults bogus # changed\n").unwrap();
+        assert_ne!(fingerprint(&problems(), &sudoers), before);
+
+        std::fs::remove_dir_all(&tmp).unwrap();
+    }
+
+    #[test]
+    fn problems_are_reported_until_they_change() {
+        let tmp = create_temporary_dir().unwrap();
+
+        // only root can own a directory that is secure enough to keep the state in
+        if tmp.metadata().unwrap().uid() != 0 {
+            std::fs::remove_dir_all(&tmp).unwrap();
+            return;
+        }
+
+        let dir = tmp.join("state");
+        assert!(record(&dir, 0x1234).unwrap());
+        assert!(!record(&dir, 0x1234).unwrap());
+        assert!(!record(&dir, 0x1234).unwrap());
+        assert!(record(&dir, 0x1).unwrap());
+        assert!(!record(&dir, 0x1).unwrap());
+        assert!(record(&dir, 0x1234).unwrap());
+
+        assert_eq!(
+            std::fs::read_to_string(dir.join(STATE_FILE)).unwrap(),
+            "0000000000001234\n"
+        );
+
+        std::fs::remove_dir_all(&tmp).unwrap();
+    }
+}
@@ -0,0 +1,202 @@
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct SudoTsOptions {
+    pub(crate) user: Option<String>,
+    pub(crate) json: bool,
+    pub(crate) action: SudoTsAction,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub(crate) enum SudoTsAction {
+    Help,
+    Version,
+    Check,
+    #[default]
+    List,
+    Remove(usize),
+}
+
+type OptionSetter = fn(&mut SudoTsOptions, Option<String>) -> Result<(), String>;
+
+struct SudoTsOption {
+    short: char,
+    long: &'static str,
+    takes_argument: bool,
+    set: OptionSetter,
+}
+
+impl SudoTsOptions {
+    const SUDO_TS_OPTIONS: &'static [SudoTsOption] = &[
+        SudoTsOption {
+            short: 'c',
+            long: "check",
+            takes_argument: false,
+            set: |options, _| {
+                options.action = SudoTsAction::Check;
+                Ok(())
+            },
+        },
+        SudoTsOption {
+            short: 'h',
+            long: "help",
+            takes_argument: false,
+            set: |options, _| {
+                options.action = SudoTsAction::Help;
+                Ok(())
+            },
+        },
+        SudoTsOption {
+            short: 'j',
+            long: "json",
+            takes_argument: false,
+            set: |options, _| {
+                options.json = true;
+                Ok(())
+            },
+        },
+        SudoTsOption {
+            short: 'r',
+            long: "remove",
+            takes_argument: true,
+            set: |options, argument| {
+                let argument = argument.ok_or("option requires an argument -- 'r'")?;
+                let record = argument
+                    .parse()
+                    .map_err(|_| format!("invalid record number '{argument}'"))?;
+                options.action = SudoTsAction::Remove(record);
+                Ok(())
+            },
+        },
+        SudoTsOption {
+            short: 'u',
+            long: "user",
+            takes_argument: true,
+            set: |options, argument| {
+                options.user = Some(argument.ok_or("option requires an argument -- 'u'")?);
+                Ok(())
+            },
+        },
+        SudoTsOption {
+            short: 'V',
+            long: "version",
+            takes_argument: false,
+            set: |options, _| {
+                options.action = SudoTsAction::Version;
+                Ok(())
+            },
+        },
+    ];
+
+    pub(crate) fn from_env() -> Result<SudoTsOptions, String> {
+        let args = std::env::args().collect();
+
+        Self::parse_arguments(args)
+    }
+
+    /// parse sudo-ts arguments into SudoTsOptions struct
+    pub(crate) fn parse_arguments(arguments: Vec<String>) -> Result<SudoTsOptions, String> {
+        let mut options = SudoTsOptions::default();
+        let mut arg_iter = arguments.into_iter().skip(1);
+
+        while let Some(arg) = arg_iter.next() {
+            // if the argument starts with -- it must be a full length option name
+            if let Some(name) = arg.strip_prefix("--") {
+                // parse assignments like '--user=ferris'
+                let (name, value) = match name.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (name, None),
+                };
+
+                let Some(option) = Self::SUDO_TS_OPTIONS.iter().find(|o| o.long == name) else {
+                    return Err(format!("unrecognized option '{arg}'"));
+                };
+
+                if option.takes_argument {
+                    let value = value.or_else(|| arg_iter.next());
+                    (option.set)(&mut options, value)?;
+                } else if value.is_some() {
+                    Err(format!("'--{}' does not take any arguments", option.long))?;
+                } else {
+                    (option.set)(&mut options, None)?;
+                }
+            } else if arg.starts_with('-') && arg != "-" {
+                // flags can be grouped, so we loop over the characters
+                for (n, char) in arg.trim_start_matches('-').chars().enumerate() {
+                    let Some(option) = Self::SUDO_TS_OPTIONS.iter().find(|o| o.short == char)
+                    else {
+                        return Err(format!("unrecognized option '{char}'"));
+                    };
+
+                    // an argument is either the rest of the current flag group or the next argument
+                    if option.takes_argument {
+                        let rest = arg[(n + 2)..].trim().to_string();
+                        let next_arg = if rest.is_empty() {
+                            arg_iter.next()
+                        } else {
+                            Some(rest)
+                        };
+                        (option.set)(&mut options, next_arg)?;
+                        // stop looping over flags if the current flag takes an argument
+                        break;
+                    } else {
+                        (option.set)(&mut options, None)?;
+                    }
+                }
+            } else {
+                Err(format!("unexpected argument '{arg}'"))?;
+            }
+        }
+
+        if matches!(options.action, SudoTsAction::Remove(_)) && options.user.is_none() {
+            Err("removing a record requires a user to be specified with -u")?;
+        }
+
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<SudoTsOptions, String> {
+        SudoTsOptions::parse_arguments(
+            std::iter::once("sudo-ts")
+                .chain(args.iter().copied())
+                .map(String::from)
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn defaults_to_listing_everything() {
+        assert_eq!(parse(&[]).unwrap(), SudoTsOptions::default());
+        assert_eq!(parse(&[]).unwrap().action, SudoTsAction::List);
+    }
+
+    #[test]
+    fn short_and_long_options() {
+        let expected = SudoTsOptions {
+            user: Some("ferris".to_string()),
+            json: true,
+            action: SudoTsAction::Remove(2),
+        };
+        assert_eq!(parse(&["-ju", "ferris", "-r2"]).unwrap(), expected);
+        assert_eq!(parse(&["-jr", "2", "-uferris"]).unwrap(), expected);
+        assert_eq!(
+            parse(&["--json", "--user=ferris", "--remove", "2"]).unwrap(),
+            expected
+        );
+        assert_eq!(parse(&["-c"]).unwrap().action, SudoTsAction::Check);
+        assert_eq!(parse(&["--version"]).unwrap().action, SudoTsAction::Version);
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        assert!(parse(&["-x"]).is_err());
+        assert!(parse(&["--json=yes"]).is_err());
+        assert!(parse(&["--user"]).is_err());
+        assert!(parse(&["ferris"]).is_err());
+        assert!(parse(&["-u", "ferris", "-r", "first"]).is_err());
+        assert!(parse(&["-r", "0"]).is_err());
+    }
+}
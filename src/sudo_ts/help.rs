@@ -0,0 +1,16 @@
+pub(crate) const USAGE_MSG: &str = "usage: sudo-ts [-cjhV] [-u user] [-r record]";
+
+const DESCRIPTOR: &str = "sudo-ts - inspect and manage the session records of sudo";
+
+const HELP_MSG: &str = "Options:
+  -c, --check              check the integrity of the session records files
+  -h, --help               display help message and exit
+  -j, --json               produce output in JSON format
+  -r, --remove=record      remove a session record of the user given by -u
+  -u, --user=user          only consider the session records of user
+  -V, --version            display version information and exit
+";
+
+pub(crate) fn long_help_message() -> String {
+    format!("{USAGE_MSG}\n\n{DESCRIPTOR}\n\n{HELP_MSG}")
+}
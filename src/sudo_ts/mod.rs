@@ -0,0 +1,523 @@
+#![forbid(unsafe_code)]
+
+mod cli;
+mod help;
+
+use std::{
+    ffi::CString,
+    fmt::Write as _,
+    fs,
+    io::{self, Write as _},
+    time::Duration,
+};
+
+use crate::{
+    common::SudoersDuration,
+    defaults::Settings,
+    sudo::candidate_sudoers_file,
+    sudoers::Sudoers,
+    system::{
+        Hostname, User,
+        file::FileLock,
+        interface::UserId,
+        term::tty_name_of,
+        time::SystemTime,
+        timestamp::{RecordScope, SessionRecord, SessionRecordFile},
+    },
+};
+
+use self::cli::{SudoTsAction, SudoTsOptions};
+use self::help::{USAGE_MSG, long_help_message};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+pub fn main() {
+    if User::effective_uid() != User::real_uid() || User::effective_gid() != User::real_gid() {
+        println_ignore_io_error!(
+            "sudo-ts must not be installed as setuid binary.\n\
+             Please notify your packager about this misconfiguration.\n\
+             To prevent privilege escalation sudo-ts will now abort.
+             "
+        );
+        std::process::exit(1);
+    }
+
+    let options = match SudoTsOptions::from_env() {
+        Ok(options) => options,
+        Err(error) => {
+            println_ignore_io_error!("sudo-ts: {error}\n{USAGE_MSG}");
+            std::process::exit(1);
+        }
+    };
+
+    match options.action {
+        SudoTsAction::Help => {
+            println_ignore_io_error!("{}", long_help_message());
+            std::process::exit(0);
+        }
+        SudoTsAction::Version => {
+            println_ignore_io_error!("sudo-ts {VERSION}");
+            std::process::exit(0);
+        }
+        _ => {}
+    }
+
+    if User::real_uid() != UserId::ROOT {
+        eprintln_ignore_io_error!("sudo-ts: must be run as root");
+        std::process::exit(1);
+    }
+
+    let result = users(options.user.as_deref()).and_then(|users| match options.action {
+        SudoTsAction::List => list(&users, options.json).map(|()| true),
+        SudoTsAction::Check => check(&users, options.json),
+        SudoTsAction::Remove(index) => remove(users[0], index).map(|()| true),
+        SudoTsAction::Help | SudoTsAction::Version => unreachable!(),
+    });
+
+    match result {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(error) => {
+            eprintln_ignore_io_error!("sudo-ts: {error}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// The users whose session records are inspected: either the one that was asked for, or
+/// everyone who has a session records file.
+fn users(user: Option<&str>) -> io::Result<Vec<UserId>> {
+    if let Some(name) = user {
+        // a uid need not correspond to an existing user
+        let uid = match name.parse() {
+            Ok(uid) => uid,
+            Err(_) => {
+                CString::new(name)
+                    .ok()
+                    .and_then(|name| User::from_name(&name).ok().flatten())
+                    .ok_or_else(|| io::Error::other(format!("unknown user '{name}'")))?
+                    .uid
+            }
+        };
+        return Ok(vec![uid]);
+    }
+
+    let entries = match fs::read_dir(SessionRecordFile::BASE_PATH) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut users = entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .collect::<Vec<UserId>>();
+    users.sort_by_key(|uid| uid.inner());
+
+    Ok(users)
+}
+
+fn user_name(uid: UserId) -> String {
+    match User::from_uid(uid) {
+        Ok(Some(user)) => user.name.to_string(),
+        _ => uid.to_string(),
+    }
+}
+
+/// How long the session records of a user remain valid according to the sudoers file
+fn timestamp_timeout(uid: UserId, host: &Hostname) -> SudoersDuration {
+    let sudoers = Sudoers::open(candidate_sudoers_file())
+        .map(|(sudoers, _)| sudoers)
+        .unwrap_or_default();
+
+    match User::from_uid(uid) {
+        Ok(Some(user)) => sudoers.timestamp_timeout(host, &user),
+        _ => Settings::default().timestamp_timeout(),
+    }
+}
+
+/// Read the session records file of a user, without creating or repairing it
+fn inspect(uid: UserId) -> io::Result<Option<(Vec<SessionRecord>, Option<String>)>> {
+    let Some(mut file) = SessionRecordFile::open_existing(uid, libc::O_RDONLY)? else {
+        return Ok(None);
+    };
+
+    let _lock = FileLock::exclusive(&file, false)?;
+    let inspection = SessionRecordFile::inspect(&mut file)?;
+
+    Ok(Some((inspection.records, inspection.problem)))
+}
+
+/// A session record, as it is presented to the user
+struct Row {
+    uid: UserId,
+    user: String,
+    index: usize,
+    scope: Scope,
+    auth_uid: UserId,
+    auth_user: String,
+    /// `None` if the record was created in the future, e.g. before a reboot
+    age: Option<Duration>,
+    status: Status,
+}
+
+enum Scope {
+    Tty { name: String, session: i32 },
+    Ppid { pid: i32, session: i32 },
+}
+
+enum Status {
+    Valid { remaining: SudoersDuration },
+    Expired,
+    Disabled,
+}
+
+impl Row {
+    fn new(
+        uid: UserId,
+        index: usize,
+        record: &SessionRecord,
+        now: SystemTime,
+        timeout: SudoersDuration,
+    ) -> Row {
+        let scope = match *record.scope() {
+            RecordScope::Tty {
+                tty_device,
+                session_pid,
+                ..
+            } => Scope::Tty {
+                name: tty_name_of(tty_device)
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| format!("device {tty_device}")),
+                session: session_pid.inner(),
+            },
+            RecordScope::Ppid {
+                group_pid,
+                session_pid,
+                ..
+            } => Scope::Ppid {
+                pid: group_pid.inner(),
+                session: session_pid.inner(),
+            },
+        };
+
+        let age = now.duration_since(record.timestamp());
+        let status = match age.and_then(|age| timeout.checked_sub(age)) {
+            _ if !record.is_enabled() => Status::Disabled,
+            Some(remaining) => Status::Valid { remaining },
+            None => Status::Expired,
+        };
+
+        Row {
+            uid,
+            user: user_name(uid),
+            index,
+            scope,
+            auth_uid: record.auth_user(),
+            auth_user: user_name(record.auth_user()),
+            age,
+            status,
+        }
+    }
+}
+
+fn list(users: &[UserId], json: bool) -> io::Result<()> {
+    let host = Hostname::resolve();
+    let now = SystemTime::now()?;
+
+    let mut rows = Vec::new();
+    for &uid in users {
+        let Some((records, _)) = inspect(uid)? else {
+            continue;
+        };
+        let timeout = timestamp_timeout(uid, &host);
+        rows.extend(
+            records
+                .iter()
+                .enumerate()
+                .map(|(index, record)| Row::new(uid, index, record, now, timeout)),
+        );
+    }
+
+    let output = if json {
+        rows_as_json(&rows)
+    } else {
+        rows_as_table(&rows)
+    };
+    let _ = io::stdout().write_all(output.as_bytes());
+
+    Ok(())
+}
+
+fn check(users: &[UserId], json: bool) -> io::Result<bool> {
+    let mut all_valid = true;
+    let mut output = String::new();
+
+    if json {
+        output.push('[');
+    }
+
+    for &uid in users {
+        let Some((records, problem)) = inspect(uid)? else {
+            continue;
+        };
+        all_valid &= problem.is_none();
+
+        let user = user_name(uid);
+        if json {
+            if output.len() > 1 {
+                output.push(',');
+            }
+            let _ = write!(
+                output,
+                "{{\"user\":{},\"uid\":{uid},\"records\":{},\"valid\":{},\"problem\":{}}}",
+                json_string(&user),
+                records.len(),
+                problem.is_none(),
+                problem.as_deref().map_or("null".to_string(), json_string),
+            );
+        } else if let Some(problem) = problem {
+            let _ = writeln!(output, "{user}: {problem}");
+        } else {
+            let _ = writeln!(output, "{user}: ok (records: {})", records.len());
+        }
+    }
+
+    if json {
+        output.push_str("]\n");
+    }
+    let _ = io::stdout().write_all(output.as_bytes());
+
+    Ok(all_valid)
+}
+
+fn remove(uid: UserId, index: usize) -> io::Result<()> {
+    let timeout = timestamp_timeout(uid, &Hostname::resolve());
+    let Some(mut records) = SessionRecordFile::open_existing_for_uid(uid, timeout)? else {
+        return Err(io::Error::other(format!(
+            "{} has no session records",
+            user_name(uid)
+        )));
+    };
+    if records.remove(index)?.is_none() {
+        return Err(io::Error::other(format!(
+            "{} has no session record {index}",
+            user_name(uid)
+        )));
+    }
+
+    Ok(())
+}
+
+fn rows_as_table(rows: &[Row]) -> String {
+    const HEADER: [&str; 7] = [
+        "USER",
+        "RECORD",
+        "SCOPE",
+        "SESSION",
+        "AUTH USER",
+        "AGE",
+        "REMAINING",
+    ];
+
+    let mut lines = vec![HEADER.map(String::from)];
+    for row in rows {
+        let (scope, session) = match &row.scope {
+            Scope::Tty { name, session } => (format!("tty {name}"), session),
+            Scope::Ppid { pid, session } => (format!("ppid {pid}"), session),
+        };
+        let remaining = match row.status {
+            Status::Valid { remaining } => remaining
+                .to_std()
+                .map_or_else(|| remaining.to_string(), format_duration),
+            Status::Expired => "expired".to_string(),
+            Status::Disabled => "disabled".to_string(),
+        };
+
+        lines.push([
+            row.user.clone(),
+            row.index.to_string(),
+            scope,
+            session.to_string(),
+            row.auth_user.clone(),
+            row.age.map_or("-".to_string(), format_duration),
+            remaining,
+        ]);
+    }
+
+    let mut widths = [0; HEADER.len()];
+    for line in &lines {
+        for (width, cell) in widths.iter_mut().zip(line) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut output = String::new();
+    for line in &lines {
+        let mut text = String::new();
+        for (width, cell) in widths.iter().zip(line) {
+            let _ = write!(text, "{cell:width$}  ");
+        }
+        output.push_str(text.trim_end());
+        output.push('\n');
+    }
+
+    output
+}
+
+fn rows_as_json(rows: &[Row]) -> String {
+    let mut output = String::from("[");
+
+    for (n, row) in rows.iter().enumerate() {
+        if n > 0 {
+            output.push(',');
+        }
+
+        let _ = write!(
+            output,
+            "{{\"user\":{},\"uid\":{},\"record\":{},",
+            json_string(&row.user),
+            row.uid,
+            row.index
+        );
+        let _ = match &row.scope {
+            Scope::Tty { name, session } => write!(
+                output,
+                "\"scope\":\"tty\",\"tty\":{},\"session\":{session},",
+                json_string(name)
+            ),
+            Scope::Ppid { pid, session } => write!(
+                output,
+                "\"scope\":\"ppid\",\"ppid\":{pid},\"session\":{session},"
+            ),
+        };
+        let (status, remaining) = match row.status {
+            Status::Valid { remaining } => ("valid", remaining.to_std()),
+            Status::Expired => ("expired", None),
+            Status::Disabled => ("disabled", None),
+        };
+        let _ = write!(
+            output,
+            "\"auth_user\":{},\"auth_uid\":{},\"age\":{},\"status\":\"{status}\",\"remaining\":{}}}",
+            json_string(&row.auth_user),
+            row.auth_uid,
+            json_seconds(row.age),
+            json_seconds(remaining),
+        );
+    }
+
+    output.push_str("]\n");
+    output
+}
+
+fn json_seconds(duration: Option<Duration>) -> String {
+    duration.map_or("null".to_string(), |d| d.as_secs().to_string())
+}
+
+fn json_string(text: &str) -> String {
+    let mut result = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            c if c.is_control() => {
+                let _ = write!(result, "\\u{:04x}", c as u32);
+            }
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+
+    if hours > 0 {
+        format!("{hours}h{minutes:02}m{seconds:02}s")
+    } else if minutes > 0 {
+        format!("{minutes}m{seconds:02}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(scope: Scope, status: Status) -> Row {
+        Row {
+            uid: UserId::new(1000),
+            user: "ferris".to_string(),
+            index: 0,
+            scope,
+            auth_uid: UserId::ROOT,
+            auth_user: "root".to_string(),
+            age: Some(Duration::from_secs(65)),
+            status,
+        }
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(format_duration(Duration::from_millis(2500)), "2s");
+        assert_eq!(format_duration(Duration::from_secs(65)), "1m05s");
+        assert_eq!(format_duration(Duration::from_secs(3600 + 59)), "1h00m59s");
+    }
+
+    #[test]
+    fn json_strings() {
+        assert_eq!(json_string("ferris"), r#""ferris""#);
+        assert_eq!(json_string("a\"b\\c\nd\u{1}"), r#""a\"b\\c\nd\u0001""#);
+    }
+
+    #[test]
+    fn table() {
+        let rows = [
+            row(
+                Scope::Tty {
+                    name: "/dev/pts/0".to_string(),
+                    session: 42,
+                },
+                Status::Valid {
+                    remaining: SudoersDuration::from_secs(835),
+                },
+            ),
+            row(
+                Scope::Ppid {
+                    pid: 1234,
+                    session: 42,
+                },
+                Status::Disabled,
+            ),
+        ];
+
+        assert_eq!(
+            rows_as_table(&rows),
+            "\
+USER    RECORD  SCOPE           SESSION  AUTH USER  AGE    REMAINING
+ferris  0       tty /dev/pts/0  42       root       1m05s  13m55s
+ferris  0       ppid 1234       42       root       1m05s  disabled
+"
+        );
+    }
+
+    #[test]
+    fn json() {
+        let rows = [row(
+            Scope::Ppid {
+                pid: 1234,
+                session: 42,
+            },
+            Status::Expired,
+        )];
+
+        assert_eq!(
+            rows_as_json(&rows),
+            r#"[{"user":"ferris","uid":1000,"record":0,"scope":"ppid","ppid":1234,"session":42,"auth_user":"root","auth_uid":0,"age":65,"status":"expired","remaining":null}]
+"#
+        );
+    }
+}
@@ -52,6 +52,7 @@ pub enum UserSpecifier {
     User(Identifier) = HARDENED_ENUM_VALUE_0,
     Group(Identifier) = HARDENED_ENUM_VALUE_1,
     NonunixGroup(Identifier) = HARDENED_ENUM_VALUE_2,
+    Netgroup(SudoString) = HARDENED_ENUM_VALUE_3,
 }
 
 /// The RunAs specification consists of a (possibly empty) list of userspecifiers, followed by a (possibly empty) list of groups.
@@ -97,24 +98,33 @@ pub enum ExecControl {
     Noexec = HARDENED_ENUM_VALUE_2,
 }
 
+#[derive(Copy, Clone, Default, PartialEq)]
+#[cfg_attr(test, derive(Debug, Eq))]
+#[repr(u32)]
+pub enum FollowControl {
+    #[default]
+    Implicit = HARDENED_ENUM_VALUE_0,
+    // FOLLOW:
+    Follow = HARDENED_ENUM_VALUE_1,
+    // NOFOLLOW:
+    Nofollow = HARDENED_ENUM_VALUE_2,
+}
+
 /// Commands in /etc/sudoers can have attributes attached to them, such as NOPASSWD, NOEXEC, ...
 #[derive(Default, Clone, PartialEq)]
 #[cfg_attr(test, derive(Debug, Eq))]
 pub struct Tag {
     pub(super) authenticate: Authenticate,
     pub(super) cwd: Option<ChDir>,
+    pub(super) chroot: Option<ChDir>,
     pub(super) env: EnvironmentControl,
     pub(super) apparmor_profile: Option<String>,
     pub(super) noexec: ExecControl,
+    pub(super) follow: FollowControl,
+    pub(super) intercept: bool,
     pub(super) ignored: Vec<Span>,
 }
 
-impl Tag {
-    pub fn needs_passwd(&self) -> bool {
-        matches!(self.authenticate, Authenticate::None | Authenticate::Passwd)
-    }
-}
-
 /// Commands with attached attributes.
 pub struct CommandSpec(pub Vec<Modifier>, pub Spec<Command>);
 
@@ -136,14 +146,20 @@ pub enum Directive {
     HostAlias(Defs<Hostname>) = HARDENED_ENUM_VALUE_1,
     CmndAlias(Defs<Command>) = HARDENED_ENUM_VALUE_2,
     RunasAlias(Defs<UserSpecifier>) = HARDENED_ENUM_VALUE_3,
-    Defaults(Vec<defaults::SettingsModifier>, ConfigScope) = HARDENED_ENUM_VALUE_4,
+    Defaults(Vec<DefaultsParam>, ConfigScope, Span) = HARDENED_ENUM_VALUE_4,
+}
+
+/// AST object for a single parameter of a Defaults directive, e.g. `env_keep+=FOO`
+pub struct DefaultsParam {
+    pub name: String,
+    pub modifier: defaults::SettingsModifier,
 }
 
 /// AST object for the 'context' (host, user, cmnd, runas) of a Defaults directive
 #[repr(u32)]
 pub enum ConfigScope {
-    // "Defaults entries are parsed in the following order:
-    // generic, host and user Defaults first, then runas Defaults and finally command defaults."
+    // generic, host, user and runas Defaults are applied in the order in which they appear;
+    // command Defaults are applied after those
     Generic = HARDENED_ENUM_VALUE_0,
     Host(SpecList<Hostname>) = HARDENED_ENUM_VALUE_1,
     User(SpecList<UserSpecifier>) = HARDENED_ENUM_VALUE_2,
@@ -256,8 +272,8 @@ impl Parse for UserSpecifier {
                 // in this case we must fail 'hard', since input has been consumed
                 ctor(expect_nonterminal(stream)?)
             } else if stream.eat_char('+') {
-                // TODO Netgroups
-                unrecoverable!(stream, "netgroups are not supported yet");
+                let Username(name) = expect_nonterminal(stream)?;
+                UserSpecifier::Netgroup(name)
             } else {
                 // in this case we must fail 'softly', since no input has been consumed yet
                 UserSpecifier::User(try_nonterminal(stream)?)
@@ -337,6 +353,11 @@ impl Parse for MetaOrTag {
             "EXEC" => switch(|tag| tag.noexec = ExecControl::Exec)?,
             "NOEXEC" => switch(|tag| tag.noexec = ExecControl::Noexec)?,
 
+            "FOLLOW" => switch(|tag| tag.follow = FollowControl::Follow)?,
+            "NOFOLLOW" => switch(|tag| tag.follow = FollowControl::Nofollow)?,
+            "INTERCEPT" => switch(|tag| tag.intercept = true)?,
+            "NOINTERCEPT" => switch(|tag| tag.intercept = false)?,
+
             "SETENV" => switch(|tag| tag.env = EnvironmentControl::Setenv)?,
             "NOSETENV" => switch(|tag| tag.env = EnvironmentControl::Nosetenv)?,
             "PASSWD" => switch(|tag| tag.authenticate = Authenticate::Passwd)?,
@@ -348,9 +369,15 @@ impl Parse for MetaOrTag {
                 Box::new(move |tag| tag.cwd = Some(path.clone()))
             }
 
+            "CHROOT" => {
+                expect_syntax('=', stream)?;
+                let path: ChDir = expect_nonterminal(stream)?;
+                Box::new(move |tag| tag.chroot = Some(path.clone()))
+            }
+
             // we do not support these, and that should make sudo-rs "fail safe"
-            spec @ ("INTERCEPT" | "CHROOT" | "TIMEOUT" | "NOTBEFORE" | "NOTAFTER") => {
-                unrecoverable!(
+            spec @ ("TIMEOUT" | "NOTBEFORE" | "NOTAFTER") => {
+                unsupported!(
                     pos = start_pos,
                     stream,
                     "{spec} is not supported by sudo-rs"
@@ -363,8 +390,7 @@ impl Parse for MetaOrTag {
             ),
 
             // this is less fatal
-            "LOG_INPUT" | "NOLOG_INPUT" | "LOG_OUTPUT" | "NOLOG_OUTPUT" | "MAIL" | "NOMAIL"
-            | "FOLLOW" => {
+            "LOG_INPUT" | "NOLOG_INPUT" | "LOG_OUTPUT" | "NOLOG_OUTPUT" | "MAIL" | "NOMAIL" => {
                 let ignored_location = Span {
                     start: start_pos,
                     end: stream.get_pos(),
@@ -373,9 +399,6 @@ impl Parse for MetaOrTag {
                 Box::new(move |tag| tag.ignored.push(ignored_location))
             }
 
-            // 'NOFOLLOW' and 'NOINTERCEPT' are the default behaviour.
-            "NOFOLLOW" | "NOINTERCEPT" => switch(|_| {})?,
-
             "APPARMOR_PROFILE" => {
                 expect_syntax('=', stream)?;
                 let StringParameter(profile) = expect_nonterminal(stream)?;
@@ -476,7 +499,7 @@ impl Directive {
     fn could_be_username(&self) -> bool {
         matches!(
             self,
-            Self::Defaults(_, ConfigScope::Generic)
+            Self::Defaults(_, ConfigScope::Generic, _)
                 | Self::UserAlias(_)
                 | Self::HostAlias(_)
                 | Self::CmndAlias(_)
@@ -701,7 +724,13 @@ fn get_directive<'a>(
                 ConfigScope::Generic
             };
 
-            make(Defaults(expect_nonterminal(stream)?, scope))
+            let params = expect_nonterminal(stream)?;
+            let span = Span {
+                start: begin_pos,
+                end: stream.get_pos(),
+            };
+
+            make(Defaults(params, scope, span))
         }
         _ => reject(),
     }
@@ -711,7 +740,7 @@ fn get_directive<'a>(
 /// ```text
 /// parameter = name [+-]?= ...
 /// ```
-impl Parse for defaults::SettingsModifier {
+impl Parse for DefaultsParam {
     fn parse(stream: &mut CharStream) -> Parsed<Self> {
         let id_pos = stream.get_pos();
 
@@ -754,12 +783,21 @@ impl Parse for defaults::SettingsModifier {
                     unrecoverable!(pos = id_pos, stream, "{name} is not a list parameter");
                 };
 
-                make(checker(mode, parse_vars(stream)?))
+                let modifier = checker(mode, parse_vars(stream)?);
+                make(DefaultsParam { name, modifier })
             };
 
         // Parse a text parameter
-        let text_item = |stream: &mut CharStream| {
+        let text_item = |name: &str, stream: &mut CharStream| {
             if stream.eat_char('"') {
+                // an empty string, as in `secure_path=""`, which means that it is not set; no
+                // other setting has a use for it
+                if stream.eat_char('"') {
+                    if name != "secure_path" {
+                        unrecoverable!(stream, "empty string not allowed");
+                    }
+                    return make(String::new());
+                }
                 let QuotedStringParameter(text) = expect_nonterminal(stream)?;
                 expect_syntax('"', stream)?;
                 make(text)
@@ -780,15 +818,15 @@ impl Parse for defaults::SettingsModifier {
                         "'{name}' cannot be used in a boolean context"
                     );
                 } else {
-                    unrecoverable!(pos = value_pos, stream, "unknown setting: '{name}'");
+                    unsupported!(pos = value_pos, stream, "unknown setting: '{name}'");
                 }
             };
 
-            make(modifier)
+            make(DefaultsParam { name, modifier })
         } else {
             let DefaultName(name) = try_nonterminal(stream)?;
             let Some(cfg) = defaults::set(&name) else {
-                unrecoverable!(pos = id_pos, stream, "unknown setting: '{name}'");
+                unsupported!(pos = id_pos, stream, "unknown setting: '{name}'");
             };
 
             if is_syntax('+', stream)? {
@@ -802,9 +840,16 @@ impl Parse for defaults::SettingsModifier {
                         unrecoverable!(stream, "can't assign to boolean setting '{name}'")
                     }
                     defaults::SettingKind::Integer(checker) => {
-                        let Numeric(denotation) = expect_nonterminal(stream)?;
+                        // only a timeout can be written with units, such as "1h30m"
+                        let denotation = if name == "command_timeout" {
+                            let Timeout(denotation) = expect_nonterminal(stream)?;
+                            denotation
+                        } else {
+                            let Numeric(denotation) = expect_nonterminal(stream)?;
+                            denotation
+                        };
                         if let Some(modifier) = checker(&denotation) {
-                            make(modifier)
+                            make(DefaultsParam { name, modifier })
                         } else {
                             unrecoverable!(
                                 pos = value_pos,
@@ -815,11 +860,12 @@ impl Parse for defaults::SettingsModifier {
                     }
                     defaults::SettingKind::List(checker) => {
                         let items = parse_vars(stream)?;
+                        let modifier = checker(defaults::ListMode::Set, items);
 
-                        make(checker(defaults::ListMode::Set, items))
+                        make(DefaultsParam { name, modifier })
                     }
                     defaults::SettingKind::Text(checker) => {
-                        let text = text_item(stream)?;
+                        let text = text_item(&name, stream)?;
                         let Some(modifier) = checker(&text) else {
                             unrecoverable!(
                                 pos = value_pos,
@@ -827,7 +873,7 @@ impl Parse for defaults::SettingsModifier {
                                 "'{text}' is not a valid value for {name}"
                             );
                         };
-                        make(modifier)
+                        make(DefaultsParam { name, modifier })
                     }
                 }
             } else {
@@ -835,10 +881,10 @@ impl Parse for defaults::SettingsModifier {
                     unrecoverable!(pos = id_pos, stream, "'{name}' is not a boolean setting");
                 };
 
-                make(modifier)
+                make(DefaultsParam { name, modifier })
             }
         }
     }
 }
 
-impl Many for defaults::SettingsModifier {}
+impl Many for DefaultsParam {}
@@ -8,7 +8,6 @@ pub trait UserFriendly {
 #[cfg(not(test))]
 mod names {
     use super::*;
-    use crate::defaults;
     use crate::sudoers::ast::*;
     use crate::sudoers::tokens;
 
@@ -20,6 +19,10 @@ mod names {
         const DESCRIPTION: &'static str = "nonnegative number";
     }
 
+    impl UserFriendly for tokens::Timeout {
+        const DESCRIPTION: &'static str = "timeout";
+    }
+
     impl UserFriendly for Identifier {
         const DESCRIPTION: &'static str = "identifier";
     }
@@ -109,7 +112,7 @@ mod names {
         const DESCRIPTION: &'static str = "directory or '*'";
     }
 
-    impl UserFriendly for defaults::SettingsModifier {
+    impl UserFriendly for DefaultsParam {
         const DESCRIPTION: &'static str = "parameter";
     }
 
@@ -37,8 +37,9 @@ pub struct Span {
 
 #[cfg_attr(test, derive(Debug, PartialEq))]
 pub enum Status {
-    Fatal(Span, String), // not recoverable; stream in inconsistent state
-    Reject,              // parsing failed by no input consumed
+    Fatal(Span, String),       // not recoverable; stream in inconsistent state
+    Reject,                    // parsing failed by no input consumed
+    Unsupported(Span, String), // like Fatal, but caused by a feature sudo-rs does not support
 }
 
 pub fn make<T>(value: T) -> Parsed<T> {
@@ -64,6 +65,14 @@ macro_rules! unrecoverable {
 
 pub(super) use unrecoverable;
 
+macro_rules! unsupported {
+    (pos=$pos:expr, $stream:ident, $($str:expr),*) => {
+        return Err(crate::sudoers::basic_parser::Status::Unsupported(Span { start: $pos, end: CharStream::get_pos($stream)}, format![$($str),*]))
+    };
+}
+
+pub(super) use unsupported;
+
 /// This recovers from a failed parsing.
 pub fn maybe<T>(status: Parsed<T>) -> Parsed<Option<T>> {
     match status {
@@ -1,4 +1,5 @@
 use core::fmt;
+use std::ffi::OsString;
 
 use crate::sudoers::{
     VecOrd,
@@ -13,7 +14,8 @@ use crate::{
 use self::verbose::Verbose;
 
 use super::{
-    ast::{Authenticate, Def, EnvironmentControl, ExecControl, RunAs, Tag},
+    AuthenticatingUser, Settings,
+    ast::{Authenticate, Def, EnvironmentControl, ExecControl, FollowControl, RunAs, Tag},
     tokens::{Args, Command},
 };
 
@@ -23,6 +25,7 @@ pub struct Entry<'a> {
     run_as: Option<&'a RunAs>,
     cmd_specs: Vec<(Tag, &'a Qualified<Meta<Command>>)>,
     cmd_alias: &'a VecOrd<Def<Command>>,
+    settings: &'a Settings,
 }
 
 impl<'a> Entry<'a> {
@@ -30,6 +33,7 @@ impl<'a> Entry<'a> {
         run_as: Option<&'a RunAs>,
         cmd_specs: Vec<(Tag, &'a Qualified<Meta<Command>>)>,
         cmd_alias: &'a VecOrd<Def<Command>>,
+        settings: &'a Settings,
     ) -> Self {
         debug_assert!(!cmd_specs.is_empty());
 
@@ -37,22 +41,38 @@ impl<'a> Entry<'a> {
             run_as,
             cmd_specs,
             cmd_alias,
+            settings,
         }
     }
 
     pub fn verbose(self) -> impl fmt::Display + 'a {
         Verbose(self)
     }
+
+    /// The commands that this entry allows by their literal path, i.e. without wildcards
+    /// (nor via `ALL`), with aliases expanded, together with the arguments they are allowed
+    /// with; commands that are negated later on in this entry are left out. Other rules may
+    /// still forbid them, so these are only candidates that should be judged before use.
+    pub fn literal_commands(&self) -> Vec<(String, Vec<OsString>)> {
+        let mut commands = Vec::new();
+        for (_, spec) in &self.cmd_specs {
+            collect_literal_commands(spec, self.cmd_alias.iter().rev(), true, &mut commands);
+        }
+
+        commands
+    }
 }
 
-fn root_runas() -> RunAs {
-    let name = User::from_uid(UserId::ROOT)
+fn root_name() -> SudoString {
+    User::from_uid(UserId::ROOT)
         .ok()
         .flatten()
         .map(|u| u.name)
-        .unwrap_or(SudoString::new("root".into()).unwrap());
+        .unwrap_or(SudoString::new("root".into()).unwrap())
+}
 
-    let name = UserSpecifier::User(Identifier::Name(name));
+fn root_runas() -> RunAs {
+    let name = UserSpecifier::User(Identifier::Name(root_name()));
     let name = Qualified::Allow(Meta::Only(name));
 
     RunAs {
@@ -67,6 +87,7 @@ impl fmt::Display for Entry<'_> {
             run_as,
             cmd_specs,
             cmd_alias,
+            settings,
         } = self;
 
         let root_runas = root_runas();
@@ -95,10 +116,31 @@ impl fmt::Display for Entry<'_> {
             write_spec(f, spec, cmd_alias.iter().rev(), true, ", ")?;
         }
 
+        if let Some(user) = cmd_specs
+            .iter()
+            .find_map(|(tag, _)| password_owner(settings, tag))
+        {
+            write!(f, "  # {}", xlat!("password of {user}", user = user))?;
+        }
+
         Ok(())
     }
 }
 
+/// Whose password is required for a command with this tag, if not that of the invoking user;
+/// this is only shown when rootpw, runaspw or targetpw are in effect.
+fn password_owner(settings: &Settings, tag: &Tag) -> Option<String> {
+    if !settings.needs_passwd(tag) {
+        return None;
+    }
+
+    match settings.authenticating_user() {
+        AuthenticatingUser::InvokingUser => None,
+        AuthenticatingUser::Root => Some(root_name().to_string()),
+        AuthenticatingUser::TargetUser => Some(xlat!("the target user").to_string()),
+    }
+}
+
 fn write_users(run_as: &RunAs, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
     if run_as.users.is_empty() {
         match CurrentUser::resolve() {
@@ -124,6 +166,7 @@ fn write_users(run_as: &RunAs, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Er
 
         match meta {
             Meta::All => f.write_str("ALL")?,
+            Meta::Only(UserSpecifier::Netgroup(name)) => write!(f, "+{name}")?,
             Meta::Only(user) => {
                 let ident = match user {
                     UserSpecifier::User(ident) => ident,
@@ -135,6 +178,7 @@ fn write_users(run_as: &RunAs, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Er
                         f.write_str("%:")?;
                         ident
                     }
+                    UserSpecifier::Netgroup(_) => unreachable!("netgroups are written above"),
                 };
 
                 match ident {
@@ -196,6 +240,15 @@ fn write_tag(
         f.write_str(" ")?;
     }
 
+    if tag.chroot != last_tag.chroot {
+        f.write_str("CHROOT=")?;
+        match tag.chroot.as_ref().expect("sudoers spec turned off") {
+            ChDir::Path(path) => write!(f, "{}", path.display())?,
+            ChDir::Any => f.write_str("*")?,
+        }
+        f.write_str(" ")?;
+    }
+
     if tag.cwd != last_tag.cwd {
         f.write_str("CWD=")?;
         match tag.cwd.as_ref().expect("sudoers spec turned off") {
@@ -221,6 +274,12 @@ fn write_tag(
     if tag.noexec != last_tag.noexec {
         write_tag("EXEC", tag.noexec == ExecControl::Exec)?;
     }
+    if tag.follow != last_tag.follow {
+        write_tag("FOLLOW", tag.follow == FollowControl::Follow)?;
+    }
+    if tag.intercept != last_tag.intercept {
+        write_tag("INTERCEPT", tag.intercept)?;
+    }
     if tag.authenticate != last_tag.authenticate {
         write_tag("PASSWD", tag.authenticate != Authenticate::Nopasswd)?;
     }
@@ -295,3 +354,44 @@ fn write_spec<'a>(
 
     Ok(())
 }
+
+fn collect_literal_commands<'a>(
+    spec: &Qualified<Meta<Command>>,
+    mut alias_list: impl Iterator<Item = &'a Def<Command>> + Clone,
+    mut sign: bool,
+    commands: &mut Vec<(String, Vec<OsString>)>,
+) {
+    let meta = match spec {
+        Qualified::Allow(meta) => meta,
+        Qualified::Forbid(meta) => {
+            sign = !sign;
+            meta
+        }
+    };
+
+    match meta {
+        Meta::All => {}
+
+        Meta::Only((cmd, args)) => {
+            let path = cmd.as_str();
+            let is_literal = path.starts_with('/') && !path.contains(['*', '?', '[', '\\']);
+            if is_literal {
+                let (Args::Prefix(args) | Args::Exact(args)) = args;
+                let command = (path.to_string(), args.to_vec());
+                commands.retain(|other| *other != command);
+                if sign {
+                    commands.push(command);
+                }
+            }
+        }
+
+        Meta::Alias(alias) => {
+            // see write_spec for why this terminates and expands correctly
+            if let Some(Def(_, spec_list)) = alias_list.find(|Def(id, _)| id == alias) {
+                for spec in spec_list {
+                    collect_literal_commands(spec, alias_list.clone(), sign, commands);
+                }
+            }
+        }
+    }
+}
@@ -15,6 +15,7 @@ impl fmt::Display for Verbose<'_> {
             run_as,
             cmd_specs,
             cmd_alias,
+            settings,
         }) = self;
 
         let root_runas = super::root_runas();
@@ -30,6 +31,9 @@ impl fmt::Display for Verbose<'_> {
 
                 write_entry_header(run_as, f)?;
                 write_tag(f, tag)?;
+                if let Some(user) = super::password_owner(settings, tag) {
+                    write!(f, "\n    {}: {user}", xlat!("Password of"))?;
+                }
                 write!(f, "\n    {}", xlat!("Commands:"))?;
             }
             last_tag = Some(tag);
@@ -74,6 +78,15 @@ fn write_tag(f: &mut fmt::Formatter, tag: &Tag) -> fmt::Result {
         f.write_str("authenticate")?;
     }
 
+    if let Some(chroot) = &tag.chroot {
+        // TRANSLATORS: This is sudo-specific jargon.
+        write!(f, "\n    {}: ", xlat!("Chroot"))?;
+        match chroot {
+            ChDir::Path(path) => write!(f, "{}", path.display())?,
+            ChDir::Any => f.write_str("*")?,
+        }
+    }
+
     if let Some(cwd) = &tag.cwd {
         // TRANSLATORS: This is sudo-specific jargon.
         write!(f, "\n    {}: ", xlat!("Cwd"))?;
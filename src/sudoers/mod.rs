@@ -10,15 +10,19 @@ mod char_stream;
 mod entry;
 mod tokens;
 
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::ffi::OsString;
 use std::fmt;
 use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::Once;
 
 use crate::common::resolve::{is_valid_executable, resolve_path};
+use crate::common::{SudoString, SudoersDuration};
 use crate::defaults;
-use crate::log::auth_warn;
+use crate::log::{auth_debug, auth_warn};
+use crate::system::group_provider::{ConfiguredGroupProvider, GroupProvider};
 use crate::system::interface::{GroupId, UnixGroup, UnixUser, UserId};
 use crate::system::{self, audit};
 use ast::*;
@@ -35,15 +39,32 @@ pub struct Error {
     pub source: Option<PathBuf>,
     pub location: Option<basic_parser::Span>,
     pub message: String,
+    /// Whether this only means that sudo-rs ignores a part of the sudoers file that it does not
+    /// support (an unknown Defaults setting or tag), rather than that the file contains a mistake.
+    pub unsupported_feature: bool,
 }
 
+const IGNORED_TAG: &str = "this tag is ignored by sudo-rs";
+
 /// A "Customiser" represents a "Defaults" setting that has 'late binding'; i.e.
 /// cannot be determined simply by reading a sudoers configuration. This is used
 /// for Defaults@host, Defaults:user, Defaults>runas and Defaults!cmd.
 ///
-/// I.e. the Setting modifications in the second part of the tuple only apply for
-/// items explicitly matched by the first part of the tuple.
-type Customiser<Scope> = (Scope, Vec<defaults::SettingsModifier>);
+/// I.e. the Setting modifications in `params` only apply for items explicitly matched
+/// by the `scope`.
+struct Customiser<Scope> {
+    scope: Scope,
+    params: Vec<DefaultsParam>,
+    source: DefaultsSource,
+}
+
+/// The location of a "Defaults" line in the sudoers file(s)
+#[derive(Clone)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct DefaultsSource {
+    pub path: PathBuf,
+    pub location: basic_parser::Span,
+}
 
 #[derive(Default)]
 pub struct Sudoers {
@@ -51,6 +72,7 @@ pub struct Sudoers {
     aliases: AliasTable,
     settings: Settings,
     customisers: CustomiserTable,
+    defaults_sources: HashMap<String, DefaultsSource>,
 }
 
 /// A structure that represents what the user wants to do
@@ -97,6 +119,18 @@ impl Sudoers {
         Ok(analyze(path.as_ref(), sudoers))
     }
 
+    /// Parse and analyze `input` like `Sudoers::read` does, but skip `@include` and
+    /// `@includedir` directives so that the outcome only depends on `input`; this is the
+    /// code that the fuzz target in `fuzz/` exercises.
+    #[cfg(any(test, fuzzing))]
+    pub fn parse_without_includes(input: &str) -> (Sudoers, Vec<Error>) {
+        let sudoers = basic_parser::parse_lines(&mut char_stream::CharStream::new(input))
+            .into_iter()
+            .filter(|item| !matches!(item, Ok(Sudo::Include(..) | Sudo::IncludeDir(..))));
+
+        analyze(Path::new("fuzz"), sudoers)
+    }
+
     fn specify_host_user_runas<User: UnixUser + PartialEq<User>>(
         &mut self,
         hostname: &system::Hostname,
@@ -104,60 +138,115 @@ impl Sudoers {
         target_user: Option<&User>,
     ) {
         let customisers = std::mem::take(&mut self.customisers.non_cmnd);
+        let matching =
+            self.matching_customisers(&customisers, hostname, requesting_user, target_user);
 
-        let host_matcher = &match_token(hostname);
-        let host_aliases = get_aliases(&self.aliases.host, host_matcher);
-
-        let user_matcher = &match_user(requesting_user);
-        let user_aliases = get_aliases(&self.aliases.user, user_matcher);
-
-        let runas_matcher_aliases = target_user.map(|target_user| {
-            let runas_matcher = match_user(target_user);
-            let runas_aliases = get_aliases(&self.aliases.runas, &runas_matcher);
+        self.apply_defaults(matching);
+    }
 
-            (runas_matcher, runas_aliases)
-        });
+    /// The generic, host, user and runas Defaults that apply, in the order in which they
+    /// have to be applied: the order in which they appear, so that later lines override
+    /// earlier ones. Command Defaults are applied after all of these, by `specify_command`.
+    fn matching_customisers<'a, User: UnixUser + PartialEq<User>>(
+        &self,
+        customisers: &'a [Customiser<ConfigScope>],
+        hostname: &system::Hostname,
+        requesting_user: &User,
+        target_user: Option<&User>,
+    ) -> Vec<&'a Customiser<ConfigScope>> {
+        let host_matcher = &match_host(hostname);
+        let host_aliases = get_aliases(&self.aliases.host, host_matcher);
 
-        let match_scope = |scope| match scope {
-            ConfigScope::Generic => true,
-            ConfigScope::Host(list) => find_item(&list, host_matcher, &host_aliases).is_some(),
-            ConfigScope::User(list) => find_item(&list, user_matcher, &user_aliases).is_some(),
-            ConfigScope::RunAs(list) => {
-                runas_matcher_aliases
-                    .as_ref()
-                    .is_some_and(|(runas_matcher, runas_aliases)| {
-                        find_item(&list, runas_matcher, runas_aliases).is_some()
-                    })
-            }
-            ConfigScope::Command(_list) => {
-                unreachable!("command-specific defaults are filtered out")
-            }
-        };
+        // whether netgroups are used can be changed by an earlier Defaults line
+        let mut netgroups = self.settings.clone();
+
+        let nonunix_groups = &ConfiguredGroupProvider;
+        let mut matching = Vec::new();
+        for customiser in customisers {
+            let scope_matches = match &customiser.scope {
+                ConfigScope::Generic => true,
+                ConfigScope::Host(list) => find_item(list, host_matcher, &host_aliases).is_some(),
+                ConfigScope::User(list) => {
+                    let user_matcher =
+                        &match_user(requesting_user, netgroups.use_netgroups(), nonunix_groups);
+                    let user_aliases = get_aliases(&self.aliases.user, user_matcher);
+                    find_item(list, user_matcher, &user_aliases).is_some()
+                }
+                ConfigScope::RunAs(list) => target_user.is_some_and(|target_user| {
+                    let runas_matcher =
+                        &match_user(target_user, netgroups.use_netgroups(), nonunix_groups);
+                    let runas_aliases = get_aliases(&self.aliases.runas, runas_matcher);
+                    find_item(list, runas_matcher, &runas_aliases).is_some()
+                }),
+                ConfigScope::Command(_list) => {
+                    unreachable!("command-specific defaults are filtered out")
+                }
+            };
 
-        for (scope, modifiers) in customisers {
-            if match_scope(scope) {
-                for modifier in modifiers {
-                    modifier(&mut self.settings);
+            if scope_matches {
+                for DefaultsParam { name, modifier } in &customiser.params {
+                    if name == "use_netgroups" {
+                        modifier(&mut netgroups);
+                    }
                 }
+                matching.push(customiser);
             }
         }
+
+        matching
     }
 
     fn specify_command(&mut self, command: &Path, arguments: &[OsString]) {
+        // unlike the other Defaults, these are kept so they can be applied for another command
         let customisers = std::mem::take(&mut self.customisers.cmnd);
 
         let cmnd_matcher = &match_command((command, arguments));
         let cmnd_aliases = get_aliases(&self.aliases.cmnd, cmnd_matcher);
 
-        for (scope, modifiers) in customisers {
-            if find_item(&scope, cmnd_matcher, &cmnd_aliases).is_some() {
-                for modifier in modifiers {
-                    modifier(&mut self.settings);
-                }
+        let matching = customisers.iter().filter(|customiser| {
+            find_item(&customiser.scope, cmnd_matcher, &cmnd_aliases).is_some()
+        });
+        self.apply_defaults(matching);
+
+        self.customisers.cmnd = customisers;
+    }
+
+    /// Apply the Defaults that were found to match, in the given order, and remember for
+    /// every setting which line was the last to change it.
+    fn apply_defaults<'a, Scope: 'a>(
+        &mut self,
+        customisers: impl IntoIterator<Item = &'a Customiser<Scope>>,
+    ) {
+        for customiser in customisers {
+            for DefaultsParam { name, modifier } in &customiser.params {
+                modifier(&mut self.settings);
+                self.defaults_sources
+                    .insert(name.clone(), customiser.source.clone());
             }
         }
     }
 
+    /// Where the current value of the setting `name` was set: the last Defaults line that
+    /// was applied and mentions it, or `None` if it still has its built-in default.
+    pub(crate) fn defaults_source(&self, name: &str) -> Option<&DefaultsSource> {
+        self.defaults_sources.get(name)
+    }
+
+    /// The settings that were changed by the Defaults that apply, as shown by `sudo -l`:
+    /// their current value written as in a Defaults line, and the line that set it. These
+    /// are ordered by that line; settings that have no effect in sudo-rs are left out.
+    pub fn matching_defaults(&self) -> Vec<(String, &DefaultsSource)> {
+        let mut entries = defaults::entries(&self.settings)
+            .into_iter()
+            .filter_map(|(name, entry)| Some((entry, self.defaults_source(name)?)))
+            .collect::<Vec<_>>();
+        entries.sort_by(|(_, left), (_, right)| {
+            (&left.path, left.location.start).cmp(&(&right.path, right.location.start))
+        });
+
+        entries
+    }
+
     pub fn check<User: UnixUser + PartialEq<User>, Group: UnixGroup>(
         &mut self,
         am_user: &User,
@@ -165,6 +254,11 @@ impl Sudoers {
         request: Request<User, Group>,
     ) -> Judgement {
         self.specify_host_user_runas(on_host, am_user, Some(request.user));
+
+        // the Defaults for the command only apply to this judgement, so that the policy can
+        // be checked again for another command
+        let settings = self.settings.clone();
+        let defaults_sources = self.defaults_sources.clone();
         self.specify_command(request.command, request.arguments);
 
         // exception: if user is root or does not switch users, NOPASSWD is implied
@@ -178,9 +272,10 @@ impl Sudoers {
             }
         }
 
+        self.defaults_sources = defaults_sources;
         Judgement {
             flags,
-            settings: self.settings.clone(),
+            settings: std::mem::replace(&mut self.settings, settings),
         }
     }
 
@@ -191,10 +286,11 @@ impl Sudoers {
         request: ListRequest<User, Group>,
     ) -> Authorization {
         let skip_passwd;
+        let mut list_settings = None;
         let mut flags = if request.inspected_user != invoking_user {
             skip_passwd = invoking_user.is_root();
 
-            self.check(
+            let judgement = self.check(
                 invoking_user,
                 hostname,
                 Request {
@@ -203,9 +299,12 @@ impl Sudoers {
                     command: Path::new("list"),
                     arguments: &[],
                 },
-            )
-            .flags
-            .or(invoking_user.is_root().then(Tag::default))
+            );
+            list_settings = Some(judgement.settings);
+
+            judgement
+                .flags
+                .or(invoking_user.is_root().then(Tag::default))
         } else {
             skip_passwd = invoking_user.is_root()
                 || (request.target_user == invoking_user
@@ -214,7 +313,7 @@ impl Sudoers {
             self.matching_user_specs(invoking_user, hostname)
                 .flatten()
                 .map(|(_, (tag, _))| tag)
-                .max_by_key(|tag| !tag.needs_passwd())
+                .max_by_key(|tag| !self.settings.needs_passwd(tag))
         };
 
         if let Some(tag) = flags.as_mut() {
@@ -222,7 +321,8 @@ impl Sudoers {
                 tag.authenticate = Authenticate::Nopasswd;
             }
 
-            Authorization::Allowed(self.settings.to_auth(tag), ())
+            let settings = list_settings.as_ref().unwrap_or(&self.settings);
+            Authorization::Allowed(settings.to_auth(tag), ())
         } else {
             Authorization::Forbidden
         }
@@ -238,13 +338,28 @@ impl Sudoers {
         // exception: if user is root, NOPASSWD is implied
         let skip_passwd = invoking_user.is_root();
 
-        let mut flags = self
+        use defaults::enums::verifypw;
+        let verifypw = self.settings.verifypw();
+
+        let tags = self
             .matching_user_specs(invoking_user, hostname)
             .flatten()
-            .map(|(_, (tag, _))| tag)
-            .max_by_key(|tag| tag.needs_passwd());
+            .map(|(_, (tag, _))| tag);
+
+        // with "any", a single NOPASSWD entry suffices; otherwise all entries need to have it
+        let mut flags = match verifypw {
+            verifypw::any => tags.min_by_key(|tag| self.settings.needs_passwd(tag)),
+            verifypw::all | verifypw::always | verifypw::never => {
+                tags.max_by_key(|tag| self.settings.needs_passwd(tag))
+            }
+        };
 
         if let Some(tag) = flags.as_mut() {
+            match verifypw {
+                verifypw::always => tag.authenticate = Authenticate::Passwd,
+                verifypw::never => tag.authenticate = Authenticate::Nopasswd,
+                verifypw::all | verifypw::any => {}
+            }
             if skip_passwd {
                 tag.authenticate = Authenticate::Nopasswd;
             }
@@ -266,19 +381,33 @@ impl Sudoers {
         invoking_user: &'a User,
         hostname: &'a system::Hostname,
     ) -> impl Iterator<Item = impl Iterator<Item = MatchedCommand<'a>>> {
-        let Self { rules, aliases, .. } = self;
-        let user_aliases = get_aliases(&aliases.user, &match_user(invoking_user));
-        let host_aliases = get_aliases(&aliases.host, &match_token(hostname));
+        let Self {
+            rules,
+            aliases,
+            settings,
+            ..
+        } = self;
+        let netgroups = settings.use_netgroups();
+        let nonunix_groups = &ConfiguredGroupProvider;
+        let user_aliases = get_aliases(
+            &aliases.user,
+            &match_user(invoking_user, netgroups, nonunix_groups),
+        );
+        let host_aliases = get_aliases(&aliases.host, &match_host(hostname));
 
         rules
             .iter()
             .filter_map(move |sudo| {
-                find_item(&sudo.users, &match_user(invoking_user), &user_aliases)?;
+                find_item(
+                    &sudo.users,
+                    &match_user(invoking_user, netgroups, nonunix_groups),
+                    &user_aliases,
+                )?;
                 Some(&sudo.permissions)
             })
             .flatten()
             .filter_map(move |(hosts, runas_cmds)| {
-                find_item(hosts, &match_token(hostname), &host_aliases)?;
+                find_item(hosts, &match_host(hostname), &host_aliases)?;
                 Some(distribute_tags(runas_cmds))
             })
     }
@@ -290,7 +419,9 @@ impl Sudoers {
     ) -> impl Iterator<Item = Entry<'a>> {
         let user_specs = self.matching_user_specs(invoking_user, hostname);
 
-        user_specs.flat_map(|cmd_specs| group_cmd_specs_per_runas(cmd_specs, &self.aliases.cmnd))
+        user_specs.flat_map(|cmd_specs| {
+            group_cmd_specs_per_runas(cmd_specs, &self.aliases.cmnd, &self.settings)
+        })
     }
 
     pub(crate) fn visudo_editor_path<User: UnixUser + PartialEq<User>>(
@@ -303,6 +434,17 @@ impl Sudoers {
 
         select_editor(&self.settings, self.settings.env_editor())
     }
+
+    /// The period for which a session record of `am_user` remains valid
+    pub(crate) fn timestamp_timeout<User: UnixUser + PartialEq<User>>(
+        mut self,
+        on_host: &system::Hostname,
+        am_user: &User,
+    ) -> SudoersDuration {
+        self.specify_host_user_runas(on_host, am_user, None);
+
+        self.settings.timestamp_timeout()
+    }
 }
 
 /// Retrieve the chosen editor from a settings object, filtering based on whether the
@@ -365,6 +507,7 @@ fn peeking_take_while<'a, T>(
 fn group_cmd_specs_per_runas<'a>(
     cmnd_specs: impl Iterator<Item = (Option<&'a RunAs>, (Tag, &'a Spec<Command>))>,
     cmnd_aliases: &'a VecOrd<Def<Command>>,
+    settings: &'a Settings,
 ) -> impl Iterator<Item = Entry<'a>> {
     // `distribute_tags` will have given every spec a reference to the "runas specification"
     // that applies to it. The output of sudo --list splits the CmndSpec list based on that:
@@ -387,6 +530,7 @@ fn group_cmd_specs_per_runas<'a>(
                 cur_runas,
                 specs.map(|x| x.1).collect(),
                 cmnd_aliases,
+                settings,
             ))
         } else {
             None
@@ -461,8 +605,13 @@ fn check_permission<User: UnixUser + PartialEq<User>, Group: UnixGroup>(
     let cmdline = (request.command, request.arguments);
 
     let aliases = &sudoers.aliases;
+    let netgroups = sudoers.settings.use_netgroups();
+    let nonunix_groups = &ConfiguredGroupProvider;
     let cmnd_aliases = get_aliases(&aliases.cmnd, &match_command(cmdline));
-    let runas_user_aliases = get_aliases(&aliases.runas, &match_user(request.user));
+    let runas_user_aliases = get_aliases(
+        &aliases.runas,
+        &match_user(request.user, netgroups, nonunix_groups),
+    );
     let runas_group_aliases = get_aliases(&aliases.runas, &match_group_alias(request.group));
 
     let matching_user_specs = sudoers.matching_user_specs(am_user, on_host).flatten();
@@ -471,7 +620,11 @@ fn check_permission<User: UnixUser + PartialEq<User>, Group: UnixGroup>(
         if let Some(RunAs { users, groups }) = runas {
             let stays_in_group = in_group(request.user, request.group);
             if request.user != am_user || (stays_in_group && !users.is_empty()) {
-                find_item(users, &match_user(request.user), &runas_user_aliases)?
+                find_item(
+                    users,
+                    &match_user(request.user, netgroups, nonunix_groups),
+                    &runas_user_aliases,
+                )?
             }
             if !stays_in_group {
                 find_item(groups, &match_group(request.group), &runas_group_aliases)?
@@ -483,7 +636,36 @@ fn check_permission<User: UnixUser + PartialEq<User>, Group: UnixGroup>(
         Some(cmdspec)
     });
 
-    find_item(allowed_commands, &match_command(cmdline), &cmnd_aliases)
+    // remember the commands that were only rejected because of their path, to explain that
+    // if the request is not allowed at all
+    let near_misses = RefCell::new(Vec::new());
+    let matches = match_command(cmdline);
+    let judgement = find_item(
+        allowed_commands,
+        &|command: &Command| {
+            let (cmdpat, argpat) = command;
+            let matched = matches(command);
+            if !matched
+                && differs_only_in_path(cmdpat, request.command)
+                && match_args(argpat, request.arguments)
+            {
+                near_misses.borrow_mut().push(cmdpat.as_str().to_string());
+            }
+            matched
+        },
+        &cmnd_aliases,
+    );
+
+    if judgement.is_none() {
+        for rule_path in near_misses.into_inner() {
+            auth_debug!(
+                "{command} does not match {rule_path} in the sudoers file, since their paths differ",
+                command = request.command.display()
+            );
+        }
+    }
+
+    judgement
 }
 
 /// Process a raw parsed AST bit of RunAs + Command specifications:
@@ -514,21 +696,39 @@ fn distribute_tags(
     )
 }
 
-/// A type to represent positive or negative association with an alias; i.e. if a key maps to true,
-/// the alias affirms membership, if a key maps to false, the alias denies membership; if a key
-/// isn't present membership is affirmed nor denied
-type FoundAliases = HashMap<String, bool>;
+/// The outcome of matching a single item in the sudoers file. Some items cannot always be
+/// checked (such as netgroups on a system that does not support them); those never grant
+/// anything, but when they are negated they do take permissions away, so that a policy that
+/// cannot be fully evaluated fails closed.
+#[derive(Clone, Copy, PartialEq)]
+enum Matched {
+    Yes,
+    No,
+    Unknown,
+}
+
+impl From<bool> for Matched {
+    fn from(matched: bool) -> Self {
+        if matched { Matched::Yes } else { Matched::No }
+    }
+}
+
+/// A type to represent positive or negative association with an alias; i.e. if a key maps to Yes,
+/// the alias affirms membership, if a key maps to No, the alias denies membership; if a key
+/// isn't present membership is affirmed nor denied. An alias that could not be fully evaluated
+/// maps to Unknown.
+type FoundAliases = HashMap<String, Matched>;
 
 /// Find an item matching a certain predicate in an collection (optionally attributed) list of
 /// identifiers; identifiers can be directly identifying, wildcards, and can either be positive or
 /// negative (i.e. preceeded by an even number of exclamation marks in the sudoers file)
-fn find_item<'a, Predicate, Iter, T: 'a>(
+fn find_item<'a, Predicate, Iter, T: 'a, M: Into<Matched>>(
     items: Iter,
     matches: &Predicate,
     aliases: &FoundAliases,
 ) -> Option<<Iter::Item as WithInfo>::Info>
 where
-    Predicate: Fn(&T) -> bool,
+    Predicate: Fn(&T) -> M,
     Iter: IntoIterator,
     Iter::Item: WithInfo<Item = &'a Spec<T>>,
 {
@@ -539,19 +739,25 @@ where
             Qualified::Allow(x) => (true, x),
         };
         let info = || item.into_info();
-        match who {
-            Meta::All => result = judgement.then(info),
-            Meta::Only(ident) if matches(ident) => result = judgement.then(info),
-            Meta::Alias(id) if aliases.contains_key(id) => {
-                result = if aliases[id] {
-                    judgement.then(info)
-                } else {
-                    // in this case, an explicit negation in the alias applies
-                    (!judgement).then(info)
+        let matched = match who {
+            Meta::All => Matched::Yes,
+            Meta::Only(ident) => matches(ident).into(),
+            Meta::Alias(id) => match aliases.get(id) {
+                // in this case, an explicit negation in the alias applies
+                Some(Matched::No) => {
+                    result = (!judgement).then(info);
+                    continue;
                 }
-            }
-            _ => {}
+                Some(&found) => found,
+                None => Matched::No,
+            },
         };
+        match matched {
+            Matched::Yes => result = judgement.then(info),
+            // an item that cannot be checked only counts when it takes permissions away
+            Matched::Unknown if !judgement => result = None,
+            Matched::Unknown | Matched::No => {}
+        }
     }
 
     result
@@ -589,19 +795,50 @@ impl<'a> WithInfo for (Tag, &'a Spec<Command>) {
 }
 
 /// Now follow a collection of functions used as closures for `find_item`
-fn match_user(user: &impl UnixUser) -> impl Fn(&UserSpecifier) -> bool + '_ {
+fn match_user<'a>(
+    user: &'a impl UnixUser,
+    netgroups: bool,
+    nonunix_groups: &'a dyn GroupProvider,
+) -> impl Fn(&UserSpecifier) -> Matched + 'a {
     move |spec| match spec {
-        UserSpecifier::User(id) => match_identifier(user, id),
-        UserSpecifier::Group(Identifier::Name(name)) => user.in_group_by_name(name.as_cstr()),
-        UserSpecifier::Group(Identifier::ID(num)) => user.in_group_by_gid(GroupId::new(*num)),
-        // nonunix-groups, netgroups, etc. are not implemented
-        UserSpecifier::NonunixGroup(group) => {
-            match group {
-                Identifier::Name(name) => auth_warn!("warning: non-unix group {name} was ignored"),
-                Identifier::ID(num) => auth_warn!("warning: non-unix group #{num} was ignored"),
-            }
+        UserSpecifier::User(id) => match_identifier(user, id).into(),
+        UserSpecifier::Group(Identifier::Name(name)) => {
+            user.in_group_by_name(name.as_cstr()).into()
+        }
+        UserSpecifier::Group(Identifier::ID(num)) => {
+            user.in_group_by_gid(GroupId::new(*num)).into()
+        }
+        UserSpecifier::NonunixGroup(Identifier::Name(name)) => {
+            match_nonunix_group(user.in_nonunix_group(name, nonunix_groups))
+        }
+        UserSpecifier::NonunixGroup(Identifier::ID(num)) => {
+            match_nonunix_group(user.in_nonunix_group(&format!("#{num}"), nonunix_groups))
+        }
+        UserSpecifier::Netgroup(netgroup) if netgroups => match_netgroup(user, netgroup),
+        UserSpecifier::Netgroup(_) => Matched::No,
+    }
+}
 
-            false
+/// Membership of non-unix groups is unknown if the helper program could not answer.
+fn match_nonunix_group(is_member: Option<bool>) -> Matched {
+    is_member.map_or(Matched::Unknown, Matched::from)
+}
+
+/// Membership of netgroups is unknown if the system does not support them; this is reported
+/// only once.
+fn match_netgroup(user: &impl UnixUser, netgroup: &SudoString) -> Matched {
+    match user.in_netgroup(netgroup.as_cstr()) {
+        Some(is_member) => is_member.into(),
+        None => {
+            static REPORTED: Once = Once::new();
+            REPORTED.call_once(|| {
+                auth_warn!(
+                    "warning: netgroups are not supported on this system, +{netgroup} only \
+                     matches where it is negated"
+                )
+            });
+
+            Matched::Unknown
         }
     }
 }
@@ -629,10 +866,16 @@ fn match_group_alias(group: &impl UnixGroup) -> impl Fn(&UserSpecifier) -> bool
     }
 }
 
-fn match_token<T: basic_parser::Token + std::ops::Deref<Target = String>>(
-    text: &str,
-) -> impl Fn(&T) -> bool + '_ {
-    move |token| token.as_str() == text
+/// A host name that contains a dot is compared with the full hostname; other names are only
+/// compared with the part of the hostname before its first dot.
+fn match_host(hostname: &system::Hostname) -> impl Fn(&Hostname) -> bool + '_ {
+    move |token| {
+        if token.contains('.') {
+            token.as_str() == &**hostname
+        } else {
+            token.as_str() == hostname.short()
+        }
+    }
 }
 
 fn match_command<'a>((cmd, args): (&'a Path, &'a [OsString])) -> impl Fn(&Command) -> bool + 'a {
@@ -640,32 +883,70 @@ fn match_command<'a>((cmd, args): (&'a Path, &'a [OsString])) -> impl Fn(&Comman
         require_literal_separator: true,
         ..glob::MatchOptions::new()
     };
-    move |(cmdpat, argpat)| {
-        cmdpat.matches_path_with(cmd, opts)
-            && match argpat {
-                Args::Prefix(vec) => args.starts_with(vec),
-                Args::Exact(vec) => args == vec.as_ref(),
-            }
+    move |(cmdpat, argpat)| cmdpat.matches_path_with(cmd, opts) && match_args(argpat, args)
+}
+
+fn match_args(argpat: &Args, args: &[OsString]) -> bool {
+    match argpat {
+        Args::Prefix(vec) => args.starts_with(vec),
+        Args::Exact(vec) => args == vec.as_ref(),
     }
 }
 
+/// Whether `cmdpat` is a command without wildcards that has the same file name as `cmd`, but
+/// a different path. Commands are compared by their path after the symbolic links in the
+/// directories leading up to them have been resolved (but not a symbolic link that is the
+/// command itself), so such a command does not match, even if both paths lead to the same
+/// file; but this may well surprise an administrator.
+fn differs_only_in_path(cmdpat: &SimpleCommand, cmd: &Path) -> bool {
+    let rule_path = Path::new(cmdpat.as_str());
+    let is_literal = !cmdpat.as_str().contains(['*', '?', '[']);
+
+    is_literal && rule_path != cmd && rule_path.file_name() == cmd.file_name()
+}
+
 /// Find all the aliases that a object is a member of; this requires [sanitize_alias_table] to have run first;
 /// I.e. this function should not be "pub".
-fn get_aliases<Predicate, T>(table: &VecOrd<Def<T>>, pred: &Predicate) -> FoundAliases
+fn get_aliases<Predicate, T, M: Into<Matched>>(
+    table: &VecOrd<Def<T>>,
+    pred: &Predicate,
+) -> FoundAliases
 where
-    Predicate: Fn(&T) -> bool,
+    Predicate: Fn(&T) -> M,
 {
     use std::iter::once;
     let all = Qualified::Allow(Meta::All);
 
+    let unknown = std::cell::Cell::new(false);
+    let pred = |item: &T| {
+        let matched = pred(item).into();
+        if matched == Matched::Unknown {
+            unknown.set(true);
+        }
+        matched
+    };
+
     let mut set = HashMap::new();
     for Def(id, list) in table.iter() {
+        // an alias that refers to an alias that could not be evaluated cannot be either
+        unknown.set(list.iter().any(|item| match item {
+            Qualified::Allow(Meta::Alias(name)) | Qualified::Forbid(Meta::Alias(name)) => {
+                set.get(name) == Some(&Matched::Unknown)
+            }
+            _ => false,
+        }));
+
         if find_item(list, &pred, &set).is_some() {
-            set.insert(id.clone(), true);
-        } else if find_item(once(&all).chain(list), &pred, &set).is_none() {
+            set.insert(id.clone(), Matched::Yes);
+        } else if find_item(once(&all).chain(list), &pred, &set).is_none() || unknown.get() {
             // the item wasn't found even if we prepend ALL to the list of definitions; that means
-            // it is explicitly excluded by the alias definition.
-            set.insert(id.clone(), false);
+            // it is explicitly excluded by the alias definition (unless that is not certain).
+            let found = if unknown.get() {
+                Matched::Unknown
+            } else {
+                Matched::No
+            };
+            set.insert(id.clone(), found);
         }
     }
 
@@ -782,6 +1063,7 @@ fn analyze(
                 source: Some(parent.to_owned()),
                 location: Some(span),
                 message,
+                unsupported_feature: false,
             });
         } else {
             let (res, next_state, kind) = match include_source {
@@ -808,6 +1090,7 @@ fn analyze(
                         source: Some(parent.to_owned()),
                         location: Some(span),
                         message,
+                        unsupported_feature: false,
                     })
                 }
             }
@@ -830,7 +1113,8 @@ fn analyze(
                         diagnostics.extend(get_ignored_tags(&permission).map(|span| Error {
                             source: Some(cur_path.to_owned()),
                             location: Some(span),
-                            message: "this tag is ignored by sudo-rs".to_string(),
+                            message: IGNORED_TAG.to_string(),
+                            unsupported_feature: true,
                         }));
                         cfg.rules.push(permission);
                     }
@@ -840,10 +1124,15 @@ fn analyze(
                     Sudo::Decl(RunasAlias(mut def)) => cfg.aliases.runas.1.append(&mut def),
                     Sudo::Decl(CmndAlias(mut def)) => cfg.aliases.cmnd.1.append(&mut def),
 
-                    Sudo::Decl(Defaults(params, scope)) => {
+                    Sudo::Decl(Defaults(params, scope, span)) => {
+                        let source = DefaultsSource {
+                            path: cur_path.to_owned(),
+                            location: span,
+                        };
+
                         if let ConfigScope::Command(specs) = scope {
-                            cfg.customisers.cmnd.push((
-                                specs
+                            cfg.customisers.cmnd.push(Customiser {
+                                scope: specs
                                     .into_iter()
                                     .map(|spec| {
                                         spec.map(|simple_command| {
@@ -852,9 +1141,14 @@ fn analyze(
                                     })
                                     .collect(),
                                 params,
-                            ));
+                                source,
+                            });
                         } else {
-                            cfg.customisers.non_cmnd.push((scope, params));
+                            cfg.customisers.non_cmnd.push(Customiser {
+                                scope,
+                                params,
+                                source,
+                            });
                         }
                     }
 
@@ -878,6 +1172,7 @@ fn analyze(
                                 message: format!(
                                     "cannot open socket {path}: path must be absolute"
                                 ),
+                                unsupported_feature: false,
                             });
                         } else {
                             include(
@@ -901,32 +1196,34 @@ fn analyze(
                                     "cannot open sudoers file {path}: \
                                      percent escape %h in includedir is unsupported"
                                 ),
+                                unsupported_feature: false,
                             });
                             continue;
                         }
 
-                        let path = resolve_relative(cur_path, path);
-                        let Ok(files) = std::fs::read_dir(&path) else {
+                        let dir = resolve_relative(cur_path, path);
+                        let Ok(files) = std::fs::read_dir(&dir) else {
                             diagnostics.push(Error {
                                 source: Some(cur_path.to_owned()),
                                 location: Some(span),
-                                message: format!("cannot open sudoers file {}", path.display()),
+                                message: format!("cannot open sudoers file {}", dir.display()),
+                                unsupported_feature: false,
                             });
                             continue;
                         };
-                        let mut safe_files = files
-                            .filter_map(|direntry| {
-                                let path = direntry.ok()?.path();
-                                let text = path.file_name()?.to_str()?;
-                                if text.ends_with('~') || text.contains('.') {
-                                    None
-                                } else {
-                                    Some(path)
-                                }
+                        let files = includedir_files(&dir, files, |path| {
+                            diagnostics.push(Error {
+                                source: Some(cur_path.to_owned()),
+                                location: Some(span),
+                                message: format!(
+                                    "ignoring {}: it links to a file outside of {}",
+                                    path.display(),
+                                    dir.display()
+                                ),
+                                unsupported_feature: false,
                             })
-                            .collect::<Vec<_>>();
-                        safe_files.sort();
-                        for file in safe_files {
+                        });
+                        for file in files {
                             include(
                                 cfg,
                                 cur_path,
@@ -944,6 +1241,13 @@ fn analyze(
                     source: Some(cur_path.to_owned()),
                     location: Some(pos),
                     message,
+                    unsupported_feature: false,
+                }),
+                Err(basic_parser::Status::Unsupported(pos, message)) => diagnostics.push(Error {
+                    source: Some(cur_path.to_owned()),
+                    location: Some(pos),
+                    message,
+                    unsupported_feature: true,
                 }),
                 Err(_) => panic!("internal parser error"),
             }
@@ -982,6 +1286,66 @@ fn analyze(
     (result, diagnostics)
 }
 
+/// Select the files in `dir` that `@includedir` reads, in the order in which they are read.
+/// Since later files override earlier ones, this order is spelled out exactly: names that end
+/// in '~' or contain a '.' are skipped, and the others are sorted by their bytes, regardless of
+/// the locale (so "10-b" comes before "9-a", and "Z" before "a"). A file that can be reached
+/// under more than one name is only read at its first position, and symbolic links that point
+/// outside of `dir` are passed to `outside_link` and skipped.
+fn includedir_files(
+    dir: &Path,
+    entries: std::fs::ReadDir,
+    mut outside_link: impl FnMut(&Path),
+) -> Vec<PathBuf> {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::MetadataExt;
+
+    let mut files = entries
+        .filter_map(|direntry| {
+            let direntry = direntry.ok()?;
+            let path = direntry.path();
+            let name = path.file_name()?.to_str()?;
+            if name.ends_with('~') || name.contains('.') {
+                None
+            } else if direntry.file_type().ok()?.is_symlink() && !links_into(&path, dir) {
+                outside_link(&path);
+                None
+            } else {
+                Some(path)
+            }
+        })
+        .collect::<Vec<_>>();
+    files.sort_by_cached_key(|path| path.file_name().unwrap_or_default().as_bytes().to_owned());
+
+    // a file that cannot be inspected is kept, so that opening it reports the problem
+    let mut seen = HashSet::new();
+    files.retain(|path| match std::fs::metadata(path) {
+        Ok(meta) => seen.insert((meta.dev(), meta.ino())),
+        Err(_) => true,
+    });
+
+    auth_debug!(
+        "reading {} in this order: {}",
+        dir.display(),
+        files
+            .iter()
+            .filter_map(|path| path.file_name()?.to_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+
+    files
+}
+
+/// Check whether the symbolic link `link` resolves to a file inside the directory `dir`;
+/// files in an includedir that link elsewhere are not included.
+fn links_into(link: &Path, dir: &Path) -> bool {
+    match (std::fs::canonicalize(link), std::fs::canonicalize(dir)) {
+        (Ok(target), Ok(dir)) => target.starts_with(dir),
+        _ => false,
+    }
+}
+
 /// Alias definition inin a Sudoers file can come in any order; and aliases can refer to other aliases, etc.
 /// It is much easier if they are presented in a "definitional order" (i.e. aliases that use other aliases occur later)
 /// At the same time, this is a good place to detect problems in the aliases, such as unknown aliases and cycles.
@@ -1007,29 +1371,46 @@ fn sanitize_alias_table<T>(table: &Vec<Def<T>>, diagnostics: &mut Vec<Error>) ->
                 source: None,
                 location: None,
                 message: text,
+                unsupported_feature: false,
             })
         }
 
-        fn visit(&mut self, pos: usize) {
+        /// Start visiting the alias at `pos`, unless that has already happened.
+        fn enter(&mut self, pos: usize, stack: &mut Vec<(usize, usize)>) {
             if self.seen.insert(pos) {
-                let Def(_, members) = &self.table[pos];
-                for elem in members {
-                    let Meta::Alias(name) = remqualify(elem) else {
-                        continue;
-                    };
-                    let Some(dependency) = self.table.iter().position(|Def(id, _)| id == name)
-                    else {
-                        self.complain(format!("undefined alias: '{name}'"));
-                        continue;
-                    };
-                    self.visit(dependency);
-                }
-                self.order.push(pos);
+                stack.push((pos, 0));
             } else if !self.order.contains(&pos) {
                 let Def(id, _) = &self.table[pos];
                 self.complain(format!("recursive alias: '{id}'"));
             }
         }
+
+        fn visit(&mut self, pos: usize) {
+            // the aliases that are being visited, with the index of the next member to look
+            // at; this is not done recursively, since a long enough chain of aliases would
+            // then overflow the stack
+            let mut stack = Vec::new();
+            self.enter(pos, &mut stack);
+
+            while let Some((pos, next)) = stack.last_mut() {
+                let Def(_, members) = &self.table[*pos];
+                let Some(elem) = members.get(*next) else {
+                    self.order.push(*pos);
+                    stack.pop();
+                    continue;
+                };
+                *next += 1;
+
+                let Meta::Alias(name) = remqualify(elem) else {
+                    continue;
+                };
+                let Some(dependency) = self.table.iter().position(|Def(id, _)| id == name) else {
+                    self.complain(format!("undefined alias: '{name}'"));
+                    continue;
+                };
+                self.enter(dependency, &mut stack);
+            }
+        }
     }
 
     let mut visitor = Visitor {
@@ -6,13 +6,13 @@
 
 use super::{Judgement, Sudoers};
 use crate::common::{
-    HARDENED_ENUM_VALUE_0, HARDENED_ENUM_VALUE_1, HARDENED_ENUM_VALUE_2, SudoPath,
+    HARDENED_ENUM_VALUE_0, HARDENED_ENUM_VALUE_1, HARDENED_ENUM_VALUE_2, SudoPath, SudoersDuration,
 };
 use crate::exec::Umask;
-use crate::sudoers::ast::{ExecControl, Tag};
+use crate::log::{LogBackend, LogFile};
+use crate::sudoers::ast::{Authenticate, ExecControl, FollowControl, Tag};
 use crate::system::{Hostname, User};
 use std::collections::HashSet;
-use std::time::Duration;
 use std::{ffi::OsString, path::PathBuf};
 
 #[must_use]
@@ -29,31 +29,64 @@ pub struct Authentication {
     pub must_authenticate: bool,
     pub credential: AuthenticatingUser,
     pub allowed_attempts: u16,
-    pub prior_validity: Duration,
+    pub prior_validity: SudoersDuration,
     pub pwfeedback: bool,
-    pub password_timeout: Option<Duration>,
+    pub password_timeout: SudoersDuration,
+    pub module_timeout: SudoersDuration,
     pub noninteractive_auth: bool,
+    pub pam_acct_mgmt: bool,
 }
 
 impl super::Settings {
+    /// The secure_path that replaces the PATH of the invoking user; setting it to the empty
+    /// string is the same as not setting it at all.
+    fn search_path(&self) -> Option<&str> {
+        self.secure_path().filter(|path| !path.is_empty())
+    }
+
+    /// Whether a command with this tag requires authentication at all; an explicit PASSWD or
+    /// NOPASSWD tag takes precedence over the `authenticate` setting.
+    pub(super) fn needs_passwd(&self, tag: &Tag) -> bool {
+        match tag.authenticate {
+            Authenticate::None => self.authenticate(),
+            Authenticate::Passwd => true,
+            Authenticate::Nopasswd => false,
+        }
+    }
+
+    /// Whose password is asked for, if authentication is required; this is independent of
+    /// whether authentication is required.
+    pub(super) fn authenticating_user(&self) -> AuthenticatingUser {
+        if self.rootpw() {
+            AuthenticatingUser::Root
+        } else if self.runaspw() {
+            // the default target user is always root
+            AuthenticatingUser::Root
+        } else if self.targetpw() {
+            AuthenticatingUser::TargetUser
+        } else {
+            AuthenticatingUser::InvokingUser
+        }
+    }
+
     pub(super) fn to_auth(&self, tag: &Tag) -> Authentication {
         Authentication {
-            must_authenticate: tag.needs_passwd(),
+            must_authenticate: self.needs_passwd(tag),
             allowed_attempts: self.passwd_tries().try_into().unwrap(),
-            prior_validity: Duration::from_secs(self.timestamp_timeout()),
+            prior_validity: self.timestamp_timeout(),
             pwfeedback: self.pwfeedback(),
+            // a passwd_timeout of zero means that there is no time limit
             password_timeout: match self.passwd_timeout() {
-                0 => None,
-                timeout => Some(Duration::from_secs(timeout)),
+                timeout if timeout.is_zero() => SudoersDuration::infinite(),
+                timeout => timeout,
             },
-            noninteractive_auth: self.noninteractive_auth(),
-            credential: if self.rootpw() {
-                AuthenticatingUser::Root
-            } else if self.targetpw() {
-                AuthenticatingUser::TargetUser
-            } else {
-                AuthenticatingUser::InvokingUser
+            module_timeout: match self.pam_auth_timeout() {
+                timeout if timeout.is_zero() => SudoersDuration::infinite(),
+                timeout => timeout,
             },
+            noninteractive_auth: self.noninteractive_auth(),
+            pam_acct_mgmt: self.pam_acct_mgmt(),
+            credential: self.authenticating_user(),
         }
     }
 }
@@ -63,16 +96,50 @@ impl super::Settings {
 pub struct Restrictions<'a> {
     pub use_pty: bool,
     pub trust_environment: bool,
+    pub set_logname: bool,
     pub noexec: bool,
     pub env_keep: &'a HashSet<String>,
     pub env_check: &'a HashSet<String>,
+    pub env_max: usize,
     pub chdir: DirChange,
+    pub chroot: DirChange,
     pub path: Option<&'a str>,
     pub umask: Umask,
+    /// How long the command may run before it is terminated, if there is a limit.
+    pub command_timeout: Option<SudoersDuration>,
+    pub user_command_timeouts: bool,
     #[cfg(feature = "apparmor")]
     pub apparmor_profile: Option<String>,
 }
 
+#[cfg(test)]
+impl<'a> Restrictions<'a> {
+    /// The restrictions of a rule without tags or settings, keeping and checking the given
+    /// variables; tests change the fields they are about using struct update syntax.
+    pub(crate) fn with_env_lists(
+        env_keep: &'a HashSet<String>,
+        env_check: &'a HashSet<String>,
+    ) -> Self {
+        Restrictions {
+            use_pty: true,
+            trust_environment: false,
+            set_logname: true,
+            noexec: false,
+            env_keep,
+            env_check,
+            env_max: usize::MAX,
+            chdir: DirChange::Strict(None),
+            chroot: DirChange::Strict(None),
+            path: None,
+            umask: Umask::Preserve,
+            command_timeout: None,
+            user_command_timeouts: false,
+            #[cfg(feature = "apparmor")]
+            apparmor_profile: None,
+        }
+    }
+}
+
 #[must_use]
 #[cfg_attr(test, derive(Debug, PartialEq))]
 #[repr(u32)]
@@ -90,6 +157,32 @@ pub enum AuthenticatingUser {
 }
 
 impl Judgement {
+    /// The log file that commands should be recorded in (if any), and how entries are formatted.
+    pub fn logfile(&self) -> Option<LogFile> {
+        Some(LogFile {
+            path: self.settings.logfile()?.into(),
+            log_year: self.settings.log_year(),
+            log_host: self.settings.log_host(),
+            line_len: self.settings.loglinelen().try_into().unwrap_or(usize::MAX),
+            locale: self.settings.sudoers_locale().to_string(),
+        })
+    }
+
+    /// Where commands that are run or denied are recorded, besides the log file.
+    pub fn log_backend(&self) -> LogBackend {
+        use crate::defaults::enums::log_backend;
+        match self.settings.log_backend() {
+            log_backend::syslog => LogBackend::Syslog,
+            log_backend::journald => LogBackend::Journald,
+            log_backend::file => LogBackend::File,
+        }
+    }
+
+    /// Whether messages sent to syslog include the process ID of sudo.
+    pub fn syslog_pid(&self) -> bool {
+        self.settings.syslog_pid()
+    }
+
     pub fn authorization(&self) -> Authorization<Restrictions<'_>> {
         // NOTE: we should add conditional compilation to the DSL; this avoids getting
         // an unused warning message
@@ -106,6 +199,7 @@ impl Judgement {
                         super::EnvironmentControl::Setenv => true,
                         super::EnvironmentControl::Nosetenv => false,
                     },
+                    set_logname: self.settings.set_logname(),
                     noexec: match tag.noexec {
                         ExecControl::Implicit => self.settings.noexec(),
                         ExecControl::Exec => false,
@@ -113,6 +207,7 @@ impl Judgement {
                     },
                     env_keep: self.settings.env_keep(),
                     env_check: self.settings.env_check(),
+                    env_max: self.settings.env_max().try_into().unwrap_or(usize::MAX),
                     chdir: match tag.cwd.clone().or_else(|| {
                         // a `runcwd` default acts as the working directory when no explicit CWD was set
                         self.settings
@@ -123,7 +218,17 @@ impl Judgement {
                         Some(super::ChDir::Any) => DirChange::Any,
                         Some(super::ChDir::Path(path)) => DirChange::Strict(Some(path)),
                     },
-                    path: self.settings.secure_path(),
+                    chroot: match tag.chroot.clone().or_else(|| {
+                        // likewise, a `runchroot` default acts as the root directory
+                        self.settings
+                            .runchroot()
+                            .and_then(|s| super::basic_parser::Token::construct(s.to_string()).ok())
+                    }) {
+                        None => DirChange::Strict(None),
+                        Some(super::ChDir::Any) => DirChange::Any,
+                        Some(super::ChDir::Path(path)) => DirChange::Strict(Some(path)),
+                    },
+                    path: self.settings.search_path(),
                     umask: {
                         let mask = self
                             .settings
@@ -138,6 +243,10 @@ impl Judgement {
                             Umask::Extend(mask)
                         }
                     },
+                    // a command_timeout of zero means that there is no time limit
+                    command_timeout: Some(self.settings.command_timeout())
+                        .filter(|timeout| !timeout.is_zero()),
+                    user_command_timeouts: self.settings.user_command_timeouts(),
                     #[cfg(feature = "apparmor")]
                     apparmor_profile: tag
                         .apparmor_profile
@@ -151,6 +260,45 @@ impl Judgement {
         }
     }
 
+    /// Whether sudoedit may open files that are symbolic links: this is controlled by the
+    /// FOLLOW and NOFOLLOW tags, or else by the `sudoedit_follow` setting.
+    pub(crate) fn sudoedit_follow(&self) -> bool {
+        match self
+            .flags
+            .as_ref()
+            .map(|tag| tag.follow)
+            .unwrap_or_default()
+        {
+            FollowControl::Implicit => self.settings.sudoedit_follow(),
+            FollowControl::Follow => true,
+            FollowControl::Nofollow => false,
+        }
+    }
+
+    /// The file that `Defaults admin_flag` asks to create once a member of one of the
+    /// `admin_flag_groups` has used sudo, if any.
+    pub(crate) fn admin_flag(&self) -> Option<(&str, &HashSet<String>)> {
+        Some((
+            self.settings.admin_flag()?,
+            self.settings.admin_flag_groups(),
+        ))
+    }
+
+    /// Whether a PAM session should be opened for the command.
+    pub(crate) fn pam_session(&self) -> bool {
+        self.settings.pam_session()
+    }
+
+    /// Whether PAM should establish credentials for the target user.
+    pub(crate) fn pam_setcred(&self) -> bool {
+        self.settings.pam_setcred()
+    }
+
+    /// Whether the command was tagged with INTERCEPT, which sudo-rs does not support.
+    pub(crate) fn intercept(&self) -> bool {
+        self.flags.as_ref().is_some_and(|tag| tag.intercept)
+    }
+
     pub(crate) fn preferred_editor(&self) -> (PathBuf, Vec<OsString>) {
         // if no editor could be selected, fall back to /bin/vi;
         // note that /bin/vi is also likely to have been tried as part of
@@ -168,17 +316,51 @@ impl Sudoers {
         target_user: &User,
     ) -> Option<&str> {
         self.specify_host_user_runas(on_host, current_user, Some(target_user));
-        self.settings.secure_path()
+        self.settings.search_path()
+    }
+
+    /// Whether `Defaults fqdn` is in effect; like the other settings that are consulted before
+    /// the fully qualified hostname is known, this is determined using the plain hostname.
+    pub fn fqdn(
+        &mut self,
+        on_host: &Hostname,
+        current_user: &User,
+        target_user: Option<&User>,
+    ) -> bool {
+        self.specify_host_user_runas(on_host, current_user, target_user);
+        self.settings.fqdn()
+    }
+
+    /// Whether `Defaults shell_noargs` allows running sudo without a command, as if `--shell`
+    /// had been given.
+    pub fn shell_noargs(
+        &mut self,
+        on_host: &Hostname,
+        current_user: &User,
+        target_user: &User,
+    ) -> bool {
+        self.specify_host_user_runas(on_host, current_user, Some(target_user));
+        self.settings.shell_noargs()
+    }
+
+    pub fn default_chroot(
+        &mut self,
+        on_host: &Hostname,
+        current_user: &User,
+        target_user: &User,
+    ) -> Option<SudoPath> {
+        self.specify_host_user_runas(on_host, current_user, Some(target_user));
+        match super::basic_parser::Token::construct(self.settings.runchroot()?.to_string()) {
+            Ok(super::ChDir::Path(path)) => Some(path),
+            Ok(super::ChDir::Any) | Err(_) => None,
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::sudoers::{
-        ast::{Authenticate, Tag},
-        tokens::ChDir,
-    };
+    use crate::sudoers::{ast::Tag, tokens::ChDir};
 
     impl Judgement {
         fn mod_flag(&mut self, mut modify: impl FnMut(&mut Tag)) {
@@ -201,11 +383,13 @@ mod test {
             Authentication {
                 must_authenticate: true,
                 allowed_attempts: 3,
-                prior_validity: Duration::from_secs(15 * 60),
+                prior_validity: SudoersDuration::from_secs(15 * 60),
                 credential: AuthenticatingUser::InvokingUser,
                 pwfeedback: true,
                 noninteractive_auth: false,
-                password_timeout: Some(Duration::from_secs(300)),
+                pam_acct_mgmt: true,
+                password_timeout: SudoersDuration::from_secs(300),
+                module_timeout: SudoersDuration::from_secs(300),
             },
         );
 
@@ -219,11 +403,13 @@ mod test {
             Authentication {
                 must_authenticate: false,
                 allowed_attempts: 3,
-                prior_validity: Duration::from_secs(15 * 60),
+                prior_validity: SudoersDuration::from_secs(15 * 60),
                 credential: AuthenticatingUser::InvokingUser,
                 pwfeedback: true,
                 noninteractive_auth: false,
-                password_timeout: Some(Duration::from_secs(300)),
+                pam_acct_mgmt: true,
+                password_timeout: SudoersDuration::from_secs(300),
+                module_timeout: SudoersDuration::from_secs(300),
             },
         );
         assert_eq!(restrictions, restrictions2);
@@ -249,4 +435,34 @@ mod test {
         judge.mod_flag(|tag| tag.cwd = Some(ChDir::Path("/bin".into())));
         assert_eq!(chdir(&mut judge), (DirChange::Strict(Some("/bin".into()))));
     }
+    #[test]
+    fn chroot_test() {
+        let mut judge = Judgement {
+            flags: Some(Tag::default()),
+            ..Default::default()
+        };
+        fn chroot(judge: &mut Judgement) -> DirChange {
+            let Authorization::Allowed(_, ctl) = judge.authorization() else {
+                panic!()
+            };
+            ctl.chroot
+        }
+        assert_eq!(chroot(&mut judge), DirChange::Strict(None));
+        let crate::defaults::SettingKind::Text(set) = crate::defaults::set("runchroot").unwrap()
+        else {
+            panic!()
+        };
+        set("/srv/jail").unwrap()(&mut judge.settings);
+        assert_eq!(
+            chroot(&mut judge),
+            DirChange::Strict(Some("/srv/jail".into()))
+        );
+        judge.mod_flag(|tag| tag.chroot = Some(ChDir::Any));
+        assert_eq!(chroot(&mut judge), DirChange::Any);
+        judge.mod_flag(|tag| tag.chroot = Some(ChDir::Path("/srv/other".into())));
+        assert_eq!(
+            chroot(&mut judge),
+            DirChange::Strict(Some("/srv/other".into()))
+        );
+    }
 }
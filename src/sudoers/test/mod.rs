@@ -3,7 +3,7 @@ use std::ffi::CStr;
 use super::ast;
 use super::char_stream::CharStream;
 use super::*;
-use basic_parser::{parse_eval, parse_lines, parse_string};
+use basic_parser::{Parse, parse_eval, parse_lines, parse_string};
 
 impl<T> Qualified<T> {
     pub fn as_allow(&self) -> Option<&T> {
@@ -87,6 +87,26 @@ impl UnixUser for Named {
         GroupId::new(dummy_cksum(self.0)) == gid
     }
 
+    // the only netgroup is "admins"; looking up "nis" behaves like a system without netgroups,
+    // and "forbidden" must never be looked up at all
+    fn in_netgroup(&self, netgroup: &CStr) -> Option<bool> {
+        match netgroup.to_str().unwrap() {
+            "admins" => Some(self.0 == "user" || self.0 == "admin"),
+            "nis" => None,
+            "forbidden" => panic!("netgroup {netgroup:?} should not have been looked up"),
+            _ => Some(false),
+        }
+    }
+
+    // "staff" (#1000) is the only non-unix group; membership of "sssd" cannot be determined
+    fn in_nonunix_group(&self, group: &str, _provider: &dyn GroupProvider) -> Option<bool> {
+        match group {
+            "staff" | "#1000" => Some(self.0 == "user" || self.0 == "admin"),
+            "sssd" => None,
+            _ => Some(false),
+        }
+    }
+
     fn is_root(&self) -> bool {
         self.0 == "root"
     }
@@ -136,6 +156,40 @@ fn try_parse_line(s: &str) -> Option<Sudo> {
         .ok()
 }
 
+/// Like `analyze(.., sudoer![..])`, but for lines that are only known at run time
+fn analyze_lines(lines: &[&str]) -> Sudoers {
+    let text = [lines.join("\n").as_str(), ""].join("\n");
+    let (sudoers, _) = analyze(
+        Path::new("/etc/fakesudoers"),
+        parse_lines(&mut CharStream::new(&text))
+            .into_iter()
+            .map(|x| Ok::<_, basic_parser::Status>(x.unwrap())),
+    );
+
+    sudoers
+}
+
+/// Judge a request of "user" on "host" to run `command` as root, which must be allowed, and
+/// pass the outcome to `inspect`
+fn inspect_allowed<T>(
+    sudoers: &mut Sudoers,
+    command: &str,
+    inspect: impl FnOnce(Authentication, Restrictions) -> T,
+) -> T {
+    let request = Request {
+        user: &Named("root"),
+        group: &Named("root"),
+        command: Path::new(command),
+        arguments: &[],
+    };
+    let judgement = sudoers.check(&Named("user"), &system::Hostname::fake("host"), request);
+    let Authorization::Allowed(auth, restrictions) = judgement.authorization() else {
+        panic!("user should be allowed to run {command}");
+    };
+
+    inspect(auth, restrictions)
+}
+
 #[test]
 fn ambiguous_spec() {
     assert!(parse_eval::<ast::Sudo>("marc, User_Alias ALL = ALL").is_spec());
@@ -150,19 +204,19 @@ fn permission_test() {
 
     macro_rules! FAIL {
         ([$($sudo:expr),*], $user:expr => $req:expr, $server:expr; $command:expr) => {
-            let (Sudoers { rules,aliases,settings, customisers }, _) = analyze(Path::new("/etc/fakesudoers"), sudoer![$($sudo),*]);
+            let (Sudoers { rules, aliases, settings, customisers, defaults_sources }, _) = analyze(Path::new("/etc/fakesudoers"), sudoer![$($sudo),*]);
             let cmdvec = $command.split_whitespace().map(OsString::from).collect::<Vec<_>>();
             let req = Request { user: $req.0, group: $req.1, command: &realpath(cmdvec[0].as_ref()), arguments: &cmdvec[1..].to_vec() };
-            assert_eq!(Sudoers { rules, aliases, settings, customisers }.check(&Named($user), &system::Hostname::fake($server), req).flags, None);
+            assert_eq!(Sudoers { rules, aliases, settings, customisers, defaults_sources }.check(&Named($user), &system::Hostname::fake($server), req).flags, None);
         }
     }
 
     macro_rules! pass {
         ([$($sudo:expr),*], $user:expr => $req:expr, $server:expr; $command:expr $(=> [$($key:ident : $val:expr),*])?) => {
-            let (Sudoers { rules,aliases,settings, customisers }, _) = analyze(Path::new("/etc/fakesudoers"), sudoer![$($sudo),*]);
+            let (Sudoers { rules, aliases, settings, customisers, defaults_sources }, _) = analyze(Path::new("/etc/fakesudoers"), sudoer![$($sudo),*]);
             let cmdvec = $command.split_whitespace().map(OsString::from).collect::<Vec<_>>();
             let req = Request { user: $req.0, group: $req.1, command: &realpath(cmdvec[0].as_ref()), arguments: &cmdvec[1..].to_vec() };
-            let result = Sudoers { rules, aliases, settings, customisers }.check(&Named($user), &system::Hostname::fake($server), req).flags;
+            let result = Sudoers { rules, aliases, settings, customisers, defaults_sources }.check(&Named($user), &system::Hostname::fake($server), req).flags;
             assert!(!result.is_none());
             $(
                 let result = result.unwrap();
@@ -193,12 +247,46 @@ fn permission_test() {
     pass!(["user ALL=(ALL:ALL) CWD=/usr/bin NOPASSWD: /bin/foo"], "user" => root(), "server"; "/bin/foo" => [authenticate: Authenticate::Nopasswd, cwd: Some(ChDir::Path("/usr/bin".into()))]);
     //note: original sudo does not allow the below
     pass!(["user ALL=(ALL:ALL) NOPASSWD: CWD=/usr/bin /bin/foo"], "user" => root(), "server"; "/bin/foo" => [authenticate: Authenticate::Nopasswd, cwd: Some(ChDir::Path("/usr/bin".into()))]);
+    pass!(["user ALL=(ALL:ALL) CHROOT=/srv/jail /bin/foo"], "user" => root(), "server"; "/bin/foo" => [chroot: Some(ChDir::Path("/srv/jail".into()))]);
+    pass!(["user ALL=(ALL:ALL) CHROOT=* CWD=/ /bin/foo"], "user" => root(), "server"; "/bin/foo" => [chroot: Some(ChDir::Any), cwd: Some(ChDir::Path("/".into()))]);
+    pass!(["user ALL=(ALL:ALL) CHROOT=/srv/jail /bin/foo, /bin/bar"], "user" => root(), "server"; "/bin/bar" => [chroot: Some(ChDir::Path("/srv/jail".into()))]);
 
     pass!(["user ALL=/bin/e##o"], "user" => root(), "vm"; "/bin/e");
     SYNTAX!(["ALL ALL=(ALL) /bin/\n/echo"]);
 
     pass!(["user server=(ALL:ALL) ALL"], "user" => root(), "server"; "/bin/hello");
     FAIL!(["user laptop=(ALL:ALL) ALL"], "user" => root(), "server"; "/bin/hello");
+    pass!(["user server=(ALL:ALL) ALL"], "user" => root(), "server.example.com"; "/bin/hello");
+    pass!(["user server.example.com=(ALL:ALL) ALL"], "user" => root(), "server.example.com"; "/bin/hello");
+    FAIL!(["user server.example.com=(ALL:ALL) ALL"], "user" => root(), "server"; "/bin/hello");
+    FAIL!(["user server.example.org=(ALL:ALL) ALL"], "user" => root(), "server.example.com"; "/bin/hello");
+
+    pass!(["+admins ALL=(ALL:ALL) ALL"], "user" => root(), "server"; "/bin/hello");
+    pass!(["+admins ALL=(ALL:ALL) ALL"], "admin" => root(), "server"; "/bin/hello");
+    FAIL!(["+admins ALL=(ALL:ALL) ALL"], "other" => root(), "server"; "/bin/hello");
+    FAIL!(["ALL,!+admins ALL=(ALL:ALL) ALL"], "user" => root(), "server"; "/bin/hello");
+    pass!(["ALL,!+admins ALL=(ALL:ALL) ALL"], "other" => root(), "server"; "/bin/hello");
+    pass!(["user ALL=(+admins) ALL"], "user" => request! { admin, admin }, "server"; "/bin/hello");
+    FAIL!(["user ALL=(+admins) ALL"], "user" => request! { other, other }, "server"; "/bin/hello");
+    FAIL!(["Defaults !use_netgroups", "+admins ALL=(ALL:ALL) ALL"], "user" => root(), "server"; "/bin/hello");
+    FAIL!(["Defaults !use_netgroups", "+forbidden ALL=(ALL:ALL) ALL"], "user" => root(), "server"; "/bin/hello");
+    FAIL!(["+nis ALL=(ALL:ALL) ALL"], "user" => root(), "server"; "/bin/hello");
+    FAIL!(["ALL,!+nis ALL=(ALL:ALL) ALL"], "user" => root(), "server"; "/bin/hello");
+    FAIL!(["User_Alias NIS = +nis", "ALL,!NIS ALL=(ALL:ALL) ALL"], "user" => root(), "server"; "/bin/hello");
+    FAIL!(["User_Alias NIS = ALL,!+nis", "NIS ALL=(ALL:ALL) ALL"], "user" => root(), "server"; "/bin/hello");
+    FAIL!(["User_Alias NIS = +nis", "User_Alias OTHERS = ALL,!NIS", "OTHERS ALL=(ALL:ALL) ALL"], "user" => root(), "server"; "/bin/hello");
+    pass!(["ALL,!+nis ALL=(ALL:ALL) /bin/foo", "user ALL=(ALL:ALL) ALL"], "user" => root(), "server"; "/bin/hello");
+    pass!(["Defaults !use_netgroups", "ALL,!+nis ALL=(ALL:ALL) ALL"], "user" => root(), "server"; "/bin/hello");
+
+    pass!(["%:staff ALL=(ALL:ALL) ALL"], "user" => root(), "server"; "/bin/hello");
+    pass!(["%:#1000 ALL=(ALL:ALL) ALL"], "user" => root(), "server"; "/bin/hello");
+    FAIL!(["%:staff ALL=(ALL:ALL) ALL"], "other" => root(), "server"; "/bin/hello");
+    pass!(["ALL,!%:staff ALL=(ALL:ALL) ALL"], "other" => root(), "server"; "/bin/hello");
+    FAIL!(["%:sssd ALL=(ALL:ALL) ALL"], "user" => root(), "server"; "/bin/hello");
+    FAIL!(["ALL,!%:sssd ALL=(ALL:ALL) ALL"], "user" => root(), "server"; "/bin/hello");
+    FAIL!(["User_Alias SSSD = %:sssd", "ALL,!SSSD ALL=(ALL:ALL) ALL"], "user" => root(), "server"; "/bin/hello");
+    pass!(["user ALL=(%:staff) ALL"], "user" => request! { admin, admin }, "server"; "/bin/hello");
+    FAIL!(["user ALL=(ALL,!%:sssd) ALL"], "user" => request! { admin, admin }, "server"; "/bin/hello");
 
     pass!(["user ALL=!/bin/hello", "user ALL=/bin/hello"], "user" => root(), "server"; "/bin/hello");
     FAIL!(["user ALL=/bin/hello", "user ALL=!/bin/hello"], "user" => root(), "server"; "/bin/hello");
@@ -280,6 +368,26 @@ fn permission_test() {
     FAIL!(["user ALL=(root) /bin/ls, (sudo) /bin/true"], "user" => request! { root }, "server"; "/bin/true");
     pass!(["user ALL=(root) NOPASSWD: /bin/ls, (sudo) /bin/ls, /bin/true"], "user" => request! { sudo }, "server"; "/bin/true");
 
+    // tests with negation in the runas spec
+    FAIL!(["user ALL=(ALL, !root) /bin/foo"], "user" => request! { root }, "server"; "/bin/foo");
+    pass!(["user ALL=(ALL, !root) /bin/foo"], "user" => request! { sudo }, "server"; "/bin/foo");
+    FAIL!(["user ALL=(ALL, !root:ALL) /bin/foo"], "user" => request! { root, wheel }, "server"; "/bin/foo");
+    pass!(["user ALL=(ALL, !root:ALL) /bin/foo"], "user" => request! { sudo, wheel }, "server"; "/bin/foo");
+    FAIL!(["user ALL=(!root) /bin/foo"], "user" => request! { root }, "server"; "/bin/foo");
+    FAIL!(["user ALL=(!root) /bin/foo"], "user" => request! { sudo }, "server"; "/bin/foo");
+    pass!(["user ALL=(ALL, !!root) /bin/foo"], "user" => request! { root }, "server"; "/bin/foo");
+    FAIL!(["user ALL=(ALL, !%wheel) /bin/foo"], "user" => request! { wheel }, "server"; "/bin/foo");
+    pass!(["user ALL=(ALL, !%wheel) /bin/foo"], "user" => request! { sudo }, "server"; "/bin/foo");
+    FAIL!(["user ALL=(ALL:ALL, !wheel) /bin/foo"], "user" => request! { user, wheel }, "server"; "/bin/foo");
+    pass!(["user ALL=(ALL:ALL, !wheel) /bin/foo"], "user" => request! { user, sudo }, "server"; "/bin/foo");
+    FAIL!(["Runas_Alias ADMINS=root, %wheel", "user ALL=(ALL, !ADMINS) /bin/foo"], "user" => request! { root }, "server"; "/bin/foo");
+    FAIL!(["Runas_Alias ADMINS=root, %wheel", "user ALL=(ALL, !ADMINS) /bin/foo"], "user" => request! { wheel }, "server"; "/bin/foo");
+    pass!(["Runas_Alias ADMINS=root, %wheel", "user ALL=(ALL, !ADMINS) /bin/foo"], "user" => request! { sudo }, "server"; "/bin/foo");
+    FAIL!(["Runas_Alias ADMINS=ALL, !sudo", "user ALL=(ADMINS) /bin/foo"], "user" => request! { sudo }, "server"; "/bin/foo");
+    pass!(["Runas_Alias ADMINS=ALL, !sudo", "user ALL=(ADMINS) /bin/foo"], "user" => request! { root }, "server"; "/bin/foo");
+    FAIL!(["Runas_Alias USERS=ALL, !root", "user ALL=(ALL, !root) /bin/foo, (USERS) /bin/bar"], "user" => request! { root }, "server"; "/bin/bar");
+    pass!(["user ALL=(ALL, !root) /bin/foo", "user ALL=(root) /bin/foo"], "user" => request! { root }, "server"; "/bin/foo");
+
     SYNTAX!(["User_Alias, marc ALL = ALL"]);
     SYNTAX!(["Host_Alias CHROOT = server"]);
     SYNTAX!(["User_Alias CWD = user1"]);
@@ -330,6 +438,13 @@ fn permission_test() {
     #[cfg(feature = "apparmor")]
     pass!(["ALL ALL=(ALL:ALL) APPARMOR_PROFILE=unconfined ALL"], "user" => root(), "server"; "/bin/bar" => [apparmor_profile: Some("unconfined".to_string())]);
 
+    // symbolic links in sudoedit, and intercepting commands
+    pass!(["ALL ALL=FOLLOW: /bin/foo"], "user" => root(), "server"; "/bin/foo" => [follow: ast::FollowControl::Follow]);
+    pass!(["ALL ALL=FOLLOW: NOFOLLOW: /bin/foo"], "user" => root(), "server"; "/bin/foo" => [follow: ast::FollowControl::Nofollow]);
+    pass!(["ALL ALL=/bin/foo"], "user" => root(), "server"; "/bin/foo" => [follow: ast::FollowControl::Implicit, intercept: false]);
+    pass!(["ALL ALL=INTERCEPT: /bin/foo, /bin/bar"], "user" => root(), "server"; "/bin/bar" => [intercept: true]);
+    pass!(["ALL ALL=INTERCEPT: /bin/foo, NOINTERCEPT: /bin/bar"], "user" => root(), "server"; "/bin/bar" => [intercept: false]);
+
     // list
     pass!(["ALL ALL=(ALL:ALL) /bin/ls, list"], "user" => root(), "server"; "list");
     FAIL!(["ALL ALL=(ALL:ALL) ALL, !list"], "user" => root(), "server"; "list");
@@ -419,6 +534,257 @@ fn default_set_test() {
     assert!(parse_string::<Sudo>("Defaults runcwd = *").is_ok());
     assert!(parse_string::<Sudo>("Defaults runcwd = /usr/local").is_ok());
     assert!(parse_string::<Sudo>("Defaults !runcwd").is_ok());
+
+    assert!(parse_string::<Sudo>("Defaults runchroot = *").is_ok());
+    assert!(parse_string::<Sudo>("Defaults runchroot = /srv/jail").is_ok());
+    assert!(parse_string::<Sudo>("Defaults !runchroot").is_ok());
+
+    // only secure_path can be set to an empty string
+    assert!(parse_string::<Sudo>("Defaults secure_path = \"\"").is_ok());
+    assert!(parse_string::<Sudo>("Defaults runcwd = \"\"").is_err());
+    assert!(parse_string::<Sudo>("Defaults editor = \"\"").is_err());
+    assert!(parse_string::<Sudo>("Defaults verifypw = \"\"").is_err());
+}
+
+#[test]
+fn verifypw_test() {
+    let must_authenticate = |verifypw: &str| {
+        let (mut sudoers, _) = analyze(
+            Path::new("/etc/fakesudoers"),
+            sudoer![
+                &format!("Defaults verifypw = {verifypw}"),
+                "user ALL=(ALL:ALL) NOPASSWD: /bin/foo",
+                "user ALL=(ALL:ALL) /bin/bar"
+            ],
+        );
+        let Authorization::Allowed(auth, ()) =
+            sudoers.check_validate_permission(&Named("user"), &system::Hostname::fake("host"))
+        else {
+            panic!("user should be allowed to validate");
+        };
+        auth.must_authenticate
+    };
+
+    assert!(must_authenticate("all"));
+    assert!(must_authenticate("always"));
+    assert!(!must_authenticate("any"));
+    assert!(!must_authenticate("never"));
+}
+
+#[test]
+fn timeout_test() {
+    let authentication = |defaults: &str| {
+        let (mut sudoers, _) = analyze(
+            Path::new("/etc/fakesudoers"),
+            sudoer![defaults, "user ALL=(ALL:ALL) ALL"],
+        );
+        let Authorization::Allowed(auth, ()) =
+            sudoers.check_validate_permission(&Named("user"), &system::Hostname::fake("host"))
+        else {
+            panic!("user should be allowed to validate");
+        };
+        (auth.prior_validity, auth.password_timeout)
+    };
+
+    let (prior_validity, password_timeout) =
+        authentication("Defaults timestamp_timeout=2.5, passwd_timeout=1");
+    assert_eq!(prior_validity, SudoersDuration::from_secs(150));
+    assert_eq!(password_timeout, SudoersDuration::from_secs(60));
+
+    let (prior_validity, password_timeout) =
+        authentication("Defaults timestamp_timeout=-1, passwd_timeout=0");
+    assert_eq!(prior_validity, SudoersDuration::infinite());
+    assert_eq!(password_timeout, SudoersDuration::infinite());
+
+    let (prior_validity, password_timeout) =
+        authentication("Defaults !timestamp_timeout, !passwd_timeout");
+    assert_eq!(prior_validity, SudoersDuration::zero());
+    assert_eq!(password_timeout, SudoersDuration::infinite());
+
+    assert!(parse_string::<Sudo>("Defaults timestamp_timeout = -").is_err());
+    assert!(parse_string::<Sudo>("Defaults timestamp_timeout = 1-").is_err());
+}
+
+#[test]
+fn authentication_identity_and_necessity_test() {
+    let authentication = |defaults: &[&str], rule: &str| {
+        let mut sudoers = analyze_lines(&[defaults, &[rule]].concat());
+        inspect_allowed(&mut sudoers, "/bin/true", |auth, _| {
+            (auth.credential, auth.must_authenticate)
+        })
+    };
+
+    let identities = [
+        ("Defaults targetpw", AuthenticatingUser::TargetUser),
+        ("Defaults rootpw", AuthenticatingUser::Root),
+        ("Defaults runaspw", AuthenticatingUser::Root),
+    ];
+    let necessities = [
+        ("", "user ALL=(ALL:ALL) NOPASSWD: ALL", false),
+        ("", "user ALL=(ALL:ALL) PASSWD: ALL", true),
+        ("Defaults !authenticate", "user ALL=(ALL:ALL) ALL", false),
+    ];
+
+    for (identity, credential) in identities {
+        for (defaults, rule, must_authenticate) in necessities {
+            let (user, must) = authentication(&[identity, defaults], rule);
+            assert_eq!(
+                (&user, must),
+                (&credential, must_authenticate),
+                "{identity} {defaults} {rule}"
+            );
+        }
+    }
+
+    // an explicit tag takes precedence over the authenticate setting
+    assert_eq!(
+        authentication(
+            &["Defaults !authenticate"],
+            "user ALL=(ALL:ALL) PASSWD: ALL"
+        ),
+        (AuthenticatingUser::InvokingUser, true)
+    );
+    assert_eq!(
+        authentication(&["Defaults rootpw, targetpw"], "user ALL=(ALL:ALL) ALL"),
+        (AuthenticatingUser::Root, true)
+    );
+}
+
+#[test]
+fn list_shows_whose_password_is_required() {
+    let entries = |defaults: &str| {
+        let (mut sudoers, _) = analyze(
+            Path::new("/etc/fakesudoers"),
+            sudoer![
+                defaults,
+                "user ALL=(ALL:ALL) /bin/foo, NOPASSWD: /bin/bar",
+                "user ALL=(ALL:ALL) NOPASSWD: /bin/baz"
+            ],
+        );
+        sudoers.specify_host_user_runas(&system::Hostname::fake("server"), &Named("user"), None);
+        sudoers
+            .matching_entries(&Named("user"), &system::Hostname::fake("server"))
+            .map(|entry| entry.to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        entries("Defaults !fqdn"),
+        [
+            "    (ALL : ALL) /bin/foo, NOPASSWD: /bin/bar",
+            "    (ALL : ALL) NOPASSWD: /bin/baz"
+        ]
+    );
+    assert_eq!(
+        entries("Defaults targetpw"),
+        [
+            "    (ALL : ALL) /bin/foo, NOPASSWD: /bin/bar  # password of the target user",
+            "    (ALL : ALL) NOPASSWD: /bin/baz"
+        ]
+    );
+    assert_eq!(
+        entries("Defaults rootpw"),
+        [
+            "    (ALL : ALL) /bin/foo, NOPASSWD: /bin/bar  # password of root",
+            "    (ALL : ALL) NOPASSWD: /bin/baz"
+        ]
+    );
+}
+
+#[test]
+fn secure_path_test() {
+    let secure_path = |lines: &[&str], command: &str| {
+        let mut sudoers = analyze_lines(&[lines, &["user ALL=(ALL:ALL) ALL"]].concat());
+        inspect_allowed(&mut sudoers, command, |_, restrictions| {
+            restrictions.path.map(str::to_string)
+        })
+    };
+
+    assert_eq!(secure_path(&[], "/bin/ls"), None);
+    assert_eq!(secure_path(&["Defaults secure_path=\"\""], "/bin/ls"), None);
+    assert_eq!(
+        secure_path(&["Defaults secure_path=/bin"], "/bin/ls"),
+        Some("/bin".to_string())
+    );
+
+    let command_specific = [
+        "Defaults secure_path=/bin",
+        "Defaults!/usr/bin/backup secure_path=\"/usr/local/backup/bin\"",
+    ];
+    assert_eq!(
+        secure_path(&command_specific, "/usr/bin/backup"),
+        Some("/usr/local/backup/bin".to_string())
+    );
+    assert_eq!(
+        secure_path(&command_specific, "/bin/ls"),
+        Some("/bin".to_string())
+    );
+
+    assert_eq!(secure_path(&command_specific[1..], "/bin/ls"), None);
+}
+
+#[test]
+fn command_defaults_only_apply_to_one_judgement() {
+    let mut sudoers = analyze_lines(&[
+        "Defaults secure_path=/bin",
+        "Defaults!/usr/bin/backup secure_path=/usr/local/backup/bin",
+        "user ALL=(ALL:ALL) ALL",
+    ]);
+
+    // the same policy is judged once more when a command is looked up again (e.g. in a chroot)
+    let mut secure_path = |command: &str| {
+        inspect_allowed(&mut sudoers, command, |_, restrictions| {
+            restrictions.path.map(str::to_string)
+        })
+    };
+
+    assert_eq!(
+        secure_path("/usr/bin/backup").as_deref(),
+        Some("/usr/local/backup/bin")
+    );
+    assert_eq!(secure_path("/bin/ls").as_deref(), Some("/bin"));
+    assert_eq!(
+        secure_path("/usr/bin/backup").as_deref(),
+        Some("/usr/local/backup/bin")
+    );
+}
+
+#[test]
+fn command_timeout_test() {
+    let command_timeout = |defaults: &str| {
+        let mut sudoers = analyze_lines(&[defaults, "user ALL=(ALL:ALL) ALL"]);
+        inspect_allowed(&mut sudoers, "/bin/sleep", |_, restrictions| {
+            (
+                restrictions.command_timeout,
+                restrictions.user_command_timeouts,
+            )
+        })
+    };
+
+    assert_eq!(command_timeout(""), (None, false));
+    assert_eq!(
+        command_timeout("Defaults command_timeout=90"),
+        (Some(SudoersDuration::from_secs(90)), false)
+    );
+    assert_eq!(
+        command_timeout("Defaults command_timeout=1h30m, user_command_timeouts"),
+        (Some(SudoersDuration::from_secs(90 * 60)), true)
+    );
+    assert_eq!(
+        command_timeout("Defaults command_timeout=0, user_command_timeouts"),
+        (None, true)
+    );
+    assert_eq!(
+        command_timeout("Defaults command_timeout=5m\nDefaults !command_timeout"),
+        (None, false)
+    );
+
+    assert!(parse_string::<Sudo>("Defaults command_timeout = 5x").is_err());
+    assert!(parse_string::<Sudo>("Defaults command_timeout = 1.5").is_err());
+    // units can only be used for a timeout
+    assert!(parse_string::<Sudo>("Defaults passwd_tries = 3s").is_err());
+    assert!(parse_string::<Sudo>("Defaults timestamp_timeout = 5m").is_err());
+    assert!(parse_string::<Sudo>("Defaults timestamp_timeout = 1.5").is_ok());
 }
 
 #[test]
@@ -564,6 +930,21 @@ fn gh676_percent_h_escape_unsupported() {
     );
 }
 
+#[test]
+fn unsupported_features_are_told_apart_from_mistakes() {
+    let text = "Defaults frobnicate\nuser ALL = NOMAIL: /bin/ls\nuser ALL = /bin/ls,\n\
+                user ALL = TIMEOUT=5m /bin/ls\n";
+    let (_, errs) = analyze(
+        Path::new("/etc/fakesudoers"),
+        parse_lines(&mut CharStream::new(text)),
+    );
+    let unsupported = errs
+        .iter()
+        .map(|err| (err.location.unwrap().start.0, err.unsupported_feature))
+        .collect::<Vec<_>>();
+    assert_eq!(unsupported, [(1, true), (2, true), (3, false), (4, true)]);
+}
+
 #[test]
 fn gh1295_escaped_equal_argument_ok() {
     assert!(try_parse_line("Cmd_Alias FOO_CMD = /bin/foo --bar=1").is_some());
@@ -740,6 +1121,209 @@ fn default_specific_test() {
     assert!(mod_sudoers.settings.use_pty());
 }
 
+#[test]
+fn netgroups_do_not_affect_other_rules() {
+    let (mut sudoers, errors) = analyze(
+        Path::new("/etc/fakesudoers"),
+        sudoer![
+            "User_Alias NETGROUPS = +nis, +admins",
+            "Defaults:+nis !use_pty",
+            "+nis ALL=(ALL:ALL) ALL",
+            "NETGROUPS server=(+nis) /bin/foo",
+            "user ALL=(root) /bin/bar",
+            "+nis,!+nis ALL=(ALL:ALL) /bin/baz"
+        ],
+    );
+    assert!(errors.is_empty());
+
+    sudoers.specify_host_user_runas(
+        &system::Hostname::fake("server"),
+        &Named("user"),
+        Some(&Named("root")),
+    );
+    assert!(sudoers.settings.use_pty());
+
+    let entries = sudoers
+        .matching_entries(&Named("user"), &system::Hostname::fake("server"))
+        .map(|entry| entry.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(entries, ["    (+nis) /bin/foo", "    (root) /bin/bar"]);
+
+    let request = Request {
+        user: &Named("root"),
+        group: &Named("root"),
+        command: Path::new("/bin/bar"),
+        arguments: &[],
+    };
+    let judgement = sudoers.check(&Named("user"), &system::Hostname::fake("server"), request);
+    assert!(judgement.flags.is_some());
+}
+
+#[test]
+fn literal_commands_of_entries() {
+    let (sudoers, errors) = analyze(
+        Path::new("/etc/fakesudoers"),
+        sudoer![
+            "Cmnd_Alias TOOLS = /usr/bin/git, /usr/bin/git-*, !/usr/bin/tig",
+            "user ALL=(root) /usr/bin/ls, /usr/sbin/*, TOOLS, /usr/bin/tig, /usr/bin/cat, !/usr/bin/cat",
+            "user ALL=(ALL) ALL",
+            "user ALL=(ferris) /bin/l[sn], /usr/bin/vi *, list, !TOOLS, /usr/bin/kill -HUP 1"
+        ],
+    );
+    assert!(errors.is_empty());
+
+    let commands = sudoers
+        .matching_entries(&Named("user"), &system::Hostname::fake("server"))
+        .map(|entry| {
+            entry
+                .literal_commands()
+                .into_iter()
+                .map(|(command, arguments)| {
+                    let arguments = arguments.iter().map(|arg| arg.to_str().unwrap());
+                    std::iter::once(command.as_str())
+                        .chain(arguments)
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        commands,
+        [
+            vec!["/usr/bin/ls", "/usr/bin/git", "/usr/bin/tig"],
+            vec![],
+            vec!["/usr/bin/vi", "/usr/bin/tig", "/usr/bin/kill -HUP 1"]
+        ]
+    );
+}
+
+#[test]
+fn use_netgroups_can_be_disabled_for_scoped_defaults() {
+    let (mut sudoers, _) = analyze(
+        Path::new("/etc/fakesudoers"),
+        sudoer![
+            "Defaults:+admins !use_pty",
+            "Defaults !use_netgroups",
+            "Defaults:+forbidden !env_editor"
+        ],
+    );
+
+    sudoers.specify_host_user_runas(&system::Hostname::fake("server"), &Named("user"), None);
+    assert!(!sudoers.settings.use_pty());
+    assert!(!sudoers.settings.use_netgroups());
+    assert!(sudoers.settings.env_editor());
+}
+
+#[test]
+fn defaults_are_applied_in_file_order_before_command_defaults() {
+    let (mut sudoers, _) = analyze(
+        Path::new("/etc/fakesudoers"),
+        sudoer![
+            "Defaults>root !use_pty, secure_path=/runas",
+            "Defaults:user secure_path=/user",
+            "Defaults use_pty",
+            "Defaults@server secure_path=/host",
+            "Defaults env_keep=FOO",
+            "Defaults>root env_keep+=BAR",
+            "Defaults:user env_keep+=BAZ",
+            "Defaults!/bin/foo env_keep-=FOO",
+            "Defaults env_keep+=QUUX"
+        ],
+    );
+
+    sudoers.specify_host_user_runas(
+        &system::Hostname::fake("server"),
+        &Named("user"),
+        Some(&Named("root")),
+    );
+    assert!(sudoers.settings.use_pty());
+    assert_eq!(sudoers.settings.secure_path(), Some("/host"));
+
+    sudoers.specify_command(Path::new("/bin/foo"), &[]);
+    let mut env_keep = sudoers.settings.env_keep().iter().collect::<Vec<_>>();
+    env_keep.sort();
+    assert_eq!(env_keep, ["BAR", "BAZ", "QUUX"]);
+}
+
+#[test]
+fn command_defaults_override_earlier_and_later_lines() {
+    let (mut sudoers, _) = analyze(
+        Path::new("/etc/fakesudoers"),
+        sudoer![
+            "Defaults!/bin/foo secure_path=/cmnd",
+            "Defaults:user secure_path=/user",
+            "Defaults>root passwd_tries=1",
+            "Defaults>ALL passwd_tries=2",
+            "Defaults!/bin/foo umask=077",
+            "Defaults!ALL umask=027",
+            "Defaults umask=022"
+        ],
+    );
+
+    sudoers.specify_host_user_runas(
+        &system::Hostname::fake("server"),
+        &Named("user"),
+        Some(&Named("root")),
+    );
+    assert_eq!(sudoers.settings.umask(), 0o022);
+    sudoers.specify_command(Path::new("/bin/foo"), &[]);
+    assert_eq!(sudoers.settings.secure_path(), Some("/cmnd"));
+    assert_eq!(sudoers.settings.passwd_tries(), 2);
+    assert_eq!(sudoers.settings.umask(), 0o027);
+}
+
+#[test]
+fn source_of_effective_defaults() {
+    let (mut sudoers, _) = analyze(
+        Path::new("/etc/fakesudoers"),
+        sudoer![
+            "Defaults secure_path=/generic, !env_editor",
+            "Defaults>root secure_path=/runas",
+            "Defaults:other env_editor",
+            "Defaults env_keep+=FOO"
+        ],
+    );
+
+    sudoers.specify_host_user_runas(
+        &system::Hostname::fake("server"),
+        &Named("user"),
+        Some(&Named("root")),
+    );
+
+    let line_of = |name| {
+        sudoers
+            .defaults_source(name)
+            .map(|source| (source.path.clone(), source.location.start.0))
+    };
+    let fakesudoers = |line| Some((PathBuf::from("/etc/fakesudoers"), line));
+
+    assert_eq!(sudoers.settings.secure_path(), Some("/runas"));
+    assert_eq!(line_of("secure_path"), fakesudoers(2));
+    assert_eq!(line_of("env_editor"), fakesudoers(1));
+    assert_eq!(line_of("env_keep"), fakesudoers(4));
+    assert_eq!(line_of("use_pty"), None);
+
+    let shown = sudoers
+        .matching_defaults()
+        .into_iter()
+        .map(|(entry, source)| (entry, source.location.start.0))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        shown,
+        [
+            ("!env_editor".to_string(), 1),
+            ("secure_path=/runas".to_string(), 2),
+            (
+                "env_keep=\"COLORS DISPLAY FOO HOSTNAME KRB5CCNAME LS_COLORS PATH PS1 PS2 \
+                 XAUTHORITY XAUTHORIZATION XDG_CURRENT_DESKTOP\""
+                    .to_string(),
+                4
+            ),
+        ]
+    );
+}
+
 #[test]
 fn useralias_underscore_regression() {
     let sudo = parse_line("FOO_BAR ALL=ALL");
@@ -874,3 +1458,192 @@ fn fuzz_topo_sort(siz: usize) {
 fn fuzz_topo_sort7() {
     fuzz_topo_sort(7)
 }
+
+#[test]
+fn long_alias_chain() {
+    const LENGTH: usize = 10_000;
+    let mut input = String::new();
+    for i in 0..LENGTH {
+        input.push_str(&format!("User_Alias U{i} = U{}\n", i + 1));
+    }
+    input.push_str(&format!(
+        "User_Alias U{LENGTH} = root\nU0 ALL=(ALL:ALL) ALL\n"
+    ));
+
+    let (sudoers, errors) = Sudoers::parse_without_includes(&input);
+    assert!(errors.is_empty());
+    assert_eq!(sudoers.aliases.user.0.len(), LENGTH + 1);
+}
+
+#[test]
+fn fuzz_corpus_and_artifacts() {
+    let fuzz_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz");
+    let corpus = fuzz_dir.join("corpus/parse_sudoers");
+    let artifacts = fuzz_dir.join("artifacts/parse_sudoers");
+
+    // the fuzz directory is not part of the published crate, so there may be nothing to replay
+    let Ok(corpus) = std::fs::read_dir(corpus) else {
+        return;
+    };
+    let mut inputs = corpus.collect::<Vec<_>>();
+    assert!(!inputs.is_empty());
+    // crashes found by the fuzzer are kept there once they have been fixed
+    if let Ok(entries) = std::fs::read_dir(artifacts) {
+        inputs.extend(entries);
+    }
+
+    for entry in inputs {
+        let path = entry.unwrap().path();
+        // the fuzz target only passes valid UTF-8 on to the parser, as does Sudoers::read
+        if let Ok(input) = String::from_utf8(std::fs::read(&path).unwrap()) {
+            let _ = Sudoers::parse_without_includes(&input);
+        }
+    }
+}
+
+#[test]
+fn includedir_files_are_read_in_byte_order() {
+    use std::os::unix::fs::symlink;
+
+    let base = std::env::temp_dir().join(format!("sudo-rs-includedir-{}", std::process::id()));
+    let dir = base.join("sudoers.d");
+    std::fs::create_dir_all(&dir).unwrap();
+
+    for (name, contents) in [
+        ("01-first", "Defaults passwd_tries = 1"),
+        ("10-ten", "Defaults passwd_tries = 10"),
+        ("9-nine", "Defaults passwd_tries = 9"),
+        ("Zulu", "Defaults secure_path = /zulu"),
+        ("alpha", "Defaults secure_path = /alpha"),
+        ("\u{fc}mlaut", "Defaults secure_path = /last"),
+        ("with.dot", "Defaults passwd_tries = 100"),
+        ("backup~", "Defaults passwd_tries = 100"),
+    ] {
+        std::fs::write(dir.join(name), format!("{contents}\n")).unwrap();
+    }
+    std::fs::write(base.join("outside"), "Defaults passwd_tries = 100\n").unwrap();
+    // the same file under another name, which would otherwise be read after "alpha"
+    symlink(dir.join("01-first"), dir.join("link-to-first")).unwrap();
+    symlink(base.join("outside"), dir.join("link-outside")).unwrap();
+
+    let mut outside = vec![];
+    let files = includedir_files(&dir, std::fs::read_dir(&dir).unwrap(), |path| {
+        outside.push(path.to_owned())
+    });
+
+    let names = files
+        .iter()
+        .map(|path| path.file_name().unwrap().to_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            "01-first",
+            "10-ten",
+            "9-nine",
+            "Zulu",
+            "alpha",
+            "\u{fc}mlaut"
+        ]
+    );
+    assert_eq!(outside, [dir.join("link-outside")]);
+
+    // later files override earlier ones
+    let input = files
+        .iter()
+        .map(|path| std::fs::read_to_string(path).unwrap())
+        .collect::<String>();
+    let (mut sudoers, errors) = analyze(
+        Path::new("/etc/sudoers"),
+        parse_lines(&mut CharStream::new(&input)),
+    );
+    assert!(errors.is_empty());
+    sudoers.specify_host_user_runas(
+        &system::Hostname::fake("host"),
+        &Named("user"),
+        Some(&Named("root")),
+    );
+    assert_eq!(sudoers.settings.passwd_tries(), 9);
+    assert_eq!(sudoers.settings.secure_path(), Some("/last"));
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn command_paths_are_compared_after_resolving_symlinked_directories() {
+    use std::os::unix::fs::symlink;
+
+    let base = std::env::temp_dir().join(format!("sudo-rs-command-paths-{}", std::process::id()));
+    std::fs::create_dir_all(base.join("real")).unwrap();
+    std::fs::create_dir_all(base.join("other")).unwrap();
+    std::fs::write(base.join("real/cmd"), "").unwrap();
+    symlink(base.join("real"), base.join("linkdir")).unwrap();
+    symlink(base.join("real/cmd"), base.join("real/link")).unwrap();
+    // the same file at a different path, like a bind mount would give
+    std::fs::hard_link(base.join("real/cmd"), base.join("other/cmd")).unwrap();
+
+    let allowed = |rule: &str, command: &str| {
+        let (mut sudoers, errors) = Sudoers::parse_without_includes(&format!(
+            "user ALL=(ALL:ALL) {}\n",
+            base.join(rule).display()
+        ));
+        assert!(errors.is_empty());
+        let command = crate::common::resolve::canonicalize(base.join(command)).unwrap();
+        let request = Request {
+            user: &Named("root"),
+            group: &Named("root"),
+            command: &command,
+            arguments: &[],
+        };
+
+        sudoers
+            .check(&Named("user"), &system::Hostname::fake("server"), request)
+            .flags
+            .is_some()
+    };
+
+    // symbolic links in the directories are resolved, on both sides
+    assert!(allowed("linkdir/cmd", "real/cmd"));
+    assert!(allowed("real/cmd", "linkdir/cmd"));
+    assert!(allowed("linkdir/cmd", "linkdir/cmd"));
+
+    // a symbolic link that is the command itself is not
+    assert!(!allowed("real/cmd", "real/link"));
+    assert!(!allowed("real/link", "real/cmd"));
+    assert!(allowed("real/link", "linkdir/link"));
+
+    // neither are other ways to reach the same file
+    assert!(!allowed("real/cmd", "other/cmd"));
+    assert!(allowed("real/", "linkdir/cmd"));
+
+    let pattern = |rule: &str| glob::Pattern::new(base.join(rule).to_str().unwrap()).unwrap();
+    let real_cmd = base.join("real/cmd");
+    assert!(differs_only_in_path(&pattern("other/cmd"), &real_cmd));
+    assert!(!differs_only_in_path(&pattern("real/cmd"), &real_cmd));
+    assert!(!differs_only_in_path(&pattern("real/link"), &real_cmd));
+    assert!(!differs_only_in_path(&pattern("*/cmd"), &real_cmd));
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn truncated_escapes() {
+    fn no_panic<T: Parse>(input: &str) {
+        let _ = parse_string::<T>(input);
+    }
+
+    for input in ["\\", "\\ ", "\\ \\ ", "\\\n", "x\\", "/bin/ls \\"] {
+        no_panic::<Command>(input);
+        no_panic::<SimpleCommand>(input);
+        no_panic::<Username>(input);
+        no_panic::<EnvVar>(input);
+        no_panic::<StringParameter>(input);
+        no_panic::<QuotedStringParameter>(input);
+        no_panic::<IncludePath>(input);
+        no_panic::<QuotedIncludePath>(input);
+        no_panic::<ChDir>(input);
+    }
+
+    // an escaped space is not a command
+    assert!(parse_string::<Command>("\\ ").is_err());
+}
@@ -68,6 +68,26 @@ impl Token for Numeric {
     fn accept(c: char) -> bool {
         c.is_ascii_hexdigit() || c == '.'
     }
+
+    // a negative duration means that it never expires
+    fn accept_1st(c: char) -> bool {
+        c == '-' || Self::accept(c)
+    }
+}
+
+/// The value of a timeout: a number of seconds, or numbers followed by units such as "1h30m"
+pub struct Timeout(pub String);
+
+impl Token for Timeout {
+    const MAX_LEN: usize = 18;
+
+    fn construct(s: String) -> Result<Self, String> {
+        Ok(Timeout(s))
+    }
+
+    fn accept(c: char) -> bool {
+        c.is_ascii_digit() || matches!(c.to_ascii_lowercase(), 'd' | 'h' | 'm' | 's')
+    }
 }
 
 /// A hostname consists of alphanumeric characters and ".", "-",  "_"
@@ -188,9 +208,11 @@ impl Token for Command {
     const MAX_LEN: usize = 1024;
 
     fn construct(s: String) -> Result<Self, String> {
-        // the tokenizer should not give us a token that consists of only whitespace
+        // a token can consist of only whitespace if that whitespace was escaped
         let mut cmd_iter = s.split_whitespace();
-        let cmd = cmd_iter.next().unwrap().to_string();
+        let Some(cmd) = cmd_iter.next().map(str::to_string) else {
+            return Err("empty command".to_string());
+        };
         let mut args = cmd_iter.map(OsString::from).collect::<Vec<OsString>>();
 
         let command = SimpleCommand::construct(cmd)?;
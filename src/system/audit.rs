@@ -1,17 +1,17 @@
 #![cfg_attr(not(feature = "unstable-remote-sudoers"), allow(unused_imports))]
 use std::collections::HashSet;
-use std::ffi::{CStr, CString, c_int, c_uint};
+use std::ffi::{CStr, CString, OsStr, c_int, c_uint};
 use std::fs::{self, DirBuilder, File, Metadata, OpenOptions};
 use std::io::{self, BufReader, Error, ErrorKind};
 use std::net::Shutdown;
 use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
 use std::os::unix::{
-    ffi::OsStrExt,
+    ffi::{OsStrExt, OsStringExt},
     fs::{DirBuilderExt, MetadataExt, PermissionsExt},
     net::UnixStream,
     prelude::OpenOptionsExt,
 };
-use std::path::{Component, Path};
+use std::path::{Component, Path, PathBuf};
 
 use super::{
     Group, GroupId, User, UserId, cerr, inject_group, interface::UnixUser, set_supplementary_groups,
@@ -137,7 +137,7 @@ pub fn secure_open_sudoers(path: impl AsRef<Path>) -> io::Result<File> {
     let mut open_options = OpenOptions::new();
     open_options.read(true);
 
-    secure_open_impl(path.as_ref(), &mut open_options, false)
+    secure_open_impl(path.as_ref(), &mut open_options)
 }
 
 #[cfg(feature = "unstable-remote-sudoers")]
@@ -147,15 +147,113 @@ pub fn secure_open_remote_sudoers(path: impl AsRef<Path>) -> io::Result<BufReade
 
 /// Open a timestamp cookie file using various security checks
 pub fn secure_open_cookie_file(path: impl AsRef<Path>) -> io::Result<File> {
-    let mut open_options = OpenOptions::new();
-    open_options
-        .read(true)
-        .write(true)
-        .create(true)
-        .truncate(false)
-        .mode(mode(Category::Owner, Op::Write) | mode(Category::Owner, Op::Read));
-
-    secure_open_impl(path.as_ref(), &mut open_options, true)
+    let (dir, name) = split_parent(path.as_ref())?;
+    SecureDir::open(dir, true)?.open_file(name, libc::O_RDWR | libc::O_CREAT)
+}
+
+/// Open the log file configured in sudoers for appending, using various security checks
+pub fn secure_open_log_file(path: impl AsRef<Path>) -> io::Result<File> {
+    let (dir, name) = split_parent(path.as_ref())?;
+    SecureDir::open(dir, false)?.open_file(name, libc::O_WRONLY | libc::O_APPEND | libc::O_CREAT)
+}
+
+fn split_parent(path: &Path) -> io::Result<(&Path, &OsStr)> {
+    match (path.parent(), path.file_name()) {
+        (Some(dir), Some(name)) => Ok((dir, name)),
+        _ => Err(Error::new(
+            ErrorKind::PermissionDenied,
+            xlat!(
+                "{path} has no valid parent directory",
+                path = path.display()
+            ),
+        )),
+    }
+}
+
+/// A directory in which sudo keeps files that it creates as root, such as session records
+/// or log files. The directory is opened once, refusing it if it is a symbolic link, and
+/// checked to be secure; after that, files in it are only accessed relative to the open
+/// directory and are never followed if they are a symbolic link. Replacing (a part of) the
+/// path by a symbolic link therefore cannot redirect root to a file elsewhere.
+pub(crate) struct SecureDir {
+    dir: File,
+    path: PathBuf,
+}
+
+impl SecureDir {
+    /// Open the directory `path`; if `create` is set, it (and its parents) are created first
+    /// if it does not exist yet.
+    pub(crate) fn open(path: impl AsRef<Path>, create: bool) -> io::Result<SecureDir> {
+        let path = path.as_ref();
+        if create && !path.exists() {
+            DirBuilder::new()
+                .recursive(true)
+                .mode(
+                    mode(Category::Owner, Op::Write)
+                        | mode(Category::Owner, Op::Read)
+                        | mode(Category::Owner, Op::Exec)
+                        | mode(Category::Group, Op::Exec)
+                        | mode(Category::World, Op::Exec),
+                )
+                .create(path)?;
+        }
+
+        let dir = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_DIRECTORY | libc::O_NOFOLLOW)
+            .open(path)?;
+        checks(path, dir.metadata()?)?;
+
+        Ok(SecureDir {
+            dir,
+            path: path.to_owned(),
+        })
+    }
+
+    /// Open the file `name` in this directory with the given open `flags`. If the file gets
+    /// created, only its owner (root) may read and write it.
+    pub(crate) fn open_file(&self, name: impl AsRef<OsStr>, flags: c_int) -> io::Result<File> {
+        let name = name.as_ref();
+        let c_name = Self::entry_name(name)?;
+        let mode = mode(Category::Owner, Op::Write) | mode(Category::Owner, Op::Read);
+
+        // SAFETY: by design, a correct CStr pointer is passed to openat; only if this call succeeds
+        // is the file descriptor it returns (which is then necessarily valid) passed to from_raw_fd
+        let file = unsafe {
+            let fd = cerr(libc::openat(
+                self.dir.as_raw_fd(),
+                c_name.as_ptr(),
+                flags | libc::O_NOFOLLOW | libc::O_CLOEXEC,
+                mode,
+            ))?;
+
+            File::from_raw_fd(fd)
+        };
+        checks(&self.path.join(name), file.metadata()?)?;
+
+        Ok(file)
+    }
+
+    /// Remove the file `name` from this directory; if it is a symbolic link, the link itself
+    /// is removed.
+    pub(crate) fn remove_file(&self, name: impl AsRef<OsStr>) -> io::Result<()> {
+        let c_name = Self::entry_name(name.as_ref())?;
+
+        // SAFETY: by design, a correct CStr pointer is passed to unlinkat
+        cerr(unsafe { libc::unlinkat(self.dir.as_raw_fd(), c_name.as_ptr(), 0) }).map(|_| ())
+    }
+
+    /// Only plain names of files directly inside the directory are accepted
+    fn entry_name(name: &OsStr) -> io::Result<CString> {
+        if name.is_empty() || name == "." || name == ".." || name.as_bytes().contains(&b'/') {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid file name {name:?}"),
+            ));
+        }
+
+        CString::new(name.as_bytes()).map_err(|err| Error::new(ErrorKind::InvalidInput, err))
+    }
 }
 
 /// Return the system zoneinfo path after validating that it is safe
@@ -197,30 +295,10 @@ fn checks(path: &Path, meta: Metadata) -> io::Result<()> {
 }
 
 // Open `path` with options `open_options`, provided that it is "secure".
-// "Secure" means that it passes the `checks` function above.
-// If `check_parent_dir` is set, also check that the parent directory is "secure" also.
-// If `create_parent_dirs` is set, create the path to the file if it does not already exist.
-fn secure_open_impl(
-    path: &Path,
-    open_options: &mut OpenOptions,
-    create_parent_dirs: bool,
-) -> io::Result<File> {
+// "Secure" means that it passes the `checks` function above, as does its parent directory.
+fn secure_open_impl(path: &Path, open_options: &mut OpenOptions) -> io::Result<File> {
     let error = |msg| Error::new(ErrorKind::PermissionDenied, msg);
     if let Some(parent_dir) = path.parent() {
-        // if we should create parent dirs and it does not yet exist, create it
-        if create_parent_dirs && !parent_dir.exists() {
-            DirBuilder::new()
-                .recursive(true)
-                .mode(
-                    mode(Category::Owner, Op::Write)
-                        | mode(Category::Owner, Op::Read)
-                        | mode(Category::Owner, Op::Exec)
-                        | mode(Category::Group, Op::Exec)
-                        | mode(Category::World, Op::Exec),
-                )
-                .create(parent_dir)?;
-        }
-
         let parent_meta = std::fs::metadata(parent_dir)?;
         checks(parent_dir, parent_meta)?;
     } else {
@@ -237,6 +315,19 @@ fn secure_open_impl(
     Ok(file)
 }
 
+/// Check that `path` (after following symbolic links) and the directory it is in are "secure",
+/// in the sense of the `checks` function above; for programs that sudo runs for the user.
+pub(crate) fn check_root_controlled(path: &Path) -> io::Result<()> {
+    // the directory that matters is the one the program is really in, not the one of a link
+    let path = fs::canonicalize(path)
+        .map_err(|error| Error::new(error.kind(), format!("{}: {error}", path.display())))?;
+    if let Some(parent_dir) = path.parent() {
+        checks(parent_dir, fs::metadata(parent_dir)?)?;
+    }
+
+    checks(&path, fs::metadata(&path)?)
+}
+
 // Open the socket at path, provided that it is "secure".
 // "Secure" means that it passes the `checks` function above.
 #[cfg(feature = "unstable-remote-sudoers")]
@@ -286,18 +377,41 @@ fn open_at(parent: BorrowedFd, file_name: &CStr, create: bool) -> io::Result<Own
     }
 }
 
+fn read_link_at(parent: BorrowedFd, file_name: &CStr) -> io::Result<PathBuf> {
+    let mut buf = vec![0u8; libc::PATH_MAX as usize];
+
+    // SAFETY: by design, a correct CStr pointer is passed to readlinkat, together with a buffer
+    // and its actual length
+    let len = cerr(unsafe {
+        libc::readlinkat(
+            parent.as_raw_fd(),
+            file_name.as_ptr(),
+            buf.as_mut_ptr().cast(),
+            buf.len(),
+        )
+    })?;
+
+    buf.truncate(len as usize);
+    Ok(PathBuf::from(std::ffi::OsString::from_vec(buf)))
+}
+
 fn faccess_at(parent: BorrowedFd, path: &CStr, mode: c_int, flags: c_int) -> io::Result<()> {
     // SAFETY: by design, a correct CStr pointer is passed to faccessat
     cerr(unsafe { libc::faccessat(parent.as_raw_fd(), path.as_ptr(), mode, flags) }).map(|_| ())
 }
 
+/// The number of symbolic links that sudoedit will follow when opening a file, if it may.
+const SUDOEDIT_MAX_SYMLINKS: u32 = 40;
+
 /// This opens a file for sudoedit, performing security checks (see below) and
-/// opening with reduced privileges.
+/// opening with reduced privileges. If the file is a symbolic link, it is only
+/// opened if `follow` is set.
 pub fn secure_open_for_sudoedit(
     path: impl AsRef<Path>,
     current_user: &CurrentUser,
     target_user: &User,
     target_group: &Group,
+    follow: bool,
 ) -> io::Result<File> {
     if current_user.is_root() {
         sudo_call(target_user, target_group, || {
@@ -309,19 +423,22 @@ pub fn secure_open_for_sudoedit(
                 .open(path)
         })?
     } else {
-        traversed_secure_open(path, current_user, target_user, target_group)
+        let follow_links = if follow { SUDOEDIT_MAX_SYMLINKS } else { 0 };
+        traversed_secure_open(path, current_user, target_user, target_group, follow_links)
     }
 }
 
 /// This opens a file making sure that
 /// - no directory leading up to the file is editable by the user
-/// - no components are a symbolic link
+/// - no components are a symbolic link, except that the file itself may be one if
+///   `follow_links` is not zero; its target is then opened in the same way
 fn traversed_secure_open(
     path: impl AsRef<Path>,
     #[cfg(not(test))] forbidden_user: &CurrentUser,
     #[cfg(test)] forbidden_user: &User,
     target_user: &User,
     target_group: &Group,
+    follow_links: u32,
 ) -> io::Result<File> {
     let path = path.as_ref();
 
@@ -401,10 +518,26 @@ fn traversed_secure_open(
         })??;
         user_cannot_write(&cur)?;
     }
-    sudo_call(target_user, target_group, || {
-        cur = open_at(cur.as_fd(), &CString::new(file_name.as_bytes())?, true)?.into();
-        io::Result::Ok(())
-    })??;
+    let file_name = CString::new(file_name.as_bytes())?;
+    match sudo_call(target_user, target_group, || {
+        open_at(cur.as_fd(), &file_name, true)
+    })? {
+        Err(error) if error.raw_os_error() == Some(libc::ELOOP) && follow_links > 0 => {
+            let link = sudo_call(target_user, target_group, || {
+                read_link_at(cur.as_fd(), &file_name)
+            })??;
+            let target = path.parent().unwrap_or(Path::new("/")).join(link);
+
+            return traversed_secure_open(
+                target,
+                forbidden_user,
+                target_user,
+                target_group,
+                follow_links - 1,
+            );
+        }
+        result => cur = result?.into(),
+    }
     user_cannot_write(&cur)?;
 
     Ok(cur)
@@ -443,6 +576,104 @@ mod test {
         assert!(secure_open_cookie_file("/etc/hosts").is_err());
     }
 
+    #[test]
+    fn secure_dir_refuses_symlinks() {
+        use crate::system::file::create_temporary_dir;
+        use std::os::unix::fs::symlink;
+
+        fn os_error<T>(result: io::Result<T>) -> Option<i32> {
+            result.err().and_then(|e| e.raw_os_error())
+        }
+
+        let tmp = create_temporary_dir().unwrap();
+        let dir = tmp.join("ts");
+        fs::create_dir(&dir).unwrap();
+        fs::write(tmp.join("target"), "").unwrap();
+        symlink("ts", tmp.join("link")).unwrap();
+        symlink("missing", tmp.join("dangling")).unwrap();
+
+        // a directory that is a symbolic link is not opened, nor created through it
+        for create in [false, true] {
+            assert!(matches!(
+                os_error(SecureDir::open(tmp.join("link"), create)),
+                Some(libc::ELOOP | libc::ENOTDIR)
+            ));
+            assert!(SecureDir::open(tmp.join("dangling"), create).is_err());
+        }
+        assert!(!tmp.join("missing").exists());
+
+        // only root can own a directory that is secure enough for the remaining checks
+        if tmp.metadata().unwrap().uid() != 0 {
+            fs::remove_dir_all(&tmp).unwrap();
+            return;
+        }
+
+        let secure = SecureDir::open(&dir, false).unwrap();
+
+        // a file that is a symbolic link is never opened, regardless of how
+        symlink("../target", dir.join("1000")).unwrap();
+        for flags in [
+            libc::O_RDONLY,
+            libc::O_RDWR | libc::O_CREAT,
+            libc::O_WRONLY | libc::O_APPEND | libc::O_CREAT,
+        ] {
+            assert_eq!(os_error(secure.open_file("1000", flags)), Some(libc::ELOOP));
+        }
+
+        // only names of files directly in the directory are accepted
+        for name in ["", ".", "..", "../target", "sub/1000"] {
+            let error = secure.open_file(name, libc::O_RDONLY).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        }
+
+        // removing a symbolic link leaves the file it points to alone
+        secure.remove_file("1000").unwrap();
+        assert!(fs::symlink_metadata(dir.join("1000")).is_err());
+        assert!(tmp.join("target").exists());
+
+        let file = secure
+            .open_file("1000", libc::O_RDWR | libc::O_CREAT)
+            .unwrap();
+        assert_eq!(file.metadata().unwrap().mode() & 0o777, 0o600);
+
+        // a directory that others can write to is not secure
+        fs::set_permissions(&dir, PermissionsExt::from_mode(0o777)).unwrap();
+        let error = SecureDir::open(&dir, false).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+
+        fs::remove_dir_all(&tmp).unwrap();
+    }
+
+    #[test]
+    fn root_controlled_programs_are_checked_where_links_lead() {
+        use crate::system::file::create_temporary_dir;
+        use std::os::unix::fs::symlink;
+
+        let tmp = create_temporary_dir().unwrap();
+        // only root can own a directory that is secure enough for these checks
+        if tmp.metadata().unwrap().uid() != 0 {
+            fs::remove_dir_all(&tmp).unwrap();
+            return;
+        }
+
+        let (secure, open) = (tmp.join("secure"), tmp.join("open"));
+        for (dir, mode) in [(&secure, 0o755), (&open, 0o777)] {
+            fs::create_dir(dir).unwrap();
+            fs::set_permissions(dir, PermissionsExt::from_mode(mode)).unwrap();
+            fs::write(dir.join("helper"), "").unwrap();
+            fs::set_permissions(dir.join("helper"), PermissionsExt::from_mode(0o755)).unwrap();
+        }
+        symlink(secure.join("helper"), open.join("link")).unwrap();
+        symlink(open.join("helper"), secure.join("link")).unwrap();
+
+        assert!(check_root_controlled(&secure.join("helper")).is_ok());
+        assert!(check_root_controlled(&open.join("link")).is_ok());
+        assert!(check_root_controlled(&open.join("helper")).is_err());
+        assert!(check_root_controlled(&secure.join("link")).is_err());
+
+        fs::remove_dir_all(&tmp).unwrap();
+    }
+
     #[test]
     fn test_traverse_secure_open_negative() {
         use crate::common::resolve::CurrentUser;
@@ -451,17 +682,19 @@ mod test {
         let user = CurrentUser::resolve().unwrap();
 
         // not allowed -- invalid
-        assert!(traversed_secure_open("/", &root, &user, &user.group()).is_err());
+        assert!(traversed_secure_open("/", &root, &user, &user.group(), 0).is_err());
         // not allowed since the path is not absolute
-        assert!(traversed_secure_open("./hello.txt", &root, &user, &user.group()).is_err());
+        assert!(traversed_secure_open("./hello.txt", &root, &user, &user.group(), 0).is_err());
         // not allowed since root can write to "/"
-        assert!(traversed_secure_open("/hello.txt", &root, &user, &user.group()).is_err());
+        assert!(traversed_secure_open("/hello.txt", &root, &user, &user.group(), 0).is_err());
         // not allowed since "/tmp" is a directory
-        assert!(traversed_secure_open("/tmp", &user, &user, &user.group()).is_err());
+        assert!(traversed_secure_open("/tmp", &user, &user, &user.group(), 0).is_err());
         // not allowed since anybody can write to "/tmp"
-        assert!(traversed_secure_open("/tmp/foo/hello.txt", &user, &user, &user.group()).is_err());
+        assert!(
+            traversed_secure_open("/tmp/foo/hello.txt", &user, &user, &user.group(), 0).is_err()
+        );
         // not allowed since "/bin" is a symlink
-        assert!(traversed_secure_open("/bin/hello.txt", &user, &user, &user.group()).is_err());
+        assert!(traversed_secure_open("/bin/hello.txt", &user, &user, &user.group(), 0).is_err());
     }
 
     #[test]
@@ -484,9 +717,45 @@ mod test {
         let path = std::env::current_dir()
             .unwrap()
             .join("sudo-rs-test-file.txt");
-        let file = traversed_secure_open(&path, &other_user, &user, &user.group()).unwrap();
+        let file = traversed_secure_open(&path, &other_user, &user, &user.group(), 0).unwrap();
         if file.metadata().is_ok_and(|meta| meta.len() == 0) {
             std::fs::remove_file(path).unwrap();
         }
     }
+
+    #[test]
+    fn test_traverse_secure_open_follow() {
+        use crate::common::resolve::CurrentUser;
+        use crate::system::{GroupId, UserId};
+
+        let user = CurrentUser::resolve().unwrap();
+        let other_user = CurrentUser::fake(User {
+            uid: UserId::new(1042),
+            gid: GroupId::new(1042),
+
+            name: "test".into(),
+            home: "/home/test".into(),
+            shell: "/bin/sh".into(),
+            groups: vec![],
+        });
+
+        let dir = std::env::current_dir().unwrap();
+        let target = dir.join("sudo-rs-test-target.txt");
+        let link = dir.join("sudo-rs-test-link.txt");
+        std::fs::write(&target, "").unwrap();
+        let _ = std::fs::remove_file(&link);
+        std::os::unix::fs::symlink("sudo-rs-test-target.txt", &link).unwrap();
+
+        let open = |follow_links| {
+            traversed_secure_open(&link, &other_user, &user, &user.group(), follow_links)
+        };
+        let refused = open(0);
+        let followed = open(1);
+
+        std::fs::remove_file(&link).unwrap();
+        std::fs::remove_file(&target).unwrap();
+
+        assert_eq!(refused.unwrap_err().raw_os_error(), Some(libc::ELOOP));
+        assert!(followed.unwrap().metadata().unwrap().is_file());
+    }
 }
@@ -0,0 +1,77 @@
+use std::{
+    ffi::{CStr, CString},
+    ptr,
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+/// Ask the resolver for the canonical name of `host`; this will typically involve a DNS query,
+/// and so may take arbitrarily long to complete.
+pub(crate) fn canonical_name(host: &str) -> Option<String> {
+    let host = CString::new(host).ok()?;
+
+    // SAFETY: addrinfo is a plain C struct for which all zeroes is a valid value
+    let mut hints: libc::addrinfo = unsafe { std::mem::zeroed() };
+    hints.ai_family = libc::AF_UNSPEC;
+    hints.ai_socktype = libc::SOCK_STREAM;
+    hints.ai_flags = libc::AI_CANONNAME;
+
+    let mut result = ptr::null_mut();
+    // SAFETY: a valid C string and valid pointers are passed to getaddrinfo
+    if unsafe { libc::getaddrinfo(host.as_ptr(), ptr::null(), &hints, &mut result) } != 0 {
+        return None;
+    }
+
+    // SAFETY: getaddrinfo succeeded, so `result` points to a valid addrinfo structure, whose
+    // ai_canonname (if set) is a C string; this is all copied before the structure is freed
+    let name = unsafe {
+        let canonical = (*result).ai_canonname;
+        let name = (!canonical.is_null())
+            .then(|| CStr::from_ptr(canonical).to_string_lossy().into_owned());
+        libc::freeaddrinfo(result);
+        name
+    };
+
+    name.filter(|name| !name.is_empty())
+}
+
+/// Run `job` on a separate thread, but stop waiting for it after `timeout` has elapsed. Since
+/// a blocking resolver call cannot be cancelled, the thread is left behind in that case.
+pub(crate) fn with_deadline<T: Send + 'static>(
+    timeout: Duration,
+    job: impl FnOnce() -> T + Send + 'static,
+) -> Option<T> {
+    let (sender, receiver) = mpsc::sync_channel(1);
+    thread::spawn(move || {
+        let _ = sender.send(job());
+    });
+
+    receiver.recv_timeout(timeout).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deadline() {
+        assert_eq!(with_deadline(Duration::from_secs(10), || 42), Some(42));
+
+        let never = with_deadline(Duration::from_millis(10), || {
+            thread::sleep(Duration::from_secs(10));
+        });
+        assert_eq!(never, None);
+    }
+
+    #[test]
+    #[ignore = "depends on the resolver configuration of the host"]
+    fn localhost_has_a_canonical_name() {
+        assert!(canonical_name("localhost").is_some());
+    }
+
+    #[test]
+    fn names_with_nul_bytes_are_not_looked_up() {
+        assert!(canonical_name("host\0name").is_none());
+    }
+}
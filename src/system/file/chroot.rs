@@ -0,0 +1,182 @@
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::os::fd::OwnedFd;
+use std::path::{Component, Path, PathBuf};
+
+/// The maximum number of symbolic links that are followed, mirroring Linux' own limit.
+const MAX_SYMLINKS: usize = 40;
+
+/// A file that was looked up inside a root directory.
+#[derive(Debug)]
+pub(crate) struct InRoot {
+    /// The path of the file as it will be seen by a process that has changed its root.
+    pub(crate) path: PathBuf,
+    /// The file itself (opened with `O_PATH`), if the kernel did the lookup. Only this refers
+    /// to the file that was found for certain: until the root is changed, anyone who can write
+    /// to a directory on the path can make it lead to another file.
+    pub(crate) file: Option<OwnedFd>,
+}
+
+/// Resolve all symbolic links in `path` as if `root` were the root directory, and return the
+/// resulting path as it will be seen by a process that has changed its root to `root`.
+///
+/// Neither `..` components nor absolute symbolic links can escape from `root`; relative paths
+/// are interpreted relative to `root` itself.
+pub(crate) fn resolve_in_root(root: &Path, path: &Path) -> io::Result<PathBuf> {
+    open_in_root(root, path).map(|found| found.path)
+}
+
+/// Like [resolve_in_root], but also keep the file that was found open if possible, so that it
+/// can be used without looking it up again.
+pub(crate) fn open_in_root(root: &Path, path: &Path) -> io::Result<InRoot> {
+    // let the kernel do the work if it can; the file it opens cannot be swapped for another one
+    #[cfg(target_os = "linux")]
+    if let Some(found) = openat2_in_root(root, path)? {
+        return Ok(found);
+    }
+
+    // this is racy: every component is looked up separately, and the file is not opened, so
+    // the result may no longer lead to the same file by the time it is used
+    Ok(InRoot {
+        path: walk_in_root(root, path)?,
+        file: None,
+    })
+}
+
+/// Resolve `path` inside `root` using `openat2(RESOLVE_IN_ROOT)`; returns `None` if the kernel
+/// (or /proc) does not support this, in which case the caller should fall back on [walk_in_root].
+#[cfg(target_os = "linux")]
+fn openat2_in_root(root: &Path, path: &Path) -> io::Result<Option<InRoot>> {
+    use std::ffi::CString;
+    use std::os::fd::{AsRawFd, FromRawFd};
+    use std::os::unix::{ffi::OsStrExt, fs::OpenOptionsExt};
+
+    use crate::system::ProcFs;
+
+    let root = fs::canonicalize(root)?;
+    let root_dir = fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_PATH | libc::O_DIRECTORY)
+        .open(&root)?;
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+
+    // SAFETY: open_how is a plain C struct for which all zeroes is a valid value
+    let mut how: libc::open_how = unsafe { std::mem::zeroed() };
+    how.flags = (libc::O_PATH | libc::O_CLOEXEC) as u64;
+    how.resolve = libc::RESOLVE_IN_ROOT | libc::RESOLVE_NO_MAGICLINKS;
+
+    // SAFETY: a valid directory descriptor, a correct CStr pointer and a pointer to a properly
+    // sized open_how struct are passed; only if this call succeeds is the resulting file
+    // descriptor (which is then necessarily valid) passed to from_raw_fd
+    let file = unsafe {
+        match crate::cutils::cerr(libc::syscall(
+            libc::SYS_openat2,
+            root_dir.as_raw_fd(),
+            c_path.as_ptr(),
+            &how as *const libc::open_how,
+            std::mem::size_of::<libc::open_how>(),
+        )) {
+            Ok(fd) => OwnedFd::from_raw_fd(fd as libc::c_int),
+            // not supported by this kernel, or blocked by a seccomp filter
+            Err(err) if matches!(err.raw_os_error(), Some(libc::ENOSYS | libc::EPERM)) => {
+                return Ok(None);
+            }
+            Err(err) => return Err(err),
+        }
+    };
+
+    // find out which file the kernel arrived at
+    let Ok(fd_link) = ProcFs::path(format!("self/fd/{}", file.as_raw_fd())) else {
+        return Ok(None);
+    };
+    let Ok(host_path) = fs::read_link(fd_link) else {
+        return Ok(None);
+    };
+
+    Ok(host_path.strip_prefix(&root).ok().map(|inner| InRoot {
+        path: Path::new("/").join(inner),
+        file: Some(file),
+    }))
+}
+
+/// Resolve `path` inside `root` by traversing it one component at a time.
+fn walk_in_root(root: &Path, path: &Path) -> io::Result<PathBuf> {
+    let mut resolved = PathBuf::from("/");
+    let mut pending = components_of(path);
+    let mut symlinks_followed = 0;
+
+    while let Some(component) = pending.pop() {
+        if component == ".." {
+            resolved.pop();
+            continue;
+        }
+
+        let candidate = resolved.join(&component);
+        let on_host = root.join(candidate.strip_prefix("/").unwrap_or(&candidate));
+
+        if fs::symlink_metadata(&on_host)?.file_type().is_symlink() {
+            symlinks_followed += 1;
+            if symlinks_followed > MAX_SYMLINKS {
+                return Err(io::Error::from_raw_os_error(libc::ELOOP));
+            }
+
+            let target = fs::read_link(&on_host)?;
+            if target.is_absolute() {
+                resolved = PathBuf::from("/");
+            }
+            pending.extend(components_of(&target));
+        } else {
+            resolved = candidate;
+        }
+    }
+
+    Ok(resolved)
+}
+
+/// The normal and parent directory components of a path, in reverse order.
+fn components_of(path: &Path) -> Vec<OsString> {
+    path.components()
+        .rev()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_owned()),
+            Component::ParentDir => Some("..".into()),
+            Component::RootDir | Component::CurDir | Component::Prefix(_) => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+    use std::path::{Path, PathBuf};
+
+    use super::{resolve_in_root, walk_in_root};
+    use crate::system::file::create_temporary_dir;
+
+    fn jail() -> PathBuf {
+        let root = create_temporary_dir().unwrap();
+        std::fs::create_dir_all(root.join("usr/bin")).unwrap();
+        std::fs::write(root.join("usr/bin/ls"), "").unwrap();
+        symlink("/usr/bin", root.join("bin")).unwrap();
+        symlink("../../../../bin/ls", root.join("usr/bin/dir")).unwrap();
+        symlink("loop", root.join("loop")).unwrap();
+        root
+    }
+
+    #[test]
+    fn resolution_stays_inside_root() {
+        let root = jail();
+        for resolve in [resolve_in_root, walk_in_root] {
+            let resolve = |path: &str| resolve(&root, Path::new(path));
+            assert_eq!(resolve("/bin/ls").unwrap(), Path::new("/usr/bin/ls"));
+            assert_eq!(resolve("/usr/bin/dir").unwrap(), Path::new("/usr/bin/ls"));
+            assert_eq!(resolve("/../../bin").unwrap(), Path::new("/usr/bin"));
+            assert_eq!(resolve("bin/./ls").unwrap(), Path::new("/usr/bin/ls"));
+            assert_eq!(resolve("/").unwrap(), Path::new("/"));
+            assert!(resolve("/bin/cat").is_err());
+            assert!(resolve("/loop").is_err());
+        }
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -1,5 +1,7 @@
+mod chroot;
 mod lock;
 mod tmpdir;
 
+pub(crate) use chroot::{InRoot, open_in_root, resolve_in_root};
 pub(crate) use lock::FileLock;
 pub(crate) use tmpdir::create_temporary_dir;
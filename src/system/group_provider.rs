@@ -0,0 +1,220 @@
+//! Membership of non-unix groups (written as `%:group` in the sudoers file). The system has no
+//! notion of these, so sudo-rs can only find out by asking a helper program, if one has been
+//! configured using a `Path nonunix_group_helper` line in sudo.conf.
+//!
+//! If the helper cannot give an answer, membership is unknown; such a group then never grants
+//! anything, but a negated one does take permissions away, so that sudo fails closed.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+    sync::{Mutex, Once, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::log::auth_warn;
+
+use super::paths;
+
+/// How long a helper program may take to answer a single question
+const HELPER_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub(crate) trait GroupProvider {
+    /// Whether `user` is a member of `group`, which is either a name or a `#`-prefixed ID;
+    /// `None` means that this cannot be determined.
+    fn user_in_group(&self, user: &str, group: &str) -> Option<bool>;
+}
+
+/// Uses the helper program configured in sudo.conf, which is only looked up once a non-unix
+/// group is actually encountered.
+pub(crate) struct ConfiguredGroupProvider;
+
+impl GroupProvider for ConfiguredGroupProvider {
+    fn user_in_group(&self, user: &str, group: &str) -> Option<bool> {
+        static HELPER: OnceLock<Option<HelperGroupProvider>> = OnceLock::new();
+
+        let helper = HELPER.get_or_init(|| match paths::nonunix_group_helper() {
+            Ok(helper) => helper.map(HelperGroupProvider::new),
+            Err(err) => {
+                auth_warn!("warning: cannot use the program for non-unix groups: {err}");
+                None
+            }
+        });
+
+        match helper {
+            Some(helper) => helper.user_in_group(user, group),
+            None => NoGroupProvider.user_in_group(user, group),
+        }
+    }
+}
+
+/// Used if no (usable) helper program has been configured: membership of non-unix groups
+/// cannot be determined.
+pub(crate) struct NoGroupProvider;
+
+impl GroupProvider for NoGroupProvider {
+    fn user_in_group(&self, _user: &str, group: &str) -> Option<bool> {
+        static REPORTED: Once = Once::new();
+        REPORTED.call_once(|| {
+            auth_warn!(
+                "warning: non-unix groups are not supported, %:{group} only matches where it is \
+                 negated"
+            )
+        });
+
+        None
+    }
+}
+
+/// Runs a helper program with the name of the user and the group as its arguments; it should
+/// exit with status 0 if the user is a member of the group, and with status 1 if not.
+pub(crate) struct HelperGroupProvider {
+    helper: PathBuf,
+}
+
+impl HelperGroupProvider {
+    pub(crate) fn new(helper: impl Into<PathBuf>) -> Self {
+        Self {
+            helper: helper.into(),
+        }
+    }
+}
+
+struct Answer {
+    helper: PathBuf,
+    user: String,
+    group: String,
+    is_member: Option<bool>,
+}
+
+// the same question can come up several times while the sudoers file is evaluated, but the
+// helper is only asked once per invocation of sudo
+static ANSWERS: Mutex<Vec<Answer>> = Mutex::new(Vec::new());
+
+impl GroupProvider for HelperGroupProvider {
+    fn user_in_group(&self, user: &str, group: &str) -> Option<bool> {
+        let mut answers = ANSWERS.lock().unwrap_or_else(|err| err.into_inner());
+        let previous = answers.iter().find(|answer| {
+            answer.helper == self.helper && answer.user == user && answer.group == group
+        });
+        if let Some(answer) = previous {
+            return answer.is_member;
+        }
+
+        let is_member = match run_helper(&self.helper, user, group, HELPER_TIMEOUT) {
+            Ok(Some(status)) if status.code() == Some(0) => Some(true),
+            Ok(Some(status)) if status.code() == Some(1) => Some(false),
+            Ok(Some(status)) => {
+                auth_warn!(
+                    "warning: {helper} failed ({status}), %:{group} only matches where it is \
+                     negated",
+                    helper = self.helper.display()
+                );
+                None
+            }
+            Ok(None) => {
+                auth_warn!(
+                    "warning: {helper} did not respond in time, %:{group} only matches where it \
+                     is negated",
+                    helper = self.helper.display()
+                );
+                None
+            }
+            Err(err) => {
+                auth_warn!(
+                    "warning: could not run {helper}: {err}, %:{group} only matches where it is \
+                     negated",
+                    helper = self.helper.display()
+                );
+                None
+            }
+        };
+
+        answers.push(Answer {
+            helper: self.helper.clone(),
+            user: user.to_string(),
+            group: group.to_string(),
+            is_member,
+        });
+
+        is_member
+    }
+}
+
+/// Run `helper`, killing it if it has not finished after `timeout`; in that case `None` is
+/// returned.
+fn run_helper(
+    helper: &Path,
+    user: &str,
+    group: &str,
+    timeout: Duration,
+) -> io::Result<Option<ExitStatus>> {
+    let mut child = Command::new(helper)
+        .args([user, group])
+        .env_clear()
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn()?;
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(None);
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+    use crate::system::file::create_temporary_dir;
+
+    #[test]
+    fn helper_answers_are_kept() {
+        let script = create_temporary_dir().unwrap().join("helper");
+        let provider = HelperGroupProvider::new(&script);
+        let answer = |group| provider.user_in_group("user", group);
+
+        fs::write(&script, "#!/bin/sh\n[ \"$2\" = staff ] || exit $2\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(answer("staff"), Some(true));
+        assert_eq!(answer("1"), Some(false));
+        assert_eq!(answer("2"), None);
+
+        // the helper is not asked again
+        fs::remove_file(&script).unwrap();
+        assert_eq!(answer("staff"), Some(true));
+        assert_eq!(answer("2"), None);
+        assert_eq!(answer("other"), None);
+
+        fs::remove_dir(script.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn helper_exit_status_and_timeout() {
+        let run = |script: &str, timeout| {
+            run_helper(Path::new("/bin/sh"), "-c", script, timeout)
+                .unwrap()
+                .map(|status| status.code())
+        };
+
+        assert_eq!(run("exit 0", HELPER_TIMEOUT), Some(Some(0)));
+        assert_eq!(run("exit 1", HELPER_TIMEOUT), Some(Some(1)));
+        assert_eq!(run("sleep 10", Duration::from_millis(100)), None);
+
+        assert!(run_helper(Path::new("/nonexistent"), "user", "group", HELPER_TIMEOUT).is_err());
+    }
+}
@@ -1,10 +1,12 @@
 use std::{ffi::CStr, fmt::Display, num::ParseIntError, str::FromStr};
 
+use super::group_provider::GroupProvider;
+
 /// Represents a group ID in the system.
 ///
 /// `GroupId` is transparent because the memory mapping should stay the same as the underlying
 /// type, so we can safely cast as a pointer.
-/// See the implementation in `system::mod::set_target_user`.
+/// See the implementation in `exec::prepare`.
 #[repr(transparent)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct GroupId(libc::gid_t);
@@ -116,6 +118,10 @@ pub trait UnixUser {
     fn is_root(&self) -> bool;
     fn in_group_by_name(&self, _name: &CStr) -> bool;
     fn in_group_by_gid(&self, _gid: GroupId) -> bool;
+    /// `None` means that netgroups are not supported on this system
+    fn in_netgroup(&self, _netgroup: &CStr) -> Option<bool>;
+    /// `None` means that membership cannot be determined
+    fn in_nonunix_group(&self, _group: &str, _provider: &dyn GroupProvider) -> Option<bool>;
 
     type Group: UnixGroup;
     fn group(&self) -> Self::Group;
@@ -146,6 +152,12 @@ impl UnixUser for super::User {
     fn in_group_by_gid(&self, gid: GroupId) -> bool {
         self.groups.contains(&gid)
     }
+    fn in_netgroup(&self, netgroup: &CStr) -> Option<bool> {
+        super::netgroup::user_in_netgroup(netgroup, self.name.as_cstr())
+    }
+    fn in_nonunix_group(&self, group: &str, provider: &dyn GroupProvider) -> Option<bool> {
+        provider.user_in_group(self.name.as_str(), group)
+    }
     type Group = super::Group;
     fn group(&self) -> super::Group {
         Self::Group {
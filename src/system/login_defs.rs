@@ -0,0 +1,193 @@
+//! Settings from `/etc/login.defs`, the configuration file of the shadow password suite.
+//! sudo-rs only needs a few of these, so only those have a getter.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use super::interface::UserId;
+
+const LOGIN_DEFS_PATH: &str = "/etc/login.defs";
+
+const MAIL_DIR_DEFAULT: &str = "/var/mail";
+const UID_MIN_DEFAULT: u32 = 1000;
+
+#[derive(Debug, Default)]
+pub(crate) struct LoginDefs {
+    values: HashMap<String, String>,
+}
+
+impl LoginDefs {
+    /// The settings of this system; `/etc/login.defs` is only read the first time this is called.
+    pub(crate) fn system() -> &'static LoginDefs {
+        static SYSTEM: OnceLock<LoginDefs> = OnceLock::new();
+        SYSTEM.get_or_init(|| Self::load(Path::new(LOGIN_DEFS_PATH)))
+    }
+
+    /// Read the settings from `path`; if it cannot be read, every getter returns its default.
+    fn load(path: &Path) -> Self {
+        match fs::read(path) {
+            Ok(contents) => Self::parse(&String::from_utf8_lossy(&contents)),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Each line holds a key and a value separated by whitespace; other lines are ignored,
+    /// as is anything after a `#` at the start of a line. If a key is set more than once, the
+    /// last value counts.
+    pub(crate) fn parse(input: &str) -> Self {
+        let mut values = HashMap::new();
+        for line in input.lines().map(str::trim) {
+            if line.starts_with('#') {
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once(char::is_whitespace) else {
+                continue;
+            };
+
+            // like the shadow suite, accept values that are surrounded by double quotes
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value);
+
+            values.insert(key.to_string(), value.to_string());
+        }
+
+        Self { values }
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    /// The directory that holds the mailboxes of users (`MAIL_DIR`); `/var/mail` if it is
+    /// not set to an absolute path.
+    pub(crate) fn mail_dir(&self) -> &Path {
+        let mail_dir = self
+            .get("MAIL_DIR")
+            .map(Path::new)
+            .filter(|path| path.is_absolute());
+
+        mail_dir.unwrap_or(Path::new(MAIL_DIR_DEFAULT))
+    }
+
+    /// The mailbox of the user called `name`.
+    pub(crate) fn mailbox(&self, name: &str) -> PathBuf {
+        self.mail_dir().join(name)
+    }
+
+    /// The lowest user ID that `useradd` gives to an account for a person, rather than a
+    /// system account (`UID_MIN`); 1000 if it is not set to a decimal number.
+    pub(crate) fn uid_min(&self) -> UserId {
+        let uid_min = self.get("UID_MIN").and_then(|value| value.parse().ok());
+
+        UserId::new(uid_min.unwrap_or(UID_MIN_DEFAULT))
+    }
+
+    /// Whether `uid` belongs to a system account rather than to a person: its user ID is
+    /// below `UID_MIN`. Root is not counted as one.
+    pub(crate) fn is_system_account(&self, uid: UserId) -> bool {
+        uid != UserId::ROOT && uid < self.uid_min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str, contents: &str) -> LoginDefs {
+        let path =
+            std::env::temp_dir().join(format!("sudo-login-defs-{name}-{}", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        let login_defs = LoginDefs::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        login_defs
+    }
+
+    #[test]
+    fn typical_file() {
+        let login_defs = fixture(
+            "typical",
+            "#
+# /etc/login.defs - Configuration control definitions for the login package.
+#
+MAIL_DIR        /var/spool/mail
+#MAIL_FILE      .mail
+
+UID_MIN                  1000
+UID_MAX                 60000
+ENV_PATH        PATH=/usr/local/bin:/usr/bin:/bin
+",
+        );
+
+        assert_eq!(login_defs.mail_dir(), Path::new("/var/spool/mail"));
+        assert_eq!(
+            login_defs.mailbox("ferris"),
+            Path::new("/var/spool/mail/ferris")
+        );
+        assert_eq!(login_defs.uid_min(), UserId::new(1000));
+        assert_eq!(
+            login_defs.get("ENV_PATH"),
+            Some("PATH=/usr/local/bin:/usr/bin:/bin")
+        );
+        assert_eq!(login_defs.get("MAIL_FILE"), None);
+    }
+
+    #[test]
+    fn junk_lines_are_ignored() {
+        let login_defs = fixture(
+            "junk",
+            "UID_MIN
+	  MAIL_DIR	/srv/mail
+this line has too many words
+\u{1F980}
+UID_MIN 500 # not a comment
+",
+        );
+
+        assert_eq!(login_defs.mail_dir(), Path::new("/srv/mail"));
+        assert_eq!(login_defs.get("this"), Some("line has too many words"));
+        // the whole rest of the line is the value, which is not a number
+        assert_eq!(login_defs.uid_min(), UserId::new(1000));
+    }
+
+    #[test]
+    fn last_value_counts() {
+        let login_defs = fixture(
+            "last",
+            "UID_MIN 500\nMAIL_DIR /first\nUID_MIN \"2000\"\nMAIL_DIR /second\n",
+        );
+
+        assert_eq!(login_defs.uid_min(), UserId::new(2000));
+        assert_eq!(login_defs.mail_dir(), Path::new("/second"));
+    }
+
+    #[test]
+    fn defaults() {
+        for login_defs in [
+            fixture("empty", ""),
+            fixture("invalid", "MAIL_DIR mail\nUID_MIN -1\n"),
+            LoginDefs::load(Path::new("/nonexistent/login.defs")),
+        ] {
+            assert_eq!(login_defs.mail_dir(), Path::new("/var/mail"));
+            assert_eq!(login_defs.uid_min(), UserId::new(1000));
+        }
+    }
+
+    #[test]
+    fn system_accounts() {
+        let login_defs = LoginDefs::parse("UID_MIN 500\n");
+
+        assert!(login_defs.is_system_account(UserId::new(1)));
+        assert!(login_defs.is_system_account(UserId::new(499)));
+        assert!(!login_defs.is_system_account(UserId::new(500)));
+        assert!(!login_defs.is_system_account(UserId::ROOT));
+    }
+}
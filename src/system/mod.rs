@@ -8,23 +8,38 @@ use std::{
     ops,
     os::unix,
     path::PathBuf,
+    time::Duration,
 };
 
 use crate::{
     common::{Error, SudoPath, SudoString},
     cutils::*,
+    log::{dev_debug, dev_warn},
 };
 use interface::{DeviceId, GroupId, ProcessId, UserId};
 pub use libc::PATH_MAX;
 use libc::{CLOSE_RANGE_CLOEXEC, EINVAL, ENOSYS, STDERR_FILENO};
+pub(crate) use proc::ProcFs;
 use time::ProcessCreateTime;
 
 use self::signal::SignalNumber;
 
 pub(crate) mod audit;
+
+mod dns;
 // generalized traits for when we want to hide implementations
 pub mod interface;
 
+pub(crate) mod group_provider;
+
+pub(crate) mod login_defs;
+
+pub(crate) mod netgroup;
+
+pub(crate) mod paths;
+
+pub(crate) mod proc;
+
 pub mod file;
 
 pub mod time;
@@ -45,8 +60,10 @@ pub(crate) fn _exit(status: c_int) -> ! {
     unsafe { libc::_exit(status) }
 }
 
-/// Mark every file descriptor that is not one of the IO streams as CLOEXEC.
-pub(crate) fn mark_fds_as_cloexec() -> io::Result<()> {
+/// Mark every file descriptor that is not one of the IO streams as CLOEXEC, in a single call.
+///
+/// Fails with `ENOSYS` or `EINVAL` if the kernel does not support this.
+fn close_range_cloexec() -> io::Result<()> {
     let lowfd = STDERR_FILENO + 1;
 
     // SAFETY: this function is safe to call:
@@ -72,13 +89,28 @@ pub(crate) fn mark_fds_as_cloexec() -> io::Result<()> {
         }
     };
 
-    match res {
-        Err(err) if err.raw_os_error() == Some(ENOSYS) || err.raw_os_error() == Some(EINVAL) => {
+    res.map(|_| ())
+}
+
+fn close_range_unsupported(err: &io::Error) -> bool {
+    err.raw_os_error() == Some(ENOSYS) || err.raw_os_error() == Some(EINVAL)
+}
+
+/// Mark every file descriptor that is not one of the IO streams as CLOEXEC.
+pub(crate) fn mark_fds_as_cloexec() -> io::Result<()> {
+    let lowfd = STDERR_FILENO + 1;
+
+    match close_range_cloexec() {
+        Err(err) if close_range_unsupported(&err) => {
             // The kernel doesn't support close_range or CLOSE_RANGE_CLOEXEC,
-            // fallback to finding all open fds using /proc/self/fd.
+            // fallback to finding all open fds using /proc/self/fd, or to trying
+            // every possible fd if procfs is not mounted.
+            let Ok(fd_dir) = ProcFs::path("self/fd") else {
+                return mark_fds_as_cloexec_below(open_max());
+            };
 
             // FIXME use /dev/fd on macOS
-            for entry in fs::read_dir("/proc/self/fd")? {
+            for entry in fs::read_dir(fd_dir)? {
                 let entry = entry?;
                 let file_name = entry.file_name();
                 let file_name = file_name.to_str().ok_or(io::Error::new(
@@ -106,11 +138,43 @@ pub(crate) fn mark_fds_as_cloexec() -> io::Result<()> {
 
             Ok(())
         }
-        Err(err) => Err(err),
-        Ok(_) => Ok(()),
+        res => res,
+    }
+}
+
+/// Like [`mark_fds_as_cloexec`], but only using async-signal-safe functions, so this can be
+/// called between `fork` and `exec`. Without support from the kernel, this has to try every file
+/// descriptor below `open_max` instead of reading the open ones from procfs.
+pub(crate) fn mark_fds_as_cloexec_below(open_max: c_int) -> io::Result<()> {
+    match close_range_cloexec() {
+        Err(err) if close_range_unsupported(&err) => {
+            for fd in STDERR_FILENO + 1..open_max {
+                // SAFETY: This only sets the CLOEXEC flag for the given fd, if it is open.
+                if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+                    let err = io::Error::last_os_error();
+                    if err.raw_os_error() != Some(libc::EBADF) {
+                        return Err(err);
+                    }
+                }
+            }
+
+            Ok(())
+        }
+        res => res,
     }
 }
 
+/// The maximum number of open file descriptors, or a reasonable guess if there is no limit.
+pub(crate) fn open_max() -> c_int {
+    // SAFETY: sysconf is always safe to call
+    let open_max = unsafe { libc::sysconf(libc::_SC_OPEN_MAX) };
+    // an indeterminate limit is reported as -1
+    c_int::try_from(open_max)
+        .ok()
+        .filter(|&max| max > 0)
+        .unwrap_or(1024)
+}
+
 pub(crate) enum ForkResult {
     // Parent process branch with the child process' PID.
     Parent(ProcessId),
@@ -237,6 +301,31 @@ impl Hostname {
             }
         }
     }
+
+    /// Replace the hostname by its fully qualified name, as reported by the resolver. Since the
+    /// resolver may not respond at all, the lookup is abandoned after `timeout`; in that case (or
+    /// if the lookup fails) the hostname is kept as it is.
+    pub fn qualify(self, timeout: Duration) -> Self {
+        let host = self.inner.clone();
+        match dns::with_deadline(timeout, move || dns::canonical_name(&host)) {
+            Some(Some(fqdn)) => Self { inner: fqdn },
+            Some(None) => {
+                dev_debug!("could not find the fully qualified name of {self}");
+                self
+            }
+            None => {
+                dev_warn!("looking up the fully qualified name of {self} timed out");
+                self
+            }
+        }
+    }
+
+    /// The hostname up to the first dot
+    pub fn short(&self) -> &str {
+        self.inner
+            .split_once('.')
+            .map_or(&self.inner, |(short, _)| short)
+    }
 }
 
 pub fn syslog(priority: c_int, facility: c_int, message: &CStr) {
@@ -253,8 +342,17 @@ pub fn syslog(priority: c_int, facility: c_int, message: &CStr) {
     }
 }
 
+/// Set the options (such as `LOG_PID`) for the messages that are sent to syslog from now on.
+pub fn openlog(options: c_int, facility: c_int) {
+    // SAFETY: a null pointer as the identity makes syslog use the name of the program, which
+    // is also what it does if openlog is never called
+    unsafe {
+        libc::openlog(std::ptr::null(), options, facility);
+    }
+}
+
 /// Makes sure that that the target is included in the groups, and is its first element
-fn inject_group(target: GroupId, groups: &mut Vec<GroupId>) {
+pub(crate) fn inject_group(target: GroupId, groups: &mut Vec<GroupId>) {
     if let Some(index) = groups.iter().position(|id| id == &target) {
         // make sure the requested group id is the first in the list (necessary on FreeBSD)
         groups.swap(0, index)
@@ -280,35 +378,6 @@ fn set_supplementary_groups(groups: &[GroupId]) -> io::Result<()> {
     Ok(())
 }
 
-/// set target user and groups (uid, gid, additional groups) for a command
-pub fn set_target_user(
-    cmd: &mut std::process::Command,
-    mut target_user: User,
-    target_group: Group,
-) {
-    use std::os::unix::process::CommandExt;
-
-    inject_group(target_group.gid, &mut target_user.groups);
-
-    // we need to do this in a `pre_exec` call since the `groups` method in `process::Command` is unstable
-    // see https://github.com/rust-lang/rust/blob/a01b4cc9f375f1b95fa8195daeea938d3d9c4c34/library/std/src/sys/unix/process/process_unix.rs#L329-L352
-    // for the std implementation of the libc calls to `setgroups`, `setgid` and `setuid`
-    // SAFETY: Setuid, setgid and setgroups are async-signal-safe.
-    unsafe {
-        cmd.pre_exec(move || {
-            set_supplementary_groups(&target_user.groups)?;
-            // setgid and setuid set the real, effective and saved version of the gid and uid
-            // respectively rather than just the real gid and uid. The original sudo uses setresgid
-            // and setresuid instead with all three arguments equal, but as this does the same as
-            // setgid and setuid using the latter is fine too.
-            cerr(libc::setgid(target_group.gid.inner()))?;
-            cerr(libc::setuid(target_user.uid.inner()))?;
-
-            Ok(())
-        });
-    }
-}
-
 /// Send a signal to a process with the specified ID.
 pub fn kill(pid: ProcessId, signal: SignalNumber) -> io::Result<()> {
     // SAFETY: This function cannot cause UB even if `pid` is not a valid process ID or if
@@ -323,6 +392,14 @@ pub fn killpg(pgid: ProcessId, signal: SignalNumber) -> io::Result<()> {
     cerr(unsafe { libc::killpg(pgid.inner(), signal) }).map(|_| ())
 }
 
+/// Have `SIGALRM` delivered to the current process once `timeout` has passed, replacing any
+/// alarm that was set before; a timeout of zero cancels the alarm.
+pub fn alarm(timeout: Duration) {
+    let seconds = timeout.as_secs().try_into().unwrap_or(c_uint::MAX);
+    // SAFETY: This function is always safe to call
+    unsafe { libc::alarm(seconds) };
+}
+
 /// Get the process group ID of the current process.
 pub fn getpgrp() -> ProcessId {
     // SAFETY: This function is always safe to call
@@ -366,6 +443,9 @@ pub struct User {
     pub groups: Vec<GroupId>,
 }
 
+/// The maximum number of groups that a user can be a member of.
+const MAX_GROUPS: usize = 1 << 20;
+
 impl User {
     /// # Safety
     /// This function expects `pwd` to be a result from a successful call to `getpwXXX_r`.
@@ -374,8 +454,13 @@ impl User {
         // NOTE: on Linux, getgrouplist could be used to simply inquire as to the size needed;
         // but on FreeBSD, getgrouplist does not specify this in its function contract, so a
         // blind allocation loop is needed.
-        let Some(groups_buffer) =
-            dynamic_fill::<libc::gid_t, std::num::TryFromIntError>(32..65536, |groups_buffer| {
+        //
+        // The group database can list a user in more groups than the kernel allows a process
+        // to have (NGROUPS_MAX, at most 65536 on Linux), so that is not the upper bound here:
+        // all of them are needed to match rules for a %group.
+        let Some(groups_buffer) = dynamic_fill::<libc::gid_t, std::num::TryFromIntError>(
+            32..MAX_GROUPS,
+            |groups_buffer| {
                 let mut buf_len: c_int = groups_buffer.len() as c_int;
                 // SAFETY: getgrouplist is passed valid pointers
                 // in particular `groups_buffer` is an array of `buf.len()` bytes, as required
@@ -393,10 +478,10 @@ impl User {
                 } else {
                     None
                 })
-            })
-            .expect("negative group size, this should not happen")
-        else {
-            panic!("user has too many groups (> 65536), this should not happen");
+            },
+        )
+        .expect("negative group size, this should not happen") else {
+            panic!("user has too many groups (> {MAX_GROUPS}), this should not happen");
         };
 
         // SAFETY: All pointers were initialized by a successful call to `getpwXXX_r` as per the
@@ -782,7 +867,8 @@ impl Process {
     }
 }
 
-/// Read the n-th field (with 0-based indexing) from `/proc/<pid>/self`.
+/// Read the n-th field (with 0-based indexing) from `/proc/<pid>/stat`; this fails if
+/// procfs is not mounted.
 ///
 /// See ["Table 1-4: Contents of the stat fields" of "The /proc
 /// Filesystem"][proc_stat_fields] in the Linux docs for all available fields.
@@ -800,7 +886,7 @@ fn read_proc_stat<T: FromStr>(pid: WithProcess, field_idx: isize) -> io::Result<
     let pidref = pid.to_proc_string();
 
     // read the data from the stat file for the process with the given pid
-    let path = PathBuf::from_iter(&["/proc", &pidref, "stat"]);
+    let path = ProcFs::path(PathBuf::from_iter(&[&pidref, "stat"]))?;
     let proc_stat = std::fs::read(path)?;
 
     // first get the part of the stat file past the second argument, we then reverse
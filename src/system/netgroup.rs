@@ -0,0 +1,47 @@
+use std::{
+    ffi::{CStr, c_char, c_int, c_void},
+    mem, ptr,
+    sync::OnceLock,
+};
+
+type Innetgr =
+    unsafe extern "C" fn(*const c_char, *const c_char, *const c_char, *const c_char) -> c_int;
+
+/// The innetgr(3) function of the C library. Not every C library provides it (musl doesn't),
+/// so it is looked up when it is first needed instead of being linked against.
+fn innetgr() -> Option<Innetgr> {
+    static INNETGR: OnceLock<Option<Innetgr>> = OnceLock::new();
+
+    *INNETGR.get_or_init(|| {
+        // SAFETY: dlsym is passed a valid C string; it returns NULL or the address of the symbol
+        let symbol = unsafe { libc::dlsym(libc::RTLD_DEFAULT, c"innetgr".as_ptr()) };
+
+        (!symbol.is_null()).then(|| {
+            // SAFETY: the symbol is non-NULL, and innetgr has this signature wherever it exists
+            unsafe { mem::transmute::<*mut c_void, Innetgr>(symbol) }
+        })
+    })
+}
+
+/// Whether `user` is a member of `netgroup` (on any host); `None` if the C library does not
+/// support netgroups.
+pub(crate) fn user_in_netgroup(netgroup: &CStr, user: &CStr) -> Option<bool> {
+    let innetgr = innetgr()?;
+
+    // SAFETY: valid C strings are passed; a NULL host or domain matches any host or domain
+    let result = unsafe { innetgr(netgroup.as_ptr(), ptr::null(), user.as_ptr(), ptr::null()) };
+
+    Some(result == 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_netgroup() {
+        if let Some(is_member) = user_in_netgroup(c"sudo-rs-no-such-netgroup", c"root") {
+            assert!(!is_member);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! Where sudo finds the helper programs that it runs for the user. These are never looked up
+//! in `PATH` or in any other place that the user controls: the defaults are absolute paths
+//! that are compiled in, which the administrator can override with `Path` lines in
+//! `sudo.conf`. Either way, a helper is only used if root controls it.
+//!
+//! The few `Set` lines in `sudo.conf` that sudo-rs understands are read here as well.
+
+use std::io::{self, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::audit;
+
+const SUDO_CONF_PATH: &str = match option_env!("SUDO_RS_SUDO_CONF") {
+    Some(path) => path,
+    None => "/etc/sudo.conf",
+};
+
+/// The askpass helper to use if `SUDO_ASKPASS` is not set and sudo.conf does not name one;
+/// there is none unless one is given in `SUDO_RS_ASKPASS` when sudo is built.
+const ASKPASS_DEFAULT: Option<&str> = option_env!("SUDO_RS_ASKPASS");
+
+const fn is_absolute(path: &str) -> bool {
+    !path.is_empty() && path.as_bytes()[0] == b'/'
+}
+
+// the defaults may be changed when sudo is built, which fails if they are not absolute
+const _: () = {
+    assert!(
+        is_absolute(SUDO_CONF_PATH),
+        "SUDO_RS_SUDO_CONF must be an absolute path"
+    );
+    if let Some(path) = ASKPASS_DEFAULT {
+        assert!(
+            is_absolute(path),
+            "SUDO_RS_ASKPASS must be an absolute path"
+        );
+    }
+};
+
+/// The askpass helper configured by the administrator, if any.
+pub(crate) fn askpass() -> io::Result<Option<PathBuf>> {
+    let configured = match sudo_conf_path("askpass")? {
+        Some(path) => Some(path),
+        None => ASKPASS_DEFAULT.map(PathBuf::from),
+    };
+
+    configured
+        .map(|path| check_helper(&path).map(|()| path))
+        .transpose()
+}
+
+/// The program that decides membership of non-unix groups, if the administrator configured
+/// one; there is no default.
+pub(crate) fn nonunix_group_helper() -> io::Result<Option<PathBuf>> {
+    sudo_conf_path("nonunix_group_helper")?
+        .map(|path| check_helper(&path).map(|()| path))
+        .transpose()
+}
+
+/// How long looking up users and groups may take before sudo tells the user that it is still
+/// busy, unless `Set slow_lookup_notice` in sudo.conf gives another number of seconds; a value
+/// of 0 turns the notice off. Since this is only about a notice, a sudo.conf that cannot be
+/// read (or a value that makes no sense) results in the default.
+pub(crate) fn slow_lookup_notice() -> Option<Duration> {
+    const DEFAULT: Duration = Duration::from_secs(2);
+
+    let configured = read_sudo_conf()
+        .ok()
+        .flatten()
+        .and_then(|conf| parse_setting(&conf, "Set", "slow_lookup_notice"));
+
+    match configured.map(|value| value.parse::<u64>()) {
+        Some(Ok(0)) => None,
+        Some(Ok(seconds)) => Some(Duration::from_secs(seconds)),
+        Some(Err(_)) | None => Some(DEFAULT),
+    }
+}
+
+/// The value of the `Path name` line in sudo.conf.
+fn sudo_conf_path(name: &str) -> io::Result<Option<PathBuf>> {
+    Ok(read_sudo_conf()?
+        .and_then(|conf| parse_setting(&conf, "Path", name))
+        .map(PathBuf::from))
+}
+
+/// The contents of sudo.conf; the file is only used if it is owned by root and not writable by
+/// anyone else, and it is fine if it does not exist.
+fn read_sudo_conf() -> io::Result<Option<String>> {
+    let mut conf = match audit::secure_open_sudoers(SUDO_CONF_PATH) {
+        Ok(file) => file,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error),
+    };
+
+    let mut contents = Vec::new();
+    conf.read_to_end(&mut contents)?;
+
+    Ok(Some(String::from_utf8_lossy(&contents).into_owned()))
+}
+
+/// Find `keyword name value` (such as `Path askpass /some/path`) in the contents of sudo.conf;
+/// other lines (which configure plugins and such) are ignored, as is anything after a `#`. If
+/// a value is given more than once, the last one counts.
+fn parse_setting(conf: &str, keyword: &str, name: &str) -> Option<String> {
+    let mut found = None;
+    for line in conf.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+
+        if fields.next() == Some(keyword) && fields.next() == Some(name) {
+            // a line without a value is ignored
+            found = fields.next().map(String::from).or(found);
+        }
+    }
+
+    found
+}
+
+/// A helper may only be run if it is given as an absolute path, and neither it nor the
+/// directory it is in can be changed by anyone but root.
+fn check_helper(path: &Path) -> io::Result<()> {
+    if !path.is_absolute() {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            xlat!("{path} is not an absolute path", path = path.display()),
+        ));
+    }
+
+    audit::check_root_controlled(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::{MetadataExt, PermissionsExt, chown};
+
+    use super::*;
+    use crate::system::file::create_temporary_dir;
+
+    #[test]
+    fn defaults_are_absolute() {
+        assert!(Path::new(SUDO_CONF_PATH).is_absolute());
+        if let Some(path) = ASKPASS_DEFAULT {
+            assert!(Path::new(path).is_absolute());
+        }
+
+        assert!(is_absolute("/usr/bin/ssh-askpass"));
+        assert!(!is_absolute("ssh-askpass"));
+        assert!(!is_absolute(""));
+    }
+
+    #[test]
+    fn paths_are_read_from_sudo_conf() {
+        let conf = "\
+            # Path askpass /commented/out\n\
+            Plugin sudoers_policy sudoers.so\n\
+            Path askpass /usr/libexec/ssh-askpass\n\
+            Path noexec /usr/libexec/sudo/sudo_noexec.so\n\
+            Path askpass\n\
+            Set disable_coredump false\n";
+
+        assert_eq!(
+            parse_setting(conf, "Path", "askpass").as_deref(),
+            Some("/usr/libexec/ssh-askpass")
+        );
+        assert_eq!(
+            parse_setting(
+                "Path askpass /a\nPath askpass /b # the last one counts",
+                "Path",
+                "askpass"
+            )
+            .as_deref(),
+            Some("/b")
+        );
+        assert_eq!(parse_setting(conf, "Path", "sesh"), None);
+        assert_eq!(
+            parse_setting("path askpass /lowercase", "Path", "askpass"),
+            None
+        );
+        assert_eq!(
+            parse_setting(conf, "Set", "disable_coredump").as_deref(),
+            Some("false")
+        );
+        assert_eq!(parse_setting(conf, "Set", "askpass"), None);
+    }
+
+    #[test]
+    fn helpers_must_be_controlled_by_root() {
+        let tmp = create_temporary_dir().unwrap();
+        let helper = tmp.join("askpass");
+        fs::write(&helper, "#!/bin/sh\necho hunter2\n").unwrap();
+        let set_mode = |path: &Path, mode| {
+            fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap()
+        };
+
+        assert!(check_helper(Path::new("askpass")).is_err());
+        assert!(check_helper(&tmp.join("does-not-exist")).is_err());
+
+        // writable by others
+        set_mode(&helper, 0o757);
+        assert!(check_helper(&helper).is_err());
+
+        // only root can create files that are owned by root
+        if fs::metadata(&helper).unwrap().uid() == 0 {
+            set_mode(&helper, 0o755);
+            assert!(check_helper(&helper).is_ok());
+
+            // in a directory that others can write to, it can be replaced
+            set_mode(&tmp, 0o777);
+            assert!(check_helper(&helper).is_err());
+            set_mode(&tmp, 0o755);
+
+            // owned by another user
+            chown(&helper, Some(1), None).unwrap();
+            assert!(check_helper(&helper).is_err());
+        } else {
+            set_mode(&helper, 0o755);
+            assert!(check_helper(&helper).is_err());
+        }
+
+        fs::remove_dir_all(&tmp).unwrap();
+    }
+}
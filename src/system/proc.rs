@@ -0,0 +1,47 @@
+//! Access to the proc filesystem, which is not mounted in minimal chroots and some
+//! containers; everything that reads it needs a way to do without.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+const PROC_ROOT: &str = "/proc";
+
+pub(crate) struct ProcFs;
+
+impl ProcFs {
+    /// Whether procfs is mounted; this is only checked once.
+    pub fn is_available() -> bool {
+        static AVAILABLE: OnceLock<bool> = OnceLock::new();
+
+        *AVAILABLE.get_or_init(|| Path::new(PROC_ROOT).join("self/stat").exists())
+    }
+
+    /// The location of `entry` (such as "self/fd") in procfs, or an error if it is not mounted.
+    pub fn path(entry: impl AsRef<Path>) -> io::Result<PathBuf> {
+        if Self::is_available() {
+            Ok(Path::new(PROC_ROOT).join(entry))
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "/proc is not mounted",
+            ))
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::ProcFs;
+
+    #[test]
+    fn paths_in_procfs() {
+        assert!(ProcFs::is_available());
+        assert_eq!(
+            ProcFs::path("self/fd").unwrap(),
+            std::path::Path::new("/proc/self/fd")
+        );
+    }
+}
@@ -1,9 +1,7 @@
 use std::io;
 
 use crate::cutils::cerr;
-use crate::log::dev_debug;
 use crate::system::make_zeroed_sigaction;
-use crate::system::signal::signal_name;
 
 use super::{SignalNumber, consts::*};
 
@@ -57,11 +55,12 @@ impl SignalsState {
         }
     }
 
+    /// Restore the signals that were updated. This is also used in between `fork` and `exec`, so
+    /// it only calls async-signal-safe functions.
     pub(crate) fn restore(&mut self) -> io::Result<()> {
         for (idx, state) in self.sa_handlers.iter_mut().enumerate() {
             if state.restore {
                 let signal = Self::SAVED_SIGNALS[idx];
-                dev_debug!("restoring updated signal: {}", signal_name(signal));
                 // safety: `signal` is a constant value and a valid signal
                 // `sa` is a valid already initialized sigaction
                 // Third parameter can be NULL
@@ -14,8 +14,7 @@ pub(super) fn ttyname_from_dev() -> io::Result<Option<OsString>> {
 
     let tty_name = dev_check(Path::new("/dev/console"), tty_dev)
         .or_else(|| ttyname_from_stdioe(tty_dev))
-        .or_else(|| find_tty_in_dir(Path::new("/dev/pts"), tty_dev))
-        .or_else(|| find_tty_in_dir(Path::new("/dev"), tty_dev));
+        .or_else(|| ttyname_from_dir(tty_dev));
 
     if tty_name.is_some() {
         Ok(tty_name)
@@ -24,6 +23,12 @@ pub(super) fn ttyname_from_dev() -> io::Result<Option<OsString>> {
     }
 }
 
+/// Find the path of the terminal with the given device id by searching through /dev
+pub(super) fn ttyname_from_dir(tty_dev: DeviceId) -> Option<OsString> {
+    find_tty_in_dir(Path::new("/dev/pts"), tty_dev)
+        .or_else(|| find_tty_in_dir(Path::new("/dev"), tty_dev))
+}
+
 fn is_our_tty(metadata: fs::Metadata, tty_dev: DeviceId) -> bool {
     metadata.file_type().is_char_device() && metadata.rdev() == tty_dev.inner()
 }
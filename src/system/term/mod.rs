@@ -13,7 +13,7 @@ use libc::{TIOCSWINSZ, ioctl, winsize};
 
 use crate::cutils::{cerr, is_fifo_or_sock, os_string_from_ptr, safe_isatty};
 
-use super::interface::ProcessId;
+use super::interface::{DeviceId, ProcessId};
 
 mod find_tty;
 
@@ -155,7 +155,7 @@ impl AsFd for PtyFollower {
     }
 }
 
-impl From<PtyFollower> for std::process::Stdio {
+impl From<PtyFollower> for std::os::fd::OwnedFd {
     fn from(follower: PtyFollower) -> Self {
         follower.file.into()
     }
@@ -266,6 +266,11 @@ pub fn current_tty_name() -> io::Result<OsString> {
         .or_else(|_| io::stderr().ttyname())
 }
 
+/// Try to get the path of the TTY with the given device id
+pub fn tty_name_of(tty_dev: DeviceId) -> Option<OsString> {
+    find_tty::ttyname_from_dir(tty_dev)
+}
+
 #[repr(transparent)]
 pub(crate) struct TermSize {
     raw: winsize,
@@ -1,4 +1,5 @@
 use std::{
+    ffi::{CStr, CString},
     io::{Read, Write},
     mem::MaybeUninit,
     ops::{Add, Sub},
@@ -53,6 +54,22 @@ impl SystemTime {
         ))
     }
 
+    /// The amount of time elapsed from `earlier` to this time, or `None` if `earlier` is later.
+    pub fn duration_since(&self, earlier: SystemTime) -> Option<Duration> {
+        if earlier > *self {
+            return None;
+        }
+
+        let mut secs = self.secs - earlier.secs;
+        let mut nsecs = self.nsecs - earlier.nsecs;
+        if nsecs < 0 {
+            secs -= 1;
+            nsecs += 1_000_000_000;
+        }
+
+        Some(Duration::new(secs as u64, nsecs as u32))
+    }
+
     #[inline]
     fn checked_add(self, rhs: Duration) -> Option<SystemTime> {
         let rhs_secs = rhs.as_secs().try_into().ok()?;
@@ -65,7 +82,7 @@ impl SystemTime {
     }
 
     #[inline]
-    fn checked_sub(self, rhs: Duration) -> Option<SystemTime> {
+    pub(super) fn checked_sub(self, rhs: Duration) -> Option<SystemTime> {
         let rhs_secs = rhs.as_secs().try_into().ok()?;
         let rhs_nsecs = rhs.subsec_nanos().into();
 
@@ -140,6 +157,51 @@ impl ProcessCreateTime {
     }
 }
 
+/// Format the wall clock time `secs` (in seconds since the epoch) in the local timezone using
+/// `strftime`, following the conventions of `locale`; if that locale is not available, the
+/// "C" locale is used instead.
+pub fn format_local_time(secs: i64, format: &CStr, locale: &str) -> Option<String> {
+    let new_locale = |name: &CStr| {
+        // SAFETY: a valid CStr pointer is passed, and a null base locale is allowed
+        unsafe { libc::newlocale(libc::LC_TIME_MASK, name.as_ptr(), std::ptr::null_mut()) }
+    };
+
+    let mut locale = CString::new(locale).map_or(std::ptr::null_mut(), |name| new_locale(&name));
+    if locale.is_null() {
+        locale = new_locale(c"C");
+    }
+    if locale.is_null() {
+        return None;
+    }
+
+    let time = secs as libc::time_t;
+    let mut tm = MaybeUninit::<libc::tm>::uninit();
+    let mut buffer = [0u8; 128];
+
+    // SAFETY: valid pointers to a time_t and a (to be initialized) tm struct are passed
+    let length = if unsafe { libc::localtime_r(&time, tm.as_mut_ptr()) }.is_null() {
+        None
+    } else {
+        // SAFETY: the buffer size is passed correctly, 'format' is a proper C string, and 'tm'
+        // was initialized by the successful call to localtime_r; strftime_l returns the number of
+        // bytes written (excluding the null terminator), or 0 if the result did not fit.
+        Some(unsafe {
+            libc::strftime_l(
+                buffer.as_mut_ptr().cast(),
+                buffer.len(),
+                format.as_ptr(),
+                tm.as_ptr(),
+                locale,
+            )
+        })
+    };
+
+    // SAFETY: 'locale' was obtained from newlocale and is not used after this point
+    unsafe { libc::freelocale(locale) };
+
+    length.map(|length| String::from_utf8_lossy(&buffer[..length]).into_owned())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -171,6 +233,27 @@ mod tests {
             SystemTime::new(10, 0) - Duration::from_nanos(3_500_000_000),
             SystemTime::new(6, 500_000_000)
         );
+        assert_eq!(
+            SystemTime::new(10, 0).duration_since(SystemTime::new(6, 500_000_000)),
+            Some(Duration::from_nanos(3_500_000_000))
+        );
+        assert_eq!(
+            SystemTime::new(6, 0).duration_since(SystemTime::new(6, 1)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_format_local_time() {
+        // this is September 9th 2001 in UTC, and not far from it in any timezone
+        let time = 1_000_000_000;
+        let date = format_local_time(time, c"%h %Y", "C").unwrap();
+        assert_eq!(date, "Sep 2001");
+        assert_eq!(
+            format_local_time(time, c"%h %Y", "xx_XX.UNKNOWN"),
+            Some(date)
+        );
+        assert_eq!(format_local_time(time, c"%T", "C").unwrap().len(), 8);
     }
 
     #[test]
@@ -1,11 +1,11 @@
 use std::{
+    ffi::c_int,
     fs::File,
     io::{self, Cursor, Read, Seek, Write},
     path::PathBuf,
-    time::Duration,
 };
 
-use crate::common::resolve::AuthUser;
+use crate::common::{SudoersDuration, resolve::AuthUser};
 use crate::{
     common::resolve::CurrentUser,
     log::{auth_info, auth_warn},
@@ -13,7 +13,7 @@ use crate::{
 
 use super::{
     Process, WithProcess,
-    audit::secure_open_cookie_file,
+    audit::{SecureDir, secure_open_cookie_file},
     file::FileLock,
     interface::{DeviceId, ProcessId, UserId},
     time::{ProcessCreateTime, SystemTime},
@@ -28,18 +28,94 @@ const MOD_OFFSET: i64 = SIZE_OF_TS + SIZE_OF_BOOL;
 #[derive(Debug)]
 pub struct SessionRecordFile {
     file: File,
-    timeout: Duration,
+    timeout: SudoersDuration,
     for_user: UserId,
 }
 
 impl SessionRecordFile {
-    const BASE_PATH: &'static str = "/var/run/sudo-rs/ts";
+    pub const BASE_PATH: &'static str = "/var/run/sudo-rs/ts";
 
-    pub fn open_for_user(user: &CurrentUser, timeout: Duration) -> io::Result<Self> {
-        let uid = user.uid;
+    /// The location of the session records file of the user with the given uid
+    pub fn path_for(uid: UserId) -> PathBuf {
         let mut path = PathBuf::from(Self::BASE_PATH);
         path.push(uid.to_string());
-        SessionRecordFile::new(uid, secure_open_cookie_file(&path)?, timeout)
+        path
+    }
+
+    pub fn open_for_user(user: &CurrentUser, timeout: SudoersDuration) -> io::Result<Self> {
+        Self::open_for_uid(user.uid, timeout)
+    }
+
+    pub fn open_for_uid(uid: UserId, timeout: SudoersDuration) -> io::Result<Self> {
+        SessionRecordFile::new(uid, secure_open_cookie_file(Self::path_for(uid))?, timeout)
+    }
+
+    /// Open the session records file of the user with the given uid with the given open
+    /// `flags`, but only if it already exists.
+    pub fn open_existing(uid: UserId, flags: c_int) -> io::Result<Option<File>> {
+        match SecureDir::open(Self::BASE_PATH, false)
+            .and_then(|dir| dir.open_file(uid.to_string(), flags))
+        {
+            Ok(file) => Ok(Some(file)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Like `open_for_uid`, but without creating a session records file if there is none yet
+    pub fn open_existing_for_uid(
+        uid: UserId,
+        timeout: SudoersDuration,
+    ) -> io::Result<Option<Self>> {
+        Self::open_existing(uid, libc::O_RDWR)?
+            .map(|file| SessionRecordFile::new(uid, file, timeout))
+            .transpose()
+    }
+
+    /// Check whether the user `actor` may modify or remove the session records of `owner`.
+    /// Session records files are always owned by root and identified by the uid in their path,
+    /// so this is decided here: users may manage their own records, but only root may manage
+    /// those of other users.
+    pub fn authorize(actor: UserId, owner: UserId) -> io::Result<()> {
+        if actor == owner || actor == UserId::ROOT {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("user {actor} may not modify the session records of user {owner}"),
+            ))
+        }
+    }
+
+    /// Remove the session records file of `owner` entirely, on behalf of `actor`. A user that
+    /// has no session records file has nothing to remove, which is not an error.
+    pub fn remove_for_uid(actor: UserId, owner: UserId) -> io::Result<()> {
+        Self::authorize(actor, owner)?;
+        match SecureDir::open(Self::BASE_PATH, false)
+            .and_then(|dir| dir.remove_file(owner.to_string()))
+        {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Disable the session records of `owner` on behalf of `actor`: those for `scope` if one is
+    /// given, or else all of them. No session records file is created if there is none yet.
+    pub fn disable_for_uid(
+        actor: UserId,
+        owner: UserId,
+        scope: Option<RecordScope>,
+    ) -> io::Result<()> {
+        Self::authorize(actor, owner)?;
+        let Some(mut record_file) = Self::open_existing_for_uid(owner, SudoersDuration::zero())?
+        else {
+            return Ok(());
+        };
+
+        match scope {
+            Some(scope) => record_file.disable(scope),
+            None => record_file.disable_all(),
+        }
     }
 
     const FILE_VERSION: u16 = 2;
@@ -51,7 +127,7 @@ impl SessionRecordFile {
     /// Create a new SessionRecordFile from the given i/o stream.
     /// Timestamps in this file are considered valid if they were created or
     /// updated at most `timeout` time ago.
-    pub fn new(for_user: UserId, io: File, timeout: Duration) -> io::Result<Self> {
+    pub fn new(for_user: UserId, io: File, timeout: SudoersDuration) -> io::Result<Self> {
         let mut session_records = SessionRecordFile {
             file: io,
             timeout,
@@ -198,7 +274,7 @@ impl SessionRecordFile {
             // only touch if record is enabled
             if record.enabled && record.matches(&scope, auth_user) {
                 let now = SystemTime::now()?;
-                if record.written_between(now - self.timeout, now) {
+                if record.written_within(self.timeout, now) {
                     // move back to where the timestamp is and overwrite with the latest time
                     self.file.seek(io::SeekFrom::Current(-MOD_OFFSET))?;
                     let new_time = SystemTime::now()?;
@@ -228,10 +304,19 @@ impl SessionRecordFile {
 
     /// Disable all records that match the given scope.
     pub fn disable(&mut self, scope: RecordScope) -> io::Result<()> {
+        self.disable_where(|record| record.scope == scope)
+    }
+
+    /// Disable all records, regardless of their scope.
+    pub fn disable_all(&mut self) -> io::Result<()> {
+        self.disable_where(|_| true)
+    }
+
+    fn disable_where(&mut self, predicate: impl Fn(&SessionRecord) -> bool) -> io::Result<()> {
         let lock = FileLock::exclusive(&self.file, false)?;
         self.seek_to_first_record()?;
         while let Some(record) = self.next_record()? {
-            if record.scope == scope {
+            if predicate(&record) {
                 self.file.seek(io::SeekFrom::Current(-SIZE_OF_BOOL))?;
                 write_bool(false, &mut self.file)?;
             }
@@ -275,9 +360,87 @@ impl SessionRecordFile {
         })
     }
 
-    /// Completely resets the entire file and removes all records.
-    pub fn reset(&mut self) -> io::Result<()> {
-        self.init(0)
+    /// Remove the record at position `index` (counting from zero, in the order in which
+    /// [SessionRecordFile::inspect] lists them), returning it if it existed.
+    pub fn remove(&mut self, index: usize) -> io::Result<Option<SessionRecord>> {
+        let lock = FileLock::exclusive(&self.file, false)?;
+        self.seek_to_first_record()?;
+        let mut records = Vec::new();
+        while let Some(record) = self.next_record()? {
+            records.push(record);
+        }
+
+        let removed = (index < records.len()).then(|| records.remove(index));
+        if removed.is_some() {
+            self.file.set_len(Self::FIRST_RECORD_OFFSET)?;
+            self.seek_to_first_record()?;
+            for record in &records {
+                self.write_record(record)?;
+            }
+        }
+
+        lock.unlock()?;
+        Ok(removed)
+    }
+
+    /// Read all records (including disabled ones) from a session records file without
+    /// modifying it. Reading stops at the first problem that would cause sudo to reset or
+    /// truncate the file, which is reported alongside the records read up to that point.
+    pub fn inspect(from: &mut impl Read) -> io::Result<Inspection> {
+        let mut inspection = Inspection {
+            records: Vec::new(),
+            problem: None,
+        };
+
+        match read_u16(from)? {
+            // an empty file will be initialized the first time sudo uses it
+            None => return Ok(inspection),
+            Some(Self::MAGIC_NUM) => (),
+            Some(_) => {
+                inspection.problem = Some("invalid magic number".to_string());
+                return Ok(inspection);
+            }
+        }
+
+        match read_u16(from)? {
+            Some(Self::FILE_VERSION) => (),
+            Some(version) => {
+                inspection.problem = Some(format!("unsupported file version {version}"));
+                return Ok(inspection);
+            }
+            None => {
+                inspection.problem = Some("missing file version".to_string());
+                return Ok(inspection);
+            }
+        }
+
+        while let Some(record_length) = read_u16(from)? {
+            let mut buf = vec![0; record_length as usize];
+            let problem = if record_length == 0 {
+                "empty record".to_string()
+            } else if let Err(e) = from.read_exact(&mut buf) {
+                if e.kind() != io::ErrorKind::UnexpectedEof {
+                    return Err(e);
+                }
+                "incomplete record".to_string()
+            } else {
+                match SessionRecord::from_bytes(&buf) {
+                    Ok(record) => {
+                        inspection.records.push(record);
+                        continue;
+                    }
+                    Err(e) => format!("invalid record: {e}"),
+                }
+            };
+
+            inspection.problem = Some(format!(
+                "{problem} after {} valid records",
+                inspection.records.len()
+            ));
+            break;
+        }
+
+        Ok(inspection)
     }
 
     /// Write a new record at the current position in the file.
@@ -308,6 +471,14 @@ impl SessionRecordFile {
     }
 }
 
+/// The result of reading a session records file using [SessionRecordFile::inspect]
+#[derive(Debug)]
+pub struct Inspection {
+    pub records: Vec<SessionRecord>,
+    /// The first problem that was found, if the file is not valid
+    pub problem: Option<String>,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum TouchResult {
     /// The record was found and within the timeout, and it was refreshed
@@ -421,6 +592,14 @@ impl RecordScope {
     /// This should never produce an error since any actual error should just be
     /// ignored and no session record file should be used in that case.
     pub fn for_process(process: &Process) -> Option<RecordScope> {
+        // the start time of a process, which guards against reuse of its pid, is read from
+        // procfs on Linux; without it, a record cannot be tied to this session safely
+        #[cfg(target_os = "linux")]
+        if !super::ProcFs::is_available() {
+            auth_warn!("/proc is not mounted, so credentials will not be cached");
+            return None;
+        }
+
         let tty = Process::tty_device_id(WithProcess::Current);
         if let Ok(Some(tty_device)) = tty {
             if let Ok(init_time) = Process::starting_time(WithProcess::Other(process.session_id)) {
@@ -450,6 +629,16 @@ impl RecordScope {
     }
 }
 
+/// Read a little-endian u16, returning `None` at the end of the input
+fn read_u16(from: &mut impl Read) -> io::Result<Option<u16>> {
+    let mut bytes = [0; std::mem::size_of::<u16>()];
+    match from.read_exact(&mut bytes) {
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),
+        Ok(()) => Ok(Some(u16::from_le_bytes(bytes))),
+    }
+}
+
 fn write_bool(b: bool, target: &mut impl Write) -> io::Result<()> {
     let s: BoolStorage = if b { 0xFF } else { 0x00 };
     let bytes = s.to_le_bytes();
@@ -565,6 +754,22 @@ impl SessionRecord {
         }
     }
 
+    pub fn scope(&self) -> &RecordScope {
+        &self.scope
+    }
+
+    pub fn auth_user(&self) -> UserId {
+        self.auth_user
+    }
+
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
     /// Returns true if this record matches the specified scope and is for the
     /// specified target auth user.
     pub fn matches(&self, scope: &RecordScope, auth_user: &AuthUser) -> bool {
@@ -577,11 +782,24 @@ impl SessionRecord {
     pub fn written_between(&self, early_time: SystemTime, later_time: SystemTime) -> bool {
         early_time <= later_time && self.timestamp >= early_time && self.timestamp <= later_time
     }
+
+    /// Returns true if this record was written at most `timeout` before `now`.
+    pub fn written_within(&self, timeout: SudoersDuration, now: SystemTime) -> bool {
+        match timeout
+            .to_std()
+            .and_then(|timeout| now.checked_sub(timeout))
+        {
+            Some(early_time) => self.written_between(early_time, now),
+            // the timeout is unlimited, or reaches back further than time itself
+            None => self.timestamp <= now,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::path::Path;
+    use std::time::Duration;
 
     use super::*;
     use crate::common::{SudoPath, SudoString};
@@ -699,6 +917,22 @@ mod tests {
         assert!(!sample.written_between(some_time + dur, some_time - dur));
         assert!(!sample.written_between(some_time + dur, some_time + dur + dur));
         assert!(!sample.written_between(some_time - dur - dur, some_time - dur));
+
+        let timeout = SudoersDuration::from_secs(30);
+        assert!(sample.written_within(timeout, some_time));
+        assert!(sample.written_within(timeout, some_time + dur));
+        assert!(!sample.written_within(timeout, some_time + dur + dur));
+        assert!(!sample.written_within(timeout, some_time - dur));
+
+        let zero = SudoersDuration::zero();
+        assert!(sample.written_within(zero, some_time));
+        assert!(!sample.written_within(zero, some_time + dur));
+
+        let unlimited = SudoersDuration::infinite();
+        assert!(sample.written_within(unlimited, some_time + dur + dur));
+        assert!(!sample.written_within(unlimited, some_time - dur));
+        let long_ago = SudoersDuration::from_secs(u64::MAX);
+        assert!(sample.written_within(long_ago, some_time + dur));
     }
 
     fn tempfile_with_data(data: &[u8]) -> io::Result<File> {
@@ -719,7 +953,7 @@ mod tests {
     fn session_record_file_header_checks() {
         // valid header should remain valid
         let c = tempfile_with_data(&[0xD0, 0x50, 0x02, 0x00]).unwrap();
-        let timeout = Duration::from_secs(30);
+        let timeout = SudoersDuration::from_secs(30);
         assert!(SessionRecordFile::new(TEST_USER_ID, c.try_clone().unwrap(), timeout).is_ok());
         let v = data_from_tempfile(c).unwrap();
         assert_eq!(&v[..], &[0xD0, 0x50, 0x02, 0x00]);
@@ -745,7 +979,7 @@ mod tests {
 
     #[test]
     fn can_create_and_update_valid_file() {
-        let timeout = Duration::from_secs(30);
+        let timeout = SudoersDuration::from_secs(30);
         let c = tempfile_with_data(&[]).unwrap();
         let mut srf =
             SessionRecordFile::new(TEST_USER_ID, c.try_clone().unwrap(), timeout).unwrap();
@@ -775,11 +1009,107 @@ mod tests {
         };
         assert_ne!(old_time, new_time);
 
-        // reset the file
-        assert!(srf.reset().is_ok());
+        // disabled records are no longer found
+        assert!(srf.disable_all().is_ok());
+        let res = srf.touch(tty_scope, &auth_user).unwrap();
+        assert!(matches!(res, TouchResult::NotFound));
+    }
+
+    #[test]
+    fn only_root_may_manage_records_of_others() {
+        let (alice, bob) = (UserId::new(1001), UserId::new(1002));
+
+        assert!(SessionRecordFile::authorize(alice, alice).is_ok());
+        assert!(SessionRecordFile::authorize(UserId::ROOT, alice).is_ok());
+
+        let denied = |result: io::Result<()>| {
+            result.is_err_and(|err| err.kind() == io::ErrorKind::PermissionDenied)
+        };
+        assert!(denied(SessionRecordFile::authorize(alice, bob)));
+        assert!(denied(SessionRecordFile::authorize(alice, UserId::ROOT)));
+        assert!(denied(SessionRecordFile::remove_for_uid(alice, bob)));
+        assert!(denied(SessionRecordFile::disable_for_uid(alice, bob, None)));
+    }
 
-        // after all this the data should be just an empty header
-        let data = data_from_tempfile(c).unwrap();
-        assert_eq!(&data, &[0xD0, 0x50, 0x02, 0x00]);
+    #[test]
+    fn can_inspect_and_remove_records() {
+        let timeout = SudoersDuration::from_secs(30);
+        let c = tempfile_with_data(&[]).unwrap();
+        let mut srf =
+            SessionRecordFile::new(TEST_USER_ID, c.try_clone().unwrap(), timeout).unwrap();
+        let scopes = [1, 2, 3].map(|pid| RecordScope::Ppid {
+            group_pid: ProcessId::new(pid),
+            session_pid: ProcessId::new(pid),
+            init_time: ProcessCreateTime::new(0, 0),
+        });
+        for scope in scopes {
+            srf.create(scope, &auth_user_from_uid(2424)).unwrap();
+        }
+
+        let inspect = |mut file: &File| {
+            file.rewind().unwrap();
+            SessionRecordFile::inspect(&mut file).unwrap()
+        };
+
+        let inspection = inspect(&c);
+        assert_eq!(inspection.problem, None);
+        assert_eq!(
+            inspection
+                .records
+                .iter()
+                .map(|r| *r.scope())
+                .collect::<Vec<_>>(),
+            scopes
+        );
+        assert!(inspection.records.iter().all(|r| r.is_enabled()));
+        assert_eq!(inspection.records[0].auth_user(), UserId::new(2424));
+
+        // removing a record leaves the others intact
+        let removed = srf.remove(1).unwrap().unwrap();
+        assert_eq!(*removed.scope(), scopes[1]);
+        assert!(srf.remove(2).unwrap().is_none());
+        let inspection = inspect(&c);
+        assert_eq!(
+            inspection
+                .records
+                .iter()
+                .map(|r| *r.scope())
+                .collect::<Vec<_>>(),
+            [scopes[0], scopes[2]]
+        );
+
+        // a damaged record is reported, but the file is left alone
+        let mut data = data_from_tempfile(c.try_clone().unwrap()).unwrap();
+        data.extend([0x10, 0x00, 0x01]);
+        let c = tempfile_with_data(&data).unwrap();
+        let inspection = inspect(&c);
+        assert_eq!(inspection.records.len(), 2);
+        assert_eq!(
+            inspection.problem.as_deref(),
+            Some("incomplete record after 2 valid records")
+        );
+        assert_eq!(data_from_tempfile(c).unwrap(), data);
+    }
+
+    #[test]
+    fn inspecting_headers() {
+        let inspect = |data: &[u8]| SessionRecordFile::inspect(&mut &data[..]).unwrap();
+
+        assert_eq!(inspect(&[]).problem, None);
+        assert_eq!(inspect(&[0xD0, 0x50, 0x02, 0x00]).problem, None);
+        assert_eq!(
+            inspect(&[0xAB, 0xBA]).problem.as_deref(),
+            Some("invalid magic number")
+        );
+        assert_eq!(
+            inspect(&[0xD0, 0x50, 0xAB, 0xBA]).problem.as_deref(),
+            Some("unsupported file version 47787")
+        );
+        assert_eq!(
+            inspect(&[0xD0, 0x50, 0x02, 0x00, 0x00, 0x00])
+                .problem
+                .as_deref(),
+            Some("empty record after 0 valid records")
+        );
     }
 }
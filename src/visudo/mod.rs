@@ -130,6 +130,7 @@ fn check(file_arg: Option<&str>, perms: bool, owner: bool) -> io::Result<()> {
         message,
         source,
         location,
+        ..
     } in errors
     {
         let path = source.as_deref().unwrap_or(&sudoers_path);
@@ -315,6 +316,7 @@ fn edit_sudoers_file(
                 message,
                 source,
                 location,
+                ..
             } in errors
             {
                 let path = source.as_deref().unwrap_or(sudoers_path);
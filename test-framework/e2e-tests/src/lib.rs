@@ -1,5 +1,6 @@
 #![cfg(test)]
 
+mod pam;
 mod pty;
 mod regression;
 mod su;
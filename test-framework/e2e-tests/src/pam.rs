@@ -0,0 +1,34 @@
+use std::time::{Duration, Instant};
+
+use sudo_test::{Command, Env};
+
+use crate::USERNAME;
+
+#[test]
+fn module_that_hangs_is_given_up_on() {
+    // 0.05 minutes is three seconds
+    let env = Env(["ALL ALL=(ALL:ALL) ALL", "Defaults pam_auth_timeout=0.05"])
+        .user(USERNAME)
+        .file(
+            "/etc/pam.d/sudo",
+            "auth required pam_exec.so /bin/sleep 1000\nauth sufficient pam_permit.so",
+        )
+        .build();
+
+    let start = Instant::now();
+    let output = Command::new("sudo")
+        .arg("true")
+        .as_user(USERNAME)
+        .output(&env);
+
+    output.assert_exit_code(1);
+    assert_contains!(
+        output.stderr(),
+        "authentication timed out: the auth modules of PAM service 'sudo' did not return (pam_exec.so, pam_permit.so)"
+    );
+    assert_contains!(
+        output.stderr(),
+        "PAM modules did not finish authenticating in time"
+    );
+    assert!(start.elapsed() < Duration::from_secs(60));
+}
@@ -1,6 +1,6 @@
 use std::collections::{HashMap, HashSet};
 
-use sudo_test::{Child, Command, Env};
+use sudo_test::{Child, Command, Env, TextFile};
 
 #[track_caller]
 pub fn parse_env_output(env_output: &str) -> HashMap<&str, &str> {
@@ -52,6 +52,54 @@ impl Drop for Rsyslogd<'_> {
     }
 }
 
+/// A stand-in for `sendmail` that appends its arguments and the message it reads from stdin to
+/// a mailbox file
+pub struct MockMailer;
+
+impl MockMailer {
+    pub const PATH: &'static str = "/usr/local/sbin/mock-mailer";
+    const MAILBOX: &'static str = "/tmp/mailbox";
+
+    /// the mailer executable; install it at [`MockMailer::PATH`]
+    pub fn file() -> TextFile {
+        TextFile(format!(
+            "#!/bin/sh\necho \"$@\" >> {mailbox}\ncat >> {mailbox}",
+            mailbox = Self::MAILBOX
+        ))
+        .chmod("755")
+    }
+
+    /// returns the contents of the mailbox, waiting a little for mail to arrive as sudo may
+    /// send it asynchronously
+    #[track_caller]
+    pub fn mailbox(env: &Env) -> String {
+        let path = Self::MAILBOX;
+        Command::new("sh")
+            .arg("-c")
+            .arg(format!(
+                "for _ in $(seq 1 20); do [ -s {path} ] && break; sleep 0.1; done; \
+                 [ ! -f {path} ] || cat {path}"
+            ))
+            .output(env)
+            .stdout()
+    }
+}
+
+#[test]
+fn mock_mailer_works() {
+    let env = Env("").file(MockMailer::PATH, MockMailer::file()).build();
+
+    Command::new(MockMailer::PATH)
+        .args(["-t", "root"])
+        .stdin("Subject: hello")
+        .output(&env)
+        .assert_success();
+
+    let mailbox = MockMailer::mailbox(&env);
+    assert_contains!(mailbox, "-t root");
+    assert_contains!(mailbox, "Subject: hello");
+}
+
 #[test]
 #[cfg_attr(
     target_os = "freebsd",
@@ -8,6 +8,7 @@ extern crate sudo_test;
 mod helpers;
 mod su;
 mod sudo;
+mod sudo_ts;
 mod sudoedit;
 mod visudo;
 
@@ -5,6 +5,8 @@ mod cli;
 mod env_reset;
 mod flag_background;
 mod flag_chdir;
+mod flag_close_from;
+mod flag_command_timeout;
 mod flag_group;
 mod flag_help;
 mod flag_list;
@@ -13,6 +15,7 @@ mod flag_non_interactive;
 mod flag_preserve_environment;
 mod flag_prompt;
 mod flag_shell;
+mod flag_status_json;
 mod flag_user;
 mod flag_version;
 mod lecture;
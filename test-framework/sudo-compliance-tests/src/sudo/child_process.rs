@@ -1,7 +1,7 @@
 use pretty_assertions::assert_eq;
-use sudo_test::{Command, Env};
+use sudo_test::{Command, Env, TextFile};
 
-use crate::SUDOERS_ROOT_ALL_NOPASSWD;
+use crate::{SUDOERS_NOT_USE_PTY, SUDOERS_ROOT_ALL_NOPASSWD};
 
 mod signal_handling;
 
@@ -17,6 +17,69 @@ fn sudo_forwards_childs_exit_code() {
     output.assert_exit_code(expected);
 }
 
+#[test]
+fn script_without_shebang_is_run_by_the_shell() {
+    let path = "/root/script";
+    let env = Env(SUDOERS_ROOT_ALL_NOPASSWD)
+        .file(path, TextFile("echo hello from $0").chmod("755"))
+        .build();
+
+    let output = Command::new("sudo").arg(path).output(&env);
+
+    assert_eq!(format!("hello from {path}"), output.stdout());
+}
+
+#[test]
+fn script_with_missing_interpreter_is_reported() {
+    let path = "/root/script";
+    let env = Env(SUDOERS_ROOT_ALL_NOPASSWD)
+        .file(path, TextFile("#!/nonexistent/sh\necho hello").chmod("755"))
+        .build();
+
+    let output = Command::new("sudo").arg(path).output(&env);
+
+    output.assert_exit_code(1);
+    let stderr = output.stderr();
+    assert_contains!(stderr, format!("unable to execute {path}"));
+    if !sudo_test::is_original_sudo() {
+        assert_contains!(stderr, "/nonexistent/sh: bad interpreter");
+    }
+}
+
+#[test]
+fn non_executable_file_is_reported() {
+    let path = "/root/script";
+    let env = Env(SUDOERS_ROOT_ALL_NOPASSWD)
+        .file(path, TextFile("#!/bin/sh\necho hello").chmod("644"))
+        .build();
+
+    let output = Command::new("sudo").arg(path).output(&env);
+
+    if sudo_test::is_original_sudo() {
+        output.assert_exit_code(1);
+        assert_contains!(output.stderr(), "command not found");
+    } else {
+        output.assert_exit_code(126);
+        assert_contains!(
+            output.stderr(),
+            format!("unable to execute {path}: Permission denied")
+        );
+    }
+}
+
+#[test]
+fn sudo_passes_a_very_long_argument_to_the_child() {
+    let env = Env(SUDOERS_ROOT_ALL_NOPASSWD).build();
+
+    let argument = "0123456789".repeat(10_000);
+    let output = Command::new("sudo")
+        .args(["sh", "-c", "printf %s \"$1\" | wc -c", "sh"])
+        .arg(&argument)
+        .output(&env);
+
+    assert_eq!(argument.len().to_string(), output.stdout().trim());
+}
+
 #[test]
 fn sudo_forwards_childs_stdout() {
     let env = Env(SUDOERS_ROOT_ALL_NOPASSWD).build();
@@ -56,3 +119,63 @@ fn sudo_forwards_stdin_to_child() {
 
     assert_eq!(expected, actual);
 }
+
+#[test]
+fn command_writing_to_a_closed_pipe_is_terminated_quietly() {
+    let env = Env(SUDOERS_ROOT_ALL_NOPASSWD).build();
+
+    // without a terminal, and with a terminal so that output is relayed through a pty
+    for tty in [false, true] {
+        let output = Command::new("bash")
+            .args(["-c", "sudo yes | head -n 1; echo \"${PIPESTATUS[@]}\""])
+            .tty(tty)
+            .output(&env);
+
+        // with a terminal, any error output would end up in stdout
+        if !tty {
+            assert!(output.stderr().is_empty());
+        }
+        // `yes` is killed by SIGPIPE, which sudo reports as 128 + 13
+        let stdout = output.stdout().replace("\r\n", "\n");
+        assert_eq!(stdout.trim_end(), "y\n141 0");
+    }
+}
+
+/// Run `script` on a terminal of its own (created by socat), and type `hello` on that terminal
+/// once it has had some time to start
+fn run_on_new_terminal(script: &str) -> String {
+    let path = "/tmp/script.sh";
+    let env = Env([SUDOERS_ROOT_ALL_NOPASSWD, SUDOERS_NOT_USE_PTY])
+        .file(path, script)
+        .build();
+
+    Command::new("sh")
+        .args([
+            "-c",
+            &format!("(sleep 1; echo hello; sleep 2) | socat - SYSTEM:'sh {path}',pty,setsid,ctty"),
+        ])
+        .output(&env)
+        .stdout()
+}
+
+#[test]
+fn command_can_read_from_the_terminal_without_pty() {
+    let stdout = run_on_new_terminal("sudo sh -c 'read x; echo \"got $x\"'; echo \"exit $?\"");
+
+    assert_contains!(stdout, "got hello");
+    assert_contains!(stdout, "exit 0");
+}
+
+#[test]
+fn foreground_is_given_back_to_the_shell_without_pty() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    // an interactive bash takes over the terminal, and is killed before it can give it back
+    let stdout = run_on_new_terminal(
+        "sudo bash --norc -ic 'kill -9 $$' 2>/dev/null; read x; echo \"got $x\"",
+    );
+
+    assert_contains!(stdout, "got hello");
+}
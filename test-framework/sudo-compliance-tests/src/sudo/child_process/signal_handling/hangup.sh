@@ -0,0 +1,11 @@
+# Run sudo as the session leader of a fresh pty. `socat` closes the leader side
+# of that pty once its stdin reaches EOF, which hangs up the terminal of sudo.
+sleep 0.5 | socat -t 0.1 - SYSTEM:'sh /root/run-sudo.sh',pty
+
+# Wait for sudo to exit.
+for _ in $(seq 1 50); do
+	[ -s /tmp/sudo-status ] && break
+	sleep 0.1
+done
+
+cat /tmp/signal /tmp/sudo-status
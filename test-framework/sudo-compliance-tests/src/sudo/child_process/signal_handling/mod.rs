@@ -207,6 +207,27 @@ fn sigchld_is_ignored(tty: bool) {
     assert_eq!(expected, actual);
 }
 
+// when the user's terminal is hung up, the command receives SIGHUP and sudo only exits after
+// the command has been reaped
+#[test]
+#[cfg_attr(
+    target_os = "freebsd",
+    ignore = "setsid(1) is not available on FreeBSD"
+)]
+fn terminal_hangup_is_forwarded_to_command() {
+    let hangup = "/root/hangup.sh";
+    let env = Env([SUDOERS_ROOT_ALL_NOPASSWD, SUDOERS_USE_PTY])
+        .file(hangup, include_str!("hangup.sh"))
+        .file("/root/run-sudo.sh", include_str!("run-sudo.sh"))
+        .file("/root/expects-signal.sh", include_str!("expects-signal.sh"))
+        .build();
+
+    let output = Command::new("sh").arg(hangup).output(&env).stdout();
+
+    // the command saw the hangup and sudo exited with its exit status
+    assert_eq!("got signal\n0", output);
+}
+
 fn sigwinch_works(use_pty: bool) {
     let print_sizes = "/root/print-sizes.sh";
     let change_size = "/root/change-size.sh";
@@ -0,0 +1,5 @@
+# `socat` sends SIGTERM to this script when it closes down; ignore that so the
+# exit status of sudo can still be recorded.
+trap '' TERM
+setsid -w -c sudo sh /root/expects-signal.sh HUP > /tmp/signal
+echo $? > /tmp/sudo-status
@@ -1,6 +1,6 @@
-use sudo_test::{Command, Env};
+use sudo_test::{Command, Env, TextFile, User};
 
-use crate::{Result, SUDOERS_ALL_ALL_NOPASSWD};
+use crate::{Result, SUDOERS_ALL_ALL_NOPASSWD, USERNAME};
 
 macro_rules! assert_snapshot {
     ($($tt:tt)*) => {
@@ -159,3 +159,71 @@ fn miscategorized_reset_timestamp_action() {
 
     output.assert_exit_code(1);
 }
+
+const PRINT_ARGV: &str = "/usr/local/bin/print-argv";
+
+/// prints the user it runs as, followed by each of its arguments in brackets on a line of its own
+fn print_argv() -> TextFile {
+    TextFile("#!/bin/sh\nid -un\nfor arg in \"$@\"; do printf '[%s]\\n' \"$arg\"; done")
+        .chmod("755")
+}
+
+#[test]
+fn arguments_after_the_command_are_passed_verbatim() {
+    let env = Env(SUDOERS_ALL_ALL_NOPASSWD)
+        .file(PRINT_ARGV, print_argv())
+        .build();
+
+    let stdout = Command::new("sudo")
+        .args([
+            "--",
+            "print-argv",
+            "-l",
+            "--",
+            "--help",
+            "-u",
+            "root",
+            "a  b",
+        ])
+        .output(&env)
+        .stdout();
+
+    assert_eq!("root\n[-l]\n[--]\n[--help]\n[-u]\n[root]\n[a  b]", stdout);
+}
+
+#[test]
+fn command_that_looks_like_an_option_after_dash_dash() {
+    let env = Env(SUDOERS_ALL_ALL_NOPASSWD)
+        .user(USERNAME)
+        .file("/usr/local/bin/-weird-binary", print_argv())
+        .build();
+
+    let stdout = Command::new("sudo")
+        .args(["-u", USERNAME, "--", "-weird-binary", "--help"])
+        .output(&env)
+        .stdout();
+
+    assert_eq!(format!("{USERNAME}\n[--help]"), stdout);
+}
+
+#[test]
+fn dash_dash_of_the_command_survives_the_shell_command_line() {
+    for flag in ["-i", "-s"] {
+        let env = Env(SUDOERS_ALL_ALL_NOPASSWD)
+            .user(User(USERNAME).shell("/bin/sh").create_home_directory())
+            .file(PRINT_ARGV, print_argv())
+            .build();
+
+        let stdout = Command::new("sudo")
+            .args(["-u", USERNAME, flag, "--", PRINT_ARGV])
+            .args(["a", "--", "-x", "--", "b c"])
+            .output(&env)
+            .stdout();
+
+        assert_eq!(
+            format!("{USERNAME}\n[a]\n[--]\n[-x]\n[--]\n[b c]"),
+            stdout,
+            "{flag}"
+        );
+    }
+}
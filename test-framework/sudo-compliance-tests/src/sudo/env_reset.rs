@@ -30,9 +30,7 @@ fn some_vars_are_set() {
     let mut sudo_env = helpers::parse_env_output(&stdout);
 
     // # man sudo
-    if sudo_test::is_original_sudo() {
-        assert_eq!(Some("/var/mail/root"), sudo_env.remove("MAIL"));
-    }
+    assert_eq!(Some("/var/mail/root"), sudo_env.remove("MAIL"));
     // "Set to the home directory of the target user"
     assert_eq!(Some("/root"), sudo_env.remove("HOME"));
 
@@ -133,12 +131,10 @@ fn user_dependent_vars() {
         Some(format!("/home/{USERNAME}")).as_deref(),
         sudo_env.remove("HOME")
     );
-    if sudo_test::is_original_sudo() {
-        assert_eq!(
-            Some(format!("/var/mail/{USERNAME}")).as_deref(),
-            sudo_env.remove("MAIL")
-        );
-    }
+    assert_eq!(
+        Some(format!("/var/mail/{USERNAME}")).as_deref(),
+        sudo_env.remove("MAIL")
+    );
     assert_eq!(Some(shell_path), sudo_env.remove("SHELL"));
     assert_eq!(Some(USERNAME), sudo_env.remove("LOGNAME"));
     assert_eq!(Some(USERNAME), sudo_env.remove("USER"));
@@ -204,9 +200,7 @@ fn some_vars_are_preserved() {
 
     // not preserved
     assert_eq!(Some("/root"), sudo_env.remove("HOME"));
-    if sudo_test::is_original_sudo() {
-        assert_eq!(Some("/var/mail/root"), sudo_env.remove("MAIL"));
-    }
+    assert_eq!(Some("/var/mail/root"), sudo_env.remove("MAIL"));
     assert_eq!(Some("/bin/sh"), sudo_env.remove("SHELL"));
     assert_eq!(Some("root"), sudo_env.remove("LOGNAME"));
     assert_eq!(Some("root"), sudo_env.remove("USER"));
@@ -255,3 +249,132 @@ fn vars_whose_values_start_with_parentheses_are_removed() {
     assert_eq!(Some(SUDO_ENV_DEFAULT_PATH), sudo_env.get("PATH").copied());
     assert_eq!(Some(SUDO_ENV_DEFAULT_TERM), sudo_env.get("TERM").copied());
 }
+
+#[test]
+fn sudo_command_includes_the_arguments() {
+    let env = Env(SUDOERS_ROOT_ALL_NOPASSWD).build();
+
+    let env_abs_path = Command::new("which").arg("env").output(&env).stdout();
+
+    let stdout = Command::new("sudo")
+        .args(["env", "-u", "FOO", "printenv", "SUDO_COMMAND"])
+        .output(&env)
+        .stdout();
+
+    assert_eq!(
+        format!("{env_abs_path} -u FOO printenv SUDO_COMMAND"),
+        stdout
+    );
+}
+
+#[test]
+fn sudo_command_joins_arguments_with_spaces_verbatim() {
+    let env = Env(SUDOERS_ROOT_ALL_NOPASSWD).build();
+
+    let sh_abs_path = Command::new("which").arg("sh").output(&env).stdout();
+
+    let script = r#"printf '%s\n' "$SUDO_COMMAND""#;
+    let stdout = Command::new("sudo")
+        .args(["sh", "-c", script, "sh", "an argument", "/tmp"])
+        .output(&env)
+        .stdout();
+
+    // arguments that contain spaces are not quoted, so they cannot be told apart
+    assert_eq!(
+        format!("{sh_abs_path} -c {script} sh an argument /tmp"),
+        stdout
+    );
+}
+
+#[test]
+fn dangerous_vars_cannot_be_set_from_commandline_without_setenv() {
+    let env = Env("ALL ALL=(ALL:ALL) NOPASSWD: /usr/bin/env").build();
+
+    let output = Command::new("sudo")
+        .args(["LD_PRELOAD=/evil.so", "env"])
+        .output(&env);
+
+    output.assert_exit_code(1);
+    assert_contains!(
+        output.stderr(),
+        "you are not allowed to set the following environment variables: LD_PRELOAD"
+    );
+}
+
+#[test]
+fn setenv_lifts_all_restrictions_on_vars_from_commandline() {
+    let env = Env("ALL ALL=(ALL:ALL) NOPASSWD: SETENV: /usr/bin/env").build();
+
+    let stdout = Command::new("sudo")
+        .args(["MYAPP_VAR=hello", "env"])
+        .output(&env)
+        .stdout();
+    let sudo_env = helpers::parse_env_output(&stdout);
+
+    assert_eq!(Some("hello"), sudo_env.get("MYAPP_VAR").copied());
+
+    // variables set on the command line are not checked against env_check, env_delete or
+    // env_keep, not even if they start with a dangerous prefix; sudoers(5) warns that only
+    // trusted users should be allowed to set variables in this manner
+    let stdout = Command::new("sudo")
+        .args(["LD_PRELOAD=/evil.so", "env"])
+        .output(&env)
+        .stdout();
+    let sudo_env = helpers::parse_env_output(&stdout);
+
+    assert_eq!(Some("/evil.so"), sudo_env.get("LD_PRELOAD").copied());
+}
+
+#[test]
+fn mail_can_be_preserved_except_with_login_shell() {
+    let env = Env([SUDOERS_ROOT_ALL_NOPASSWD, "Defaults env_keep += MAIL"]).build();
+
+    let stdout = Command::new("env")
+        .args(["MAIL=my-mail", "sudo", "printenv", "MAIL"])
+        .output(&env)
+        .stdout();
+    assert_eq!("my-mail", stdout);
+
+    let stdout = Command::new("env")
+        .args(["MAIL=my-mail", "sudo", "-i", "printenv", "MAIL"])
+        .output(&env)
+        .stdout();
+    assert_eq!("/var/mail/root", stdout);
+}
+
+#[test]
+fn mail_is_in_mail_dir_from_login_defs() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = Env(SUDOERS_ROOT_ALL_NOPASSWD)
+        .file("/etc/login.defs", "MAIL_DIR /var/spool/mail")
+        .user(USERNAME)
+        .build();
+
+    let stdout = Command::new("sudo")
+        .args(["-u", USERNAME, "printenv", "MAIL"])
+        .output(&env)
+        .stdout();
+    assert_eq!(format!("/var/spool/mail/{USERNAME}"), stdout);
+}
+
+#[test]
+fn sudo_user_is_always_set_to_the_invoking_user() {
+    // also when the invoking environment comes from another sudo call, and even if SUDO_USER
+    // is in env_keep
+    for defaults in ["", "Defaults env_keep += SUDO_USER"] {
+        let env = Env(["ALL ALL=(ALL:ALL) NOPASSWD: ALL", defaults])
+            .user(USERNAME)
+            .build();
+
+        let stdout = Command::new("env")
+            .args(["SUDO_USER=hacker", "sudo", "printenv", "SUDO_USER"])
+            .as_user(USERNAME)
+            .output(&env)
+            .stdout();
+
+        assert_eq!(USERNAME, stdout);
+    }
+}
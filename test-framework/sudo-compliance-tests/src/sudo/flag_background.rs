@@ -27,6 +27,35 @@ fn runs_in_background() {
         .assert_success();
 }
 
+#[test]
+fn returns_immediately_while_the_command_keeps_running() {
+    let env = Env(SUDOERS_ALL_ALL_NOPASSWD).build();
+
+    // the command must not hold on to our stdout, or we would wait for it after all
+    let stdout = Command::new("sh")
+        .args([
+            "-c",
+            "start=$(date +%s)
+             sudo -b sleep 30 < /dev/null > /dev/null 2>&1
+             echo $(( $(date +%s) - start ))
+             pgrep -x sleep",
+        ])
+        .output(&env)
+        .stdout();
+
+    let (elapsed, pid) = stdout.split_once('\n').unwrap();
+    assert!(
+        elapsed.parse::<u64>().unwrap() <= 1,
+        "sudo -b took {elapsed}s"
+    );
+    assert!(pid.trim().parse::<u32>().is_ok(), "{stdout}");
+
+    Command::new("kill")
+        .arg(pid.trim())
+        .output(&env)
+        .assert_success();
+}
+
 #[test]
 fn stdin_pipe() {
     if sudo_test::sudo_version() < sudo_test::ogsudo("1.9.18") {
@@ -64,3 +93,61 @@ fn stdin_pipe() {
     assert_contains!(term_background, " 1 -> /tmp/output");
     assert_contains!(term_background, " 2 -> /dev/pts/0");
 }
+
+#[test]
+fn does_not_consume_terminal_input() {
+    for use_pty in ["use_pty", "!use_pty"] {
+        if sudo_test::is_original_sudo() && use_pty == "!use_pty" {
+            // ogsudo leaves the terminal as stdin; reading from it fails with EIO
+            continue;
+        }
+
+        let env = Env([SUDOERS_ALL_ALL_NOPASSWD, &format!("Defaults {use_pty}")]).build();
+
+        Command::new("sudo")
+            .args([
+                "-b",
+                "sh",
+                "-c",
+                "ls -l /proc/self/fd/0 > /tmp/stdin; cat > /tmp/read; echo $? > /tmp/status",
+            ])
+            .tty(true)
+            .output(&env)
+            .assert_success();
+
+        let stdout = Command::new("sh")
+            .args([
+                "-c",
+                "until [ -s /tmp/status ]; do sleep 0.1; done; cat /tmp/status /tmp/read /tmp/stdin",
+            ])
+            .output(&env)
+            .stdout();
+
+        // cat reached the end of its input immediately, without reading anything
+        let (status, stdin) = stdout.split_once('\n').unwrap();
+        assert_eq!(status, "0", "{use_pty}");
+        if use_pty == "use_pty" {
+            assert_contains!(stdin, " 0 -> pipe:");
+        } else {
+            assert_contains!(stdin, " 0 -> /dev/null");
+        }
+    }
+}
+
+#[test]
+fn output_arrives_after_sudo_exits() {
+    let env = Env(SUDOERS_ALL_ALL_NOPASSWD).build();
+
+    let stdout = Command::new("sh")
+        .args([
+            "-c",
+            "sudo -b sh -c 'sleep 1; echo done' > /tmp/output
+             echo \"before: $(cat /tmp/output)\"
+             until [ -s /tmp/output ]; do sleep 0.1; done
+             echo \"after: $(cat /tmp/output)\"",
+        ])
+        .output(&env)
+        .stdout();
+
+    assert_eq!(stdout, "before: \nafter: done");
+}
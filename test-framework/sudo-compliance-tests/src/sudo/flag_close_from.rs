@@ -0,0 +1,84 @@
+use sudo_test::{Command, Env};
+
+use crate::SUDOERS_ALL_ALL_NOPASSWD;
+
+// `-C` is only honoured by sudo if the policy allows it
+const SUDOERS_CLOSEFROM_OVERRIDE: &str = "Defaults closefrom_override";
+
+// sudo-rs does not support `-C`: the command only ever inherits the standard IO descriptors
+const UNSUPPORTED: &str = "invalid option '-C'";
+
+#[test]
+#[cfg_attr(target_os = "freebsd", ignore = "FreeBSD does not mount /proc")]
+fn closes_descriptors_from_the_given_number() {
+    let env = Env([SUDOERS_ALL_ALL_NOPASSWD, SUDOERS_CLOSEFROM_OVERRIDE]).build();
+
+    // `$$` is the shell started by sudo; listing its descriptors (rather than those of `ls`)
+    // avoids counting the one `ls` uses to read the directory
+    let output = Command::new("bash")
+        .args([
+            "-c",
+            "exec 3</etc/hosts 4</etc/hosts 5</etc/hosts 6</etc/hosts 7</etc/hosts \
+                  8</etc/hosts 9</etc/hosts 10</etc/hosts 11</etc/hosts 12</etc/hosts
+             sudo -C 5 sh -c 'ls /proc/$$/fd'",
+        ])
+        .output(&env);
+
+    if sudo_test::is_original_sudo() {
+        let stdout = output.stdout();
+        let fds = stdout.split_whitespace().collect::<Vec<_>>();
+        assert_eq!(fds, ["0", "1", "2", "3", "4"]);
+    } else {
+        output.assert_exit_code(1);
+        assert_contains!(output.stderr(), UNSUPPORTED);
+    }
+}
+
+#[test]
+fn rejects_closing_the_standard_descriptors() {
+    let env = Env([SUDOERS_ALL_ALL_NOPASSWD, SUDOERS_CLOSEFROM_OVERRIDE]).build();
+
+    let output = Command::new("sudo")
+        .args(["-C", "2", "echo", "hello"])
+        .output(&env);
+
+    output.assert_exit_code(1);
+    assert!(output.stdout_unchecked().is_empty());
+
+    let diagnostic = if sudo_test::is_original_sudo() {
+        "the argument to -C must be a number greater than or equal to 3"
+    } else {
+        UNSUPPORTED
+    };
+    assert_contains!(output.stderr(), diagnostic);
+}
+
+#[test]
+fn is_rejected_without_closefrom_override() {
+    let env = Env(SUDOERS_ALL_ALL_NOPASSWD).build();
+
+    let output = Command::new("sudo").args(["-C", "5", "true"]).output(&env);
+
+    output.assert_exit_code(1);
+
+    let diagnostic = if sudo_test::is_original_sudo() {
+        "you are not permitted to use the -C option"
+    } else {
+        UNSUPPORTED
+    };
+    assert_contains!(output.stderr(), diagnostic);
+}
+
+#[test]
+fn is_accepted_with_closefrom_override() {
+    let env = Env([SUDOERS_ALL_ALL_NOPASSWD, SUDOERS_CLOSEFROM_OVERRIDE]).build();
+
+    let output = Command::new("sudo").args(["-C", "5", "true"]).output(&env);
+
+    if sudo_test::is_original_sudo() {
+        output.assert_success();
+    } else {
+        output.assert_exit_code(1);
+        assert_contains!(output.stderr(), UNSUPPORTED);
+    }
+}
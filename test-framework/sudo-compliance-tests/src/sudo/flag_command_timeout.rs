@@ -0,0 +1,108 @@
+use sudo_test::{Command, Env};
+
+use crate::{SUDOERS_ALL_ALL_NOPASSWD, SUDOERS_NOT_USE_PTY, USERNAME};
+
+const SUDOERS_USER_COMMAND_TIMEOUTS: &str = "Defaults user_command_timeouts";
+
+/// Run a command that leaves a background process behind using `sudo -T 5`, and return the
+/// exit status of sudo and the number of processes from the command that are still around.
+fn run_with_timeout(sudoers: &[&str]) -> (String, usize) {
+    let env = Env(sudoers.join("\n")).build();
+
+    let stdout = Command::new("sh")
+        .args([
+            "-c",
+            "sudo -T 5 sh -c 'sleep 100 & wait'; echo status=$?; ps -eo args",
+        ])
+        .tty(true)
+        .output(&env)
+        .stdout();
+
+    let status = stdout
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("status="))
+        .expect("sudo did not finish")
+        .to_string();
+    let leftovers = stdout
+        .lines()
+        .filter(|line| line.trim() == "sleep 100")
+        .count();
+
+    (status, leftovers)
+}
+
+#[test]
+fn no_processes_are_left_after_timeout_with_pty() {
+    let (status, leftovers) = run_with_timeout(&[
+        SUDOERS_ALL_ALL_NOPASSWD,
+        SUDOERS_USER_COMMAND_TIMEOUTS,
+        "Defaults use_pty",
+    ]);
+
+    assert_ne!(status, "0");
+    assert_eq!(leftovers, 0);
+}
+
+#[test]
+fn no_processes_are_left_after_timeout_without_pty() {
+    // the original sudo only terminates the command itself if there is no pty
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let (status, leftovers) = run_with_timeout(&[
+        SUDOERS_ALL_ALL_NOPASSWD,
+        SUDOERS_USER_COMMAND_TIMEOUTS,
+        SUDOERS_NOT_USE_PTY,
+    ]);
+
+    assert_ne!(status, "0");
+    assert_eq!(leftovers, 0);
+}
+
+#[test]
+fn command_timeout_applies_without_flag() {
+    let env = Env([SUDOERS_ALL_ALL_NOPASSWD, "Defaults command_timeout=2"]).build();
+
+    let output = Command::new("sudo")
+        .args(["sh", "-c", "sleep 10; echo finished"])
+        .output(&env);
+
+    assert!(!output.status().success());
+    assert_not_contains!(output.stdout_unchecked(), "finished");
+}
+
+#[test]
+fn user_may_not_set_timeout_by_default() {
+    let env = Env(SUDOERS_ALL_ALL_NOPASSWD).user(USERNAME).build();
+
+    let output = Command::new("sudo")
+        .args(["-T", "5", "true"])
+        .as_user(USERNAME)
+        .output(&env);
+
+    output.assert_exit_code(1);
+    let diagnostic = if sudo_test::is_original_sudo() {
+        "sorry, you are not allowed set a command timeout"
+    } else {
+        "you are not allowed to set a command timeout"
+    };
+    assert_contains!(output.stderr(), diagnostic);
+}
+
+#[test]
+fn user_timeout_does_not_extend_command_timeout() {
+    let env = Env([
+        SUDOERS_ALL_ALL_NOPASSWD,
+        SUDOERS_USER_COMMAND_TIMEOUTS,
+        "Defaults command_timeout=2",
+    ])
+    .build();
+
+    let output = Command::new("sudo")
+        .args(["-T", "1m", "sh", "-c", "sleep 10; echo finished"])
+        .output(&env);
+
+    assert!(!output.status().success());
+    assert_not_contains!(output.stdout_unchecked(), "finished");
+}
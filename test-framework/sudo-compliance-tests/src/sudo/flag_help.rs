@@ -23,6 +23,8 @@ fn no_args_gives_usage() -> Result<()> {
 
     let output = Command::new("sudo").output(&env);
 
+    output.assert_exit_code(1);
+
     let output = output.stderr();
     assert_contains!(output, "usage: sudo");
 
@@ -35,6 +37,8 @@ fn no_command_gives_usage() -> Result<()> {
 
     let output = Command::new("sudo").args(["-u", USERNAME]).output(&env);
 
+    output.assert_exit_code(1);
+
     let output = output.stderr();
     assert_contains!(output, "usage: sudo");
 
@@ -17,8 +17,8 @@ mod not_allowed;
 mod short_format;
 mod sudoers_list;
 
-// sudo-rs doesn't yet support showing Defaults in the `-l` output, so strip
-// them with og-sudo to get the same output between both.
+// sudo-rs only shows the Defaults that have an effect in the `-l` output, while og-sudo
+// shows them as they were written, so strip them to get the same output between both.
 fn strip_matching_defaults_message(s: &str) -> &str {
     if s.starts_with("Matching Defaults entries for") {
         s.split_once("\n")
@@ -518,3 +518,136 @@ fn does_not_panic_on_io_errors() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn all_commands_are_listed_as_all() {
+    let env = Env(format!("{USERNAME} ALL=NOPASSWD: ALL"))
+        .user(USERNAME)
+        .build();
+
+    let stdout = Command::new("sudo")
+        .arg("-l")
+        .as_user(USERNAME)
+        .output(&env)
+        .stdout();
+
+    assert_contains!(stdout, "(root) NOPASSWD: ALL");
+}
+
+#[test]
+fn long_format_expands_nested_command_aliases() {
+    let env = Env(format!(
+        "Cmnd_Alias INNER = {BIN_TRUE}
+Cmnd_Alias OUTER = INNER, {BIN_FALSE}
+{USERNAME} ALL=NOPASSWD: OUTER, {BIN_LS}"
+    ))
+    .user(USERNAME)
+    .build();
+
+    let stdout = Command::new("sudo")
+        .arg("-ll")
+        .as_user(USERNAME)
+        .output(&env)
+        .stdout();
+
+    assert_contains!(stdout, "RunAsUsers: root");
+    assert_contains!(
+        stdout,
+        format!("Commands:\n\t{BIN_TRUE}\n\t{BIN_FALSE}\n\t{BIN_LS}")
+    );
+    assert_not_contains!(stdout, "INNER");
+    assert_not_contains!(stdout, "OUTER");
+}
+
+#[test]
+fn suggest_lists_literal_commands_with_prefix() {
+    // --suggest is specific to sudo-rs
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = Env(format!(
+        "Cmnd_Alias TOOLS = {BIN_TRUE}, /usr/bin/git-*
+{USERNAME} ALL=NOPASSWD: {BIN_FALSE}, /usr/bin/*sum, TOOLS, {BIN_LS}, !{BIN_LS}"
+    ))
+    .user(USERNAME)
+    .build();
+
+    let stdout = Command::new("sudo")
+        .args(["--suggest", "/"])
+        .as_user(USERNAME)
+        .output(&env)
+        .stdout();
+
+    let mut expected = vec![BIN_FALSE, BIN_TRUE];
+    expected.sort();
+    assert_eq!(stdout.lines().collect::<Vec<_>>(), expected);
+
+    let stdout = Command::new("sudo")
+        .args(["--suggest", BIN_TRUE])
+        .as_user(USERNAME)
+        .output(&env)
+        .stdout();
+    assert_eq!(stdout, BIN_TRUE);
+}
+
+#[test]
+fn suggest_gives_nothing_for_all() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = Env(format!("{USERNAME} ALL=NOPASSWD: ALL"))
+        .user(USERNAME)
+        .build();
+
+    let stdout = Command::new("sudo")
+        .args(["--suggest", "/"])
+        .as_user(USERNAME)
+        .output(&env)
+        .stdout();
+    assert_eq!(stdout, "");
+}
+
+#[test]
+fn suggest_requires_list_permission() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = Env(format!("{USERNAME} ALL=(ALL:ALL) {BIN_TRUE}"))
+        .user(User(USERNAME).password(PASSWORD))
+        .build();
+
+    let output = Command::new("sudo")
+        .args(["-n", "--suggest", "/"])
+        .as_user(USERNAME)
+        .output(&env);
+
+    output.assert_exit_code(1);
+    assert_eq!(output.stdout_unchecked(), "");
+}
+
+#[test]
+fn suggest_judges_every_candidate() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    // commands for another target user, or forbidden by a later rule, are not suggested
+    let env = Env(format!(
+        "{USERNAME} ALL=NOPASSWD: {BIN_TRUE}, {BIN_FALSE}
+{USERNAME} ALL=(ferris) NOPASSWD: {BIN_LS}
+{USERNAME} ALL=NOPASSWD: !{BIN_FALSE}"
+    ))
+    .user(USERNAME)
+    .user("ferris")
+    .build();
+
+    let stdout = Command::new("sudo")
+        .args(["--suggest", "/"])
+        .as_user(USERNAME)
+        .output(&env)
+        .stdout();
+    assert_eq!(stdout, BIN_TRUE);
+}
@@ -9,7 +9,7 @@ macro_rules! assert_snapshot {
                 (BIN_LS, "<BIN_LS>"),
                 (&format!("Sudoers entry: {ETC_SUDOERS}"), "Sudoers entry:"),
                 ("Matching Defaults entries for ferruccio on container:
-    !fqdn, !lecture, !mailerpath
+    (!fqdn, !lecture, !mailerpath|!fqdn)
 ", "")
             ],
             prepend_module_to_snapshot => false,
@@ -8,7 +8,7 @@ macro_rules! assert_snapshot {
             filters => vec![
                 (BIN_LS, "<BIN_LS>"),
                 ("Matching Defaults entries for ferruccio on container:
-    !fqdn, !lecture, !mailerpath
+    (!fqdn, !lecture, !mailerpath|!fqdn)
 ", "")],
             prepend_module_to_snapshot => false,
         }, {
@@ -298,3 +298,18 @@ fn empty_runas_with_colon() {
     let stdout = sudo_list_of(&format!(" ALL  ALL  = (:) {BIN_TRUE} "));
     assert_snapshot!(stdout);
 }
+
+#[test]
+fn notes_whose_password_is_required() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let stdout = sudo_list_of("Defaults targetpw\nALL ALL = (ALL) /usr/bin/true");
+    assert_contains!(stdout, "(ALL) /usr/bin/true  # password of the target user");
+    assert_not_contains!(stdout, "/tmp  #");
+
+    let stdout = sudo_list_of("Defaults rootpw\nALL ALL = (ALL) /usr/bin/true");
+    assert_contains!(stdout, "(ALL) /usr/bin/true  # password of root");
+    assert_not_contains!(stdout, "/tmp  #");
+}
@@ -76,6 +76,81 @@ echo $0";
     assert_eq!(expected, actual);
 }
 
+#[test]
+fn shell_is_invoked_with_dash_prefixed_argv0() {
+    let env = Env(SUDOERS_ALL_ALL_NOPASSWD)
+        .user(User(USERNAME).shell("/bin/sh").create_home_directory())
+        .build();
+
+    let actual = Command::new("sudo")
+        .args(["-u", USERNAME, "-i", "echo", "$0"])
+        .output(&env)
+        .stdout();
+
+    // argv[0] is derived from the passwd entry, not from the canonicalized shell path
+    assert_eq!("-sh", actual);
+}
+
+#[test]
+fn bash_is_executed_as_dash_bash_in_the_home_directory() {
+    let env = Env(SUDOERS_ALL_ALL_NOPASSWD)
+        .user(User(USERNAME).shell("/bin/bash").create_home_directory())
+        .build();
+
+    let actual = Command::new("sh")
+        .arg("-c")
+        .arg(format!("cd /; sudo -u {USERNAME} -i echo \\$0 \\$PWD"))
+        .output(&env)
+        .stdout();
+
+    assert_eq!(format!("-bash /home/{USERNAME}"), actual);
+
+    // `test` is a builtin, so `$$` is the shell itself; its argv[0] is not a path, but the
+    // binary that was executed still is the shell from the passwd database
+    Command::new("sudo")
+        .args([
+            "-u",
+            USERNAME,
+            "-i",
+            "test",
+            "/proc/$$/exe",
+            "-ef",
+            "/bin/bash",
+        ])
+        .output(&env)
+        .assert_success();
+}
+
+#[test]
+fn login_shell_sources_profile() {
+    let profile = format!("/home/{USERNAME}/.profile");
+    let env = Env(SUDOERS_ALL_ALL_NOPASSWD)
+        .user(User(USERNAME).shell("/bin/sh").create_home_directory())
+        .file(profile, TextFile("export FROM_PROFILE=1").chown(USERNAME))
+        .build();
+
+    let actual = Command::new("sudo")
+        .args(["-u", USERNAME, "-i", "printenv", "FROM_PROFILE"])
+        .output(&env)
+        .stdout();
+
+    assert_eq!("1", actual);
+}
+
+#[test]
+fn sets_user_and_logname_to_target_user() {
+    let env = Env(SUDOERS_ALL_ALL_NOPASSWD)
+        .user(User(USERNAME).shell("/bin/sh").create_home_directory())
+        .build();
+
+    let actual = Command::new("sudo")
+        .args(["-u", USERNAME, "-i", "printenv", "USER", "LOGNAME"])
+        .output(&env)
+        .stdout();
+
+    assert_eq!(format!("{USERNAME}\n{USERNAME}"), actual);
+}
+
 #[test]
 fn argument_is_invoke_with_dash_c_flag() {
     let shell_path = "/tmp/my-shell";
@@ -217,12 +292,12 @@ fn insufficient_permissions_to_execute_shell() {
         .args(["-u", USERNAME, "-i"])
         .output(&env);
 
-    output.assert_exit_code(1);
-
     let stderr = output.stderr();
     if sudo_test::is_original_sudo() {
+        output.assert_exit_code(1);
         assert_snapshot!(stderr);
     } else {
+        output.assert_exit_code(126);
         assert_contains!(stderr, "Permission denied");
     }
 }
@@ -137,6 +137,50 @@ fn noninteractive_auth() {
         .assert_success();
 }
 
+#[test]
+fn noninteractive_auth_does_not_prompt_for_a_password() {
+    // there is no terminal, as when sudo is run from cron
+    let env = Env("Defaults noninteractive_auth\nALL ALL=(ALL:ALL) ALL")
+        .user(User(USERNAME).password(PASSWORD))
+        .build();
+
+    let output = Command::new("sudo")
+        .args(["-n", "true"])
+        .as_user(USERNAME)
+        .output(&env);
+
+    output.assert_exit_code(1);
+    assert_not_contains!(output.stderr(), "Password:");
+    if sudo_test::is_original_sudo() {
+        assert_contains!(output.stderr(), "a password is required");
+    } else {
+        assert_contains!(output.stderr(), "interactive authentication is required");
+    }
+}
+
+#[test]
+fn noninteractive_auth_shows_pam_messages_without_a_terminal() {
+    let env = Env("Defaults noninteractive_auth\nALL ALL=(ALL:ALL) ALL")
+        .user(USERNAME)
+        .file(
+            "/etc/pam.d/sudo",
+            "auth optional pam_echo.so hello from pam\nauth sufficient pam_permit.so",
+        )
+        .build();
+
+    // sudo-rs asks PAM modules to be quiet when it runs a command directly, so use a shell
+    let output = Command::new("sudo")
+        .args(["-n", "-s", "true"])
+        .as_user(USERNAME)
+        .output(&env);
+
+    output.assert_success();
+    // only check where sudo-rs shows them
+    if !sudo_test::is_original_sudo() {
+        assert_contains!(output.stderr(), "hello from pam");
+    }
+}
+
 /* misc */
 #[test]
 fn lecture_is_not_shown() {
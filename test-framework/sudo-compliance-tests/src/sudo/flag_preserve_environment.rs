@@ -1,6 +1,6 @@
 use sudo_test::{Command, Env};
 
-use crate::{SUDOERS_ALL_ALL_NOPASSWD, helpers};
+use crate::{SUDOERS_ALL_ALL_NOPASSWD, USERNAME, helpers};
 
 #[test]
 fn env_var_is_preserved() {
@@ -85,3 +85,72 @@ fn preserve_overrides_env_var() {
 
     assert_eq!(Some(value), sudo_env.get(name).copied());
 }
+
+// a rule for ALL commands implies SETENV, so a specific command is used here
+#[test]
+fn is_rejected_without_setenv() {
+    let env = Env("ALL ALL=(ALL:ALL) NOPASSWD: /usr/bin/env")
+        .user(USERNAME)
+        .build();
+
+    let output = Command::new("env")
+        .args(["SECRET_KEY=42", "sudo", "--preserve-env=SECRET_KEY", "env"])
+        .as_user(USERNAME)
+        .output(&env);
+
+    output.assert_exit_code(1);
+    assert_contains!(
+        output.stderr(),
+        "not allowed to set the following environment variables: SECRET_KEY"
+    );
+    assert_not_contains!(output.stdout_unchecked(), "SECRET_KEY=42");
+}
+
+#[test]
+fn rejection_names_every_variable() {
+    let env = Env("ALL ALL=(ALL:ALL) NOPASSWD: /usr/bin/env")
+        .user(USERNAME)
+        .build();
+
+    let output = Command::new("env")
+        .args([
+            "SECRET_KEY=42",
+            "API_TOKEN=37",
+            "sudo",
+            "--preserve-env=SECRET_KEY,API_TOKEN",
+            "env",
+        ])
+        .as_user(USERNAME)
+        .output(&env);
+
+    output.assert_exit_code(1);
+    let stderr = output.stderr();
+    assert_contains!(stderr, "SECRET_KEY");
+    assert_contains!(stderr, "API_TOKEN");
+}
+
+#[test]
+fn value_that_is_not_utf8_is_passed_on_unmodified() {
+    let env = Env([
+        SUDOERS_ALL_ALL_NOPASSWD,
+        "Defaults setenv",
+        "Defaults env_keep += KEPT",
+    ])
+    .build();
+
+    // "café" in latin-1, followed by a byte that is not valid in any UTF-8 string
+    let value = r"$(printf 'caf\351\377')";
+    let print_bytes = r#"printf %s "$PRESERVED" "$KEPT" "$SET" | od -An -tx1"#;
+    let stdout = Command::new("sh")
+        .args([
+            "-c",
+            &format!(
+                "export PRESERVED={value} KEPT={value}; sudo --preserve-env=PRESERVED SET={value} sh -c '{print_bytes}'"
+            ),
+        ])
+        .output(&env)
+        .stdout();
+
+    let bytes = stdout.split_whitespace().collect::<Vec<_>>();
+    assert_eq!(bytes, ["63", "61", "66", "e9", "ff"].repeat(3));
+}
@@ -190,6 +190,53 @@ echo $@";
     assert_eq!(r"-c a 1 _ - $ $VAR $\{VAR\}", output);
 }
 
+#[test]
+fn command_is_passed_to_the_shell_as_a_single_dash_c_argument() {
+    let shell_path = "/root/my-shell";
+    // the kernel runs this as `/bin/sh /root/my-shell ARGS..`, so its cmdline shows the exact
+    // arguments that sudo passed to the shell
+    let my_shell = "#!/bin/sh
+tr '\\0' '\\n' < /proc/$$/cmdline";
+    let env = Env(SUDOERS_ALL_ALL_NOPASSWD)
+        .file(shell_path, TextFile(my_shell).chmod("100"))
+        .build();
+
+    for (args, command) in [
+        (&["echo", "hello", "world"][..], "echo hello world"),
+        (&["echo", "hello  world"][..], "echo hello\\ \\ world"),
+    ] {
+        let output = Command::new("env")
+            .arg(format!("SHELL={shell_path}"))
+            .args(["sudo", "-s"])
+            .args(args)
+            .output(&env)
+            .stdout();
+
+        let argv = output.lines().collect::<Vec<_>>();
+        assert_eq!(["/bin/sh", shell_path, "-c", command], argv[..]);
+    }
+}
+
+#[test]
+fn arguments_with_spaces_are_not_split_by_the_shell() {
+    let env = Env(SUDOERS_ALL_ALL_NOPASSWD).build();
+
+    let output = Command::new("env")
+        .args([
+            "SHELL=/bin/sh",
+            "sudo",
+            "-s",
+            "printf",
+            "[%s]",
+            "hello  world",
+            "a b",
+        ])
+        .output(&env)
+        .stdout();
+
+    assert_eq!("[hello  world][a b]", output);
+}
+
 #[test]
 fn shell_is_not_invoked_as_a_login_shell() {
     let env = Env(SUDOERS_ALL_ALL_NOPASSWD).build();
@@ -208,6 +255,33 @@ fn shell_is_not_invoked_as_a_login_shell() {
     assert_contains!(actual, "/bin/sh");
 }
 
+#[test]
+fn shell_is_invoked_with_shell_env_var_as_argv0() {
+    let env = Env(SUDOERS_ALL_ALL_NOPASSWD).build();
+
+    let actual = Command::new("env")
+        .args(["SHELL=/bin/sh", "sudo", "-s", "echo", "$0"])
+        .output(&env)
+        .stdout();
+
+    // the shell is not resolved to its canonical location for the purpose of argv[0]
+    assert_eq!("/bin/sh", actual);
+}
+
+#[test]
+fn shell_does_not_source_login_profile() {
+    let env = Env(SUDOERS_ALL_ALL_NOPASSWD)
+        .file("/root/.profile", "export FROM_PROFILE=1")
+        .build();
+
+    let output = Command::new("env")
+        .args(["SHELL=/bin/sh", "sudo", "-s", "printenv", "FROM_PROFILE"])
+        .output(&env);
+
+    output.assert_exit_code(1);
+    assert_eq!("", output.stdout());
+}
+
 #[test]
 fn shell_does_not_exist() {
     let shell_path = "/root/my-shell";
@@ -240,12 +314,12 @@ fn shell_is_not_executable() {
         .args(["sudo", "-s"])
         .output(&env);
 
-    output.assert_exit_code(1);
-
     let stderr = output.stderr();
     if sudo_test::is_original_sudo() {
+        output.assert_exit_code(1);
         assert_snapshot!(stderr);
     } else {
+        output.assert_exit_code(126);
         assert_contains!(stderr, "Permission denied");
     }
 }
@@ -265,6 +339,46 @@ fn shell_with_open_permissions_is_accepted() {
     output.assert_success();
 }
 
+#[test]
+fn no_command_gives_usage_without_shell_noargs() {
+    let env = Env(SUDOERS_ALL_ALL_NOPASSWD).user(USERNAME).build();
+
+    for args in [&[][..], &["-u", USERNAME]] {
+        let output = Command::new("sudo").args(args).output(&env);
+
+        output.assert_exit_code(1);
+        assert_starts_with!(output.stderr(), "usage: sudo");
+    }
+}
+
+#[test]
+fn no_command_runs_a_shell_with_shell_noargs() {
+    let shell_path = "/tmp/my-shell";
+    let my_shell = "#!/bin/sh
+echo \"$(id -un) got a shell with $# arguments\"";
+    let env = Env([SUDOERS_ALL_ALL_NOPASSWD, "Defaults shell_noargs"])
+        .user(USERNAME)
+        .file(shell_path, TextFile(my_shell).chmod("777"))
+        .build();
+
+    let output = Command::new("env")
+        .arg(format!("SHELL={shell_path}"))
+        .arg("sudo")
+        .output(&env);
+    output.assert_success();
+    assert_eq!(output.stdout(), "root got a shell with 0 arguments");
+
+    let output = Command::new("env")
+        .arg(format!("SHELL={shell_path}"))
+        .args(["sudo", "-u", USERNAME])
+        .output(&env);
+    output.assert_success();
+    assert_eq!(
+        output.stdout(),
+        format!("{USERNAME} got a shell with 0 arguments")
+    );
+}
+
 type UserToShell<'a> = HashMap<&'a str, &'a str>;
 
 fn parse_getent_passwd_output(passwd: &str) -> UserToShell<'_> {
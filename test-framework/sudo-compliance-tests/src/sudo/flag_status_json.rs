@@ -0,0 +1,105 @@
+use sudo_test::{Command, Env, User};
+
+use crate::{PASSWORD, USERNAME};
+
+/// Run `sudo --status-json=3` with `password` on stdin, and return its progress, one JSON
+/// object per line.
+fn status_of(password: &str, command: &str) -> String {
+    let env = Env(format!("{USERNAME} ALL=(ALL:ALL) ALL"))
+        .user(User(USERNAME).password(PASSWORD))
+        .build();
+
+    Command::new("sh")
+        .arg("-c")
+        .arg(format!(
+            "echo {password} | sudo -S --status-json=3 {command} 3>/tmp/status; cat /tmp/status"
+        ))
+        .as_user(USERNAME)
+        .output(&env)
+        .stdout()
+}
+
+fn phases(status: &str) -> Vec<&str> {
+    status
+        .lines()
+        .map(|line| {
+            line.strip_prefix(r#"{"phase":""#)
+                .and_then(|rest| rest.split('"').next())
+                .unwrap_or_else(|| panic!("not a phase: {line}"))
+        })
+        .collect()
+}
+
+#[test]
+fn reports_every_phase_of_a_successful_run() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let status = status_of(PASSWORD, "sh -c 'exit 3'");
+
+    assert_eq!(
+        phases(&status),
+        [
+            "policy-loaded",
+            "judged",
+            "authenticated",
+            "session-opened",
+            "exec",
+            "exited"
+        ]
+    );
+    assert_contains!(
+        status,
+        r#"{"phase":"judged","allowed":true,"must_authenticate":true}"#
+    );
+    assert_contains!(status, r#"{"phase":"exec","pid":"#);
+    assert_contains!(status, r#"{"phase":"exited","code":3}"#);
+    assert_not_contains!(status, PASSWORD);
+}
+
+#[test]
+fn reports_an_authentication_failure() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let status = status_of("wrong-password", "true");
+
+    assert_eq!(phases(&status), ["policy-loaded", "judged", "failed"]);
+    assert_contains!(status, r#"{"phase":"failed","error":""#);
+    assert_not_contains!(status, "wrong-password");
+}
+
+#[test]
+fn standard_streams_are_refused() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = Env("ALL ALL=(ALL:ALL) NOPASSWD: ALL")
+        .user(USERNAME)
+        .build();
+
+    for fd in ["0", "1", "2"] {
+        let output = Command::new("sudo")
+            .args(["--status-json", fd, "true"])
+            .as_user(USERNAME)
+            .output(&env);
+
+        output.assert_exit_code(1);
+        assert_contains!(output.stderr(), "invalid file descriptor");
+    }
+
+    // a descriptor that was not open when sudo started
+    let output = Command::new("sudo")
+        .args(["--status-json=9", "true"])
+        .as_user(USERNAME)
+        .output(&env);
+
+    output.assert_exit_code(1);
+    assert_contains!(
+        output.stderr(),
+        "cannot report progress on file descriptor 9"
+    );
+}
@@ -163,3 +163,21 @@ fn user_does_not_exist() {
     };
     assert_contains!(output.stderr(), diagnostic);
 }
+
+#[test]
+fn escape_sequences_in_the_user_name_are_not_sent_to_the_terminal() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = Env(SUDOERS_ALL_ALL_NOPASSWD).build();
+
+    let output = Command::new("sh")
+        .args(["-c", "sudo -u \"$(printf '\\033]0;pwned\\007')\" true"])
+        .output(&env);
+
+    output.assert_exit_code(1);
+    let stderr = output.stderr();
+    assert_contains!(stderr, "\\u{1b}]0;pwned\\u{7}");
+    assert!(!stderr.contains('\x1b'), "{stderr:?}");
+}
@@ -1,6 +1,6 @@
 use sudo_test::{Command, Env};
 
-use crate::{PANIC_EXIT_CODE, Result};
+use crate::{PANIC_EXIT_CODE, Result, USERNAME};
 
 #[test]
 fn does_not_panic_on_io_errors() -> Result<()> {
@@ -38,3 +38,83 @@ fn prints_on_stdout() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn exits_successfully_and_prints_version_number() {
+    let env = Env("").build();
+
+    for flag in ["-V", "--version"] {
+        let output = Command::new("sudo").arg(flag).output(&env);
+        output.assert_exit_code(0);
+
+        let stdout = output.stdout();
+        let first_line = stdout.lines().next().unwrap();
+        let (name, version) = first_line.rsplit_once(' ').unwrap();
+
+        if sudo_test::is_original_sudo() {
+            assert_eq!(name, "Sudo version");
+        } else {
+            // scripts use this to tell sudo-rs apart from the original sudo
+            assert_eq!(name, "sudo-rs");
+        }
+
+        // the original sudo appends a patch level, as in "1.9.16p2"
+        let components = version.splitn(3, '.').collect::<Vec<_>>();
+        assert_eq!(components.len(), 3, "{version}");
+        for component in &components[..2] {
+            assert!(component.parse::<u32>().is_ok(), "{version}");
+        }
+        assert!(
+            components[2].starts_with(|c: char| c.is_ascii_digit()),
+            "{version}"
+        );
+    }
+}
+
+#[test]
+fn root_also_sees_the_default_settings() {
+    if sudo_test::is_original_sudo() {
+        // ogsudo shows a similar list, in its own words
+        return;
+    }
+
+    let env = Env("").user(USERNAME).build();
+
+    let stdout = Command::new("sudo").arg("-V").output(&env).stdout();
+    assert_contains!(stdout, "Sudoers path: /etc/sudoers");
+    assert_contains!(stdout, "PAM service name to use: sudo");
+    assert_contains!(
+        stdout,
+        "Sudoers defaults:\nAlways run commands in a pseudo-terminal"
+    );
+    assert_contains!(stdout, "Number of tries to enter a password: 3");
+    assert_contains!(stdout, "Environment variables to preserve:\n\tCOLORS\n");
+
+    let stdout = Command::new("sudo")
+        .arg("-V")
+        .as_user(USERNAME)
+        .output(&env)
+        .stdout();
+    assert_eq!(stdout.lines().count(), 1, "{stdout}");
+}
+
+#[test]
+fn root_sees_the_helpers_from_sudo_conf() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = Env("")
+        .file(
+            "/etc/sudo.conf",
+            "Path askpass /usr/bin/true\nPath nonunix_group_helper /usr/bin/missing",
+        )
+        .build();
+
+    let stdout = Command::new("sudo").arg("-V").output(&env).stdout();
+    assert_contains!(stdout, "Path to the askpass helper: /usr/bin/true\n");
+    assert_contains!(
+        stdout,
+        "Path to the non-unix group helper: none (/usr/bin/missing: "
+    );
+}
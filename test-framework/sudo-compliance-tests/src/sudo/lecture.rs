@@ -80,6 +80,31 @@ fn lecture_always_shown() {
     assert_contains!(second_sudo.stderr(), OG_SUDO_STANDARD_LECTURE);
 }
 
+#[test]
+#[ignore = "gh399"]
+fn lecture_always_shown_on_every_invocation_unlike_once() {
+    let lectures = |setting| {
+        let env = Env([SUDOERS_ROOT_ALL, SUDOERS_USER_ALL_ALL, setting])
+            .user(User(USERNAME).password(PASSWORD))
+            .build();
+
+        // every invocation is a new session, so each one prompts for a password
+        [1, 2].map(|_| {
+            let output = Command::new("sudo")
+                .args(["-S", "true"])
+                .as_user(USERNAME)
+                .stdin(PASSWORD)
+                .output(&env);
+            output.assert_success();
+            output.stderr().contains(OG_SUDO_STANDARD_LECTURE)
+        })
+    };
+
+    // whether the user has been lectured before is not taken into account
+    assert_eq!(lectures(SUDOERS_ALWAYS_LECTURE), [true, true]);
+    assert_eq!(lectures(SUDOERS_ONCE_LECTURE), [true, false]);
+}
+
 #[test]
 fn lecture_never_shown() {
     let env = Env([SUDOERS_ROOT_ALL, SUDOERS_USER_ALL_ALL, SUDOERS_NO_LECTURE])
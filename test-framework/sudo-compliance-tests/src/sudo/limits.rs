@@ -107,3 +107,28 @@ fn cannot_override_the_default_core_file_size_with_a_limits_file() {
         assert_eq!("0", sudo_limit);
     }
 }
+
+#[test]
+#[ignore = "sudo-rs does not support the rlimit_* settings"]
+fn rlimit_nofile_in_sudoers_applies_to_the_command() {
+    for (setting, soft, hard) in [("100", 100, 100), ("\"50,100\"", 50, 100)] {
+        let env = Env(format!(
+            "Defaults rlimit_nofile={setting}\n{SUDOERS_ALL_ALL_NOPASSWD}"
+        ))
+        .user(USERNAME)
+        .build();
+
+        let stdout = Command::new("sudo")
+            .args(["sh", "-c", "ulimit -Sn; ulimit -Hn"])
+            .as_user(USERNAME)
+            .output(&env)
+            .stdout();
+
+        let limits = stdout
+            .lines()
+            .map(|limit| limit.parse::<u64>().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!([soft, hard], limits[..], "{setting}");
+        assert!(limits[0] <= limits[1]);
+    }
+}
@@ -1,6 +1,6 @@
 use sudo_test::{BIN_SUDO, Command, Env, User, helpers::assert_ls_output, is_original_sudo};
 
-use crate::{PANIC_EXIT_CODE, Result, SUDOERS_ALL_ALL_NOPASSWD, USERNAME};
+use crate::{PANIC_EXIT_CODE, PASSWORD, Result, SUDOERS_ALL_ALL_NOPASSWD, USERNAME};
 
 macro_rules! assert_snapshot {
     ($($tt:tt)*) => {
@@ -71,6 +71,42 @@ fn closes_open_file_descriptors_without_tty() {
     closes_open_file_descriptors(false)
 }
 
+fn only_stdio_is_inherited(tty: bool) {
+    let defaults = if tty {
+        "Defaults use_pty"
+    } else {
+        "Defaults !use_pty"
+    };
+    let env = Env([SUDOERS_ALL_ALL_NOPASSWD, defaults]).build();
+
+    // `$$` is the shell started by sudo; listing its descriptors (rather than those of `ls`)
+    // avoids counting the one `ls` uses to read the directory
+    let output = Command::new("bash")
+        .args([
+            "-c",
+            "exec 3</etc/hosts 5>/tmp/log 9<>/tmp/log 42</dev/null 255</etc/passwd
+             sudo sh -c 'ls /proc/$$/fd'",
+        ])
+        .tty(tty)
+        .output(&env);
+
+    let stdout = output.stdout();
+    let fds = stdout.split_whitespace().collect::<Vec<_>>();
+    assert_eq!(fds, ["0", "1", "2"]);
+}
+
+#[test]
+#[cfg_attr(target_os = "freebsd", ignore = "FreeBSD does not mount /proc")]
+fn only_stdio_is_inherited_with_tty() {
+    only_stdio_is_inherited(true)
+}
+
+#[test]
+#[cfg_attr(target_os = "freebsd", ignore = "FreeBSD does not mount /proc")]
+fn only_stdio_is_inherited_without_tty() {
+    only_stdio_is_inherited(false)
+}
+
 #[test]
 fn sudo_binary_lacks_setuid_flag() {
     let env = Env(SUDOERS_ALL_ALL_NOPASSWD).user(USERNAME).build();
@@ -136,6 +172,18 @@ fn sudo_binary_is_not_owned_by_root_and_ran_as_root() {
         .assert_success();
 }
 
+#[test]
+fn command_is_invoked_with_the_name_it_was_given_as_argv0() {
+    let env = Env(SUDOERS_ALL_ALL_NOPASSWD).build();
+
+    let stdout = Command::new("sudo")
+        .args(["sh", "-c", "echo $0"])
+        .output(&env)
+        .stdout();
+
+    assert_eq!("sh", stdout);
+}
+
 #[test]
 fn works_when_invoked_through_a_symlink() {
     let symlink_path = "/tmp/sudo";
@@ -209,13 +257,14 @@ fn does_not_panic_on_invalid_executable() {
         .args(["-c", "sudo /tmp; a=$?; sleep .1; exit $a"])
         .tty(true) // Necessary to reproduce the panic
         .output(&env);
-    output.assert_exit_code(1);
 
     assert!(!output.stderr().contains("panic"), "{output:?}");
     assert!(!output.stdout_unchecked().contains("panic"), "{output:?}");
     if is_original_sudo() {
+        output.assert_exit_code(1);
         assert_contains!(output.stdout_unchecked(), "command not found");
     } else {
+        output.assert_exit_code(126);
         assert_contains!(output.stdout_unchecked(), "Permission denied");
     }
 }
@@ -400,3 +449,42 @@ fn rootpw_takes_priority_over_targetpw() {
         .output(&env);
     assert!(!output.status().success());
 }
+
+#[test]
+#[cfg_attr(
+    target_os = "freebsd",
+    ignore = "sudo-rs does not use procfs on FreeBSD"
+)]
+fn works_without_procfs() {
+    // how sudo copes with a missing /proc is not covered by original sudo's documentation
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = Env(format!("{USERNAME} ALL=(ALL:ALL) ALL"))
+        .user(User(USERNAME).password(PASSWORD))
+        .build();
+
+    // unmounting /proc in a new mount namespace needs privileges that the container does not
+    // have, so use a chroot that contains a copy of the file system instead
+    Command::new("sh")
+        .arg("-c")
+        .arg(
+            "set -e
+mkdir -p /srv/noproc/dev
+for dir in /bin /etc /home /lib /lib64 /root /sbin /tmp /usr /var; do
+    if [ -e $dir ]; then cp -a $dir /srv/noproc/; fi
+done
+cp -a /dev/null /srv/noproc/dev/",
+        )
+        .output(&env)
+        .assert_success();
+
+    let stdout = Command::new("chroot")
+        .args(["--userspec", USERNAME, "/srv/noproc", "sh", "-c"])
+        .arg(format!("echo {PASSWORD} | sudo -S id -u"))
+        .output(&env)
+        .stdout();
+
+    assert_eq!("0", stdout);
+}
@@ -8,6 +8,8 @@ use crate::{PASSWORD, USERNAME};
 
 #[cfg(target_os = "linux")]
 mod env;
+#[cfg(target_os = "linux")]
+mod faillock;
 
 const TEST_ENV_EXPECTED_TTY: &str = "SUDO_RS_TEST_ENV_EXPECTED_TTY";
 const PAM_ENV_VALUE: &str = "/tmp/PAM_ENV_VALUE";
@@ -446,3 +448,68 @@ cat {PAM_ENV_VALUE} >&3"#
     let (expected, pam_env) = parse_expected_tty_and_pam_env(&stdout);
     assert_pam_tty_matches_expected(&expected, &pam_env);
 }
+
+const FAILING_SESSION: &str = "auth sufficient pam_permit.so\nsession required pam_deny.so";
+
+#[test]
+fn failing_session_module_is_skipped_with_pam_session_off() {
+    for flag in ["", "-i"] {
+        for (setting, succeeds) in [("", false), ("Defaults !pam_session", true)] {
+            let env = Env(["ALL ALL=(ALL:ALL) NOPASSWD: ALL", setting])
+                .user(User(USERNAME).create_home_directory())
+                .file("/etc/pam.d/sudo", FAILING_SESSION)
+                .file("/etc/pam.d/sudo-i", FAILING_SESSION)
+                .build();
+
+            let output = Command::new("sh")
+                .args(["-c", &format!("sudo {flag} true")])
+                .as_user(USERNAME)
+                .output(&env);
+
+            if succeeds {
+                output.assert_success();
+            } else {
+                output.assert_exit_code(1);
+            }
+        }
+    }
+}
+
+#[test]
+fn session_is_still_opened_with_pam_setcred_off() {
+    let env = Env(["ALL ALL=(ALL:ALL) NOPASSWD: ALL", "Defaults !pam_setcred"])
+        .user(User(USERNAME).create_home_directory())
+        .file("/etc/pam.d/sudo", FAILING_SESSION)
+        .file("/etc/pam.d/sudo-i", FAILING_SESSION)
+        .build();
+
+    let output = Command::new("sudo")
+        .args(["-i", "true"])
+        .as_user(USERNAME)
+        .output(&env);
+    output.assert_exit_code(1);
+}
+
+#[test]
+fn failing_account_module_is_skipped_with_pam_acct_mgmt_off() {
+    for (setting, succeeds) in [("", false), ("Defaults !pam_acct_mgmt", true)] {
+        let env = Env(["ALL ALL=(ALL:ALL) NOPASSWD: ALL", setting])
+            .user(USERNAME)
+            .file(
+                "/etc/pam.d/sudo",
+                "auth sufficient pam_permit.so\naccount required pam_deny.so",
+            )
+            .build();
+
+        let output = Command::new("sudo")
+            .arg("true")
+            .as_user(USERNAME)
+            .output(&env);
+
+        if succeeds {
+            output.assert_success();
+        } else {
+            output.assert_exit_code(1);
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! `pam_faillock` integration: a successful authentication must reset the count of failed
+//! attempts, so that users who mistype their password now and then are not locked out.
+//! These tests only run on Linux since FreeBSD doesn't have pam_faillock.so
+
+use sudo_test::{Command, Env, Output, User};
+
+use crate::{PASSWORD, USERNAME};
+
+const PAM_D_SUDO_FAILLOCK: &str = "auth required pam_faillock.so preauth deny=3
+auth sufficient pam_unix.so
+auth [default=die] pam_faillock.so authfail deny=3
+account required pam_faillock.so
+account required pam_unix.so
+session required pam_permit.so";
+
+fn env() -> Env {
+    // every invocation of sudo makes a single attempt, and always authenticates
+    Env(format!(
+        "{USERNAME} ALL=(ALL:ALL) ALL\nDefaults passwd_tries=1, timestamp_timeout=0"
+    ))
+    .user(User(USERNAME).password(PASSWORD))
+    .file("/etc/pam.d/sudo", PAM_D_SUDO_FAILLOCK)
+    .directory("/var/run/faillock")
+    .build()
+}
+
+fn sudo_with_password(env: &Env, password: &str) -> Output {
+    Command::new("sudo")
+        .args(["-S", "true"])
+        .as_user(USERNAME)
+        .stdin(password)
+        .output(env)
+}
+
+#[test]
+fn successful_authentication_does_not_count_as_failure() {
+    let env = env();
+
+    for _ in 0..3 {
+        sudo_with_password(&env, PASSWORD).assert_success();
+    }
+
+    sudo_with_password(&env, PASSWORD).assert_success();
+}
+
+#[test]
+fn successful_authentication_resets_failures() {
+    let env = env();
+
+    // four failures in total, but never three in a row
+    for _ in 0..2 {
+        for _ in 0..2 {
+            sudo_with_password(&env, "wrong").assert_exit_code(1);
+        }
+        sudo_with_password(&env, PASSWORD).assert_success();
+    }
+}
+
+#[test]
+fn three_failures_lock_the_account() {
+    let env = env();
+
+    for _ in 0..3 {
+        sudo_with_password(&env, "wrong").assert_exit_code(1);
+    }
+
+    // the correct password is no longer accepted
+    sudo_with_password(&env, PASSWORD).assert_exit_code(1);
+}
@@ -204,7 +204,7 @@ fn askpass_not_executable() {
 }
 
 #[test]
-fn askpass_exit_code_ignored() {
+fn askpass_exit_code() {
     let env = Env("ALL ALL=(ALL:ALL) ALL")
         .file(
             "/bin/askpass",
@@ -213,11 +213,65 @@ fn askpass_exit_code_ignored() {
         .user(User(USERNAME).password(PASSWORD))
         .build();
 
-    Command::new("sh")
+    let output = Command::new("sh")
         .args(["-c", "SUDO_ASKPASS=/bin/askpass sudo -A true"])
         .as_user(USERNAME)
-        .output(&env)
-        .assert_success();
+        .output(&env);
+
+    if sudo_test::is_original_sudo() {
+        // the exit code is ignored
+        output.assert_success();
+    } else {
+        // a helper that fails did not answer, even if it printed something
+        output.assert_exit_code(1);
+        assert_contains!(output.stderr(), "Authentication failed, try again.");
+    }
+}
+
+#[test]
+fn sudo_askpass_empty() {
+    let env = Env("ALL ALL=(ALL:ALL) ALL").user(User(USERNAME)).build();
+
+    let output = Command::new("sh")
+        .args(["-c", "SUDO_ASKPASS= sudo -A true"])
+        .as_user(USERNAME)
+        .output(&env);
+
+    output.assert_exit_code(1);
+
+    let stderr = output.stderr();
+    if sudo_test::is_original_sudo() {
+        assert_contains!(
+            stderr,
+            "no askpass program specified, try setting SUDO_ASKPASS"
+        );
+    } else {
+        assert_contains!(stderr, "No askpass program specified in SUDO_ASKPASS");
+    }
+}
+
+#[test]
+fn pam_messages_are_shown_on_stderr() {
+    let env = Env("ALL ALL=(ALL:ALL) ALL")
+        .file("/bin/askpass", generate_askpass(PASSWORD))
+        .file(
+            "/etc/pam.d/sudo",
+            "auth optional pam_echo.so hello from pam\n@include common-auth",
+        )
+        .user(User(USERNAME).password(PASSWORD))
+        .build();
+
+    // sudo-rs asks PAM modules to be quiet when it runs a command directly, so use a shell
+    let output = Command::new("sh")
+        .args(["-c", "SUDO_ASKPASS=/bin/askpass sudo -A -s true"])
+        .as_user(USERNAME)
+        .output(&env);
+
+    output.assert_success();
+    // only check where sudo-rs shows them
+    if !sudo_test::is_original_sudo() {
+        assert_contains!(output.stderr(), "hello from pam");
+    }
 }
 
 #[test]
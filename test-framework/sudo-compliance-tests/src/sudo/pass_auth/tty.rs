@@ -101,3 +101,57 @@ fn input_longer_than_password_should_not_be_accepted_as_correct_password() {
         assert_contains!(stderr, diagnostic);
     }
 }
+
+#[test]
+fn terminal_is_restored_when_interrupted_during_password_prompt() {
+    let env = Env(format!("{USERNAME}    ALL=(ALL:ALL) ALL"))
+        .user(User(USERNAME).password(PASSWORD))
+        .build();
+
+    // interrupt sudo once it has turned off echoing and is waiting for the password, and
+    // record the terminal settings before and after
+    let script = "stty -g > /tmp/before
+(until pidof sudo > /dev/null; do sleep 0.1; done; sleep 1; kill -INT $(pidof sudo)) &
+sudo true
+echo
+cat /tmp/before
+stty -g";
+
+    let stdout = Command::new("sh")
+        .args(["-c", script])
+        .as_user(USERNAME)
+        .tty(true)
+        .output(&env)
+        .stdout();
+
+    let mut lines = stdout.lines().rev().map(str::trim);
+    let after = lines.next().unwrap();
+    let before = lines.next().unwrap();
+    assert!(!before.is_empty());
+    assert_eq!(before, after);
+}
+
+#[test]
+fn exactly_one_newline_is_written_after_hidden_input() {
+    let env = Env(format!(
+        "Defaults !pwfeedback
+{USERNAME}    ALL=(ALL:ALL) ALL"
+    ))
+    .user(User(USERNAME).password(PASSWORD))
+    .build();
+
+    // type the password only once sudo has turned off echoing
+    let stdout = Command::new("sh")
+        .arg("-c")
+        .arg(format!(
+            "(sleep 1; echo {PASSWORD}; sleep 2) | socat -d0 -t 3 STDIO SYSTEM:'sudo true; echo done',pty,setsid,ctty"
+        ))
+        .as_user(USERNAME)
+        .output(&env)
+        .stdout();
+
+    // the password is not echoed, and the newline that sudo writes in its place is the
+    // only thing that separates the prompt from the output of the command
+    assert!(stdout.trim_end().ends_with(": \r\ndone"), "{stdout:?}");
+    assert!(!stdout.contains(PASSWORD));
+}
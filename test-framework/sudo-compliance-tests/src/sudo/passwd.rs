@@ -51,3 +51,83 @@ fn explicit_passwd_overrides_nopasswd() {
         assert_contains!(stderr, "Authentication required but not attempted");
     }
 }
+
+#[test]
+fn nopasswd_overrides_authenticate_default() {
+    let env = Env([
+        "Defaults authenticate".to_owned(),
+        format!("ALL ALL=(ALL:ALL) NOPASSWD: {BIN_TRUE}"),
+        SUDOERS_NO_LECTURE.to_owned(),
+    ])
+    .user(USERNAME)
+    .build();
+
+    let output = Command::new("sudo")
+        .arg("true")
+        .as_user(USERNAME)
+        .output(&env);
+
+    output.assert_success();
+}
+
+#[test]
+fn authenticate_can_be_negated_per_command() {
+    let env = Env([
+        "Defaults authenticate".to_owned(),
+        format!("Defaults!{BIN_LS} !authenticate"),
+        "ALL ALL=(ALL:ALL) ALL".to_owned(),
+        SUDOERS_NO_LECTURE.to_owned(),
+    ])
+    .user(USERNAME)
+    .build();
+
+    Command::new("sudo")
+        .args(["-S", "ls"])
+        .as_user(USERNAME)
+        .output(&env)
+        .assert_success();
+
+    let output = Command::new("sudo")
+        .args(["-S", "cat", "/etc/passwd"])
+        .as_user(USERNAME)
+        .output(&env);
+
+    output.assert_exit_code(1);
+
+    let diagnostic = if sudo_test::is_original_sudo() {
+        "a password is required"
+    } else {
+        "Authentication required but not attempted"
+    };
+    assert_contains!(output.stderr(), diagnostic);
+}
+
+#[test]
+fn passwd_overrides_nopasswd_all() {
+    let env = Env([
+        format!("ALL ALL=(ALL:ALL) NOPASSWD: ALL, PASSWD: {BIN_LS}"),
+        SUDOERS_NO_LECTURE.to_owned(),
+    ])
+    .user(USERNAME)
+    .build();
+
+    Command::new("sudo")
+        .arg("true")
+        .as_user(USERNAME)
+        .output(&env)
+        .assert_success();
+
+    let output = Command::new("sudo")
+        .args(["-S", "ls"])
+        .as_user(USERNAME)
+        .output(&env);
+
+    output.assert_exit_code(1);
+
+    let diagnostic = if sudo_test::is_original_sudo() {
+        "a password is required"
+    } else {
+        "Authentication required but not attempted"
+    };
+    assert_contains!(output.stderr(), diagnostic);
+}
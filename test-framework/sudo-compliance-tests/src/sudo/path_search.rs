@@ -213,3 +213,36 @@ fn arg0_script_is_resolved_from_commandline() {
     let stdout = output.stdout();
     assert_eq!(stdout, "/usr/bin/foo");
 }
+
+#[test]
+fn shell_builtins_come_with_a_hint() {
+    let env = Env(SUDOERS_ALL_ALL_NOPASSWD).user(USERNAME).build();
+
+    for builtin in ["cd", "ulimit", "export"] {
+        let output = Command::new("sudo")
+            .args([builtin, "/root"])
+            .as_user(USERNAME)
+            .output(&env);
+
+        output.assert_exit_code(1);
+        let stderr = output.stderr();
+        if sudo_test::is_original_sudo() {
+            assert_contains!(stderr, format!("{builtin}: command not found"));
+        } else {
+            assert_contains!(stderr, format!("sudo: '{builtin}': command not found"));
+            assert_contains!(
+                stderr,
+                format!("sudo: '{builtin}' is a shell builtin, so it cannot be run directly")
+            );
+            assert_contains!(stderr, format!("sudo sh -c '{builtin} ...'"));
+        }
+    }
+
+    let output = Command::new("sudo")
+        .arg("does-not-exist")
+        .as_user(USERNAME)
+        .output(&env);
+
+    output.assert_exit_code(1);
+    assert_not_contains!(output.stderr(), "builtin");
+}
@@ -2,25 +2,34 @@ use std::collections::HashSet;
 
 use sudo_test::{Command, ETC_DIR, Env, ROOT_GROUP, TextFile, User};
 
-use crate::{PASSWORD, SUDOERS_ROOT_ALL_NOPASSWD, USERNAME};
+use crate::{GROUPNAME, PASSWORD, SUDOERS_ROOT_ALL_NOPASSWD, SUDOERS_USER_ALL_NOPASSWD, USERNAME};
 
+mod admin_flag;
+mod chroot;
 mod cmnd;
 mod cmnd_alias;
 mod cwd;
+mod defaults_precedence;
 mod env;
+mod env_max;
+mod fqdn;
 mod host_alias;
 mod host_list;
 mod include;
 mod includedir;
+mod mailsub;
 mod noexec;
+mod nonunix_group_helper;
 mod passwd_timeout;
 mod run_as;
 mod runas_alias;
 mod runcwd;
 mod secure_path;
+mod set_logname;
 mod specific_defaults;
 mod timestamp_timeout;
 mod user_list;
+mod verifypw;
 
 const KEYWORDS: &[&str] = &[
     "ALL",
@@ -169,6 +178,52 @@ fn cannot_sudo_if_sudoers_file_is_group_writable() {
     assert_contains!(output.stderr(), diagnostic);
 }
 
+#[test]
+fn cannot_sudo_if_sudoers_file_is_writable_by_a_group_other_than_root() {
+    let env = Env(TextFile(SUDOERS_ROOT_ALL_NOPASSWD)
+        .chmod("664")
+        .chown(format!("root:{GROUPNAME}")))
+    .group(GROUPNAME)
+    .build();
+
+    let output = Command::new("sudo").arg("true").output(&env);
+    output.assert_exit_code(1);
+
+    let diagnostic = if sudo_test::is_original_sudo() {
+        format!("{ETC_DIR}/sudoers is owned by gid ")
+    } else {
+        format!("invalid configuration: {ETC_DIR}/sudoers cannot be group-writable")
+    };
+    assert_contains!(output.stderr(), diagnostic);
+}
+
+#[test]
+fn can_sudo_if_sudoers_file_is_only_writable_by_the_root_group() {
+    let env = Env(TextFile(SUDOERS_ROOT_ALL_NOPASSWD)
+        .chmod("664")
+        .chown(format!("root:{ROOT_GROUP}")))
+    .build();
+
+    Command::new("sudo")
+        .arg("true")
+        .output(&env)
+        .assert_success();
+}
+
+#[test]
+fn can_sudo_if_sudoers_file_is_group_readable() {
+    let env = Env(TextFile(SUDOERS_ROOT_ALL_NOPASSWD)
+        .chmod("640")
+        .chown(format!("root:{GROUPNAME}")))
+    .group(GROUPNAME)
+    .build();
+
+    Command::new("sudo")
+        .arg("true")
+        .output(&env)
+        .assert_success();
+}
+
 #[test]
 fn can_sudo_if_sudoers_file_is_owner_writable() {
     let env = Env(TextFile(SUDOERS_ROOT_ALL_NOPASSWD).chmod("644")).build();
@@ -214,6 +269,78 @@ fn cannot_sudo_if_sudoers_file_is_missing() {
     assert_contains!(output.stderr(), diagnostic);
 }
 
+#[test]
+fn sudoers_rs_file_takes_precedence_over_sudoers_file() {
+    let env = Env(SUDOERS_ROOT_ALL_NOPASSWD)
+        .file(
+            format!("{ETC_DIR}/sudoers-rs"),
+            TextFile(SUDOERS_USER_ALL_NOPASSWD).chmod("440"),
+        )
+        .user(USERNAME)
+        .build();
+
+    let output = Command::new("sudo")
+        .arg("true")
+        .as_user(USERNAME)
+        .output(&env);
+
+    if sudo_test::is_original_sudo() {
+        // ogsudo only knows about the sudoers file
+        output.assert_exit_code(1);
+        assert_contains!(output.stderr(), "ferris is not in the sudoers file");
+    } else {
+        output.assert_success();
+    }
+}
+
+#[test]
+fn sudoers_file_is_ignored_if_sudoers_rs_file_exists() {
+    let env = Env(SUDOERS_USER_ALL_NOPASSWD)
+        .file(
+            format!("{ETC_DIR}/sudoers-rs"),
+            TextFile(SUDOERS_ROOT_ALL_NOPASSWD).chmod("440"),
+        )
+        .user(USERNAME)
+        .build();
+
+    let output = Command::new("sudo")
+        .arg("true")
+        .as_user(USERNAME)
+        .output(&env);
+
+    if sudo_test::is_original_sudo() {
+        output.assert_success();
+    } else {
+        output.assert_exit_code(1);
+        assert_contains!(
+            output.stderr(),
+            "I'm sorry ferris. I'm afraid I can't do that"
+        );
+    }
+}
+
+#[test]
+fn cannot_sudo_if_sudoers_rs_file_is_world_writable() {
+    if sudo_test::is_original_sudo() {
+        // ogsudo does not read the sudoers-rs file
+        return;
+    }
+
+    let env = Env(SUDOERS_ROOT_ALL_NOPASSWD)
+        .file(
+            format!("{ETC_DIR}/sudoers-rs"),
+            TextFile(SUDOERS_ROOT_ALL_NOPASSWD).chmod("446"),
+        )
+        .build();
+
+    let output = Command::new("sudo").arg("true").output(&env);
+    output.assert_exit_code(1);
+    assert_contains!(
+        output.stderr(),
+        format!("invalid configuration: {ETC_DIR}/sudoers-rs cannot be world-writable")
+    );
+}
+
 #[test]
 fn user_specifications_evaluated_bottom_to_top() {
     let env = Env(format!(
@@ -278,6 +405,74 @@ fn negated_defaults_errors() {
     assert_contains!(output.stderr(), diagnostic2);
 }
 
+#[test]
+fn warnings_are_reported_once_until_the_sudoers_file_changes() {
+    // original sudo reports them every time
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = Env([SUDOERS_USER_ALL_NOPASSWD, "Defaults bogus_setting"])
+        .user(USERNAME)
+        .build();
+    let diagnostic = "unknown setting: 'bogus_setting'";
+
+    let sudo_true = || {
+        let output = Command::new("sudo")
+            .arg("true")
+            .as_user(USERNAME)
+            .output(&env);
+        output.assert_success();
+        output.stderr().to_string()
+    };
+
+    assert_contains!(sudo_true(), diagnostic);
+    assert_not_contains!(sudo_true(), diagnostic);
+
+    // root can always see them
+    let output = Command::new("sudo").arg("-V").output(&env);
+    output.assert_success();
+    assert_contains!(output.stderr(), diagnostic);
+    assert_not_contains!(sudo_true(), diagnostic);
+
+    Command::new("sh")
+        .arg("-c")
+        .arg(format!("echo '# edited' >> {}", sudo_test::ETC_SUDOERS))
+        .output(&env)
+        .assert_success();
+
+    assert_contains!(sudo_true(), diagnostic);
+    assert_not_contains!(sudo_true(), diagnostic);
+}
+
+#[test]
+fn syntax_errors_are_reported_every_time() {
+    let env = Env([
+        SUDOERS_USER_ALL_NOPASSWD,
+        "Defaults bogus_setting",
+        "ALL ALL=(ALL) /bin/ls,",
+    ])
+    .user(USERNAME)
+    .build();
+
+    let sudo_true = || {
+        let output = Command::new("sudo")
+            .arg("true")
+            .as_user(USERNAME)
+            .output(&env);
+        output.assert_success();
+        output.stderr().to_string()
+    };
+
+    for _ in 0..2 {
+        let stderr = sudo_true();
+        assert!(
+            stderr.contains("syntax error") || stderr.contains("expected path to binary"),
+            "{stderr}"
+        );
+    }
+}
+
 #[test]
 fn regex_not_interpreted_literally() {
     let env = Env("ALL ALL=(ALL:ALL) NOPASSWD: /bin/echo ^huk$").build();
@@ -286,3 +481,58 @@ fn regex_not_interpreted_literally() {
 
     output.assert_exit_code(1);
 }
+
+#[test]
+fn accepts_utf8_in_comments() {
+    let env = Env(format!(
+        "# Åsa's rules — ünïcödé is fine here
+{USERNAME} ALL=(ALL:ALL) NOPASSWD: /usr/bin/true # ✓ trailing comment
+# ☃"
+    ))
+    .user(USERNAME)
+    .build();
+
+    Command::new("sudo")
+        .arg("/usr/bin/true")
+        .as_user(USERNAME)
+        .output(&env)
+        .assert_success();
+
+    let output = Command::new("sudo")
+        .args(["-n", "/usr/bin/ls"])
+        .as_user(USERNAME)
+        .output(&env);
+    output.assert_exit_code(1);
+}
+
+#[test]
+fn utf8_in_defaults_value_is_kept_intact() {
+    let secure_path = "/usr/bin:/bin:/opt/Åsa/bïn:/ünïcödé";
+    let env = Env(format!(
+        "Defaults secure_path=\"{secure_path}\"
+{SUDOERS_ROOT_ALL_NOPASSWD}"
+    ))
+    .build();
+
+    let stdout = Command::new("sudo")
+        .args(["printenv", "PATH"])
+        .output(&env)
+        .stdout();
+
+    assert_eq!(secure_path, stdout);
+}
+
+#[test]
+fn intercept_tag_is_accepted() {
+    let env = Env("ALL ALL=(ALL:ALL) NOPASSWD: NOINTERCEPT: ALL, INTERCEPT: /usr/bin/true").build();
+
+    let output = Command::new("sudo").arg("/usr/bin/true").output(&env);
+    output.assert_success();
+    if !sudo_test::is_original_sudo() {
+        assert_contains!(output.stderr(), "INTERCEPT is not supported by sudo-rs");
+    }
+
+    let output = Command::new("sudo").arg("/usr/bin/ls").output(&env);
+    output.assert_success();
+    assert_not_contains!(output.stderr(), "INTERCEPT");
+}
@@ -0,0 +1,111 @@
+use sudo_test::{Command, Env, User};
+
+use crate::{SUDOERS_ALL_ALL_NOPASSWD, USERNAME};
+
+const ADMIN_FLAG: &str = r#"Defaults admin_flag="~/.sudo_as_admin_successful""#;
+
+fn flag_file_status(env: &Env) -> String {
+    Command::new("sh")
+        .arg("-c")
+        .arg(format!(
+            "stat -c '%U %a' /home/{USERNAME}/.sudo_as_admin_successful || echo missing"
+        ))
+        .output(env)
+        .stdout()
+}
+
+#[test]
+fn creates_flag_file_for_member_of_admin_group() {
+    // the original sudo only supports admin_flag when built with --enable-admin-flag (Ubuntu)
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = Env([ADMIN_FLAG, SUDOERS_ALL_ALL_NOPASSWD])
+        .group("admin")
+        .user(
+            User(USERNAME)
+                .secondary_group("admin")
+                .create_home_directory(),
+        )
+        .build();
+
+    assert_eq!(flag_file_status(&env), "missing");
+
+    Command::new("sudo")
+        .arg("true")
+        .as_user(USERNAME)
+        .output(&env)
+        .assert_success();
+
+    // created by the invoking user, not by root
+    assert_eq!(flag_file_status(&env), format!("{USERNAME} 644"));
+}
+
+#[test]
+fn does_not_create_flag_file_for_other_users() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = Env([ADMIN_FLAG, SUDOERS_ALL_ALL_NOPASSWD])
+        .group("admin")
+        .user(User(USERNAME).create_home_directory())
+        .build();
+
+    Command::new("sudo")
+        .arg("true")
+        .as_user(USERNAME)
+        .output(&env)
+        .assert_success();
+
+    assert_eq!(flag_file_status(&env), "missing");
+}
+
+#[test]
+fn admin_groups_are_configurable() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = Env([
+        ADMIN_FLAG,
+        "Defaults admin_flag_groups=staff",
+        SUDOERS_ALL_ALL_NOPASSWD,
+    ])
+    .group("staff")
+    .user(
+        User(USERNAME)
+            .secondary_group("staff")
+            .create_home_directory(),
+    )
+    .build();
+
+    Command::new("sudo")
+        .arg("true")
+        .as_user(USERNAME)
+        .output(&env)
+        .assert_success();
+
+    assert_eq!(flag_file_status(&env), format!("{USERNAME} 644"));
+}
+
+#[test]
+fn flag_file_is_not_created_without_admin_flag() {
+    let env = Env(SUDOERS_ALL_ALL_NOPASSWD)
+        .group("admin")
+        .user(
+            User(USERNAME)
+                .secondary_group("admin")
+                .create_home_directory(),
+        )
+        .build();
+
+    Command::new("sudo")
+        .arg("true")
+        .as_user(USERNAME)
+        .output(&env)
+        .assert_success();
+
+    assert_eq!(flag_file_status(&env), "missing");
+}
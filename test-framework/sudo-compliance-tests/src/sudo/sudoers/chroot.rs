@@ -0,0 +1,161 @@
+use sudo_test::{Command, Env};
+
+use crate::{USERNAME, helpers::Rsyslogd};
+
+const CHROOT: &str = "/srv/jail";
+
+/// Populate the chroot with a `ls` that is actually `echo`, and a `jailtool` that does not
+/// exist outside of the chroot; the layout of /bin and /usr/bin mirrors that of the host.
+fn build_chroot(env: &Env) {
+    let script = format!(
+        "set -e
+mkdir -p {CHROOT}/usr/bin
+ln -s usr/bin {CHROOT}/bin
+for lib in $(ldd /usr/bin/echo | grep -o '/[^ ]*'); do
+    mkdir -p {CHROOT}$(dirname $lib)
+    cp $lib {CHROOT}$lib
+done
+cp /usr/bin/echo {CHROOT}/usr/bin/ls
+cp /usr/bin/echo {CHROOT}/usr/bin/jailtool"
+    );
+
+    Command::new("sh")
+        .args(["-c", &script])
+        .output(env)
+        .assert_success();
+}
+
+#[test]
+#[cfg_attr(
+    target_os = "freebsd",
+    ignore = "the chroot assumes a Linux file system layout"
+)]
+fn runchroot_runs_the_command_found_inside_the_chroot() {
+    let env = Env(format!(
+        "Defaults runchroot={CHROOT}
+ALL ALL=(ALL:ALL) NOPASSWD: /usr/bin/ls"
+    ))
+    .user(USERNAME)
+    .build();
+    build_chroot(&env);
+
+    let stdout = Command::new("sudo")
+        .args(["ls", "hello"])
+        .as_user(USERNAME)
+        .output(&env)
+        .stdout();
+
+    assert_eq!("hello", stdout);
+}
+
+#[test]
+#[cfg_attr(
+    target_os = "freebsd",
+    ignore = "the chroot assumes a Linux file system layout"
+)]
+fn command_is_matched_against_the_path_inside_the_chroot() {
+    let env = Env(format!(
+        "Defaults runchroot={CHROOT}
+ALL ALL=(ALL:ALL) NOPASSWD: /usr/bin/jailtool"
+    ))
+    .user(USERNAME)
+    .build();
+    build_chroot(&env);
+
+    let stdout = Command::new("sudo")
+        .args(["jailtool", "hello"])
+        .as_user(USERNAME)
+        .output(&env)
+        .stdout();
+
+    assert_eq!("hello", stdout);
+}
+
+#[test]
+#[cfg_attr(
+    target_os = "freebsd",
+    ignore = "the chroot assumes a Linux file system layout"
+)]
+fn chroot_tag_runs_the_command_inside_the_chroot() {
+    let env = Env(format!(
+        "ALL ALL=(ALL:ALL) NOPASSWD: CHROOT={CHROOT} /usr/bin/ls"
+    ))
+    .user(USERNAME)
+    .build();
+    build_chroot(&env);
+
+    let stdout = Command::new("sudo")
+        .args(["/bin/ls", "hello"])
+        .as_user(USERNAME)
+        .output(&env)
+        .stdout();
+
+    assert_eq!("hello", stdout);
+}
+
+#[test]
+#[cfg_attr(
+    target_os = "freebsd",
+    ignore = "the chroot assumes a Linux file system layout"
+)]
+fn flag_chroot_is_allowed_by_a_glob() {
+    let env = Env("ALL ALL=(ALL:ALL) NOPASSWD: CHROOT=* ALL")
+        .user(USERNAME)
+        .build();
+    build_chroot(&env);
+
+    let stdout = Command::new("sudo")
+        .args(["-R", CHROOT, "ls", "hello"])
+        .as_user(USERNAME)
+        .output(&env)
+        .stdout();
+
+    assert_eq!("hello", stdout);
+}
+
+#[test]
+fn flag_chroot_is_rejected_without_a_glob() {
+    let env = Env("ALL ALL=(ALL:ALL) NOPASSWD: ALL")
+        .user(USERNAME)
+        .build();
+
+    let output = Command::new("sudo")
+        .args(["-R", "/", "true"])
+        .as_user(USERNAME)
+        .output(&env);
+
+    output.assert_exit_code(1);
+
+    let diagnostic = if sudo_test::is_original_sudo() {
+        "you are not permitted to use the -R option"
+    } else {
+        "you are not allowed to use '--chroot /'"
+    };
+    assert_contains!(output.stderr(), diagnostic);
+}
+
+#[test]
+#[cfg_attr(
+    target_os = "freebsd",
+    ignore = "the chroot assumes a Linux file system layout"
+)]
+fn chroot_is_logged_along_with_the_command() {
+    let env = Env(format!(
+        "Defaults runchroot={CHROOT}
+ALL ALL=(ALL:ALL) NOPASSWD: /usr/bin/ls"
+    ))
+    .user(USERNAME)
+    .build();
+    build_chroot(&env);
+    let rsyslog = Rsyslogd::start(&env);
+
+    Command::new("sudo")
+        .args(["ls", "hello"])
+        .as_user(USERNAME)
+        .output(&env)
+        .assert_success();
+
+    let auth_log = rsyslog.auth_log();
+    assert_contains!(auth_log, format!("CHROOT={CHROOT} ;"));
+    assert_contains!(auth_log, "COMMAND=/usr/bin/ls hello");
+}
@@ -0,0 +1,249 @@
+//! Test which value wins when an option is set by several Defaults lines: generic, host, user
+//! and runas Defaults are applied in the order in which they appear, so later lines override
+//! earlier ones; command Defaults are applied after all of these.
+
+use sudo_test::{Command, Env};
+
+use crate::{HOSTNAME, OTHER_USERNAME, USERNAME, helpers};
+
+const RULE: &str = "ALL ALL=(ALL:ALL) NOPASSWD: ALL";
+
+/// What `/usr/bin/env` sees when `user` runs it using sudo as `target`: its PATH (which is
+/// `secure_path` if that is set), and which of FOO, BAR and BAZ were kept (due to `env_keep`)
+fn effective_defaults(defaults: &[&str], user: &str, target: &str) -> (String, Vec<String>) {
+    let mut sudoers = defaults.to_vec();
+    sudoers.push(RULE);
+    let env = Env(sudoers.join("\n"))
+        .user(USERNAME)
+        .user(OTHER_USERNAME)
+        .hostname(HOSTNAME)
+        .build();
+
+    let stdout = Command::new("env")
+        .args(["FOO=foo", "BAR=bar", "BAZ=baz"])
+        .args(["sudo", "-u", target, "/usr/bin/env"])
+        .as_user(user)
+        .output(&env)
+        .stdout();
+    let env_vars = helpers::parse_env_output(&stdout);
+
+    let path = env_vars
+        .get("PATH")
+        .copied()
+        .unwrap_or_default()
+        .to_string();
+    let kept = ["FOO", "BAR", "BAZ"]
+        .into_iter()
+        .filter(|name| env_vars.contains_key(name))
+        .map(String::from)
+        .collect();
+
+    (path, kept)
+}
+
+fn secure_path(defaults: &[&str]) -> String {
+    effective_defaults(defaults, USERNAME, "root").0
+}
+
+fn kept_vars(defaults: &[&str]) -> Vec<String> {
+    effective_defaults(defaults, USERNAME, "root").1
+}
+
+#[test]
+fn later_generic_line_wins() {
+    let path = secure_path(&[
+        "Defaults secure_path=/first",
+        "Defaults secure_path=/second",
+    ]);
+    assert_eq!(path, "/second");
+}
+
+#[test]
+fn later_parameter_on_the_same_line_wins() {
+    let path = secure_path(&["Defaults secure_path=/first, secure_path=/second"]);
+    assert_eq!(path, "/second");
+}
+
+#[test]
+fn user_line_overrides_earlier_generic_line() {
+    let defaults = [
+        "Defaults secure_path=/generic",
+        &format!("Defaults:{USERNAME} secure_path=/user"),
+    ];
+    assert_eq!(secure_path(&defaults), "/user");
+    assert_eq!(effective_defaults(&defaults, "root", "root").0, "/generic");
+}
+
+#[test]
+fn generic_line_overrides_earlier_user_line() {
+    let path = secure_path(&[
+        &format!("Defaults:{USERNAME} secure_path=/user"),
+        "Defaults secure_path=/generic",
+    ]);
+    assert_eq!(path, "/generic");
+}
+
+#[test]
+fn host_line_overrides_earlier_user_line() {
+    let path = secure_path(&[
+        &format!("Defaults:{USERNAME} secure_path=/user"),
+        &format!("Defaults@{HOSTNAME} secure_path=/host"),
+    ]);
+    assert_eq!(path, "/host");
+}
+
+#[test]
+fn user_line_overrides_earlier_host_line() {
+    let path = secure_path(&[
+        &format!("Defaults@{HOSTNAME} secure_path=/host"),
+        &format!("Defaults:{USERNAME} secure_path=/user"),
+    ]);
+    assert_eq!(path, "/user");
+}
+
+#[test]
+fn runas_line_overrides_earlier_generic_line() {
+    let path = secure_path(&[
+        "Defaults secure_path=/generic",
+        "Defaults>root secure_path=/runas",
+    ]);
+    assert_eq!(path, "/runas");
+}
+
+#[test]
+fn generic_line_overrides_earlier_runas_line() {
+    let path = secure_path(&[
+        "Defaults>root secure_path=/runas",
+        "Defaults secure_path=/generic",
+    ]);
+    assert_eq!(path, "/generic");
+}
+
+#[test]
+fn runas_line_only_applies_to_matching_target() {
+    let defaults = [
+        "Defaults secure_path=/generic",
+        &format!("Defaults>{OTHER_USERNAME} secure_path=/runas"),
+    ];
+    assert_eq!(secure_path(&defaults), "/generic");
+    assert_eq!(
+        effective_defaults(&defaults, USERNAME, OTHER_USERNAME).0,
+        "/runas"
+    );
+}
+
+#[test]
+fn user_alias_line_overrides_earlier_generic_line() {
+    let path = secure_path(&[
+        &format!("User_Alias ADMINS = {USERNAME}"),
+        "Defaults secure_path=/generic",
+        "Defaults:ADMINS secure_path=/alias",
+    ]);
+    assert_eq!(path, "/alias");
+}
+
+#[test]
+fn command_line_overrides_later_generic_line() {
+    let path = secure_path(&[
+        "Defaults!/usr/bin/env secure_path=/cmnd",
+        "Defaults secure_path=/generic",
+    ]);
+    assert_eq!(path, "/cmnd");
+}
+
+#[test]
+fn command_line_overrides_later_runas_line() {
+    let path = secure_path(&[
+        "Defaults!/usr/bin/env secure_path=/cmnd",
+        "Defaults>root secure_path=/runas",
+    ]);
+    assert_eq!(path, "/cmnd");
+}
+
+#[test]
+fn later_command_line_wins() {
+    let path = secure_path(&[
+        "Defaults!/usr/bin/env secure_path=/first",
+        "Defaults!ALL secure_path=/second",
+    ]);
+    assert_eq!(path, "/second");
+}
+
+#[test]
+fn command_line_for_other_command_is_ignored() {
+    let path = secure_path(&[
+        "Defaults!/usr/bin/true secure_path=/cmnd",
+        "Defaults secure_path=/generic",
+    ]);
+    assert_eq!(path, "/generic");
+}
+
+#[test]
+fn negation_overrides_earlier_value() {
+    let path = secure_path(&[
+        "Defaults secure_path=/generic",
+        &format!("Defaults:{USERNAME} !secure_path"),
+    ]);
+    assert_ne!(path, "/generic");
+}
+
+#[test]
+fn list_additions_accumulate_across_scopes() {
+    let kept = kept_vars(&[
+        "Defaults env_keep=FOO",
+        &format!("Defaults:{USERNAME} env_keep+=BAR"),
+        "Defaults>root env_keep+=BAZ",
+    ]);
+    assert_eq!(kept, ["FOO", "BAR", "BAZ"]);
+}
+
+#[test]
+fn list_assignment_discards_earlier_additions() {
+    let kept = kept_vars(&[
+        &format!("Defaults:{USERNAME} env_keep+=FOO"),
+        "Defaults>root env_keep+=BAZ",
+        "Defaults env_keep=BAR",
+    ]);
+    assert_eq!(kept, ["BAR"]);
+}
+
+#[test]
+fn list_removal_in_host_line() {
+    let kept = kept_vars(&[
+        "Defaults env_keep=\"FOO BAR\"",
+        &format!("Defaults@{HOSTNAME} env_keep-=FOO"),
+    ]);
+    assert_eq!(kept, ["BAR"]);
+}
+
+#[test]
+fn list_addition_after_negation() {
+    let kept = kept_vars(&[
+        "Defaults env_keep=\"FOO BAR\"",
+        "Defaults !env_keep",
+        &format!("Defaults:{USERNAME} env_keep+=BAZ"),
+    ]);
+    assert_eq!(kept, ["BAZ"]);
+}
+
+#[test]
+fn command_list_removal_applies_after_later_additions() {
+    let kept = kept_vars(&[
+        "Defaults!/usr/bin/env env_keep-=FOO",
+        "Defaults env_keep+=\"FOO BAR\"",
+    ]);
+    assert_eq!(kept, ["BAR"]);
+}
+
+#[test]
+fn runas_list_addition_only_applies_to_matching_target() {
+    let defaults = [
+        "Defaults env_keep=FOO",
+        &format!("Defaults>{OTHER_USERNAME} env_keep+=BAR"),
+    ];
+    assert_eq!(kept_vars(&defaults), ["FOO"]);
+    assert_eq!(
+        effective_defaults(&defaults, USERNAME, OTHER_USERNAME).1,
+        ["FOO", "BAR"]
+    );
+}
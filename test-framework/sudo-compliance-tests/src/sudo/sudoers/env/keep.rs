@@ -1,6 +1,9 @@
 use sudo_test::{Command, Env};
 
-use crate::{SUDO_ENV_DEFAULT_PATH, SUDOERS_ALL_ALL_NOPASSWD, helpers};
+use crate::{
+    OTHER_USERNAME, SUDO_ENV_DEFAULT_PATH, SUDO_ENV_DEFAULT_TERM, SUDOERS_ALL_ALL_NOPASSWD,
+    USERNAME, helpers,
+};
 
 const ENV_LIST: crate::EnvList = crate::EnvList::Keep;
 
@@ -239,6 +242,73 @@ fn checks_not_applied() {
     assert_eq!(Some(value), sudo_env.get(name).copied());
 }
 
+#[test]
+fn user_specific_plus_equal_appends_to_global_list() {
+    let name = "FERRIS_VAR";
+    let value = "42";
+    let term = "xterm-compliance";
+    let env = Env([
+        SUDOERS_ALL_ALL_NOPASSWD,
+        "Defaults env_keep = \"TERM\"",
+        &format!("Defaults:{USERNAME} env_keep += \"{name}\""),
+    ])
+    .user(USERNAME)
+    .user(OTHER_USERNAME)
+    .build();
+
+    let run_env_as = |user| {
+        let stdout = Command::new("env")
+            .arg(format!("TERM={term}"))
+            .arg(format!("{name}={value}"))
+            .args(["sudo", "env"])
+            .as_user(user)
+            .output(&env)
+            .stdout();
+
+        helpers::parse_env_output(&stdout)
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect::<std::collections::HashMap<_, _>>()
+    };
+
+    // the user-specific `+=` extends the global list instead of replacing it
+    let sudo_env = run_env_as(USERNAME);
+    assert_eq!(Some(term), sudo_env.get("TERM").map(String::as_str));
+    assert_eq!(Some(value), sudo_env.get(name).map(String::as_str));
+
+    // other users are only subject to the global list
+    let sudo_env = run_env_as(OTHER_USERNAME);
+    assert_eq!(Some(term), sudo_env.get("TERM").map(String::as_str));
+    assert_eq!(None, sudo_env.get(name));
+}
+
+#[test]
+fn user_specific_equal_replaces_global_list() {
+    let name = "FERRIS_VAR";
+    let value = "42";
+    let term = "xterm-compliance";
+    let env = Env([
+        SUDOERS_ALL_ALL_NOPASSWD,
+        "Defaults env_keep = \"TERM\"",
+        &format!("Defaults:{USERNAME} env_keep = \"{name}\""),
+    ])
+    .user(USERNAME)
+    .build();
+
+    let stdout = Command::new("env")
+        .arg(format!("TERM={term}"))
+        .arg(format!("{name}={value}"))
+        .args(["sudo", "env"])
+        .as_user(USERNAME)
+        .output(&env)
+        .stdout();
+    let sudo_env = helpers::parse_env_output(&stdout);
+
+    assert_eq!(Some(value), sudo_env.get(name).copied());
+    // TERM is no longer preserved, and gets its default value
+    assert_eq!(Some(SUDO_ENV_DEFAULT_TERM), sudo_env.get("TERM").copied());
+}
+
 #[test]
 fn can_set_from_commandline() {
     let name = "CAN_BE_SET";
@@ -0,0 +1,56 @@
+use sudo_test::{Command, Env};
+
+use crate::SUDOERS_ALL_ALL_NOPASSWD;
+
+const ENV_KEEP: &str = "Defaults env_keep += \"SMALL LARGE\"";
+
+#[test]
+fn large_variables_are_preserved_by_default() {
+    let env = Env([SUDOERS_ALL_ALL_NOPASSWD, ENV_KEEP]).build();
+
+    let stdout = Command::new("sh")
+        .args([
+            "-c",
+            "LARGE=$(head -c 100000 /dev/zero | tr '\\0' x) sudo printenv LARGE | wc -c",
+        ])
+        .output(&env)
+        .stdout();
+
+    // including the trailing newline
+    assert_eq!(stdout.trim(), "100001");
+}
+
+#[test]
+fn variables_that_exceed_env_max_are_dropped() {
+    if sudo_test::is_original_sudo() {
+        // env_max is specific to sudo-rs
+        return;
+    }
+
+    let env = Env([
+        SUDOERS_ALL_ALL_NOPASSWD,
+        ENV_KEEP,
+        "Defaults env_max = 4096",
+    ])
+    .build();
+
+    let output = Command::new("sh")
+        .args([
+            "-c",
+            "LARGE=$(head -c 8192 /dev/zero | tr '\\0' x); \
+             SMALL=hello LARGE=$LARGE OTHER=$LARGE sudo env",
+        ])
+        .output(&env);
+
+    output.assert_success();
+    assert_contains!(
+        output.stderr(),
+        "environment variable LARGE is too large and has been removed"
+    );
+    // variables that are not passed on anyway are not mentioned
+    assert_not_contains!(output.stderr(), "OTHER");
+
+    let stdout = output.stdout();
+    assert_contains!(stdout, "SMALL=hello");
+    assert_not_contains!(stdout, "LARGE=");
+}
@@ -0,0 +1,141 @@
+use sudo_test::{Command, Env};
+
+use crate::SUDOERS_ALL_ALL_NOPASSWD;
+
+const HOSTNAME: &str = "container";
+
+/// Point the resolver at a name server that never answers, and make sure the local hostname can
+/// only be found by asking it.
+fn blackhole_dns(env: &Env) {
+    Command::new("sh")
+        .arg("-c")
+        .arg(format!(
+            "set -e
+echo 'nameserver 192.0.2.1' > /etc/resolv.conf
+echo 'options timeout:30 attempts:1' >> /etc/resolv.conf
+grep -v {HOSTNAME} /etc/hosts > /tmp/hosts
+cat /tmp/hosts > /etc/hosts"
+        ))
+        .output(env)
+        .assert_success();
+}
+
+/// Run `sudo true`, returning the number of seconds it took
+fn time_sudo(env: &Env) -> u64 {
+    let stdout = Command::new("sh")
+        .arg("-c")
+        .arg("start=$(date +%s); sudo true; echo $(( $(date +%s) - start ))")
+        .output(env)
+        .stdout();
+
+    stdout.parse().unwrap()
+}
+
+#[test]
+fn no_lookup_is_done_by_default() {
+    let env = Env(SUDOERS_ALL_ALL_NOPASSWD).hostname(HOSTNAME).build();
+    blackhole_dns(&env);
+
+    let elapsed = time_sudo(&env);
+    assert!(elapsed <= 1, "sudo took {elapsed}s");
+}
+
+#[test]
+fn lookup_is_abandoned_when_dns_is_down() {
+    // the lookup timeout is specific to sudo-rs
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = Env(format!("{SUDOERS_ALL_ALL_NOPASSWD}\nDefaults fqdn"))
+        .hostname(HOSTNAME)
+        .build();
+    blackhole_dns(&env);
+
+    let elapsed = time_sudo(&env);
+    assert!(elapsed <= 4, "sudo took {elapsed}s");
+}
+
+/// Make the resolver report `container.example.org` as the fully qualified name of the host
+fn resolvable_fqdn(env: &Env) {
+    Command::new("sh")
+        .arg("-c")
+        .arg(format!(
+            "grep -v {HOSTNAME} /etc/hosts > /tmp/hosts
+echo '127.0.1.1 {HOSTNAME}.example.org {HOSTNAME}' >> /tmp/hosts
+cat /tmp/hosts > /etc/hosts"
+        ))
+        .output(env)
+        .assert_success();
+}
+
+/// Build an environment with `Defaults fqdn` in which only `host` is allowed to run commands
+fn fqdn_env(host: &str) -> Env {
+    let env = Env(format!("ALL {host}=(ALL:ALL) NOPASSWD: ALL\nDefaults fqdn"))
+        .hostname(HOSTNAME)
+        .build();
+    resolvable_fqdn(&env);
+
+    env
+}
+
+#[test]
+fn fully_qualified_name_is_matched() {
+    let env = fqdn_env(&format!("{HOSTNAME}.example.org"));
+
+    Command::new("sudo")
+        .arg("true")
+        .output(&env)
+        .assert_success();
+}
+
+#[test]
+fn fully_qualified_name_in_another_domain_is_not_matched() {
+    let env = fqdn_env(&format!("{HOSTNAME}.example.com"));
+
+    let output = Command::new("sudo").arg("true").output(&env);
+
+    output.assert_exit_code(1);
+}
+
+#[test]
+fn short_name_that_is_not_a_prefix_of_the_fqdn_is_not_matched() {
+    let env = fqdn_env("example");
+
+    let output = Command::new("sudo").arg("true").output(&env);
+
+    output.assert_exit_code(1);
+}
+
+#[test]
+fn short_name_is_compared_with_the_first_label_of_the_fqdn() {
+    // a host name without a dot is not compared with the full name, but only with the part of it
+    // before the first dot; so this still matches when fqdn is set
+    let env = fqdn_env(HOSTNAME);
+
+    Command::new("sudo")
+        .arg("true")
+        .output(&env)
+        .assert_success();
+}
+
+#[test]
+fn fully_qualified_name_is_not_looked_up_without_fqdn() {
+    let env = Env(format!(
+        "ALL {HOSTNAME}.example.org=(ALL:ALL) NOPASSWD: ALL"
+    ))
+    .hostname(HOSTNAME)
+    .build();
+
+    Command::new("sh")
+        .arg("-c")
+        .arg(format!(
+            "echo '127.0.1.1 {HOSTNAME}.example.org {HOSTNAME}' >> /etc/hosts"
+        ))
+        .output(&env)
+        .assert_success();
+
+    let output = Command::new("sudo").arg("true").output(&env);
+
+    output.assert_exit_code(1);
+}
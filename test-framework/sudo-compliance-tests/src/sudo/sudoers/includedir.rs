@@ -95,6 +95,28 @@ fn loads_files_in_lexical_order() {
         .assert_success();
 }
 
+#[test]
+fn sorting_is_not_numeric() {
+    // "10-..." is read before "9-...", so the rule in the latter is the one that applies
+    let env = Env(format!("@includedir {ETC_DIR}/sudoers.d"))
+        .file(
+            format!("{ETC_DIR}/sudoers.d/9-nopasswd"),
+            "ALL ALL=(ALL:ALL) NOPASSWD: ALL",
+        )
+        .file(
+            format!("{ETC_DIR}/sudoers.d/10-passwd"),
+            "ALL ALL=(ALL:ALL) ALL",
+        )
+        .user(USERNAME)
+        .build();
+
+    Command::new("sudo")
+        .arg("true")
+        .as_user(USERNAME)
+        .output(&env)
+        .assert_success();
+}
+
 #[test]
 fn ignores_and_warns_about_file_with_bad_perms() {
     let env = Env([
@@ -430,3 +452,61 @@ fn relative_path_dot_slash() {
         .output(&env)
         .assert_success();
 }
+
+#[test]
+fn follows_symlink_to_file_inside_directory() {
+    let env = Env(format!("@includedir {ETC_DIR}/sudoers.d"))
+        .file(format!("{ETC_DIR}/sudoers.d/a"), SUDOERS_ALL_ALL_NOPASSWD)
+        .build();
+
+    Command::new("sh")
+        .arg("-c")
+        .arg(format!(
+            "mv {ETC_DIR}/sudoers.d/a {ETC_DIR}/sudoers.d/rules && ln -s rules {ETC_DIR}/sudoers.d/a"
+        ))
+        .output(&env)
+        .assert_success();
+
+    Command::new("sudo")
+        .arg("true")
+        .output(&env)
+        .assert_success();
+}
+
+#[test]
+fn ignores_symlink_to_file_outside_directory() {
+    // ignoring such a link is a hardening measure specific to sudo-rs
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = Env([
+        SUDOERS_USER_ALL_NOPASSWD,
+        &format!("@includedir {ETC_DIR}/sudoers.d"),
+    ])
+    // if this was NOT ignored, then the `sudo true` below would fail because no password
+    // was provided
+    .file(format!("{ETC_DIR}/outside"), SUDOERS_USER_ALL_ALL)
+    .directory(format!("{ETC_DIR}/sudoers.d"))
+    .user(USERNAME)
+    .build();
+
+    Command::new("ln")
+        .args(["-s", "../outside"])
+        .arg(format!("{ETC_DIR}/sudoers.d/a"))
+        .output(&env)
+        .assert_success();
+
+    let output = Command::new("sudo")
+        .arg("true")
+        .as_user(USERNAME)
+        .output(&env);
+
+    output.assert_success();
+    assert_contains!(
+        output.stderr(),
+        format!(
+            "ignoring {ETC_DIR}/sudoers.d/a: it links to a file outside of {ETC_DIR}/sudoers.d"
+        )
+    );
+}
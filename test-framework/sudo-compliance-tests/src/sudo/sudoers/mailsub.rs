@@ -0,0 +1,31 @@
+use sudo_test::{Command, Env, User};
+
+use crate::{HOSTNAME, PASSWORD, SUDOERS_USER_ALL_ALL, USERNAME, helpers::MockMailer};
+
+// ogsudo calls this setting `mailsub`; only the `%h` escape is expanded in it
+#[test]
+#[ignore = "sudo-rs does not send mail"]
+fn mailsub_sets_the_subject_of_badpass_mail() {
+    let env = Env([
+        SUDOERS_USER_ALL_ALL,
+        &format!("Defaults mailerpath={}", MockMailer::PATH),
+        "Defaults mail_badpass",
+        "Defaults mailsub=\"Sudo alert on %h\"",
+    ])
+    .file(MockMailer::PATH, MockMailer::file())
+    .user(User(USERNAME).password(PASSWORD))
+    .hostname(HOSTNAME)
+    .build();
+
+    let output = Command::new("sudo")
+        .args(["-S", "true"])
+        .as_user(USERNAME)
+        .stdin("not-the-password")
+        .output(&env);
+
+    assert!(!output.status().success());
+
+    let mailbox = MockMailer::mailbox(&env);
+    assert_contains!(mailbox, format!("Subject: Sudo alert on {HOSTNAME}"));
+    assert_contains!(mailbox, "incorrect password attempt");
+}
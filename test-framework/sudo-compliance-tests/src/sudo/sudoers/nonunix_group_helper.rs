@@ -0,0 +1,160 @@
+//! Test non-unix groups (`%:group`), whose membership is determined by the program set by
+//! `Path nonunix_group_helper` in sudo.conf. The original sudo uses a group_plugin for these
+//! instead.
+
+use sudo_test::{Command, Env, TextFile};
+
+use crate::{OTHER_USERNAME, USERNAME, helpers::Rsyslogd};
+
+const HELPER_PATH: &str = "/usr/local/libexec/sudo-group-helper";
+const SUDO_CONF_PATH: &str = "/etc/sudo.conf";
+
+/// a helper that says that only USERNAME is a member, of the group "staff" and of group 1234
+fn stub_helper() -> TextFile {
+    TextFile(format!(
+        "#!/bin/sh
+[ \"$1\" = {USERNAME} ] && {{ [ \"$2\" = staff ] || [ \"$2\" = '#1234' ]; }}"
+    ))
+    .chmod("755")
+}
+
+fn sudoers(user_list: &str) -> String {
+    format!("{user_list} ALL=(ALL:ALL) NOPASSWD: ALL")
+}
+
+fn sudo_conf(helper: &str) -> String {
+    format!("Path nonunix_group_helper {helper}")
+}
+
+#[test]
+fn member_of_nonunix_group_is_allowed() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    for user_list in ["%:staff", "%:#1234"] {
+        let env = Env(sudoers(user_list))
+            .file(HELPER_PATH, stub_helper())
+            .file(SUDO_CONF_PATH, sudo_conf(HELPER_PATH))
+            .user(USERNAME)
+            .build();
+
+        Command::new("sudo")
+            .arg("true")
+            .as_user(USERNAME)
+            .output(&env)
+            .assert_success();
+    }
+}
+
+#[test]
+fn non_member_is_denied() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = Env(sudoers("%:staff, %:wheel"))
+        .file(HELPER_PATH, stub_helper())
+        .file(SUDO_CONF_PATH, sudo_conf(HELPER_PATH))
+        .user(USERNAME)
+        .user(OTHER_USERNAME)
+        .build();
+
+    let output = Command::new("sudo")
+        .args(["-S", "true"])
+        .as_user(OTHER_USERNAME)
+        .output(&env);
+
+    output.assert_exit_code(1);
+    assert_contains!(output.stderr(), "I'm afraid I can't do that");
+}
+
+#[test]
+fn nonunix_group_never_matches_without_helper() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = Env("%:staff ALL=(ALL:ALL) NOPASSWD: ALL")
+        .file(HELPER_PATH, stub_helper())
+        .user(USERNAME)
+        .build();
+    let rsyslog = Rsyslogd::start(&env);
+
+    Command::new("sudo")
+        .args(["-S", "true"])
+        .as_user(USERNAME)
+        .output(&env)
+        .assert_exit_code(1);
+
+    assert_contains!(rsyslog.auth_log(), "non-unix groups are not supported");
+}
+
+#[test]
+fn helper_that_does_not_respond_in_time_is_ignored() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = Env(sudoers("%:staff"))
+        .file(HELPER_PATH, TextFile("#!/bin/sh\nsleep 10").chmod("755"))
+        .file(SUDO_CONF_PATH, sudo_conf(HELPER_PATH))
+        .user(USERNAME)
+        .build();
+    let rsyslog = Rsyslogd::start(&env);
+
+    Command::new("sudo")
+        .args(["-S", "true"])
+        .as_user(USERNAME)
+        .output(&env)
+        .assert_exit_code(1);
+
+    assert_contains!(
+        rsyslog.auth_log(),
+        format!("{HELPER_PATH} did not respond in time")
+    );
+}
+
+#[test]
+fn negated_group_denies_if_the_helper_does_not_answer() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = Env(sudoers("ALL, !%:staff"))
+        .file(HELPER_PATH, TextFile("#!/bin/sh\nexit 2").chmod("755"))
+        .file(SUDO_CONF_PATH, sudo_conf(HELPER_PATH))
+        .user(USERNAME)
+        .build();
+    let rsyslog = Rsyslogd::start(&env);
+
+    Command::new("sudo")
+        .args(["-S", "true"])
+        .as_user(USERNAME)
+        .output(&env)
+        .assert_exit_code(1);
+
+    assert_contains!(rsyslog.auth_log(), format!("{HELPER_PATH} failed"));
+}
+
+#[test]
+fn helper_must_be_an_absolute_path() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = Env(sudoers("%:staff"))
+        .file(HELPER_PATH, stub_helper())
+        .file(SUDO_CONF_PATH, sudo_conf("sudo-group-helper"))
+        .user(USERNAME)
+        .build();
+    let rsyslog = Rsyslogd::start(&env);
+
+    Command::new("sudo")
+        .args(["-S", "true"])
+        .as_user(USERNAME)
+        .output(&env)
+        .assert_exit_code(1);
+
+    assert_contains!(rsyslog.auth_log(), "is not an absolute path");
+}
@@ -4,7 +4,7 @@ use std::collections::HashSet;
 
 use sudo_test::{BIN_TRUE, Command, Env, ROOT_GROUP, User};
 
-use crate::{GROUPNAME, PAMD_SUDO_PAM_PERMIT, SUDOERS_NO_LECTURE, USERNAME};
+use crate::{GROUPNAME, OTHER_USERNAME, PAMD_SUDO_PAM_PERMIT, SUDOERS_NO_LECTURE, USERNAME};
 
 macro_rules! assert_snapshot {
     ($($tt:tt)*) => {
@@ -394,6 +394,63 @@ fn supplemental_group_matching() {
     }
 }
 
+#[test]
+fn when_all_but_root_then_as_root_is_not_allowed() {
+    let env = Env(format!("{USERNAME} ALL=(ALL, !root) NOPASSWD: ALL"))
+        .user(USERNAME)
+        .user(OTHER_USERNAME)
+        .build();
+
+    Command::new("sudo")
+        .args(["-u", OTHER_USERNAME, "true"])
+        .as_user(USERNAME)
+        .output(&env)
+        .assert_success();
+
+    let output = Command::new("sudo")
+        .args(["-u", "root", "true"])
+        .as_user(USERNAME)
+        .output(&env);
+
+    output.assert_exit_code(1);
+
+    let diagnostic = if sudo_test::is_original_sudo() {
+        format!(" is not allowed to execute '{BIN_TRUE}' as root")
+    } else {
+        format!("I'm sorry {USERNAME}. I'm afraid I can't do that")
+    };
+    assert_contains!(output.stderr(), diagnostic);
+}
+
+#[test]
+fn when_all_but_group_members_then_as_group_member_is_not_allowed() {
+    let env = Env(format!("{USERNAME} ALL=(ALL, !%{GROUPNAME}) NOPASSWD: ALL"))
+        .user(USERNAME)
+        .user(User(OTHER_USERNAME).secondary_group(GROUPNAME))
+        .group(GROUPNAME)
+        .build();
+
+    Command::new("sudo")
+        .args(["-u", "root", "true"])
+        .as_user(USERNAME)
+        .output(&env)
+        .assert_success();
+
+    let output = Command::new("sudo")
+        .args(["-u", OTHER_USERNAME, "true"])
+        .as_user(USERNAME)
+        .output(&env);
+
+    output.assert_exit_code(1);
+
+    let diagnostic = if sudo_test::is_original_sudo() {
+        format!(" is not allowed to execute '{BIN_TRUE}' as {OTHER_USERNAME}")
+    } else {
+        format!("I'm sorry {USERNAME}. I'm afraid I can't do that")
+    };
+    assert_contains!(output.stderr(), diagnostic);
+}
+
 /// This test tracks [CVE-2019-14287](https://cve.mitre.org/cgi-bin/cvename.cgi?name=CVE-2019-14287)
 /// which is explained in more detail [here](https://www.sudo.ws/security/advisories/minus_1_uid/).
 #[test]
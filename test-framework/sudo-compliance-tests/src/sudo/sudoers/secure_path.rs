@@ -123,3 +123,152 @@ ALL ALL=(ALL:ALL) NOPASSWD: ALL"
         assert_eq!(secure_path, &path);
     }
 }
+
+#[test]
+fn command_specific_value_applies_only_to_that_command() {
+    let env = Env("\
+Defaults secure_path=/usr/bin:/bin
+Defaults!/usr/bin/printenv secure_path=/root:/usr/bin:/bin
+ALL ALL=(ALL:ALL) NOPASSWD: ALL")
+    .build();
+
+    let path = Command::new("sudo")
+        .args(["printenv", "PATH"])
+        .output(&env)
+        .stdout();
+    assert_eq!("/root:/usr/bin:/bin", path);
+
+    let path = Command::new("sudo")
+        .args(["sh", "-c", "echo $PATH"])
+        .output(&env)
+        .stdout();
+    assert_eq!("/usr/bin:/bin", path);
+}
+
+#[test]
+fn command_specific_value_is_used_to_look_up_the_command() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = Env("\
+Defaults secure_path=/usr/local/bin:/usr/bin:/bin
+Cmnd_Alias TOOLS = /usr/local/bin/my-tool, /root/bin/my-tool
+Defaults!TOOLS secure_path=/root/bin:/usr/bin:/bin
+ALL ALL=(ALL:ALL) NOPASSWD: ALL")
+    .file(
+        "/usr/local/bin/my-tool",
+        TextFile("#!/bin/sh\necho usr").chmod("755"),
+    )
+    .directory("/root/bin")
+    .file(
+        "/root/bin/my-tool",
+        TextFile("#!/bin/sh\necho root").chmod("755"),
+    )
+    .build();
+
+    let output = Command::new("sudo").arg("my-tool").output(&env).stdout();
+    assert_eq!("root", output);
+}
+
+#[test]
+fn command_is_denied_if_command_specific_value_finds_another_command() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = Env("\
+Defaults secure_path=/usr/local/bin:/usr/bin:/bin
+Defaults!/usr/local/bin/my-tool secure_path=/root/bin:/usr/bin:/bin
+ALL ALL=(ALL:ALL) NOPASSWD: /usr/local/bin/my-tool")
+    .file(
+        "/usr/local/bin/my-tool",
+        TextFile("#!/bin/sh\necho usr").chmod("755"),
+    )
+    .directory("/root/bin")
+    .file(
+        "/root/bin/my-tool",
+        TextFile("#!/bin/sh\necho root").chmod("755"),
+    )
+    .build();
+
+    let output = Command::new("sudo").arg("my-tool").output(&env);
+    output.assert_exit_code(1);
+    assert_contains!(
+        output.stderr(),
+        "I'm sorry root. I'm afraid I can't do that"
+    );
+}
+
+#[test]
+fn empty_value_is_the_same_as_unset() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let path = "/root/my-script";
+    let env = Env("\
+Defaults secure_path=\"\"
+ALL ALL=(ALL:ALL) NOPASSWD: ALL")
+    .file(path, TextFile("#!/bin/sh").chmod("100"))
+    .build();
+
+    Command::new("sh")
+        .args([
+            "-c",
+            &format!("export PATH=/root; cd /; {BIN_SUDO} my-script"),
+        ])
+        .output(&env)
+        .assert_success();
+}
+
+#[test]
+fn command_is_found_through_a_symlinked_directory() {
+    // the rule names the command by its real path, not by the path through the symlink
+    for rule in ["ALL", "/usr/local/tools/my-tool"] {
+        let env = Env(format!(
+            "Defaults secure_path=/usr/local/linked:/usr/bin:/bin
+ALL ALL=(ALL:ALL) NOPASSWD: {rule}"
+        ))
+        .directory("/usr/local/tools")
+        .file(
+            "/usr/local/tools/my-tool",
+            TextFile("#!/bin/sh\necho my-tool").chmod("755"),
+        )
+        .build();
+
+        let output = Command::new("sh")
+            .args([
+                "-c",
+                "ln -s /usr/local/tools /usr/local/linked; cd /; sudo my-tool",
+            ])
+            .output(&env)
+            .stdout();
+
+        assert_eq!("my-tool", output);
+    }
+}
+
+#[test]
+fn sudoedit_follow_does_not_affect_searching_through_a_symlinked_directory() {
+    let env = Env("\
+Defaults secure_path=/usr/local/linked:/usr/bin:/bin
+Defaults !sudoedit_follow
+ALL ALL=(ALL:ALL) NOPASSWD: ALL")
+    .directory("/usr/local/tools")
+    .file(
+        "/usr/local/tools/my-tool",
+        TextFile("#!/bin/sh\necho my-tool").chmod("755"),
+    )
+    .build();
+
+    let output = Command::new("sh")
+        .args([
+            "-c",
+            "ln -s /usr/local/tools /usr/local/linked; cd /; sudo my-tool",
+        ])
+        .output(&env)
+        .stdout();
+
+    assert_eq!("my-tool", output);
+}
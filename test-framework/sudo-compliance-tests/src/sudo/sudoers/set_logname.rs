@@ -0,0 +1,61 @@
+use sudo_test::{Command, Env, User};
+
+use crate::{SUDOERS_ALL_ALL_NOPASSWD, USERNAME};
+
+// by default, `USER` and `LOGNAME` name the target user
+#[test]
+fn user_and_logname_are_set_to_the_target_user() {
+    let env = Env(SUDOERS_ALL_ALL_NOPASSWD).user(USERNAME).build();
+
+    let stdout = Command::new("sudo")
+        .args(["-u", USERNAME, "printenv", "USER", "LOGNAME"])
+        .output(&env)
+        .stdout();
+
+    assert_eq!(format!("{USERNAME}\n{USERNAME}"), stdout);
+}
+
+// `Defaults !set_logname` keeps `USER` and `LOGNAME` pointing at the invoking user
+#[test]
+fn when_disabled_user_and_logname_are_those_of_the_invoking_user() {
+    let env = Env([SUDOERS_ALL_ALL_NOPASSWD, "Defaults !set_logname"])
+        .user(USERNAME)
+        .build();
+
+    let stdout = Command::new("sudo")
+        .args(["-u", USERNAME, "printenv", "USER", "LOGNAME"])
+        .output(&env)
+        .stdout();
+
+    assert_eq!("root\nroot", stdout);
+}
+
+#[test]
+fn when_disabled_shell_mode_also_uses_the_invoking_user() {
+    let env = Env([SUDOERS_ALL_ALL_NOPASSWD, "Defaults !set_logname"])
+        .user(USERNAME)
+        .build();
+
+    let stdout = Command::new("env")
+        .args(["SHELL=/bin/sh", "sudo", "-u", USERNAME, "-s"])
+        .args(["printenv", "USER", "LOGNAME"])
+        .output(&env)
+        .stdout();
+
+    assert_eq!("root\nroot", stdout);
+}
+
+// a login shell always gets the target user's identity
+#[test]
+fn when_disabled_login_shell_still_uses_the_target_user() {
+    let env = Env([SUDOERS_ALL_ALL_NOPASSWD, "Defaults !set_logname"])
+        .user(User(USERNAME).shell("/bin/sh").create_home_directory())
+        .build();
+
+    let stdout = Command::new("sudo")
+        .args(["-u", USERNAME, "-i", "printenv", "USER", "LOGNAME"])
+        .output(&env)
+        .stdout();
+
+    assert_eq!(format!("{USERNAME}\n{USERNAME}"), stdout);
+}
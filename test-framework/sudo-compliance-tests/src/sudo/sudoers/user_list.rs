@@ -88,6 +88,42 @@ fn group_id() {
         .assert_success();
 }
 
+#[test]
+fn group_beyond_ngroups_max() {
+    // the kernel limits a process to at most 65536 supplementary groups, but the group
+    // database can list a user in more; a `%group` rule must still match the last of them
+    const GROUP_COUNT: usize = 65536 + 8;
+
+    let env = Env(format!(
+        "root ALL=(ALL:ALL) NOPASSWD: ALL
+%overflow{} ALL=(ALL:ALL) NOPASSWD: {BIN_TRUE}",
+        GROUP_COUNT - 1
+    ))
+    .user(USERNAME)
+    .build();
+
+    Command::new("sh")
+        .arg("-c")
+        .arg(format!(
+            "awk 'BEGIN {{ for (i = 0; i < {GROUP_COUNT}; i++) \
+                printf \"overflow%d:x:%d:{USERNAME}\\n\", i, 100000 + i }}' >> /etc/group"
+        ))
+        .output(&env)
+        .assert_success();
+
+    // the user itself cannot log in with that many groups, so ask on its behalf
+    let stdout = Command::new("sudo")
+        .args(["-l", "-U", USERNAME])
+        .output(&env)
+        .stdout();
+
+    assert_contains!(
+        stdout,
+        format!("User {USERNAME} may run the following commands")
+    );
+    assert_contains!(stdout, format!("(ALL : ALL) NOPASSWD: {BIN_TRUE}"));
+}
+
 #[test]
 fn many_different() {
     let env = Env(format!("root, {USERNAME} ALL=(ALL:ALL) NOPASSWD: ALL"))
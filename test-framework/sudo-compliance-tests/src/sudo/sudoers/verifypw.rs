@@ -0,0 +1,90 @@
+use sudo_test::{Command, Env, User};
+
+use crate::{PASSWORD, USERNAME};
+
+#[test]
+fn never_validates_without_a_password_even_if_the_timestamp_was_removed() {
+    let env = Env(format!(
+        "Defaults verifypw=never
+{USERNAME} ALL=(ALL:ALL) ALL"
+    ))
+    .user(User(USERNAME).password(PASSWORD))
+    .build();
+
+    // create a timestamp, then remove it completely
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(format!(
+            "echo {PASSWORD} | sudo -S true 2>/dev/null; sudo -K; sudo -v"
+        ))
+        .as_user(USERNAME)
+        .output(&env);
+
+    output.assert_success();
+    assert_not_contains!(output.stderr(), "assword");
+}
+
+#[test]
+fn never_validates_without_a_password_if_there_never_was_a_timestamp() {
+    let env = Env(format!(
+        "Defaults verifypw=never
+{USERNAME} ALL=(ALL:ALL) ALL"
+    ))
+    .user(User(USERNAME).password(PASSWORD))
+    .build();
+
+    let output = Command::new("sudo")
+        .arg("-v")
+        .as_user(USERNAME)
+        .output(&env);
+
+    output.assert_success();
+    assert_not_contains!(output.stderr(), "assword");
+}
+
+#[test]
+fn always_prompts_even_if_all_entries_are_nopasswd() {
+    let env = Env(format!(
+        "Defaults verifypw=always
+{USERNAME} ALL=(ALL:ALL) NOPASSWD: ALL"
+    ))
+    .user(User(USERNAME).password(PASSWORD))
+    .build();
+
+    let output = Command::new("sudo")
+        .args(["-S", "-v"])
+        .as_user(USERNAME)
+        .output(&env);
+
+    output.assert_exit_code(1);
+
+    let password_prompt = if sudo_test::is_original_sudo() {
+        "password for ferris"
+    } else {
+        "Password:"
+    };
+    assert_contains!(output.stderr(), password_prompt);
+
+    Command::new("sh")
+        .arg("-c")
+        .arg(format!("echo {PASSWORD} | sudo -S -v"))
+        .as_user(USERNAME)
+        .output(&env)
+        .assert_success();
+}
+
+#[test]
+fn always_does_not_affect_running_commands() {
+    let env = Env(format!(
+        "Defaults verifypw=always
+{USERNAME} ALL=(ALL:ALL) NOPASSWD: ALL"
+    ))
+    .user(User(USERNAME).password(PASSWORD))
+    .build();
+
+    Command::new("sudo")
+        .arg("true")
+        .as_user(USERNAME)
+        .output(&env)
+        .assert_success();
+}
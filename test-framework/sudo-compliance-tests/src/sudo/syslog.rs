@@ -1,6 +1,9 @@
-use sudo_test::{BIN_TRUE, Command, Env};
+use sudo_test::{BIN_TRUE, Command, Env, User};
 
-use crate::{SUDOERS_ALL_ALL_NOPASSWD, SUDOERS_USER_ALL_ALL, USERNAME, helpers::Rsyslogd};
+use crate::{
+    PASSWORD, SUDOERS_ALL_ALL_NOPASSWD, SUDOERS_ROOT_ALL_NOPASSWD, SUDOERS_USER_ALL_ALL, USERNAME,
+    helpers::Rsyslogd,
+};
 
 #[test]
 fn sudo_logs_every_executed_command() {
@@ -41,3 +44,255 @@ fn sudo_logs_every_failed_authentication_attempt() {
     let auth_log = rsyslog.auth_log();
     assert_contains!(auth_log, "auth could not identify password");
 }
+
+#[test]
+#[cfg_attr(
+    target_os = "freebsd",
+    ignore = "Logging not really functional on FreeBSD even with og-sudo"
+)]
+fn sudo_logs_denial_of_user_not_in_sudoers_file() {
+    let env = Env(SUDOERS_ROOT_ALL_NOPASSWD)
+        .user(User(USERNAME).password(PASSWORD))
+        .build();
+    let rsyslog = Rsyslogd::start(&env);
+
+    let auth_log = rsyslog.auth_log();
+    assert_eq!("", auth_log);
+
+    let output = Command::new("sudo")
+        .args(["-S", "true"])
+        .as_user(USERNAME)
+        .stdin(PASSWORD)
+        .output(&env);
+
+    output.assert_exit_code(1);
+
+    let diagnostic = if sudo_test::is_original_sudo() {
+        format!("{USERNAME} is not in the sudoers file")
+    } else {
+        format!("I'm sorry {USERNAME}. I'm afraid I can't do that")
+    };
+    assert_contains!(output.stderr(), diagnostic);
+
+    let auth_log = rsyslog.auth_log();
+    let denial = if sudo_test::is_original_sudo() {
+        "user NOT in sudoers"
+    } else {
+        "command not allowed"
+    };
+    assert_contains!(auth_log, format!("{USERNAME} : {denial} ;"));
+    assert_contains!(auth_log, format!("USER=root ; COMMAND={BIN_TRUE}"));
+}
+
+#[test]
+#[cfg_attr(
+    target_os = "freebsd",
+    ignore = "Logging not really functional on FreeBSD even with og-sudo"
+)]
+fn sudo_logs_the_lifecycle_of_an_invocation() {
+    let env = Env(SUDOERS_USER_ALL_ALL)
+        .user(User(USERNAME).password(PASSWORD))
+        .build();
+    let rsyslog = Rsyslogd::start(&env);
+
+    let auth_log = rsyslog.auth_log();
+    assert_eq!("", auth_log);
+
+    Command::new("sudo")
+        .args(["-S", "true"])
+        .as_user(USERNAME)
+        .stdin(PASSWORD)
+        .output(&env)
+        .assert_success();
+
+    let auth_log = rsyslog.auth_log();
+    let entries = auth_log
+        .lines()
+        .filter(|line| line.contains("sudo"))
+        .collect::<Vec<_>>();
+    let position = |event: &str| {
+        entries
+            .iter()
+            .position(|entry| entry.contains(event))
+            .unwrap_or_else(|| panic!("no '{event}' entry in the auth log:\n{auth_log}"))
+    };
+
+    // the outcome of the policy check, which is logged once the user has authenticated
+    let command = position(&format!("USER=root ; COMMAND={BIN_TRUE}"));
+    assert_contains!(entries[command], format!("{USERNAME} : "));
+    assert_contains!(entries[command], "PWD=");
+    assert_not_contains!(entries[command], "command not allowed");
+
+    // the PAM session the command runs in
+    let opened = position("session opened for user root");
+    let closed = position("session closed for user root");
+    assert!(opened < closed, "{auth_log}");
+    assert!(command < closed, "{auth_log}");
+
+    // rsyslog prefixes every entry with an RFC 3339 timestamp, which sorts chronologically
+    let timestamps = entries
+        .iter()
+        .map(|entry| entry.split_whitespace().next().unwrap())
+        .collect::<Vec<_>>();
+    assert!(timestamps.is_sorted(), "{auth_log}");
+}
+
+#[test]
+#[cfg_attr(
+    target_os = "freebsd",
+    ignore = "Logging not really functional on FreeBSD even with og-sudo"
+)]
+fn sudo_logs_non_ascii_username_unmodified() {
+    const NAME: &str = "rüdiger";
+    const USER_ID: u16 = 1500;
+
+    let env = Env("ALL ALL=(ALL:ALL) ALL").build();
+
+    // useradd refuses a name like this, so add the user to the databases directly
+    Command::new("sh")
+        .arg("-c")
+        .arg(format!(
+            "echo '{NAME}:x:{USER_ID}:100::/tmp:/bin/sh' >> /etc/passwd && \
+             echo '{NAME}:!:20000:0:99999:7:::' >> /etc/shadow && \
+             echo '{NAME}:{PASSWORD}' | chpasswd"
+        ))
+        .output(&env)
+        .assert_success();
+
+    let rsyslog = Rsyslogd::start(&env);
+
+    // the user is authenticated by PAM under this name, as the prompt shows
+    let output = Command::new("sudo")
+        .args(["-S", "-p", "password for %p: ", "true"])
+        .as_user_id(USER_ID)
+        .stdin(PASSWORD)
+        .output(&env);
+
+    output.assert_success();
+    assert_contains!(output.stderr(), format!("password for {NAME}: "));
+
+    let auth_log = rsyslog.auth_log();
+    let command = auth_log
+        .lines()
+        .find(|line| line.contains(&format!("USER=root ; COMMAND={BIN_TRUE}")))
+        .unwrap_or_else(|| panic!("no command entry in the auth log:\n{auth_log}"));
+    assert_contains!(command, format!(" {NAME} : "));
+}
+
+#[test]
+#[cfg_attr(
+    target_os = "freebsd",
+    ignore = "Logging not really functional on FreeBSD even with og-sudo"
+)]
+fn syslog_pid_adds_the_process_id_to_the_tag() {
+    for (setting, has_pid) in [("", false), ("Defaults syslog_pid", true)] {
+        let env = Env([SUDOERS_ALL_ALL_NOPASSWD, setting]).build();
+        let rsyslog = Rsyslogd::start(&env);
+
+        Command::new("sudo")
+            .arg("true")
+            .output(&env)
+            .assert_success();
+
+        let auth_log = rsyslog.auth_log();
+        let command = auth_log
+            .lines()
+            .find(|line| line.contains(&format!("COMMAND={BIN_TRUE}")))
+            .unwrap_or_else(|| panic!("no command entry in the auth log:\n{auth_log}"));
+        assert_eq!(command.contains(" sudo["), has_pid, "{command}");
+    }
+}
+
+#[test]
+fn journald_log_backend_falls_back_to_syslog() {
+    if sudo_test::is_original_sudo() {
+        // ogsudo has no log_backend setting
+        return;
+    }
+
+    // there is no journald running in the test containers
+    let env = Env([SUDOERS_ALL_ALL_NOPASSWD, "Defaults log_backend=journald"]).build();
+    let rsyslog = Rsyslogd::start(&env);
+
+    Command::new("sudo")
+        .arg("true")
+        .output(&env)
+        .assert_success();
+
+    let auth_log = rsyslog.auth_log();
+    assert_contains!(auth_log, format!("COMMAND={BIN_TRUE}"));
+}
+
+#[test]
+fn journald_log_backend_sends_structured_entries() {
+    if sudo_test::is_original_sudo() {
+        // ogsudo has no log_backend setting
+        return;
+    }
+
+    let env = Env([SUDOERS_ALL_ALL_NOPASSWD, "Defaults log_backend=journald"]).build();
+    let rsyslog = Rsyslogd::start(&env);
+
+    // a stand-in for journald that appends the entries it receives to a file
+    let _journald = Command::new("sh")
+        .arg("-c")
+        .arg(
+            "mkdir -p /run/systemd/journal && \
+             exec socat -u UNIX-RECV:/run/systemd/journal/socket OPEN:/tmp/journal,creat,append",
+        )
+        .spawn(&env);
+    Command::new("sh")
+        .arg("-c")
+        .arg("until [ -S /run/systemd/journal/socket ]; do sleep 0.1; done")
+        .output(&env)
+        .assert_success();
+
+    Command::new("sudo")
+        .arg("true")
+        .output(&env)
+        .assert_success();
+
+    let journal = Command::new("sh")
+        .arg("-c")
+        .arg("sleep 0.5; cat /tmp/journal")
+        .output(&env)
+        .stdout();
+    // the container cannot be stopped while socat is still running
+    let _ = Command::new("sh")
+        .args(["-c", "kill -9 $(pidof socat) || true"])
+        .output(&env);
+
+    assert_contains!(journal, "SYSLOG_IDENTIFIER=sudo");
+    assert_contains!(journal, "SUDO_USER=root");
+    assert_contains!(journal, "SUDO_RUNAS_USER=root");
+    assert_contains!(journal, format!("SUDO_COMMAND={BIN_TRUE}"));
+    assert_contains!(journal, "SUDO_RESULT=allowed");
+    assert_not_contains!(rsyslog.auth_log(), "COMMAND=");
+}
+
+#[test]
+fn file_log_backend_only_logs_commands_to_the_logfile() {
+    if sudo_test::is_original_sudo() {
+        // ogsudo has no log_backend setting
+        return;
+    }
+
+    let env = Env([
+        SUDOERS_ALL_ALL_NOPASSWD,
+        "Defaults log_backend=file, logfile=/var/log/sudo.log",
+    ])
+    .build();
+    let rsyslog = Rsyslogd::start(&env);
+
+    Command::new("sudo")
+        .arg("true")
+        .output(&env)
+        .assert_success();
+
+    let logfile = Command::new("cat")
+        .arg("/var/log/sudo.log")
+        .output(&env)
+        .stdout();
+    assert_contains!(logfile, format!("COMMAND={BIN_TRUE}"));
+    assert_not_contains!(rsyslog.auth_log(), "COMMAND=");
+}
@@ -245,3 +245,30 @@ fn double_negation_also_equals_never() {
 
     output.assert_exit_code(1);
 }
+
+#[test]
+fn nopasswd_command_does_not_cache_credentials() {
+    let env = Env(format!(
+        "{USERNAME} ALL=(ALL:ALL) NOPASSWD: /usr/bin/true
+{USERNAME} ALL=(ALL:ALL) ALL"
+    ))
+    .user(User(USERNAME).password(PASSWORD))
+    .build();
+
+    // run a command that needs no password
+    // try to sudo another command without a password
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg("set -e; sudo /usr/bin/true; sudo ls")
+        .as_user(USERNAME)
+        .output(&env);
+
+    output.assert_exit_code(1);
+
+    let diagnostic = if sudo_test::is_original_sudo() {
+        "a password is required"
+    } else {
+        "A terminal is required to authenticate"
+    };
+    assert_contains!(output.stderr(), diagnostic);
+}
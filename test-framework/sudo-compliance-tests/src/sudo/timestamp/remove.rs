@@ -87,3 +87,84 @@ fn also_works_locally() {
     };
     assert_contains!(output.stderr(), diagnostic);
 }
+
+#[test]
+fn root_can_remove_the_records_of_another_user() {
+    // selecting another user with -u is specific to sudo-rs
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = Env(format!("{USERNAME} ALL=(ALL:ALL) ALL"))
+        .user(User(USERNAME).password(PASSWORD))
+        .build();
+
+    Command::new("sh")
+        .arg("-c")
+        .arg(format!("echo {PASSWORD} | sudo -S true"))
+        .as_user(USERNAME)
+        .output(&env)
+        .assert_success();
+
+    let records = format!("/var/run/sudo-rs/ts/$(id -u {USERNAME})");
+    Command::new("sh")
+        .arg("-c")
+        .arg(format!(
+            "set -e; [ \"$(stat -c %U {records})\" = root ]; sudo -K -u {USERNAME}; ! [ -e {records} ]"
+        ))
+        .output(&env)
+        .assert_success();
+}
+
+#[test]
+fn removing_records_of_a_user_without_records_succeeds() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = Env("").user(USERNAME).build();
+
+    Command::new("sudo")
+        .args(["-K", "-u", USERNAME])
+        .output(&env)
+        .assert_success();
+}
+
+#[test]
+fn user_cannot_remove_the_records_of_another_user() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let second_user = "ghost";
+    let env = Env("ALL ALL=(ALL:ALL) ALL")
+        .user(User(USERNAME).password(PASSWORD))
+        .user(User(second_user).password(PASSWORD))
+        .build();
+
+    Command::new("sh")
+        .arg("-c")
+        .arg(format!("echo {PASSWORD} | sudo -S true"))
+        .as_user(second_user)
+        .output(&env)
+        .assert_success();
+
+    let output = Command::new("sudo")
+        .args(["-K", "-u", second_user])
+        .as_user(USERNAME)
+        .output(&env);
+
+    output.assert_exit_code(1);
+    assert_contains!(
+        output.stderr(),
+        format!("I'm sorry {USERNAME}. I'm afraid I can't do that")
+    );
+
+    Command::new("sh")
+        .arg("-c")
+        .arg(format!(
+            "test -e /var/run/sudo-rs/ts/$(id -u {second_user})"
+        ))
+        .output(&env)
+        .assert_success();
+}
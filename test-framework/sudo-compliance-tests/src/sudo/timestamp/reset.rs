@@ -148,3 +148,33 @@ fn with_command_does_not_cache_credentials() {
     };
     assert_contains!(output.stderr(), diagnostic);
 }
+
+#[test]
+fn user_cannot_reset_the_records_of_another_user() {
+    // selecting another user with -u is specific to sudo-rs
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = Env("ALL ALL=(ALL:ALL) ALL")
+        .user(User(USERNAME).password(PASSWORD))
+        .build();
+
+    let output = Command::new("sudo")
+        .args(["-k", "-u", "root"])
+        .as_user(USERNAME)
+        .output(&env);
+
+    output.assert_exit_code(1);
+    assert_contains!(
+        output.stderr(),
+        format!("I'm sorry {USERNAME}. I'm afraid I can't do that")
+    );
+
+    // but resetting their own records is fine
+    Command::new("sudo")
+        .args(["-k", "-u", USERNAME])
+        .as_user(USERNAME)
+        .output(&env)
+        .assert_success();
+}
@@ -226,3 +226,49 @@ fn stdout_pipe_tty() {
 
     assert_eq!(output.stdout(), "hello world");
 }
+
+/// The process ID, process group ID and session ID of the shell that invokes sudo, and of the
+/// command that sudo runs
+fn process_ids(defaults: &str) -> ([u32; 3], [u32; 3]) {
+    let env = Env([SUDOERS_ALL_ALL_NOPASSWD, defaults]).build();
+
+    let print_ids = "ps -o pid= -o pgid= -o sid= -p $$";
+    let stdout = Command::new("sh")
+        .args(["-c", &format!("{print_ids}; sudo sh -c '{print_ids}'")])
+        .tty(true)
+        .output(&env)
+        .stdout();
+
+    let mut lines = stdout.lines().map(|line| {
+        let ids = line
+            .split_whitespace()
+            .map(|id| id.parse().unwrap())
+            .collect::<Vec<u32>>();
+        <[u32; 3]>::try_from(ids).unwrap()
+    });
+
+    (lines.next().unwrap(), lines.next().unwrap())
+}
+
+#[test]
+fn command_runs_in_a_new_session_with_pty() {
+    let ([_, _, shell_sid], [command_pid, command_pgid, command_sid]) =
+        process_ids("Defaults use_pty");
+
+    // the monitor process is the leader of the new session, the command leads its own
+    // process group in that session
+    assert_ne!(shell_sid, command_sid);
+    assert_ne!(command_pid, command_sid);
+    assert_eq!(command_pid, command_pgid);
+}
+
+#[test]
+fn command_runs_in_the_same_session_without_pty() {
+    let ([_, shell_pgid, shell_sid], [_, command_pgid, command_sid]) =
+        process_ids("Defaults !use_pty");
+
+    // the command stays in the process group of sudo, which was started by a shell that does
+    // not use job control
+    assert_eq!(shell_sid, command_sid);
+    assert_eq!(shell_pgid, command_pgid);
+}
@@ -0,0 +1,161 @@
+use sudo_test::{Command, Env, User};
+
+use crate::{PASSWORD, USERNAME};
+
+#[cfg(not(target_os = "freebsd"))]
+const SUDO_TS: &str = "/usr/sbin/sudo-ts";
+#[cfg(target_os = "freebsd")]
+const SUDO_TS: &str = "/usr/local/sbin/sudo-ts";
+
+fn env() -> Env {
+    Env(format!("{USERNAME} ALL=(ALL:ALL) ALL"))
+        .user(User(USERNAME).password(PASSWORD))
+        .build()
+}
+
+fn create_record(env: &Env) {
+    Command::new("sh")
+        .arg("-c")
+        .arg(format!("echo {PASSWORD} | sudo -S true"))
+        .as_user(USERNAME)
+        .output(env)
+        .assert_success();
+}
+
+#[test]
+fn lists_records_created_by_sudo() {
+    // sudo-ts is specific to sudo-rs
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = env();
+    create_record(&env);
+
+    let stdout = Command::new(SUDO_TS).output(&env).stdout();
+    let lines = stdout.lines().collect::<Vec<_>>();
+
+    assert_eq!(lines.len(), 2, "{stdout}");
+    assert!(lines[0].starts_with("USER"));
+    assert!(lines[1].starts_with(&format!("{USERNAME} ")));
+    assert_contains!(lines[1], "ppid");
+    assert_not_contains!(lines[1], "expired");
+}
+
+#[test]
+fn lists_records_as_json() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = env();
+    create_record(&env);
+    create_record(&env);
+
+    let stdout = Command::new(SUDO_TS)
+        .args(["--json", "-u", USERNAME])
+        .output(&env)
+        .stdout();
+
+    assert!(stdout.starts_with('[') && stdout.ends_with(']'), "{stdout}");
+    assert_contains!(stdout, format!("\"user\":\"{USERNAME}\",\"uid\":"));
+    assert_contains!(stdout, "\"record\":0,\"scope\":\"ppid\"");
+    assert_contains!(stdout, "\"record\":1,\"scope\":\"ppid\"");
+    assert_contains!(stdout, format!("\"auth_user\":\"{USERNAME}\""));
+    assert_contains!(stdout, "\"status\":\"valid\"");
+}
+
+#[test]
+fn removed_record_requires_authentication() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = env();
+
+    Command::new("sh")
+        .arg("-c")
+        .arg(format!(
+            "set -e; echo {PASSWORD} | sudo -S true; sudo {SUDO_TS} -u {USERNAME} -r 0; ! sudo -n true"
+        ))
+        .as_user(USERNAME)
+        .output(&env)
+        .assert_success();
+
+    let stdout = Command::new(SUDO_TS)
+        .args(["-u", USERNAME])
+        .output(&env)
+        .stdout();
+    assert_eq!(stdout.lines().count(), 1, "{stdout}");
+}
+
+#[test]
+fn removing_a_missing_record_fails() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = env();
+    create_record(&env);
+
+    let output = Command::new(SUDO_TS)
+        .args(["-u", USERNAME, "-r", "1"])
+        .output(&env);
+
+    output.assert_exit_code(1);
+    assert_contains!(
+        output.stderr(),
+        format!("{USERNAME} has no session record 1")
+    );
+}
+
+#[test]
+fn check_reports_damaged_files() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = env();
+    create_record(&env);
+
+    let stdout = Command::new(SUDO_TS).arg("-c").output(&env).stdout();
+    assert_eq!(stdout, format!("{USERNAME}: ok (records: 1)"));
+
+    // append half a record
+    Command::new("sh")
+        .arg("-c")
+        .arg(format!(
+            "printf '\\020\\000\\001' >> /var/run/sudo-rs/ts/$(id -u {USERNAME})"
+        ))
+        .output(&env)
+        .assert_success();
+
+    let output = Command::new(SUDO_TS).arg("--check").output(&env);
+
+    output.assert_exit_code(1);
+    assert_contains!(
+        output.stdout_unchecked(),
+        format!("{USERNAME}: incomplete record after 1 valid records")
+    );
+
+    // the file is left as it was
+    let stdout = Command::new(SUDO_TS)
+        .args(["-c", "-j"])
+        .output(&env)
+        .stdout();
+    assert_contains!(stdout, "\"valid\":false");
+}
+
+#[test]
+fn must_be_run_as_root() {
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    let env = env();
+
+    let output = Command::new(SUDO_TS).as_user(USERNAME).output(&env);
+
+    output.assert_exit_code(1);
+    assert_contains!(output.stderr(), "must be run as root");
+}
@@ -4,8 +4,8 @@ use sudo_test::{
 };
 
 use crate::{
-    DEFAULT_EDITOR, GROUPNAME, PAMD_SUDO_ACCOUNT_DENY, PAMD_SUDO_ACCOUNT_PERMIT, PANIC_EXIT_CODE,
-    Result, SUDOERS_ALL_ALL_NOPASSWD, USERNAME,
+    DEFAULT_EDITOR, GROUPNAME, OTHER_USERNAME, PAMD_SUDO_ACCOUNT_DENY, PAMD_SUDO_ACCOUNT_PERMIT,
+    PANIC_EXIT_CODE, Result, SUDOERS_ALL_ALL_NOPASSWD, USERNAME,
 };
 
 mod flag_help;
@@ -185,6 +185,46 @@ ls -l "$1" > {LOGS_PATH}"#
     assert_ls_output(&ls_output, "-rw-------", USERNAME, "users");
 }
 
+#[test]
+fn temporary_file_and_directories_are_private() {
+    let path = "/etc/config";
+    let editor_script = format!(
+        r#"#!/bin/sh
+file="$1"
+parent="$(dirname "$file")"
+ls -l "$file" > {LOGS_PATH}
+ls -ld "$parent" "$(dirname "$parent")" >> {LOGS_PATH}"#
+    );
+
+    let env = Env(SUDOERS_ALL_ALL_NOPASSWD)
+        .user(USERNAME)
+        .file(path, TextFile("setting=1").chmod("644"))
+        .file(DEFAULT_EDITOR, TextFile(editor_script).chmod(CHMOD_EXEC))
+        .build();
+
+    Command::new("sudoedit")
+        .arg(path)
+        .as_user(USERNAME)
+        .output(&env)
+        .assert_success();
+
+    let ls_output = Command::new("cat").arg(LOGS_PATH).output(&env).stdout();
+    let mut lines = ls_output.lines();
+
+    let file = lines.next().unwrap();
+    assert_ls_output(file, "-rw-------", USERNAME, "users");
+
+    // original sudo creates the temporary file directly in /var/tmp
+    if sudo_test::is_original_sudo() {
+        return;
+    }
+
+    for directory in lines {
+        assert_ls_output(directory, "drwx------", USERNAME, "users");
+        assert_contains!(directory, " /tmp/");
+    }
+}
+
 #[test]
 fn stderr_message_when_file_is_not_modified() {
     let expected = "
@@ -396,6 +436,36 @@ echo ' ' >> $1",
     Ok(())
 }
 
+#[test]
+fn unreadable_file_is_left_untouched() {
+    let file = "/secret.txt";
+    let env = Env(format!(
+        "{USERNAME} ALL=({OTHER_USERNAME}) NOPASSWD: sudoedit"
+    ))
+    .user(USERNAME)
+    .user(OTHER_USERNAME)
+    .file(DEFAULT_EDITOR, TextFile(EDITOR_OVERWRITE).chmod(CHMOD_EXEC))
+    .file(
+        file,
+        TextFile("original").chmod("000").chown(OTHER_USERNAME),
+    )
+    .build();
+
+    let output = Command::new("sudoedit")
+        .args(["-u", OTHER_USERNAME, file])
+        .as_user(USERNAME)
+        .output(&env);
+
+    output.assert_exit_code(1);
+    assert_contains!(output.stderr(), "Permission denied");
+
+    let contents = Command::new("cat").arg(file).output(&env).stdout();
+    assert_eq!("original", contents);
+
+    let ls_output = Command::new("ls").args(["-l", file]).output(&env).stdout();
+    assert_ls_output(&ls_output, "----------", OTHER_USERNAME, ROOT_GROUP);
+}
+
 #[test]
 fn known_under_many_names() {
     for editor in ["sudoedit", "sudo -e", "sudo sudoedit"] {
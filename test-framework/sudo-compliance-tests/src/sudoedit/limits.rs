@@ -88,6 +88,39 @@ fn cannot_edit_symlinks() {
     output.assert_exit_code(1);
 }
 
+#[test]
+fn follow_tag_allows_editing_symlinks() {
+    let target = "/etc/sudoedit-target.txt";
+    let link = "/etc/sudoedit-link.txt";
+    for (tag, followed) in [("", false), ("NOFOLLOW: ", false), ("FOLLOW: ", true)] {
+        let env = Env(format!("ALL ALL=(ALL:ALL) NOPASSWD: {tag}ALL"))
+            .user(USERNAME)
+            .file(DEFAULT_EDITOR, TextFile(EDITOR_DUMMY).chmod(CHMOD_EXEC))
+            .file(target, "")
+            .build();
+
+        Command::new("ln")
+            .args(["-s", target, link])
+            .output(&env)
+            .assert_success();
+
+        let output = Command::new("sudoedit")
+            .as_user(USERNAME)
+            .arg(link)
+            .output(&env);
+
+        let contents = Command::new("cat").arg(target).output(&env).stdout();
+        if followed {
+            output.assert_success();
+            assert_eq!(contents, "#");
+        } else {
+            output.assert_exit_code(1);
+            assert_contains!(output.stderr(), "editing symbolic links is not permitted");
+            assert_eq!(contents, "");
+        }
+    }
+}
+
 #[test]
 fn cannot_edit_files_target_user_cannot_access() {
     let file = "/test.txt";
@@ -257,7 +257,7 @@ pub fn build_base_image() {
                     Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
                     Err(e) => panic!("failed to create build dir: {e}"),
                 }
-                for f in ["sudo", "su", "visudo"] {
+                for f in ["sudo", "su", "visudo", "sudo-ts"] {
                     fs::copy(target_debug_dir.join(f), build_dir.join(f)).unwrap();
                 }
             }
@@ -3,7 +3,7 @@
 set -eo pipefail
 
 docs_dir="docs/man"
-files=("sudo.8" "visudo.8" "sudoers.5" "su.1")
+files=("sudo.8" "visudo.8" "sudo-ts.8" "sudoers.5" "su.1")
 
 function docker_pandoc() {
     docker run --rm -i -v "$(pwd):/data" -u "$(id -u):$(id -g)" "pandoc/core@sha256:668f5ced9d99ed0fd8b0efda93d6cead066565bb400fc1fb165e77ddbb586a16" "$@"